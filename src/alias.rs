@@ -0,0 +1,346 @@
+//
+// alias.rs
+//
+// Copyright 2015-2019 Laurent Wandrebeck <l.wandrebeck@quelquesmots.fr>
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston,
+// MA 02110-1301, USA.
+//
+
+//! RuSh aliases expansion.
+//!
+//! alias.rs substitutes aliases while input is read, before it is parsed, as bash does: input is split into words
+//! and operators, and a word in command position which is an unquoted alias name is replaced by the alias value.
+//! An alias value may thus open a compound command, and an alias defined on a line applies from next line read.
+
+use crate::arrays::Index;
+use crate::rush::RuSh;
+
+/// Words that leave next word in command position.
+const OPENING: [&str; 10] = [
+    "if", "then", "else", "elif", "do", "while", "until", "!", "{", "time",
+];
+
+/// Progress within a case command.
+#[derive(Clone, Copy, PartialEq)]
+enum Case {
+    /// next word is the word to match.
+    Subject,
+    /// next word is `in`.
+    In,
+    /// patterns, up to `)`.
+    Pattern,
+    /// commands of a case item.
+    Body,
+}
+
+/// Alias expansion state along input.
+struct Lexer<'a> {
+    rush: &'a RuSh,
+    /// aliases whose value is being expanded, which are not expanded again.
+    expanding: Vec<String>,
+    /// is next word in command position ?
+    command: bool,
+    /// does the last alias value end with a blank, which makes next word checked too ?
+    blank: bool,
+    /// case commands being read, innermost last.
+    cases: Vec<Case>,
+}
+
+/// Expand aliases of input, if expand_aliases is set. A word is in command position when it is the first of a
+/// command, or follows assignments or a reserved word like `then`. If an alias value ends with a blank, next word is
+/// checked for an alias too. An alias is not expanded again within its own expansion.
+///
+/// # Examples
+/// ```rust
+/// use rush::RuSh;
+/// use rush::alias;
+/// use rush::parse::parse;
+///
+/// let mut rush = RuSh::default();
+/// parse(&mut rush, "alias l='list -l' cond='if true; then'");
+/// assert_eq!(alias::expand(&rush, "l /; echo l 'l'"), "list -l /; echo l 'l'");
+/// assert_eq!(alias::expand(&rush, "cond l; fi"), "if true; then list -l; fi");
+/// assert_eq!(alias::expand(&rush, "a=1 l | \\l"), "a=1 list -l | \\l");
+/// ```
+pub fn expand(rush: &RuSh, input: &str) -> String {
+    match rush.shopt_options.get("expand_aliases") {
+        Some(opt) if opt.get() => (),
+        _ => return input.to_string(),
+    }
+    let mut lexer = Lexer {
+        rush,
+        expanding: Vec::new(),
+        command: true,
+        blank: false,
+        cases: Vec::new(),
+    };
+    lexer.expand(input)
+}
+
+impl<'a> Lexer<'a> {
+    /// Expand aliases of `input`, starting in current state.
+    fn expand(&mut self, input: &str) -> String {
+        let b = input.as_bytes();
+        let mut out = String::with_capacity(input.len());
+        let mut i = 0;
+        while i < b.len() {
+            match b[i] {
+                b' ' | b'\t' => {
+                    out.push(b[i] as char);
+                    i += 1;
+                }
+                b'\\' if b.get(i + 1) == Some(&b'\n') => {
+                    out.push_str("\\\n");
+                    i += 2;
+                }
+                b'#' => {
+                    let end = b[i..]
+                        .iter()
+                        .position(|c| *c == b'\n')
+                        .map_or(b.len(), |e| i + e);
+                    out.push_str(&input[i..end]);
+                    i = end;
+                }
+                // arithmetic command, whose words are not commands.
+                b'(' if self.command && b.get(i + 1) == Some(&b'(') => {
+                    let end = balanced(b, i, b'(', b')');
+                    out.push_str(&input[i..end]);
+                    i = end;
+                    self.command = false;
+                    self.blank = false;
+                }
+                b'\n' | b';' | b'&' | b'|' | b'(' | b')' => {
+                    let len = if b[i] != b'\n'
+                        && b[i] != b'('
+                        && b[i] != b')'
+                        && b.get(i + 1) == Some(&b[i])
+                    {
+                        2
+                    } else {
+                        1
+                    };
+                    let op = &input[i..i + len];
+                    out.push_str(op);
+                    i += len;
+                    self.operator(op);
+                }
+                _ => {
+                    let end = word_end(b, i);
+                    let word = self.word(&input[i..end]);
+                    out.push_str(&word);
+                    i = end;
+                }
+            }
+        }
+        out
+    }
+
+    /// Update state after an operator.
+    fn operator(&mut self, op: &str) {
+        self.blank = false;
+        match (self.cases.last().copied(), op) {
+            (Some(_), ";;") => {
+                if let Some(case) = self.cases.last_mut() {
+                    *case = Case::Pattern;
+                }
+                self.command = false;
+            }
+            (Some(Case::Pattern), ")") => {
+                if let Some(case) = self.cases.last_mut() {
+                    *case = Case::Body;
+                }
+                self.command = true;
+            }
+            (Some(Case::Subject), _) | (Some(Case::In), _) | (Some(Case::Pattern), _) => (),
+            (_, ")") => self.command = false,
+            _ => self.command = true,
+        }
+    }
+
+    /// Expand a word if it is an alias in command position, updating state.
+    fn word(&mut self, word: &str) -> String {
+        match self.cases.last().copied() {
+            Some(Case::Subject) => {
+                self.cases.pop();
+                self.cases.push(Case::In);
+                return word.to_string();
+            }
+            Some(Case::In) => {
+                if word == "in" {
+                    self.cases.pop();
+                    self.cases.push(Case::Pattern);
+                }
+                return word.to_string();
+            }
+            Some(Case::Pattern) => {
+                if word == "esac" {
+                    self.cases.pop();
+                    self.command = false;
+                }
+                return word.to_string();
+            }
+            _ => (),
+        }
+        // function bodies follow `name()`.
+        if word == "{" {
+            self.command = true;
+            return word.to_string();
+        }
+        let checked = self.command || self.blank;
+        self.blank = false;
+        if !checked {
+            return word.to_string();
+        }
+        if self.command {
+            if assignment(word) || OPENING.contains(&word) {
+                return word.to_string();
+            }
+            match word {
+                "case" => {
+                    self.cases.push(Case::Subject);
+                    self.command = false;
+                    return word.to_string();
+                }
+                "esac" if self.cases.last() == Some(&Case::Body) => {
+                    self.cases.pop();
+                    self.command = false;
+                    return word.to_string();
+                }
+                _ => (),
+            }
+        }
+        let plain = !word.contains(|c| "'\"\\$`".contains(c));
+        if plain && !self.expanding.iter().any(|a| a == word) {
+            if let Some(value) = self
+                .rush
+                .shell_array_vars
+                .get("RUSH_ALIASES", &Index::A(word.to_string()))
+            {
+                let value = value.to_string();
+                self.expanding.push(word.to_string());
+                self.command = true;
+                let out = self.expand(&value);
+                self.expanding.pop();
+                self.blank = value.ends_with(' ') || value.ends_with('\t');
+                return out;
+            }
+        }
+        self.command = false;
+        word.to_string()
+    }
+}
+
+/// Is `word` an assignment: `name=value`, `name+=value` or `name[index]=value` ?
+fn assignment(word: &str) -> bool {
+    let name_end = word
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(word.len());
+    if name_end == 0 || word.starts_with(|c: char| c.is_ascii_digit()) {
+        return false;
+    }
+    let mut rest = &word[name_end..];
+    if rest.starts_with('[') {
+        match rest.find(']') {
+            Some(end) => rest = &rest[end + 1..],
+            None => return false,
+        }
+    }
+    rest.starts_with('=') || rest.starts_with("+=")
+}
+
+/// End of the word starting at `start`: quotes, escapes, substitutions and array assignments are part of it.
+fn word_end(b: &[u8], start: usize) -> usize {
+    let mut i = start;
+    while i < b.len() {
+        match b[i] {
+            b' ' | b'\t' | b'\n' | b';' | b'&' | b'|' | b')' => break,
+            b'(' if i > start && b"=@?*+!".contains(&b[i - 1]) => i = balanced(b, i, b'(', b')'),
+            b'(' => break,
+            b'\\' => i += 2,
+            b'\'' => i = quoted(b, i),
+            b'"' => i = dquoted(b, i),
+            b'`' => i = backquoted(b, i),
+            b'$' if b.get(i + 1) == Some(&b'(') => i = balanced(b, i + 1, b'(', b')'),
+            b'$' if b.get(i + 1) == Some(&b'{') => i = balanced(b, i + 1, b'{', b'}'),
+            _ => i += 1,
+        }
+    }
+    i.min(b.len())
+}
+
+/// Position after the single quoted string starting at `start`.
+fn quoted(b: &[u8], start: usize) -> usize {
+    b[start + 1..]
+        .iter()
+        .position(|c| *c == b'\'')
+        .map_or(b.len(), |e| start + e + 2)
+}
+
+/// Position after the double quoted string starting at `start`.
+fn dquoted(b: &[u8], start: usize) -> usize {
+    let mut i = start + 1;
+    while i < b.len() {
+        match b[i] {
+            b'"' => return i + 1,
+            b'\\' => i += 2,
+            b'`' => i = backquoted(b, i),
+            b'$' if b.get(i + 1) == Some(&b'(') => i = balanced(b, i + 1, b'(', b')'),
+            b'$' if b.get(i + 1) == Some(&b'{') => i = balanced(b, i + 1, b'{', b'}'),
+            _ => i += 1,
+        }
+    }
+    b.len()
+}
+
+/// Position after the backquoted command starting at `start`.
+fn backquoted(b: &[u8], start: usize) -> usize {
+    let mut i = start + 1;
+    while i < b.len() {
+        match b[i] {
+            b'`' => return i + 1,
+            b'\\' => i += 2,
+            _ => i += 1,
+        }
+    }
+    b.len()
+}
+
+/// Position after the `close` matching the `open` at `start`, quotes within being skipped.
+fn balanced(b: &[u8], start: usize, open: u8, close: u8) -> usize {
+    let mut depth = 0;
+    let mut i = start;
+    while i < b.len() {
+        match b[i] {
+            c if c == open => {
+                depth += 1;
+                i += 1;
+            }
+            c if c == close => {
+                depth -= 1;
+                i += 1;
+                if depth == 0 {
+                    return i;
+                }
+            }
+            b'\\' => i += 2,
+            b'\'' => i = quoted(b, i),
+            b'"' => i = dquoted(b, i),
+            b'`' => i = backquoted(b, i),
+            _ => i += 1,
+        }
+    }
+    b.len()
+}
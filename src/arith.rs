@@ -0,0 +1,472 @@
+//
+// arith.rs
+//
+// Copyright 2015-2019 Laurent Wandrebeck <l.wandrebeck@quelquesmots.fr>
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston,
+// MA 02110-1301, USA.
+//
+
+//! RuSh arithmetic evaluation.
+//!
+//! arith.rs evaluates arithmetic expressions, as found in `$(( ))`, the way bash does: on 64 bits signed integers,
+//! with C operators and precedence, `**` power, assignments to shell variables, and constants in base 8, 10, 16
+//! or any base from 2 to 64 with `base#digits`. A variable whose value is not a number is evaluated as an expression.
+
+use crate::execute;
+use crate::rush::RuSh;
+use crate::variables::Value;
+
+/// Operators, longest first so that they are recognized before their prefixes.
+const OPERATORS: &[&str] = &[
+    "<<=", ">>=", "**", "++", "--", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "*=", "/=",
+    "%=", "+=", "-=", "&=", "^=", "|=", "+", "-", "*", "/", "%", "<", ">", "=", "!", "~", "&", "^",
+    "|", "?", ":", ",", "(", ")",
+];
+
+/// Assignment operators.
+const ASSIGNMENTS: &[&str] = &[
+    "=", "*=", "/=", "%=", "+=", "-=", "<<=", ">>=", "&=", "^=", "|=",
+];
+
+/// Maximum number of variables evaluated as expressions within each other, low enough for the default thread stack.
+const MAX_DEPTH: usize = 128;
+
+/// Lexical token, with its position in the expression.
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Operator(&'static str),
+}
+
+/// Value of a constant: decimal, octal with a leading 0, hexadecimal with a leading 0x, or `base#digits`.
+fn number(text: &str) -> Result<i64, String> {
+    let too_great = || format!("value too great for base (error token is \"{}\")", text);
+    let (base, digits) = if let Some(hash) = text.find('#') {
+        match text[..hash].parse::<u32>() {
+            Ok(base) if (2..=64).contains(&base) => (base, &text[hash + 1..]),
+            _ => {
+                return Err(format!(
+                    "invalid arithmetic base (error token is \"{}\")",
+                    text
+                ))
+            }
+        }
+    } else if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        (16, hex)
+    } else if text.len() > 1 && text.starts_with('0') {
+        (8, &text[1..])
+    } else {
+        (10, text)
+    };
+    if digits.is_empty() {
+        return Err(too_great());
+    }
+    let mut value: i64 = 0;
+    for c in digits.chars() {
+        let digit = match c {
+            '0'..='9' => c as u32 - '0' as u32,
+            'a'..='z' => c as u32 - 'a' as u32 + 10,
+            'A'..='Z' if base <= 36 => c as u32 - 'A' as u32 + 10,
+            'A'..='Z' => c as u32 - 'A' as u32 + 36,
+            '@' => 62,
+            '_' => 63,
+            _ => return Err(too_great()),
+        };
+        if digit >= base {
+            return Err(too_great());
+        }
+        value = value
+            .wrapping_mul(i64::from(base))
+            .wrapping_add(i64::from(digit));
+    }
+    Ok(value)
+}
+
+/// Split an expression into tokens, with their position.
+fn tokens(expr: &str) -> Result<Vec<(Token, usize)>, String> {
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < expr.len() {
+        let rest = &expr[i..];
+        let c = rest.chars().next().unwrap();
+        if c.is_whitespace() {
+            i += c.len_utf8();
+        } else if c.is_ascii_digit() {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '#' || c == '@' || c == '_'))
+                .unwrap_or(rest.len());
+            tokens.push((Token::Number(number(&rest[..len])?), i));
+            i += len;
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            tokens.push((Token::Name(rest[..len].to_string()), i));
+            i += len;
+        } else {
+            match OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+                Some(op) => {
+                    tokens.push((Token::Operator(op), i));
+                    i += op.len();
+                }
+                None => {
+                    return Err(format!(
+                        "syntax error: invalid arithmetic operator (error token is \"{}\")",
+                        rest
+                    ))
+                }
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent evaluator. Side effects (assignments) only happen when `eval` is set,
+/// which is not the case of the operands && || and ?: do not evaluate.
+struct Evaluator<'a> {
+    rush: &'a mut RuSh,
+    expr: &'a str,
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    eval: bool,
+    depth: usize,
+}
+
+impl<'a> Evaluator<'a> {
+    /// Next token, if any.
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(t, _)| t)
+    }
+
+    /// Is next token the operator `op` ?
+    fn next_is(&self, op: &str) -> bool {
+        matches!(self.peek(), Some(Token::Operator(o)) if *o == op)
+    }
+
+    /// Consume next token if it is one of `ops`, and give it back.
+    fn accept(&mut self, ops: &[&'static str]) -> Option<&'static str> {
+        match self.peek() {
+            Some(Token::Operator(o)) if ops.contains(o) => {
+                let o = *o;
+                self.pos += 1;
+                Some(o)
+            }
+            _ => None,
+        }
+    }
+
+    /// Rest of the expression from the current token, for error messages.
+    fn rest(&self) -> &str {
+        match self.tokens.get(self.pos) {
+            Some((_, start)) => &self.expr[*start..],
+            None => "",
+        }
+    }
+
+    /// Value of a variable: 0 if unset or empty, the value of the expression it holds otherwise.
+    fn variable(&mut self, name: &str) -> Result<i64, String> {
+        let value = match self.rush.shell_vars.get(name) {
+            Some(var) => var.value,
+            None => return Ok(0),
+        };
+        match value {
+            Value::I(i) => Ok(i),
+            Value::F(f) => Ok(f as i64),
+            Value::S(s) if s.trim().is_empty() => Ok(0),
+            Value::S(s) => {
+                if self.depth >= MAX_DEPTH {
+                    return Err(format!(
+                        "expression recursion level exceeded (error token is \"{}\")",
+                        s
+                    ));
+                }
+                evaluate(self.rush, &s, self.depth + 1)
+            }
+        }
+    }
+
+    /// Set a variable, if evaluating.
+    fn assign(&mut self, name: &str, value: i64) {
+        if self.eval {
+            execute::assign(self.rush, name, None, &value.to_string());
+        }
+    }
+
+    /// `expr , expr`
+    fn comma(&mut self) -> Result<i64, String> {
+        let mut value = self.assignment()?;
+        while self.accept(&[","]).is_some() {
+            value = self.assignment()?;
+        }
+        Ok(value)
+    }
+
+    /// `name op= expr`, right associative.
+    fn assignment(&mut self) -> Result<i64, String> {
+        if let (Some((Token::Name(name), _)), Some((Token::Operator(op), _))) =
+            (self.tokens.get(self.pos), self.tokens.get(self.pos + 1))
+        {
+            if ASSIGNMENTS.contains(op) {
+                let (name, op) = (name.clone(), *op);
+                self.pos += 2;
+                let value = self.assignment()?;
+                let value = if op == "=" {
+                    value
+                } else {
+                    let old = self.variable(&name)?;
+                    self.binary(&op[..op.len() - 1], old, value)?
+                };
+                self.assign(&name, value);
+                return Ok(value);
+            }
+        }
+        self.conditional()
+    }
+
+    /// `cond ? expr : expr`
+    fn conditional(&mut self) -> Result<i64, String> {
+        let cond = self.or()?;
+        if self.accept(&["?"]).is_none() {
+            return Ok(cond);
+        }
+        let eval = self.eval;
+        self.eval = eval && cond != 0;
+        let yes = self.comma()?;
+        if self.accept(&[":"]).is_none() {
+            self.eval = eval;
+            return Err(format!(
+                "`:' expected for conditional expression (error token is \"{}\")",
+                self.rest()
+            ));
+        }
+        self.eval = eval && cond == 0;
+        let no = self.conditional()?;
+        self.eval = eval;
+        Ok(if cond != 0 { yes } else { no })
+    }
+
+    /// `expr || expr`, right operand being evaluated only if needed.
+    fn or(&mut self) -> Result<i64, String> {
+        let mut value = self.and()?;
+        while self.accept(&["||"]).is_some() {
+            let eval = self.eval;
+            self.eval = eval && value == 0;
+            let right = self.and()?;
+            self.eval = eval;
+            value = (value != 0 || right != 0) as i64;
+        }
+        Ok(value)
+    }
+
+    /// `expr && expr`, right operand being evaluated only if needed.
+    fn and(&mut self) -> Result<i64, String> {
+        let mut value = self.binary_level(0)?;
+        while self.accept(&["&&"]).is_some() {
+            let eval = self.eval;
+            self.eval = eval && value != 0;
+            let right = self.binary_level(0)?;
+            self.eval = eval;
+            value = (value != 0 && right != 0) as i64;
+        }
+        Ok(value)
+    }
+
+    /// Left associative binary operators, by increasing precedence from `level`.
+    fn binary_level(&mut self, level: usize) -> Result<i64, String> {
+        const LEVELS: &[&[&str]] = &[
+            &["|"],
+            &["^"],
+            &["&"],
+            &["==", "!="],
+            &["<", ">", "<=", ">="],
+            &["<<", ">>"],
+            &["+", "-"],
+            &["*", "/", "%"],
+        ];
+        if level == LEVELS.len() {
+            return self.power();
+        }
+        let mut value = self.binary_level(level + 1)?;
+        loop {
+            let start = self.pos;
+            let op = match self.accept(LEVELS[level]) {
+                Some(op) => op,
+                None => return Ok(value),
+            };
+            let right = self.binary_level(level + 1)?;
+            if self.eval || !["/", "%"].contains(&op) {
+                value = self.binary(op, value, right).map_err(|e| {
+                    format!(
+                        "{} (error token is \"{}\")",
+                        e,
+                        &self.expr[self.tokens[start + 1].1..]
+                    )
+                })?;
+            }
+        }
+    }
+
+    /// Result of a binary operator.
+    fn binary(&self, op: &str, left: i64, right: i64) -> Result<i64, String> {
+        Ok(match op {
+            "|" => left | right,
+            "^" => left ^ right,
+            "&" => left & right,
+            "==" => (left == right) as i64,
+            "!=" => (left != right) as i64,
+            "<" => (left < right) as i64,
+            ">" => (left > right) as i64,
+            "<=" => (left <= right) as i64,
+            ">=" => (left >= right) as i64,
+            "<<" => left.wrapping_shl(right as u32),
+            ">>" => left.wrapping_shr(right as u32),
+            "+" => left.wrapping_add(right),
+            "-" => left.wrapping_sub(right),
+            "*" => left.wrapping_mul(right),
+            "/" | "%" if right == 0 => return Err(String::from("division by 0")),
+            "/" => left.wrapping_div(right),
+            "%" => left.wrapping_rem(right),
+            "**" if right < 0 => return Err(String::from("exponent less than 0")),
+            "**" => left.wrapping_pow(right.min(i64::from(u32::MAX)) as u32),
+            _ => unreachable!(),
+        })
+    }
+
+    /// `expr ** expr`, right associative, binding tighter than everything but unary operators.
+    fn power(&mut self) -> Result<i64, String> {
+        let value = self.unary()?;
+        if self.accept(&["**"]).is_some() {
+            let right = self.power()?;
+            return self.binary("**", value, right);
+        }
+        Ok(value)
+    }
+
+    /// `! ~ - +` and prefix `++ --`.
+    fn unary(&mut self) -> Result<i64, String> {
+        if let Some(op) = self.accept(&["!", "~", "-", "+", "++", "--"]) {
+            if op == "++" || op == "--" {
+                if let Some(Token::Name(name)) = self.peek() {
+                    let name = name.clone();
+                    self.pos += 1;
+                    let value = self.variable(&name)?;
+                    let value = if op == "++" {
+                        value.wrapping_add(1)
+                    } else {
+                        value.wrapping_sub(1)
+                    };
+                    self.assign(&name, value);
+                    return Ok(value);
+                }
+                // -- is a double negation when not followed by a variable.
+                let value = self.unary()?;
+                return Ok(value);
+            }
+            let value = self.unary()?;
+            return Ok(match op {
+                "!" => (value == 0) as i64,
+                "~" => !value,
+                "-" => value.wrapping_neg(),
+                _ => value,
+            });
+        }
+        self.primary()
+    }
+
+    /// Numbers, variables with postfix `++ --`, and parenthesized expressions.
+    fn primary(&mut self) -> Result<i64, String> {
+        match self.peek().cloned() {
+            Some(Token::Number(n)) => {
+                self.pos += 1;
+                Ok(n)
+            }
+            Some(Token::Name(name)) => {
+                self.pos += 1;
+                let value = self.variable(&name)?;
+                if let Some(op) = self.accept(&["++", "--"]) {
+                    let next = if op == "++" {
+                        value.wrapping_add(1)
+                    } else {
+                        value.wrapping_sub(1)
+                    };
+                    self.assign(&name, next);
+                }
+                Ok(value)
+            }
+            Some(Token::Operator("(")) => {
+                self.pos += 1;
+                let value = self.comma()?;
+                if self.accept(&[")"]).is_none() {
+                    return Err(format!("missing `)' (error token is \"{}\")", self.rest()));
+                }
+                Ok(value)
+            }
+            _ => Err(format!(
+                "syntax error: operand expected (error token is \"{}\")",
+                self.rest()
+            )),
+        }
+    }
+}
+
+/// Evaluate an expression, `depth` being the number of variables it is the value of.
+fn evaluate(rush: &mut RuSh, expr: &str, depth: usize) -> Result<i64, String> {
+    let tokens = tokens(expr)?;
+    if tokens.is_empty() {
+        return Ok(0);
+    }
+    let mut evaluator = Evaluator {
+        rush,
+        expr,
+        tokens,
+        pos: 0,
+        eval: true,
+        depth,
+    };
+    let value = evaluator.comma()?;
+    if evaluator.pos < evaluator.tokens.len() {
+        let message = if evaluator.next_is(")") {
+            "syntax error: invalid arithmetic operator"
+        } else {
+            "syntax error in expression"
+        };
+        return Err(format!(
+            "{} (error token is \"{}\")",
+            message,
+            evaluator.rest()
+        ));
+    }
+    Ok(value)
+}
+
+/// Evaluate an arithmetic expression. Gives back an error message if it is not valid.
+///
+/// # Examples
+/// ```rust
+/// use rush::RuSh;
+/// use rush::arith::eval;
+///
+/// let mut rush = RuSh::default();
+/// assert_eq!(eval(&mut rush, "1 + 2 * 3"), Ok(7));
+/// assert_eq!(eval(&mut rush, "x = 2 ** 10, x >> 2"), Ok(256));
+/// assert_eq!(eval(&mut rush, "x++ + 0x10 + 2#11 + 010"), Ok(1024 + 16 + 3 + 8));
+/// assert_eq!(eval(&mut rush, "x"), Ok(1025));
+/// assert!(eval(&mut rush, "1 / 0").is_err());
+/// ```
+pub fn eval(rush: &mut RuSh, expr: &str) -> Result<i64, String> {
+    evaluate(rush, expr, 0)
+}
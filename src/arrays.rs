@@ -91,6 +91,7 @@ impl Array {
     ///     None => panic!("TEST[\"TESTINDEX\"] array Value should be defined at index \"TESTINDEX\".")
     /// }
     /// ```
+    #[allow(clippy::manual_map)]
     pub fn get(&self, key: &str, index: &Index) -> Option<Value> {
         //~ self.arrayvars.get(&Array { name: key.to_string(), access: Access::ReadWrite }).and_then(|val| val.get(index::A).or(val.get(index::I)))
         match self.arrayvars.get(key) {
@@ -125,6 +126,7 @@ impl Array {
     /// };
     /// arrayvars.set(Array { name: "ARRAYVARNAME", access: Access::ReadWrite }, Index::A("INDEX"), Value::I(42));
     /// assert_eq!(arrayvars.get_access("ARRAYVARNAME"), Some(Access::ReadWrite));
+    #[allow(clippy::manual_map)]
     pub fn get_access(&mut self, key: &str) -> Option<Access> {
         match self.arrayvars.get(key) {
             Some((access, _hm)) => Some(access.clone()),
@@ -269,6 +271,31 @@ impl Array {
         };
     }
 
+    /// Remove every element of an array, if it is rw. Array is created if needed.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use rush::variables::Value;
+    /// use rush::arrays::{Array, Index};
+    ///
+    /// let mut arrayvars = Array::init_shell_array_vars();
+    /// arrayvars.set("TEST", Index::I(0), Value::I(42));
+    /// arrayvars.clear("TEST");
+    /// assert!(arrayvars.get("TEST", &Index::I(0)).is_none());
+    /// assert!(arrayvars.arrayvars.contains_key("TEST"));
+    /// ```
+    pub fn clear(&mut self, key: &str) {
+        match self.arrayvars.get_mut(key) {
+            Some((Access::ReadWrite, hm)) => hm.clear(),
+            Some((Access::ReadOnly, _hm)) => (),
+            None => {
+                let hm = HashMap::with_capacity(20);
+                self.arrayvars
+                    .insert(key.to_string(), (Access::ReadWrite, hm));
+            }
+        }
+    }
+
     /// Default shell array variables are set here, following the bash way.
     ///
     /// # Examples
@@ -288,7 +315,7 @@ impl Array {
         };
         //~ // see man bash (Shell vars)
         //~ // An associative array variable whose members correspond to the internal list of aliases as maintained by the alias builtin. Elements added to this array appear in the alias list; unsetting array elements cause aliases to be removed from the alias list.
-        //~ // TODO RUSH_ALIASES
+        //~ // RUSH_ALIASES is read and written by alias and unalias builtins.
         arrayvars.set(
             "RUSH_ALIASES",
             Index::A("egrep".to_string()),
@@ -0,0 +1,2504 @@
+//
+// builtins.rs
+//
+// Copyright 2015-2019 Laurent Wandrebeck <l.wandrebeck@quelquesmots.fr>
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston,
+// MA 02110-1301, USA.
+//
+
+//! RuSh builtins are located in this module.
+//!
+//! builtins.rs maps builtin names to the functions implementing them.
+//! A builtin is given `RuSh` and its arguments (builtin name included), and gives back an exit status.
+
+use crate::arrays::{Array, Index};
+use crate::completion::{self, CompSpec};
+use crate::execute;
+use crate::expand;
+use crate::history;
+use crate::inputrc::{self, Binding};
+use crate::opt::{Opt, SET_FLAGS};
+use crate::parse;
+use crate::rush::RuSh;
+use crate::script;
+use crate::variables::{Access, Value, Variables};
+use chrono::{Local, TimeZone};
+use libc::{c_char, c_void};
+use rustyline::KeyPress;
+use std::convert::TryFrom;
+use std::ffi::CString;
+use std::fmt::Write as FmtWrite;
+use std::io::{self, Write};
+use std::os::unix::io::FromRawFd;
+use std::str;
+use std::time::{Duration, Instant};
+
+/// Builtin function prototype.
+pub type Builtin = fn(&mut RuSh, &[String]) -> i32;
+
+/// Names of the builtins `get` knows.
+pub const NAMES: &[&str] = &[
+    ".",
+    "alias",
+    "bind",
+    "compgen",
+    "complete",
+    "compopt",
+    "echo",
+    "export",
+    "fc",
+    "getopts",
+    "history",
+    "mapfile",
+    "printf",
+    "read",
+    "readarray",
+    "return",
+    "set",
+    "shopt",
+    "source",
+    "unalias",
+];
+
+/// Get the function implementing a builtin from its name.
+///
+/// # Examples
+/// ```rust
+/// use rush::builtins;
+///
+/// assert!(builtins::get("export").is_some());
+/// assert!(builtins::get("ls").is_none());
+/// ```
+pub fn get(name: &str) -> Option<Builtin> {
+    match name {
+        "." | "source" => Some(source),
+        "alias" => Some(alias),
+        "bind" => Some(bind),
+        "compgen" => Some(compgen),
+        "complete" => Some(complete),
+        "compopt" => Some(compopt),
+        "echo" => Some(echo),
+        "export" => Some(export),
+        "fc" => Some(fc),
+        "getopts" => Some(getopts),
+        "history" => Some(history),
+        "mapfile" | "readarray" => Some(mapfile),
+        "printf" => Some(printf),
+        "read" => Some(read),
+        "return" => Some(return_builtin),
+        "set" => Some(set),
+        "shopt" => Some(shopt),
+        "unalias" => Some(unalias),
+        _ => None,
+    }
+}
+
+/// POSIX special builtins: variable assignments preceding them remain in effect after they complete.
+pub fn is_special(name: &str) -> bool {
+    matches!(
+        name,
+        "." | ":"
+            | "break"
+            | "continue"
+            | "eval"
+            | "exec"
+            | "exit"
+            | "export"
+            | "readonly"
+            | "return"
+            | "set"
+            | "shift"
+            | "times"
+            | "trap"
+            | "unset"
+    )
+}
+
+/// Parse builtin options, `optstring` listing them the getopts way: a letter followed by `:` takes an argument.
+/// Returns options with their argument (empty if none) and position of the first operand.
+/// An invalid option or a missing argument prints an error and usage, and gives back None.
+fn options(
+    builtin: &str,
+    args: &[String],
+    optstring: &str,
+    usage: &str,
+) -> Option<(Vec<(char, String)>, usize)> {
+    let mut opts = Vec::new();
+    let mut i = 1;
+    while i < args.len() {
+        let arg = &args[i];
+        if arg == "--" {
+            return Some((opts, i + 1));
+        }
+        if !arg.starts_with('-') || arg.len() == 1 {
+            break;
+        }
+        i += 1;
+        for (pos, c) in arg.char_indices().skip(1) {
+            match optstring.find(c) {
+                Some(o) if c != ':' => {
+                    if optstring[o + 1..].starts_with(':') {
+                        let value = if pos + 1 < arg.len() {
+                            arg[pos + 1..].to_string()
+                        } else if i < args.len() {
+                            i += 1;
+                            args[i - 1].clone()
+                        } else {
+                            eprintln!("rush: {}: -{}: option requires an argument", builtin, c);
+                            eprintln!("{}: usage: {}", builtin, usage);
+                            return None;
+                        };
+                        opts.push((c, value));
+                        break;
+                    }
+                    opts.push((c, String::new()));
+                }
+                _ => {
+                    eprintln!("rush: {}: -{}: invalid option", builtin, c);
+                    eprintln!("{}: usage: {}", builtin, usage);
+                    return None;
+                }
+            }
+        }
+    }
+    Some((opts, i))
+}
+
+/// Result of reading a byte from a file descriptor.
+pub enum Input {
+    /// a byte has been read.
+    Byte(u8),
+    /// end of file.
+    Eof,
+    /// deadline has been reached before anything could be read.
+    Timeout,
+    /// read error.
+    Error,
+}
+
+/// Read a single byte from a file descriptor, so that nothing after the delimiter is consumed.
+pub fn read_byte(fd: i32, deadline: Option<Instant>) -> Input {
+    if let Some(deadline) = deadline {
+        let now = Instant::now();
+        let ms = if deadline > now {
+            (deadline - now).as_millis() as i32
+        } else {
+            0
+        };
+        let mut pfd = libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        match unsafe { libc::poll(&mut pfd, 1, ms) } {
+            0 => return Input::Timeout,
+            -1 => return Input::Error,
+            _ => (),
+        }
+    }
+    let mut b = 0u8;
+    loop {
+        match unsafe { libc::read(fd, &mut b as *mut u8 as *mut c_void, 1) } {
+            1 => return Input::Byte(b),
+            0 => return Input::Eof,
+            _ if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted => (),
+            _ => return Input::Error,
+        }
+    }
+}
+
+/// Check a file descriptor is open.
+fn valid_fd(fd: i32) -> bool {
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+    flags != -1
+}
+
+/// Split read input on IFS into at most `max` fields, the last one getting the remaining of the line.
+/// Characters are given with a flag telling if they were escaped by a backslash, in which case they do not delimit fields.
+fn split_fields(line: &[(char, bool)], ifs: &str, max: usize) -> Vec<String> {
+    let is_ifs = |(c, escaped): (char, bool)| !escaped && ifs.contains(c);
+    let is_blank = |(c, escaped): (char, bool)| is_ifs((c, escaped)) && c.is_whitespace();
+    let text = |part: &[(char, bool)]| part.iter().map(|(c, _)| *c).collect::<String>();
+    let len = line.len();
+    let mut fields = Vec::new();
+    let mut i = 0;
+    while i < len && is_blank(line[i]) {
+        i += 1;
+    }
+    while i < len {
+        if fields.len() + 1 == max {
+            let mut end = len;
+            while end > i && is_blank(line[end - 1]) {
+                end -= 1;
+            }
+            fields.push(text(&line[i..end]));
+            break;
+        }
+        let start = i;
+        while i < len && !is_ifs(line[i]) {
+            i += 1;
+        }
+        fields.push(text(&line[start..i]));
+        // a delimiter is made of IFS whitespaces, around at most one other IFS character.
+        while i < len && is_blank(line[i]) {
+            i += 1;
+        }
+        if i < len && is_ifs(line[i]) {
+            i += 1;
+            while i < len && is_blank(line[i]) {
+                i += 1;
+            }
+        }
+    }
+    fields
+}
+
+/// Where backslash escapes are expanded, which changes octal and `\c` handling.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Escapes {
+    /// echo -e: `\0nnn` octal, `\c` stops output.
+    Echo,
+    /// printf %b argument: `\0nnn` and `\nnn` octal, `\c` stops output.
+    PrintfArg,
+    /// printf format: `\nnn` octal.
+    PrintfFormat,
+}
+
+/// Expand backslash escapes: `\a \b \e \f \n \r \t \v \\`, octal, `\xHH`, `\uHHHH` and `\UHHHHHHHH`.
+/// Unknown escapes are kept as is. Returns expanded string, and true if `\c` asked to stop output.
+///
+/// # Examples
+/// ```rust
+/// use rush::builtins::{escapes, Escapes};
+///
+/// assert_eq!(escapes("a\\tb\\0101\\x41\\u00e9", Escapes::Echo), ("a\tbAAé".to_string(), false));
+/// assert_eq!(escapes("a\\cb", Escapes::Echo), ("a".to_string(), true));
+/// assert_eq!(escapes("\\101\\q", Escapes::PrintfFormat), ("A\\q".to_string(), false));
+/// ```
+pub fn escapes(s: &str, mode: Escapes) -> (String, bool) {
+    let chars: Vec<char> = s.chars().collect();
+    let mut out: Vec<u8> = Vec::with_capacity(s.len());
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == '\\' && i + 1 < chars.len() {
+            match escape(&chars, i + 1, mode, &mut out) {
+                Some(next) => i = next,
+                None => return (String::from_utf8_lossy(&out).into_owned(), true),
+            }
+        } else {
+            push_char(&mut out, chars[i]);
+            i += 1;
+        }
+    }
+    (String::from_utf8_lossy(&out).into_owned(), false)
+}
+
+/// Append a character to an output buffer, UTF-8 encoded.
+fn push_char(out: &mut Vec<u8>, c: char) {
+    let mut buf = [0; 4];
+    out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+}
+
+/// Expand the escape sequence starting at `chars[i]`, just after a backslash, into `out`.
+/// Returns position following the sequence, None if it is `\c`.
+fn escape(chars: &[char], i: usize, mode: Escapes, out: &mut Vec<u8>) -> Option<usize> {
+    // value of at most `max` digits in given radix, and number of digits.
+    let digits = |start: usize, max: usize, radix: u32| -> (u32, usize) {
+        let mut value = 0;
+        let mut count = 0;
+        while count < max {
+            match chars.get(start + count).and_then(|c| c.to_digit(radix)) {
+                Some(d) => value = value * radix + d,
+                None => break,
+            }
+            count += 1;
+        }
+        (value, count)
+    };
+    let e = chars[i];
+    let byte = match e {
+        'a' => Some(7),
+        'b' => Some(8),
+        'e' | 'E' => Some(27),
+        'f' => Some(12),
+        'n' => Some(b'\n'),
+        'r' => Some(b'\r'),
+        't' => Some(b'\t'),
+        'v' => Some(11),
+        '\\' => Some(b'\\'),
+        _ => None,
+    };
+    if let Some(b) = byte {
+        out.push(b);
+        return Some(i + 1);
+    }
+    match e {
+        'c' if mode != Escapes::PrintfFormat => None,
+        '0' if mode != Escapes::PrintfFormat => {
+            let (value, count) = digits(i + 1, 3, 8);
+            out.push(value as u8);
+            Some(i + 1 + count)
+        }
+        '0'..='7' if mode != Escapes::Echo => {
+            let (value, count) = digits(i, 3, 8);
+            out.push(value as u8);
+            Some(i + count)
+        }
+        'x' | 'u' | 'U' => {
+            let max = match e {
+                'x' => 2,
+                'u' => 4,
+                _ => 8,
+            };
+            let (value, count) = digits(i + 1, max, 16);
+            if count == 0 {
+                out.push(b'\\');
+                push_char(out, e);
+            } else if e == 'x' {
+                out.push(value as u8);
+            } else {
+                push_char(out, std::char::from_u32(value).unwrap_or('\u{fffd}'));
+            }
+            Some(i + 1 + count)
+        }
+        '"' | '\'' if mode == Escapes::PrintfFormat => {
+            out.push(e as u8);
+            Some(i + 1)
+        }
+        _ => {
+            out.push(b'\\');
+            push_char(out, e);
+            Some(i + 1)
+        }
+    }
+}
+
+/// Quote a value with backslashes so that it can be reused as shell input, the way printf %q does.
+/// Values containing control characters are quoted with $'...'.
+///
+/// # Examples
+/// ```rust
+/// use rush::builtins;
+///
+/// assert_eq!(builtins::quote("it's a test"), "it\\'s\\ a\\ test");
+/// assert_eq!(builtins::quote("a\tb"), "$'a\\tb'");
+/// assert_eq!(builtins::quote(""), "''");
+/// ```
+pub fn quote(value: &str) -> String {
+    if value.is_empty() {
+        return String::from("''");
+    }
+    if value.chars().any(|c| c.is_control()) {
+        let mut s = String::from("$'");
+        for c in value.chars() {
+            match c {
+                '\x07' => s.push_str("\\a"),
+                '\x08' => s.push_str("\\b"),
+                '\x1b' => s.push_str("\\E"),
+                '\x0c' => s.push_str("\\f"),
+                '\n' => s.push_str("\\n"),
+                '\r' => s.push_str("\\r"),
+                '\t' => s.push_str("\\t"),
+                '\x0b' => s.push_str("\\v"),
+                '\\' | '\'' => {
+                    s.push('\\');
+                    s.push(c);
+                }
+                _ if c.is_control() => {
+                    let _ = write!(s, "\\{:03o}", c as u32);
+                }
+                _ => s.push(c),
+            }
+        }
+        s.push('\'');
+        return s;
+    }
+    let mut s = String::with_capacity(value.len() * 2);
+    for (i, c) in value.chars().enumerate() {
+        if " \t!\"$&'()*,;<>?[\\]^`{|}".contains(c) || (i == 0 && (c == '~' || c == '#')) {
+            s.push('\\');
+        }
+        s.push(c);
+    }
+    s
+}
+
+/// Quote a value between double quotes, the way declare -p does.
+pub fn dquote(value: &str) -> String {
+    let mut s = String::with_capacity(value.len() + 2);
+    s.push('"');
+    for c in value.chars() {
+        if let '"' | '\\' | '$' | '`' = c {
+            s.push('\\');
+        }
+        s.push(c);
+    }
+    s.push('"');
+    s
+}
+
+/// Quote a value between single quotes if needed, so that it can be reused as input, the way set does.
+///
+/// # Examples
+/// ```rust
+/// use rush::builtins;
+///
+/// assert_eq!(builtins::squote("/usr/bin:/bin"), "/usr/bin:/bin");
+/// assert_eq!(builtins::squote("it's"), "'it'\\''s'");
+/// assert_eq!(builtins::squote(""), "''");
+/// ```
+pub fn squote(value: &str) -> String {
+    if !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_alphanumeric() || "_-./:,+=@%".contains(c))
+    {
+        return value.to_string();
+    }
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// export builtin: export [-n] [-p] [name[=value] ...]
+/// Mark variables to be passed to child processes environment, -n removes the export attribute.
+pub fn export(rush: &mut RuSh, args: &[String]) -> i32 {
+    let mut unexport = false;
+    let mut names = Vec::new();
+    let mut options = true;
+    for arg in &args[1..] {
+        if options && arg == "--" {
+            options = false;
+        } else if options && arg.starts_with('-') && arg.len() > 1 {
+            for c in arg[1..].chars() {
+                match c {
+                    'n' => unexport = true,
+                    'p' => (),
+                    _ => {
+                        eprintln!("rush: export: -{}: invalid option", c);
+                        eprintln!("export: usage: export [-n] [-p] [name[=value] ...]");
+                        return 2;
+                    }
+                }
+            }
+        } else {
+            options = false;
+            names.push(arg);
+        }
+    }
+    if names.is_empty() {
+        for name in rush.shell_vars.exported() {
+            if let Some(var) = rush.shell_vars.get(&name) {
+                let _ = writeln!(
+                    io::stdout(),
+                    "declare -x {}={}",
+                    name,
+                    dquote(&var.value.to_string())
+                );
+            }
+        }
+        return 0;
+    }
+    let mut status = 0;
+    for arg in names {
+        let (name, value) = match arg.find('=') {
+            Some(pos) => (&arg[..pos], Some(&arg[pos + 1..])),
+            None => (arg.as_str(), None),
+        };
+        if !Variables::is_name(name) {
+            eprintln!("rush: export: `{}': not a valid identifier", arg);
+            status = 1;
+            continue;
+        }
+        if let Some(value) = value {
+            execute::assign(rush, name, None, value);
+        }
+        if !unexport || rush.shell_vars.get(name).is_some() {
+            rush.shell_vars.set_export(name.to_string(), !unexport);
+        }
+    }
+    status
+}
+
+/// Print an option status, as `set -o`/`shopt` do, or as a command allowing to restore it.
+fn print_opt(name: &str, set: bool, reusable: bool, setcmd: bool) {
+    let _ = match (reusable, setcmd) {
+        (false, _) => writeln!(
+            io::stdout(),
+            "{:<15}\t{}",
+            name,
+            if set { "on" } else { "off" }
+        ),
+        (true, true) => writeln!(
+            io::stdout(),
+            "set {}o {}",
+            if set { '-' } else { '+' },
+            name
+        ),
+        (true, false) => writeln!(
+            io::stdout(),
+            "shopt {} {}",
+            if set { "-s" } else { "-u" },
+            name
+        ),
+    };
+}
+
+/// Set an option. vi and emacs editing modes exclude each other.
+fn set_opt(rush: &mut RuSh, name: &str, set: bool) -> i32 {
+    match rush.set_options.get(name) {
+        Some(_) => {
+            if !rush.set_options.update(name, set) {
+                eprintln!("rush: set: {}: readonly option", name);
+                return 1;
+            }
+            match (name, set) {
+                ("vi", true) => rush.set_options.update("emacs", false),
+                ("emacs", true) => rush.set_options.update("vi", false),
+                _ => true,
+            };
+            0
+        }
+        None => {
+            eprintln!("rush: set: {}: invalid option name", name);
+            1
+        }
+    }
+}
+
+/// set builtin: set [-abefhkmnptuvxBCEHPT] [-o option-name] [--] [-] [arg ...]
+/// Set or unset shell options and positional parameters. Without arguments, print shell variables.
+pub fn set(rush: &mut RuSh, args: &[String]) -> i32 {
+    if args.len() == 1 {
+        for name in rush.shell_vars.names() {
+            if let Some(var) = rush.shell_vars.get(&name) {
+                let _ = writeln!(io::stdout(), "{}={}", name, squote(&var.value.to_string()));
+            }
+        }
+        return 0;
+    }
+    let mut status = 0;
+    let mut positional = None;
+    let mut i = 1;
+    while i < args.len() {
+        let arg = &args[i];
+        i += 1;
+        if arg == "--" {
+            positional = Some(i);
+            break;
+        }
+        if arg == "-" {
+            // - turns off -x and -v, remaining arguments are positional parameters.
+            set_opt(rush, "xtrace", false);
+            set_opt(rush, "verbose", false);
+            positional = Some(i);
+            break;
+        }
+        let set = match arg.chars().next() {
+            Some('-') if arg.len() > 1 => true,
+            Some('+') if arg.len() > 1 => false,
+            _ => {
+                positional = Some(i - 1);
+                break;
+            }
+        };
+        for c in arg[1..].chars() {
+            if c == 'o' {
+                match args.get(i) {
+                    Some(name) => {
+                        i += 1;
+                        status |= set_opt(rush, name, set);
+                    }
+                    None => {
+                        for name in rush.set_options.names() {
+                            let o = rush.set_options.get(&name).unwrap();
+                            print_opt(&name, o.get(), !set, true);
+                        }
+                    }
+                }
+                continue;
+            }
+            match SET_FLAGS.iter().find(|(flag, _)| *flag == c) {
+                Some((_, name)) => status |= set_opt(rush, name, set),
+                None => {
+                    eprintln!(
+                        "rush: set: {}{}: invalid option",
+                        if set { '-' } else { '+' },
+                        c
+                    );
+                    eprintln!("set: usage: set [-abefhkmnptuvxBCEHPT] [-o option-name] [--] [-] [arg ...]");
+                    rush.update_opts_vars();
+                    return 2;
+                }
+            }
+        }
+    }
+    if let Some(start) = positional {
+        rush.positional.truncate(1);
+        rush.positional.extend_from_slice(&args[start..]);
+    }
+    rush.update_opts_vars();
+    status
+}
+
+/// shopt builtin: shopt [-pqsu] [-o] [optname ...]
+/// Set (-s) or unset (-u) shell options, -o restricting to set -o ones. Without -s or -u, print options status,
+/// -q only giving back 0 if every given option is set.
+pub fn shopt(rush: &mut RuSh, args: &[String]) -> i32 {
+    let (mut set, mut unset, mut quiet, mut reusable, mut setcmd) =
+        (false, false, false, false, false);
+    let mut names = Vec::new();
+    let mut options = true;
+    for arg in &args[1..] {
+        if options && arg == "--" {
+            options = false;
+        } else if options && arg.starts_with('-') && arg.len() > 1 {
+            for c in arg[1..].chars() {
+                match c {
+                    's' => set = true,
+                    'u' => unset = true,
+                    'q' => quiet = true,
+                    'p' => reusable = true,
+                    'o' => setcmd = true,
+                    _ => {
+                        eprintln!("rush: shopt: -{}: invalid option", c);
+                        eprintln!("shopt: usage: shopt [-pqsu] [-o] [optname ...]");
+                        return 2;
+                    }
+                }
+            }
+        } else {
+            options = false;
+            names.push(arg.as_str());
+        }
+    }
+    if set && unset {
+        eprintln!("rush: shopt: cannot set and unset shell options simultaneously");
+        return 1;
+    }
+    let opts: &mut Opt = if setcmd {
+        &mut rush.set_options
+    } else {
+        &mut rush.shopt_options
+    };
+    // no option name given, list options, only set or unset ones with -s or -u.
+    if names.is_empty() {
+        for name in opts.names() {
+            let value = opts.get(&name).unwrap().get();
+            // -s lists set options only, -u unset ones.
+            let listed = (!set || value) && (!unset || !value);
+            if !quiet && listed {
+                print_opt(&name, value, reusable, setcmd);
+            }
+        }
+        return 0;
+    }
+    let mut status = 0;
+    for name in names {
+        let value = match opts.get(name) {
+            Some(o) => o.get(),
+            None => {
+                if setcmd {
+                    eprintln!("rush: shopt: {}: invalid option name", name);
+                } else {
+                    eprintln!("rush: shopt: {}: invalid shell option name", name);
+                }
+                status = 1;
+                continue;
+            }
+        };
+        if set || unset {
+            if !opts.update(name, set) {
+                eprintln!("rush: shopt: {}: readonly option", name);
+                status = 1;
+            } else if setcmd && set && (name == "vi" || name == "emacs") {
+                opts.update(if name == "vi" { "emacs" } else { "vi" }, false);
+            }
+        } else {
+            if !value {
+                status = 1;
+            }
+            if !quiet {
+                print_opt(name, value, reusable, setcmd);
+            }
+        }
+    }
+    rush.update_opts_vars();
+    status
+}
+
+/// read builtin: read [-rs] [-a array] [-d delim] [-n nchars] [-p prompt] [-t timeout] [-u fd] [name ...]
+/// Read a line from stdin (or -u file descriptor), split it on IFS and assign fields to names,
+/// the last one getting the remaining of the line. Line is assigned to REPLY if no name is given.
+/// Gives back 1 on end of file, and more than 128 on timeout.
+pub fn read(rush: &mut RuSh, args: &[String]) -> i32 {
+    let usage =
+        "read [-rs] [-a array] [-d delim] [-n nchars] [-p prompt] [-t timeout] [-u fd] [name ...]";
+    let (opts, first) = match options("read", args, "a:d:n:p:rst:u:", usage) {
+        Some(o) => o,
+        None => return 2,
+    };
+    let mut array = None;
+    let mut delim = b'\n';
+    let mut nchars = None;
+    let mut prompt = None;
+    let (mut raw, mut silent) = (false, false);
+    let mut timeout = None;
+    let mut fd = 0;
+    for (opt, arg) in opts {
+        match opt {
+            'a' => array = Some(arg),
+            // an empty delimiter means NUL.
+            'd' => delim = arg.bytes().next().unwrap_or(0),
+            'n' => match arg.parse::<usize>() {
+                Ok(n) => nchars = Some(n),
+                Err(_) => {
+                    eprintln!("rush: read: {}: invalid number", arg);
+                    return 1;
+                }
+            },
+            'p' => prompt = Some(arg),
+            'r' => raw = true,
+            's' => silent = true,
+            't' => match arg.parse::<f64>() {
+                Ok(t) if t >= 0.0 => timeout = Some(t),
+                _ => {
+                    eprintln!("rush: read: {}: invalid timeout specification", arg);
+                    return 1;
+                }
+            },
+            'u' => match arg.parse::<i32>() {
+                Ok(f) if f >= 0 => fd = f,
+                _ => {
+                    eprintln!("rush: read: {}: invalid file descriptor specification", arg);
+                    return 1;
+                }
+            },
+            _ => unreachable!(),
+        }
+    }
+    let names = &args[first..];
+    for name in names.iter().chain(array.iter()) {
+        if !Variables::is_name(name) {
+            eprintln!("rush: read: `{}': not a valid identifier", name);
+            return 1;
+        }
+    }
+    if !valid_fd(fd) {
+        eprintln!(
+            "rush: read: {}: invalid file descriptor: {}",
+            fd,
+            execute::os_error(&io::Error::last_os_error())
+        );
+        return 1;
+    }
+    // -t 0 only tells if there is something to read.
+    if timeout == Some(0.0) {
+        let mut pfd = libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        return if unsafe { libc::poll(&mut pfd, 1, 0) } > 0 {
+            0
+        } else {
+            1
+        };
+    }
+    let tty = unsafe { libc::isatty(fd) } == 1;
+    if let Some(prompt) = prompt {
+        if tty {
+            eprint!("{}", prompt);
+        }
+    }
+    // silent mode: terminal echo is disabled while reading.
+    let mut termios = None;
+    if silent && tty {
+        let mut t: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(fd, &mut t) } == 0 {
+            let mut noecho = t;
+            noecho.c_lflag &= !(libc::ECHO | libc::ECHONL);
+            unsafe { libc::tcsetattr(fd, libc::TCSANOW, &noecho) };
+            termios = Some(t);
+        }
+    }
+    let deadline = timeout.map(|t| Instant::now() + Duration::from_secs_f64(t));
+    let mut line: Vec<(char, bool)> = Vec::new();
+    let mut pending: Vec<u8> = Vec::with_capacity(4);
+    let (mut backslash, mut escaped) = (false, false);
+    let mut status = 0;
+    while nchars.is_none_or(|n| line.len() < n) {
+        let b = match read_byte(fd, deadline) {
+            Input::Byte(b) => b,
+            Input::Eof | Input::Error => {
+                status = 1;
+                break;
+            }
+            Input::Timeout => {
+                status = 142;
+                break;
+            }
+        };
+        if backslash {
+            backslash = false;
+            // backslash newline is a line continuation.
+            if b == b'\n' {
+                continue;
+            }
+            escaped = true;
+        } else if !raw && b == b'\\' {
+            backslash = true;
+            continue;
+        } else if b == delim {
+            break;
+        }
+        pending.push(b);
+        match str::from_utf8(&pending) {
+            Ok(s) => {
+                line.extend(s.chars().map(|c| (c, escaped)));
+                pending.clear();
+                escaped = false;
+            }
+            // incomplete multibyte character.
+            Err(e) if e.error_len().is_none() => (),
+            Err(_) => {
+                line.extend(
+                    String::from_utf8_lossy(&pending)
+                        .chars()
+                        .map(|c| (c, escaped)),
+                );
+                pending.clear();
+                escaped = false;
+            }
+        }
+    }
+    if let Some(t) = termios {
+        unsafe { libc::tcsetattr(fd, libc::TCSANOW, &t) };
+    }
+    let ifs = expand::ifs(rush);
+    if let Some(array) = array {
+        rush.shell_array_vars.clear(&array);
+        for (i, field) in split_fields(&line, &ifs, usize::MAX)
+            .into_iter()
+            .enumerate()
+        {
+            rush.shell_array_vars
+                .set(&array, Index::I(i), parse::value(&field));
+        }
+    } else if names.is_empty() {
+        let reply: String = line.iter().map(|(c, _)| *c).collect();
+        execute::assign(rush, "REPLY", None, &reply);
+    } else {
+        let fields = split_fields(&line, &ifs, names.len());
+        for (i, name) in names.iter().enumerate() {
+            let value = fields.get(i).map_or("", |f| f.as_str());
+            execute::assign(rush, name, None, value);
+        }
+    }
+    status
+}
+
+/// getopts builtin: getopts optstring name [arg ...]
+/// Parse options from positional parameters, or from given arguments. Each call stores next option letter in name,
+/// its argument in OPTARG, and index of next argument to process in OPTIND. Returns 1 once options are exhausted.
+/// A leading `:` in optstring selects silent error reporting: name gets `?` or `:` and OPTARG the faulty letter.
+/// Otherwise errors are printed when OPTERR is 1.
+pub fn getopts(rush: &mut RuSh, args: &[String]) -> i32 {
+    if args.len() < 3 {
+        eprintln!("getopts: usage: getopts optstring name [arg ...]");
+        return 2;
+    }
+    let (optstring, name) = (&args[1], &args[2]);
+    if !Variables::is_name(name) {
+        eprintln!("rush: getopts: `{}': not a valid identifier", name);
+        return 1;
+    }
+    let argv: Vec<String> = if args.len() > 3 {
+        args[3..].to_vec()
+    } else {
+        rush.positional[1..].to_vec()
+    };
+    let silent = optstring.starts_with(':');
+    let opterr = match rush.shell_vars.get("OPTERR") {
+        Some(v) => v.value.to_string() == "1",
+        None => false,
+    };
+    let mut optind = match rush.shell_vars.get("OPTIND") {
+        Some(v) => v.value.to_string().parse::<i64>().unwrap_or(1).max(1),
+        None => 1,
+    };
+    let current = argv.get(optind as usize - 1).cloned().unwrap_or_default();
+    let word: Vec<char> = current.chars().collect();
+    // OPTIND changed behind our back (reset to 1 to parse again, typically), or arguments changed: start from a
+    // new argument.
+    let mut pos =
+        if optind == rush.getopts.0 && current == rush.getopts.2 && rush.getopts.1 < word.len() {
+            rush.getopts.1
+        } else {
+            0
+        };
+    if pos == 0 {
+        // options end with first operand, or with --, which is skipped.
+        if word.len() < 2 || word[0] != '-' {
+            return getopts_end(rush, name, optind);
+        }
+        if word == ['-', '-'] {
+            return getopts_end(rush, name, optind + 1);
+        }
+        pos = 1;
+    }
+    let c = word[pos];
+    pos += 1;
+    if pos >= word.len() {
+        optind += 1;
+        pos = 0;
+    }
+    let mut value = c.to_string();
+    let mut optarg = None;
+    match optstring.find(c) {
+        Some(o) if c != ':' => {
+            if optstring[o + 1..].starts_with(':') {
+                if pos > 0 {
+                    // argument is the remaining of current word.
+                    optarg = Some(word[pos..].iter().collect());
+                    optind += 1;
+                    pos = 0;
+                } else if let Some(arg) = argv.get(optind as usize - 1) {
+                    optarg = Some(arg.clone());
+                    optind += 1;
+                } else if silent {
+                    value = ":".to_string();
+                    optarg = Some(c.to_string());
+                } else {
+                    if opterr {
+                        eprintln!(
+                            "{}: option requires an argument -- {}",
+                            rush.positional[0], c
+                        );
+                    }
+                    value = "?".to_string();
+                }
+            }
+        }
+        _ => {
+            if silent {
+                optarg = Some(c.to_string());
+            } else if opterr {
+                eprintln!("{}: illegal option -- {}", rush.positional[0], c);
+            }
+            value = "?".to_string();
+        }
+    }
+    match optarg {
+        Some(arg) => execute::assign(rush, "OPTARG", None, &arg),
+        None => rush.shell_vars.unset("OPTARG".to_string()),
+    }
+    let current = argv.get(optind as usize - 1).cloned().unwrap_or_default();
+    rush.getopts = (optind, pos, current);
+    execute::assign(rush, "OPTIND", None, &optind.to_string());
+    execute::assign(rush, name, None, &value);
+    0
+}
+
+/// End of getopts options: name is set to `?`, OPTARG is unset, and OPTIND points to first operand.
+fn getopts_end(rush: &mut RuSh, name: &str, optind: i64) -> i32 {
+    rush.shell_vars.unset("OPTARG".to_string());
+    rush.getopts = (optind, 0, String::new());
+    execute::assign(rush, "OPTIND", None, &optind.to_string());
+    execute::assign(rush, name, None, "?");
+    1
+}
+
+/// mapfile builtin: mapfile [-t] [-d delim] [-n count] [-O origin] [-s count] [-u fd] [-C callback] [-c quantum] [array]
+/// Also known as readarray. Read lines into an indexed array, MAPFILE by default.
+/// The array is emptied first, unless an origin is given with -O.
+/// -C callback is evaluated every quantum lines (5000 by default), with index and line as arguments.
+pub fn mapfile(rush: &mut RuSh, args: &[String]) -> i32 {
+    let builtin = args[0].as_str();
+    let usage = format!(
+        "{} [-d delim] [-n count] [-O origin] [-s count] [-t] [-u fd] [-C callback] [-c quantum] [array]",
+        builtin
+    );
+    let (opts, first) = match options(builtin, args, "C:c:d:n:O:s:tu:", &usage) {
+        Some(o) => o,
+        None => return 2,
+    };
+    let mut callback = None;
+    let mut quantum = 5000;
+    let mut delim = b'\n';
+    let mut count = 0;
+    let mut origin = None;
+    let mut skip = 0;
+    let mut trim = false;
+    let mut fd = 0;
+    for (opt, arg) in opts {
+        let number = arg.parse::<usize>();
+        match opt {
+            'C' => callback = Some(arg),
+            'c' => match number {
+                Ok(n) if n > 0 => quantum = n,
+                _ => {
+                    eprintln!("rush: {}: {}: invalid callback quantum", builtin, arg);
+                    return 1;
+                }
+            },
+            // an empty delimiter means NUL.
+            'd' => delim = arg.bytes().next().unwrap_or(0),
+            'n' | 'O' | 's' => match number {
+                Ok(n) => match opt {
+                    'n' => count = n,
+                    'O' => origin = Some(n),
+                    _ => skip = n,
+                },
+                Err(_) => {
+                    let what = match opt {
+                        'O' => "array origin",
+                        _ => "line count",
+                    };
+                    eprintln!("rush: {}: {}: invalid {}", builtin, arg, what);
+                    return 1;
+                }
+            },
+            't' => trim = true,
+            'u' => match arg.parse::<i32>() {
+                Ok(f) if f >= 0 => fd = f,
+                _ => {
+                    eprintln!(
+                        "rush: {}: {}: invalid file descriptor specification",
+                        builtin, arg
+                    );
+                    return 1;
+                }
+            },
+            _ => unreachable!(),
+        }
+    }
+    let array = match args.get(first) {
+        Some(name) => name.clone(),
+        None => "MAPFILE".to_string(),
+    };
+    if !Variables::is_name(&array) {
+        eprintln!("rush: {}: `{}': not a valid identifier", builtin, array);
+        return 1;
+    }
+    if rush.shell_array_vars.get_access(&array) == Some(Access::ReadOnly) {
+        eprintln!("rush: {}: readonly variable", array);
+        return 1;
+    }
+    if !valid_fd(fd) {
+        eprintln!(
+            "rush: {}: {}: invalid file descriptor: {}",
+            builtin,
+            fd,
+            execute::os_error(&io::Error::last_os_error())
+        );
+        return 1;
+    }
+    if origin.is_none() {
+        rush.shell_array_vars.clear(&array);
+    }
+    let mut index = origin.unwrap_or(0);
+    let mut read = 0;
+    let mut line: Vec<u8> = Vec::new();
+    let mut eof = false;
+    while !eof && (count == 0 || read < count) && !execute::stopped(rush) {
+        line.clear();
+        loop {
+            match read_byte(fd, None) {
+                Input::Byte(b) => {
+                    line.push(b);
+                    if b == delim {
+                        break;
+                    }
+                }
+                _ => {
+                    eof = true;
+                    break;
+                }
+            }
+        }
+        if line.is_empty() {
+            break;
+        }
+        if skip > 0 {
+            skip -= 1;
+            continue;
+        }
+        if trim && line.last() == Some(&delim) {
+            line.pop();
+        }
+        let value = String::from_utf8_lossy(&line).into_owned();
+        if let Some(callback) = &callback {
+            if (read + 1) % quantum == 0 {
+                parse::parse(rush, &format!("{} {} {}", callback, index, squote(&value)));
+            }
+        }
+        rush.shell_array_vars
+            .set(&array, Index::I(index), parse::value(&value));
+        index += 1;
+        read += 1;
+    }
+    0
+}
+
+/// source builtin: source filename [arguments]
+/// Also known as `.`. Execute commands from a file in the current shell, filename being looked for in PATH
+/// when sourcepath option is set. Arguments, if any, become positional parameters while the file is executed.
+pub fn source(rush: &mut RuSh, args: &[String]) -> i32 {
+    let name = match args.get(1) {
+        Some(name) => name,
+        None => {
+            eprintln!("rush: {}: filename argument required", args[0]);
+            eprintln!("{}: usage: {} filename [arguments]", args[0], args[0]);
+            return 2;
+        }
+    };
+    let path = script::find_source(rush, name);
+    script::source(rush, &path, &args[2..])
+}
+
+/// Argument of a C format conversion.
+enum CArg {
+    /// integer conversions: d i o u x X.
+    Int(i64),
+    /// floating point conversions: f F e E g G a A.
+    Float(f64),
+}
+
+/// Format a numeric argument with C library snprintf, so that every flag behaves the C way.
+fn c_format(spec: &str, arg: CArg) -> String {
+    let cspec = match CString::new(spec) {
+        Ok(c) => c,
+        Err(_) => return String::new(),
+    };
+    let mut buf: Vec<u8> = vec![0; 128];
+    loop {
+        let len = buf.len();
+        let ptr = buf.as_mut_ptr() as *mut c_char;
+        let n = unsafe {
+            match arg {
+                CArg::Int(i) => libc::snprintf(ptr, len, cspec.as_ptr(), i as libc::c_longlong),
+                CArg::Float(f) => libc::snprintf(ptr, len, cspec.as_ptr(), f as libc::c_double),
+            }
+        };
+        if n < 0 {
+            return String::new();
+        }
+        if (n as usize) < len {
+            buf.truncate(n as usize);
+            return String::from_utf8_lossy(&buf).into_owned();
+        }
+        buf.resize(n as usize + 1, 0);
+    }
+}
+
+/// Width or precision of a printf conversion, which has to fit in a C int: None if it does not, which is reported.
+fn c_int(n: i64, arg: &str, status: &mut i32) -> Option<i64> {
+    match i32::try_from(n) {
+        Ok(_) => Some(n),
+        Err(_) => {
+            eprintln!("rush: printf: warning: {}: Result too large", arg);
+            *status = 1;
+            None
+        }
+    }
+}
+
+/// Pad a string to `width` characters, on the left unless `left` is set.
+fn pad(s: &str, width: usize, left: bool) -> String {
+    let len = s.chars().count();
+    if len >= width {
+        return s.to_string();
+    }
+    let fill = " ".repeat(width - len);
+    if left {
+        format!("{}{}", s, fill)
+    } else {
+        format!("{}{}", fill, s)
+    }
+}
+
+/// Get an integer printf argument: decimal, 0x hexadecimal, 0 octal, or 'c character code. Integer values are
+/// used as is. An invalid number prints an error and gives back its valid beginning, an out of range one saturates.
+fn int_arg(arg: &Value, status: &mut i32) -> i64 {
+    if let Value::I(i) = arg {
+        return *i;
+    }
+    let arg = arg.to_string();
+    let s = arg.trim_start();
+    if s.starts_with('\'') || s.starts_with('"') {
+        return s.chars().nth(1).map_or(0, |c| c as i64);
+    }
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(d) => (true, d),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    let (radix, digits) = if let Some(h) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        (16, h)
+    } else if digits.starts_with('0') && digits.len() > 1 {
+        (8, &digits[1..])
+    } else {
+        (10, digits)
+    };
+    let valid: String = digits.chars().take_while(|c| c.is_digit(radix)).collect();
+    if valid.len() != digits.len() || (digits.is_empty() && !s.is_empty()) {
+        eprintln!("rush: printf: {}: invalid number", arg);
+        *status = 1;
+    }
+    let value = match i128::from_str_radix(&valid, radix) {
+        Ok(v) => Some(if negative { -v } else { v }),
+        Err(_) if valid.is_empty() => Some(0),
+        Err(_) => None,
+    };
+    match value.filter(|v| *v >= i128::from(i64::MIN) && *v <= i128::from(i64::MAX)) {
+        Some(v) => v as i64,
+        None => {
+            eprintln!("rush: printf: warning: {}: Result too large", arg);
+            *status = 1;
+            if negative {
+                i64::MIN
+            } else {
+                i64::MAX
+            }
+        }
+    }
+}
+
+/// Get a floating point printf argument. Float and integer values are used as is.
+fn float_arg(arg: &Value, status: &mut i32) -> f64 {
+    let s = match arg {
+        Value::F(f) => return *f,
+        Value::I(i) => return *i as f64,
+        Value::S(s) => s.trim(),
+    };
+    if s.is_empty() {
+        return 0.0;
+    }
+    match s.parse::<f64>() {
+        Ok(f) => f,
+        Err(_) => {
+            if s.starts_with('\'') || s.starts_with('"') {
+                return s.chars().nth(1).map_or(0.0, |c| c as u32 as f64);
+            }
+            eprintln!("rush: printf: {}: invalid number", s);
+            *status = 1;
+            0.0
+        }
+    }
+}
+
+/// Format `params` once according to `format`, appending to `out`. Arguments are consumed from `pos`.
+/// Returns true if output has to stop (`\c` in a %b argument, invalid format).
+fn format_once(
+    rush: &RuSh,
+    format: &[char],
+    params: &[Value],
+    pos: &mut usize,
+    out: &mut Vec<u8>,
+    status: &mut i32,
+) -> bool {
+    let next = |pos: &mut usize| -> Option<Value> {
+        let arg = params.get(*pos).cloned();
+        if arg.is_some() {
+            *pos += 1;
+        }
+        arg
+    };
+    let mut i = 0;
+    while i < format.len() {
+        let c = format[i];
+        i += 1;
+        if c == '\\' && i < format.len() {
+            i = escape(format, i, Escapes::PrintfFormat, out).unwrap_or(i);
+            continue;
+        }
+        if c != '%' {
+            push_char(out, c);
+            continue;
+        }
+        if i < format.len() && format[i] == '%' {
+            out.push(b'%');
+            i += 1;
+            continue;
+        }
+        // %[flags][width][.precision][length]conversion
+        let start = i - 1;
+        let mut flags = String::new();
+        while i < format.len() && "-+ #0'".contains(format[i]) {
+            flags.push(format[i]);
+            i += 1;
+        }
+        let mut width: Option<i64> = None;
+        if i < format.len() && format[i] == '*' {
+            let arg = next(pos).unwrap_or(Value::S(String::new()));
+            width = c_int(int_arg(&arg, status), &arg.to_string(), status);
+            i += 1;
+        } else {
+            let start = i;
+            while i < format.len() && format[i].is_ascii_digit() {
+                i += 1;
+            }
+            if i > start {
+                let text: String = format[start..i].iter().collect();
+                width = c_int(text.parse().unwrap_or(i64::MAX), &text, status);
+            }
+        }
+        let mut precision: Option<i64> = None;
+        if i < format.len() && format[i] == '.' {
+            i += 1;
+            if i < format.len() && format[i] == '*' {
+                let arg = next(pos).unwrap_or(Value::S(String::new()));
+                precision = c_int(int_arg(&arg, status), &arg.to_string(), status);
+                i += 1;
+            } else {
+                let start = i;
+                while i < format.len() && format[i].is_ascii_digit() {
+                    i += 1;
+                }
+                let text: String = format[start..i].iter().collect();
+                precision = match text.parse() {
+                    Ok(p) => c_int(p, &text, status),
+                    Err(_) if text.is_empty() => Some(0),
+                    Err(_) => c_int(i64::MAX, &text, status),
+                };
+            }
+        }
+        // a negative width means left justification.
+        if let Some(w) = width {
+            if w < 0 {
+                flags.push('-');
+                width = w.checked_neg();
+            }
+        }
+        while i < format.len() && "hlLjzt".contains(format[i]) {
+            i += 1;
+        }
+        let conv = match format.get(i) {
+            Some(c) => *c,
+            None => {
+                let spec: String = format[start..].iter().collect();
+                eprintln!("rush: printf: `{}': missing format character", spec);
+                *status = 1;
+                return true;
+            }
+        };
+        i += 1;
+        let left = flags.contains('-');
+        let width_n = width.unwrap_or(0) as usize;
+        let mut spec = format!("%{}", flags.replace('\'', ""));
+        if let Some(w) = width {
+            spec.push_str(&w.to_string());
+        }
+        if let Some(p) = precision {
+            if p >= 0 {
+                spec.push('.');
+                spec.push_str(&p.to_string());
+            }
+        }
+        let truncate = |s: String| match precision {
+            Some(p) if p >= 0 => s.chars().take(p as usize).collect(),
+            _ => s,
+        };
+        match conv {
+            'd' | 'i' | 'o' | 'u' | 'x' | 'X' => {
+                let n = int_arg(&next(pos).unwrap_or(Value::S(String::new())), status);
+                spec.push_str("ll");
+                spec.push(if conv == 'i' { 'd' } else { conv });
+                out.extend_from_slice(c_format(&spec, CArg::Int(n)).as_bytes());
+            }
+            'f' | 'F' | 'e' | 'E' | 'g' | 'G' | 'a' | 'A' => {
+                let f = float_arg(&next(pos).unwrap_or(Value::S(String::new())), status);
+                spec.push(conv);
+                out.extend_from_slice(c_format(&spec, CArg::Float(f)).as_bytes());
+            }
+            'c' => {
+                // a missing argument gives a NUL character.
+                let c: String = match next(pos) {
+                    Some(arg) => arg.to_string().chars().take(1).collect(),
+                    None => String::from("\0"),
+                };
+                out.extend_from_slice(pad(&c, width_n, left).as_bytes());
+            }
+            's' => {
+                let arg = truncate(next(pos).map(|a| a.to_string()).unwrap_or_default());
+                out.extend_from_slice(pad(&arg, width_n, left).as_bytes());
+            }
+            'q' => {
+                let arg = truncate(quote(&next(pos).map(|a| a.to_string()).unwrap_or_default()));
+                out.extend_from_slice(pad(&arg, width_n, left).as_bytes());
+            }
+            'b' => {
+                let arg = next(pos).map(|a| a.to_string()).unwrap_or_default();
+                let (arg, stop) = escapes(&arg, Escapes::PrintfArg);
+                out.extend_from_slice(pad(&truncate(arg), width_n, left).as_bytes());
+                if stop {
+                    return true;
+                }
+            }
+            // %(fmt)T: date and time formatted with strftime like fmt.
+            '(' => {
+                let rest: String = format[i..].iter().collect();
+                let end = match rest.find(")T") {
+                    Some(e) => e,
+                    None => {
+                        eprintln!("rush: printf: `(': invalid format character");
+                        *status = 1;
+                        return true;
+                    }
+                };
+                i += rest[..end].chars().count() + 2;
+                let fmt = if end == 0 { "%X" } else { &rest[..end] };
+                let arg = next(pos).unwrap_or(Value::S(String::new()));
+                let secs = match arg.to_string().trim() {
+                    "" | "-1" => Local::now().timestamp(),
+                    "-2" => rush.start_time,
+                    _ => int_arg(&arg, status),
+                };
+                let mut date = String::new();
+                if let Some(dt) = Local.timestamp_opt(secs, 0).single() {
+                    if write!(date, "{}", dt.format(fmt)).is_err() {
+                        date.clear();
+                    }
+                }
+                out.extend_from_slice(pad(&truncate(date), width_n, left).as_bytes());
+            }
+            _ => {
+                eprintln!("rush: printf: `{}': invalid format character", conv);
+                *status = 1;
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// Aliases defined in RUSH_ALIASES, sorted by name.
+pub fn aliases(arrays: &Array) -> Vec<(String, String)> {
+    let mut aliases: Vec<(String, String)> = match arrays.arrayvars.get("RUSH_ALIASES") {
+        Some((_, hm)) => hm
+            .iter()
+            .filter_map(|(index, value)| match index {
+                Index::A(name) => Some((name.clone(), value.to_string())),
+                Index::I(_) => None,
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+    aliases.sort();
+    aliases
+}
+
+/// Print an alias so that it can be reused as input.
+fn print_alias(name: &str, value: &str) {
+    let _ = writeln!(
+        io::stdout(),
+        "alias {}='{}'",
+        name,
+        value.replace('\'', "'\\''")
+    );
+}
+
+/// alias builtin: alias [-p] [name[=value] ...]
+/// Define aliases, stored in RUSH_ALIASES. Without value, print the alias. Without arguments or with -p, print every alias.
+pub fn alias(rush: &mut RuSh, args: &[String]) -> i32 {
+    let (opts, first) = match options("alias", args, "p", "alias [-p] [name[=value] ... ]") {
+        Some(o) => o,
+        None => return 2,
+    };
+    if !opts.is_empty() || first == args.len() {
+        for (name, value) in aliases(&rush.shell_array_vars) {
+            print_alias(&name, &value);
+        }
+    }
+    let mut status = 0;
+    for arg in &args[first..] {
+        match arg.find('=') {
+            Some(pos) => {
+                let name = &arg[..pos];
+                if name.is_empty()
+                    || name
+                        .chars()
+                        .any(|c| c.is_whitespace() || "/$`=\\'\"&;|()<>".contains(c))
+                {
+                    eprintln!("rush: alias: `{}': invalid alias name", name);
+                    status = 1;
+                    continue;
+                }
+                rush.shell_array_vars.set(
+                    "RUSH_ALIASES",
+                    Index::A(name.to_string()),
+                    Value::S(arg[pos + 1..].to_string()),
+                );
+            }
+            None => match rush
+                .shell_array_vars
+                .get("RUSH_ALIASES", &Index::A(arg.to_string()))
+            {
+                Some(value) => print_alias(arg, &value.to_string()),
+                None => {
+                    eprintln!("rush: alias: {}: not found", arg);
+                    status = 1;
+                }
+            },
+        }
+    }
+    status
+}
+
+/// unalias builtin: unalias [-a] name [name ...]
+/// Remove aliases from RUSH_ALIASES, every one of them with -a.
+pub fn unalias(rush: &mut RuSh, args: &[String]) -> i32 {
+    let usage = "unalias [-a] name [name ...]";
+    let (opts, first) = match options("unalias", args, "a", usage) {
+        Some(o) => o,
+        None => return 2,
+    };
+    if !opts.is_empty() {
+        for (name, _) in aliases(&rush.shell_array_vars) {
+            rush.shell_array_vars.unset("RUSH_ALIASES", &Index::A(name));
+        }
+        return 0;
+    }
+    if first == args.len() {
+        eprintln!("unalias: usage: {}", usage);
+        return 2;
+    }
+    let mut status = 0;
+    for name in &args[first..] {
+        let index = Index::A(name.to_string());
+        if rush.shell_array_vars.get("RUSH_ALIASES", &index).is_some() {
+            rush.shell_array_vars.unset("RUSH_ALIASES", &index);
+        } else {
+            eprintln!("rush: unalias: {}: not found", name);
+            status = 1;
+        }
+    }
+    status
+}
+
+/// echo builtin: echo [-neE] [arguments]
+/// Write arguments separated by spaces, followed by a newline unless -n is given.
+/// Backslash escapes are expanded with -e, or by default when xpg_echo is set. -E disables them.
+pub fn echo(rush: &mut RuSh, args: &[String]) -> i32 {
+    let mut newline = true;
+    let mut interpret = match rush.shopt_options.get("xpg_echo") {
+        Some(opt) => opt.get(),
+        None => false,
+    };
+    let mut first = 1;
+    // options are only recognized as long as they are made of n, e and E letters.
+    for arg in &args[1..] {
+        if arg.len() < 2 || !arg.starts_with('-') || !arg[1..].chars().all(|c| "neE".contains(c)) {
+            break;
+        }
+        for c in arg[1..].chars() {
+            match c {
+                'n' => newline = false,
+                'e' => interpret = true,
+                _ => interpret = false,
+            }
+        }
+        first += 1;
+    }
+    let mut out = Vec::new();
+    'args: for (n, arg) in args[first..].iter().enumerate() {
+        if n > 0 {
+            out.push(b' ');
+        }
+        if !interpret {
+            out.extend_from_slice(arg.as_bytes());
+            continue;
+        }
+        let chars: Vec<char> = arg.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            if chars[i] == '\\' && i + 1 < chars.len() {
+                match escape(&chars, i + 1, Escapes::Echo, &mut out) {
+                    Some(next) => i = next,
+                    // \c stops output, trailing newline included.
+                    None => {
+                        newline = false;
+                        break 'args;
+                    }
+                }
+            } else {
+                push_char(&mut out, chars[i]);
+                i += 1;
+            }
+        }
+    }
+    if newline {
+        out.push(b'\n');
+    }
+    let mut stdout = io::stdout();
+    match stdout.write_all(&out).and_then(|_| stdout.flush()) {
+        Ok(_) => 0,
+        Err(e) => {
+            eprintln!("rush: echo: write error: {}", execute::os_error(&e));
+            1
+        }
+    }
+}
+
+/// printf builtin: printf [-v var] format [arguments]
+/// Write arguments formatted according to format, which is reused as long as arguments remain.
+/// With -v, output is assigned to var instead of being written to stdout.
+pub fn printf(rush: &mut RuSh, args: &[String]) -> i32 {
+    let usage = "printf [-v var] format [arguments]";
+    let (opts, first) = match options("printf", args, "v:", usage) {
+        Some(o) => o,
+        None => return 2,
+    };
+    let var = opts.into_iter().map(|(_, v)| v).next_back();
+    if let Some(name) = &var {
+        if !Variables::is_name(name) {
+            eprintln!("rush: printf: `{}': not a valid identifier", name);
+            return 2;
+        }
+    }
+    let format: Vec<char> = match args.get(first) {
+        Some(f) => f.chars().collect(),
+        None => {
+            eprintln!("printf: usage: {}", usage);
+            return 2;
+        }
+    };
+    // arguments expanded from a variable keep its type.
+    let values = std::mem::take(&mut rush.values);
+    let params: Vec<Value> = args[first + 1..]
+        .iter()
+        .enumerate()
+        .map(
+            |(i, arg)| match values.get(first + 1 + i).filter(|v| v.to_string() == *arg) {
+                Some(value) => value.clone(),
+                None => Value::S(arg.clone()),
+            },
+        )
+        .collect();
+    let mut out = Vec::new();
+    let mut status = 0;
+    let mut pos = 0;
+    loop {
+        let start = pos;
+        if format_once(rush, &format, &params, &mut pos, &mut out, &mut status) {
+            break;
+        }
+        // format is reused while there are arguments left, and if it consumed any.
+        if pos >= params.len() || pos == start {
+            break;
+        }
+    }
+    match var {
+        Some(name) => execute::assign(rush, &name, None, &String::from_utf8_lossy(&out)),
+        None => {
+            let mut stdout = io::stdout();
+            let _ = stdout.write_all(&out);
+            let _ = stdout.flush();
+        }
+    }
+    status
+}
+
+/// return builtin: return [n]
+/// Leave a function or a sourced file with status n, or the one of the last command executed.
+pub fn return_builtin(rush: &mut RuSh, args: &[String]) -> i32 {
+    if rush.returnable == 0 {
+        eprintln!("rush: return: can only `return' from a function or sourced script");
+        return 1;
+    }
+    let status = match args.get(1) {
+        Some(n) => match n.parse::<i64>() {
+            Ok(n) => (n & 255) as i32,
+            Err(_) => {
+                eprintln!("rush: return: {}: numeric argument required", n);
+                2
+            }
+        },
+        None => rush.last_status,
+    };
+    rush.returning = Some(status);
+    status
+}
+
+/// history builtin: history [-c] [-d offset] [n] or history -anrw [filename] or history -ps arg [arg...]
+/// Display history list with entry numbers, the last n entries only if n is given, timestamps being formatted
+/// with HISTTIMEFORMAT when it is set. -c clears history list, -d deletes an entry or a start-end range of them,
+/// negative offsets counting back from the end. -a appends new entries to history file, -n reads entries added
+/// to it since it was last read, -r reads it, -w writes history list to it. -p prints its arguments history
+/// expanded, and is not kept in history. -s stores them as a single entry in place of the history command.
+pub fn history(rush: &mut RuSh, args: &[String]) -> i32 {
+    let usage =
+        "history [-c] [-d offset] [n] or history -anrw [filename] or history -ps arg [arg...]";
+    let (opts, first) = match options("history", args, "cd:anrwps", usage) {
+        Some(o) => o,
+        None => return 2,
+    };
+    let operands = &args[first..];
+    let has = |c: char| opts.iter().any(|(o, _)| *o == c);
+    if has('p') {
+        // history -p is not part of history.
+        rush.history.remove_current();
+        let mut status = 0;
+        for arg in operands {
+            match history::expand(rush, arg) {
+                Ok(exp) => {
+                    let _ = writeln!(io::stdout(), "{}", exp.line);
+                }
+                Err(e) => {
+                    eprintln!("rush: {}", e);
+                    status = 1;
+                }
+            }
+        }
+        return status;
+    }
+    if has('s') {
+        if !operands.is_empty() {
+            rush.history.remove_current();
+            rush.history.add(&operands.join(" "));
+        }
+        return 0;
+    }
+    if has('c') {
+        rush.history.clear();
+    }
+    if let Some((_, offset)) = opts.iter().rev().find(|(o, _)| *o == 'd') {
+        return history_delete(rush, offset);
+    }
+    let file_opts: Vec<char> = "anrw".chars().filter(|c| has(*c)).collect();
+    if file_opts.len() > 1 {
+        eprintln!("rush: history: cannot use more than one of -anrw");
+        return 1;
+    }
+    if let Some(op) = file_opts.first() {
+        let path = match operands.first().cloned().or_else(|| history::file(rush)) {
+            Some(path) => path,
+            None => return 1,
+        };
+        let result = match op {
+            'a' => history::write_file(rush, &path, true),
+            'w' => history::write_file(rush, &path, false),
+            'n' => history::read_file(rush, &path, true),
+            _ => history::read_file(rush, &path, false),
+        };
+        return match result {
+            Ok(()) => 0,
+            Err(e) => {
+                eprintln!("rush: history: {}: {}", path, execute::os_error(&e));
+                1
+            }
+        };
+    }
+    if has('c') {
+        return 0;
+    }
+    if operands.len() > 1 {
+        eprintln!("rush: history: too many arguments");
+        return 2;
+    }
+    let count = match operands.first() {
+        Some(n) => match n.parse::<usize>() {
+            Ok(n) => n,
+            Err(_) => {
+                eprintln!("rush: history: {}: numeric argument required", n);
+                return 1;
+            }
+        },
+        None => rush.history.len(),
+    };
+    let timefmt = rush
+        .shell_vars
+        .get("HISTTIMEFORMAT")
+        .map(|v| v.value.to_string());
+    let mut out = String::new();
+    let skip = rush.history.len().saturating_sub(count);
+    for (i, entry) in rush.history.iter().enumerate().skip(skip) {
+        let _ = write!(out, "{:5}  ", rush.history.base + i);
+        if let Some(fmt) = &timefmt {
+            let mut date = String::new();
+            match entry.time.and_then(|t| Local.timestamp_opt(t, 0).single()) {
+                Some(dt) => {
+                    if write!(date, "{}", dt.format(fmt)).is_err() {
+                        date.clear();
+                    }
+                }
+                None => date.push_str("?? "),
+            }
+            out.push_str(&date);
+        }
+        out.push_str(&entry.line);
+        out.push('\n');
+    }
+    let _ = io::stdout().write_all(out.as_bytes());
+    0
+}
+
+/// Delete history entries for history -d: an offset, negative ones counting back from the end, or a start-end range.
+fn history_delete(rush: &mut RuSh, offset: &str) -> i32 {
+    let position = |s: &str| -> Option<usize> {
+        let n: i64 = s.parse().ok()?;
+        let n = if n < 0 {
+            i64::try_from(rush.history.number()).ok()? + n
+        } else {
+            n
+        };
+        usize::try_from(n)
+            .ok()
+            .filter(|n| rush.history.get(*n).is_some())
+    };
+    // a range separator is a dash which does not start a negative offset.
+    let (start, end) = match offset.char_indices().skip(1).find(|(_, c)| *c == '-') {
+        Some((pos, _)) => (position(&offset[..pos]), position(&offset[pos + 1..])),
+        None => (position(offset), position(offset)),
+    };
+    match (start, end) {
+        (Some(start), Some(end)) if start <= end => {
+            for n in (start..=end).rev() {
+                rush.history.remove(n);
+            }
+            0
+        }
+        _ => {
+            eprintln!("rush: history: {}: history position out of range", offset);
+            1
+        }
+    }
+}
+
+/// History number a fc operand refers to: a history number, a negative offset from `last`, the last command
+/// before fc, or the most recent command starting with it. Numbers out of history are brought back into it.
+fn fc_find(rush: &RuSh, arg: &str, last: usize) -> Option<usize> {
+    let base = rush.history.base;
+    match arg.parse::<i64>() {
+        Ok(n) => {
+            let n = if n < 0 {
+                i64::try_from(last).ok()? + 1 + n
+            } else {
+                n
+            };
+            Some(usize::try_from(n).unwrap_or(0).clamp(base, last))
+        }
+        Err(_) => (base..=last).rev().find(|n| {
+            rush.history
+                .get(*n)
+                .is_some_and(|e| e.line.starts_with(arg))
+        }),
+    }
+}
+
+/// Create a file from `template`, whose trailing XXXXXX are replaced so that its name is unique. It is only
+/// readable and writable by its owner. Returns its path and the file, open for writing.
+fn temp_file(template: &str) -> io::Result<(String, std::fs::File)> {
+    let template =
+        CString::new(template).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut name = template.into_bytes_with_nul();
+    let fd = unsafe { libc::mkstemp(name.as_mut_ptr() as *mut c_char) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    name.pop();
+    let path = String::from_utf8_lossy(&name).into_owned();
+    Ok((path, unsafe { std::fs::File::from_raw_fd(fd) }))
+}
+
+/// Execute commands given back by fc, after echoing them, in place of fc in history. Returns their exit status.
+fn fc_execute(rush: &mut RuSh, commands: &str) -> i32 {
+    eprintln!("{}", commands.trim_end_matches('\n'));
+    rush.history.remove_current();
+    history::add(rush, commands);
+    if !parse::parse(rush, commands) {
+        return 2;
+    }
+    rush.last_status
+}
+
+/// fc builtin: fc [-e ename] [-lnr] [first] [last] or fc -s [pat=rep] [command]
+/// List (-l) or edit then execute history entries from first to last, which are history numbers, negative offsets
+/// from fc, or prefixes of the most recent command starting with them. Listing goes from the 16th previous
+/// command to the last one by default, edition is about the last one. -n omits entry numbers, -r reverses order.
+/// Commands are edited with ename, FCEDIT, EDITOR or vi, the first one being set. -s, or -e -, executes a command
+/// again after replacing pat with rep. Executed commands take the place of fc in history.
+pub fn fc(rush: &mut RuSh, args: &[String]) -> i32 {
+    let usage = "fc [-e ename] [-lnr] [first] [last] or fc -s [pat=rep] [command]";
+    // negative offsets are not options.
+    let end = args
+        .iter()
+        .skip(1)
+        .position(|a| {
+            a.len() > 1 && a.starts_with('-') && a[1..].chars().all(|c| c.is_ascii_digit())
+        })
+        .map_or(args.len(), |p| p + 1);
+    let (opts, first) = match options("fc", &args[..end], "e:lnrs", usage) {
+        Some(o) => o,
+        None => return 2,
+    };
+    let operands = &args[first..];
+    let has = |c: char| opts.iter().any(|(o, _)| *o == c);
+    let editor = opts
+        .iter()
+        .rev()
+        .find(|(o, _)| *o == 'e')
+        .map(|(_, e)| e.clone());
+    // fc itself is left out of the entries it works on.
+    let current = if rush.history.current { 1 } else { 0 };
+    let last = match (rush.history.number() - 1).checked_sub(current) {
+        Some(last) if last >= rush.history.base => last,
+        _ => {
+            eprintln!("rush: fc: history specification out of range");
+            return 1;
+        }
+    };
+    if has('s') || editor.as_deref() == Some("-") {
+        let (subst, command) = match operands.first() {
+            Some(o) if o.contains('=') => (Some(o), operands.get(1)),
+            _ => (None, operands.first()),
+        };
+        let number = match command {
+            Some(c) => fc_find(rush, c, last),
+            None => Some(last),
+        };
+        let mut line = match number.and_then(|n| rush.history.get(n)) {
+            Some(e) => e.line.clone(),
+            None => {
+                eprintln!("rush: fc: no command found");
+                return 1;
+            }
+        };
+        if let Some((pat, rep)) = subst.and_then(|s| s.split_once('=')) {
+            if !pat.is_empty() {
+                line = line.replace(pat, rep);
+            }
+        }
+        return fc_execute(rush, &line);
+    }
+    let list = has('l');
+    let from = match operands.first() {
+        Some(arg) => fc_find(rush, arg, last),
+        None if list => Some(last.saturating_sub(15).max(rush.history.base)),
+        None => Some(last),
+    };
+    let to = match operands.get(1) {
+        Some(arg) => fc_find(rush, arg, last),
+        None if list => Some(last),
+        None => from,
+    };
+    let (from, to) = match (from, to) {
+        (Some(from), Some(to)) => (from, to),
+        _ => {
+            eprintln!("rush: fc: no command found");
+            return 1;
+        }
+    };
+    let mut numbers: Vec<usize> = (from.min(to)..=from.max(to)).collect();
+    if has('r') != (from > to) {
+        numbers.reverse();
+    }
+    let lines: Vec<(usize, String)> = numbers
+        .iter()
+        .filter_map(|n| rush.history.get(*n).map(|e| (*n, e.line.clone())))
+        .collect();
+    if list {
+        let mut out = String::new();
+        for (n, line) in &lines {
+            if has('n') {
+                let _ = writeln!(out, "\t {}", line);
+            } else {
+                let _ = writeln!(out, "{}\t {}", n, line);
+            }
+        }
+        let _ = io::stdout().write_all(out.as_bytes());
+        return 0;
+    }
+    let editor = match editor {
+        Some(e) => e,
+        None => ["FCEDIT", "EDITOR"]
+            .iter()
+            .filter_map(|name| rush.shell_vars.get(name))
+            .map(|v| v.value.to_string())
+            .find(|e| !e.is_empty())
+            .unwrap_or_else(|| String::from("vi")),
+    };
+    let template = std::env::temp_dir().join("rush-fc-XXXXXX");
+    let (path, mut file) = match temp_file(&template.to_string_lossy()) {
+        Ok(temp) => temp,
+        Err(e) => {
+            eprintln!(
+                "rush: fc: {}: {}",
+                template.display(),
+                execute::os_error(&e)
+            );
+            return 1;
+        }
+    };
+    let contents: String = lines.iter().map(|(_, l)| format!("{}\n", l)).collect();
+    if let Err(e) = file.write_all(contents.as_bytes()) {
+        eprintln!("rush: fc: {}: {}", path, execute::os_error(&e));
+        let _ = std::fs::remove_file(&path);
+        return 1;
+    }
+    drop(file);
+    let edited = if parse::parse(rush, &format!("{} {}", editor, squote(&path))) {
+        rush.last_status
+    } else {
+        2
+    };
+    let commands = std::fs::read(&path).map(|c| String::from_utf8_lossy(&c).into_owned());
+    let _ = std::fs::remove_file(&path);
+    if edited != 0 {
+        return edited;
+    }
+    match commands {
+        Ok(commands) if !commands.trim().is_empty() => fc_execute(rush, &commands),
+        Ok(_) => 0,
+        Err(e) => {
+            eprintln!("rush: fc: {}: {}", path, execute::os_error(&e));
+            1
+        }
+    }
+}
+
+/// Build a completion specification from complete or compgen options.
+/// An invalid option or action name prints an error and gives back None.
+fn compspec(builtin: &str, opts: &[(char, String)]) -> Option<CompSpec> {
+    let mut spec = CompSpec::default();
+    for (c, arg) in opts {
+        let value = Some(arg.clone());
+        match c {
+            'o' => {
+                if !completion::OPTIONS.contains(&arg.as_str()) {
+                    eprintln!("rush: {}: {}: invalid option name", builtin, arg);
+                    return None;
+                }
+                spec.set_option(arg, true);
+            }
+            'A' => {
+                if !completion::ACTIONS.iter().any(|(name, _)| name == arg) {
+                    eprintln!("rush: {}: {}: invalid action name", builtin, arg);
+                    return None;
+                }
+                spec.actions.push(arg.clone());
+            }
+            'G' => spec.glob = value,
+            'W' => spec.words = value,
+            'F' => spec.function = value,
+            'C' => spec.command = value,
+            'X' => spec.filter = value,
+            'P' => spec.prefix = value,
+            'S' => spec.suffix = value,
+            _ => {
+                if let Some((name, _)) = completion::ACTIONS.iter().find(|(_, l)| *l == Some(*c)) {
+                    spec.actions.push(name.to_string());
+                }
+            }
+        }
+    }
+    let mut actions = Vec::new();
+    for action in spec.actions.drain(..) {
+        if !actions.contains(&action) {
+            actions.push(action);
+        }
+    }
+    spec.actions = actions;
+    Some(spec)
+}
+
+/// complete builtin: complete [-abcdefgjksuv] [-pr] [-DEI] [-o option] [-A action] [-G globpat] [-W wordlist]
+/// [-F function] [-C command] [-X filterpat] [-P prefix] [-S suffix] [name ...]
+/// Define how arguments of commands named are completed. -D defines default completion, -E empty line one,
+/// -I command name one. -p (or no option) prints specifications so that they can be reused as input, -r removes them.
+pub fn complete(rush: &mut RuSh, args: &[String]) -> i32 {
+    let usage = "complete [-abcdefgjksuv] [-pr] [-DEI] [-o option] [-A action] [-G globpat] [-W wordlist] [-F function] [-C command] [-X filterpat] [-P prefix] [-S suffix] [name ...]";
+    let (opts, first) = match options(
+        "complete",
+        args,
+        "abcdefgjksuvprDEIo:A:G:W:F:C:X:P:S:",
+        usage,
+    ) {
+        Some(o) => o,
+        None => return 2,
+    };
+    let mut names: Vec<String> = opts
+        .iter()
+        .filter_map(|(c, _)| match c {
+            'D' => Some(completion::DEFAULT),
+            'E' => Some(completion::EMPTY),
+            'I' => Some(completion::INITIAL),
+            _ => None,
+        })
+        .map(String::from)
+        .collect();
+    names.extend_from_slice(&args[first..]);
+    let has = |flag: char| opts.iter().any(|(c, _)| *c == flag);
+    if has('r') {
+        if names.is_empty() {
+            rush.completions.clear();
+            return 0;
+        }
+        let mut status = 0;
+        for name in &names {
+            if rush.completions.remove(name).is_none() {
+                eprintln!("rush: complete: {}: no completion specification", name);
+                status = 1;
+            }
+        }
+        return status;
+    }
+    if has('p') || opts.is_empty() {
+        if names.is_empty() {
+            names = rush.completions.keys().cloned().collect();
+            names.sort();
+        }
+        let mut status = 0;
+        for name in &names {
+            match rush.completions.get(name) {
+                Some(spec) => {
+                    let _ = writeln!(io::stdout(), "{}", spec.print(name));
+                }
+                None => {
+                    eprintln!("rush: complete: {}: no completion specification", name);
+                    status = 1;
+                }
+            }
+        }
+        return status;
+    }
+    let spec = match compspec("complete", &opts) {
+        Some(s) => s,
+        None => return 2,
+    };
+    if names.is_empty() {
+        eprintln!("complete: usage: {}", usage);
+        return 2;
+    }
+    for name in names {
+        rush.completions.insert(name, spec.clone());
+    }
+    0
+}
+
+/// compgen builtin: compgen [-V varname] [-abcdefgjksuv] [-o option] [-A action] [-G globpat] [-W wordlist]
+/// [-F function] [-C command] [-X filterpat] [-P prefix] [-S suffix] [word]
+/// Print matches for word that options given the complete way describe, or store them in array varname.
+/// Exit status is 1 if there are none.
+pub fn compgen(rush: &mut RuSh, args: &[String]) -> i32 {
+    let usage = "compgen [-V varname] [-abcdefgjksuv] [-o option] [-A action] [-G globpat] [-W wordlist] [-F function] [-C command] [-X filterpat] [-P prefix] [-S suffix] [word]";
+    let (opts, first) = match options("compgen", args, "abcdefgjksuvo:A:G:W:F:C:X:P:S:V:", usage) {
+        Some(o) => o,
+        None => return 2,
+    };
+    let spec = match compspec("compgen", &opts) {
+        Some(s) => s,
+        None => return 2,
+    };
+    let word = args.get(first).map(String::as_str).unwrap_or("");
+    let mut matches = completion::generate(rush, &spec, word, None).unwrap_or_default();
+    completion::fallback(rush, &spec, word, &mut matches);
+    match opts.iter().rev().find(|(c, _)| *c == 'V') {
+        Some((_, name)) => {
+            rush.shell_array_vars.clear(name);
+            for (i, m) in matches.iter().enumerate() {
+                rush.shell_array_vars
+                    .set(name, Index::I(i), Value::S(m.clone()));
+            }
+        }
+        None => {
+            let mut out = io::stdout();
+            for m in &matches {
+                let _ = writeln!(out, "{}", m);
+            }
+        }
+    }
+    if matches.is_empty() {
+        1
+    } else {
+        0
+    }
+}
+
+/// Print completion options the way they are given to compopt.
+fn print_compopt(spec: &CompSpec, name: Option<&str>) {
+    let mut s = String::from("compopt");
+    for option in completion::OPTIONS {
+        let flag = if spec.option(option) { '-' } else { '+' };
+        let _ = write!(s, " {}o {}", flag, option);
+    }
+    match name {
+        Some(completion::DEFAULT) => s.push_str(" -D"),
+        Some(completion::EMPTY) => s.push_str(" -E"),
+        Some(completion::INITIAL) => s.push_str(" -I"),
+        Some(name) => {
+            s.push(' ');
+            s.push_str(name);
+        }
+        None => (),
+    }
+    let _ = writeln!(io::stdout(), "{}", s);
+}
+
+/// compopt builtin: compopt [-o option] [+o option] [-DEI] [name ...]
+/// Set (-o) or unset (+o) completion options of commands named, or of the completion being generated
+/// when no name is given. Without -o nor +o, print completion options.
+pub fn compopt(rush: &mut RuSh, args: &[String]) -> i32 {
+    let usage = "compopt [-o|+o option] [-DEI] [name ...]";
+    let mut changes: Vec<(String, bool)> = Vec::new();
+    let mut names: Vec<String> = Vec::new();
+    let mut i = 1;
+    while let Some(arg) = args.get(i) {
+        if arg == "--" {
+            i += 1;
+            break;
+        }
+        if arg.len() < 2 || !(arg.starts_with('-') || arg.starts_with('+')) {
+            break;
+        }
+        i += 1;
+        let set = arg.starts_with('-');
+        for c in arg[1..].chars() {
+            match c {
+                'o' => {
+                    let name = match args.get(i) {
+                        Some(name) => name,
+                        None => {
+                            eprintln!("rush: compopt: -o: option requires an argument");
+                            eprintln!("compopt: usage: {}", usage);
+                            return 2;
+                        }
+                    };
+                    i += 1;
+                    if !completion::OPTIONS.contains(&name.as_str()) {
+                        eprintln!("rush: compopt: {}: invalid option name", name);
+                        return 2;
+                    }
+                    changes.push((name.clone(), set));
+                }
+                'D' if set => names.push(completion::DEFAULT.to_string()),
+                'E' if set => names.push(completion::EMPTY.to_string()),
+                'I' if set => names.push(completion::INITIAL.to_string()),
+                _ => {
+                    eprintln!("rush: compopt: {}{}: invalid option", &arg[..1], c);
+                    eprintln!("compopt: usage: {}", usage);
+                    return 2;
+                }
+            }
+        }
+    }
+    names.extend_from_slice(&args[i..]);
+    if names.is_empty() {
+        return match rush.completion.as_mut() {
+            Some(spec) => {
+                if changes.is_empty() {
+                    print_compopt(spec, None);
+                }
+                for (name, set) in &changes {
+                    spec.set_option(name, *set);
+                }
+                0
+            }
+            None => {
+                eprintln!("rush: compopt: not currently executing completion function");
+                1
+            }
+        };
+    }
+    let mut status = 0;
+    for name in &names {
+        match rush.completions.get_mut(name) {
+            Some(spec) => {
+                if changes.is_empty() {
+                    print_compopt(spec, Some(name));
+                }
+                for (option, set) in &changes {
+                    spec.set_option(option, *set);
+                }
+            }
+            None => {
+                eprintln!("rush: compopt: {}: no completion specification", name);
+                status = 1;
+            }
+        }
+    }
+    status
+}
+
+/// Print key bindings of a keymap, re-readable or not: functions and the keys bound to them,
+/// or macros if `macros` is set.
+fn print_bindings(rush: &RuSh, keymap: &str, macros: bool, reusable: bool) {
+    for (key, binding) in rush.inputrc.bindings(keymap) {
+        let seq = inputrc::keyseq(*key);
+        match binding {
+            Binding::Macro(text) if macros => {
+                if reusable {
+                    let _ = writeln!(io::stdout(), "\"{}\": \"{}\"", seq, text.escape_default());
+                } else {
+                    let _ = writeln!(io::stdout(), "{} outputs {}", seq, text);
+                }
+            }
+            _ => (),
+        }
+    }
+    if macros {
+        return;
+    }
+    for name in inputrc::FUNCTIONS {
+        let keys = function_keys(rush, keymap, name);
+        let _ = match (keys.is_empty(), reusable) {
+            (true, true) => writeln!(io::stdout(), "# {} (not bound)", name),
+            (true, false) => writeln!(io::stdout(), "{} is not bound to any keys", name),
+            (false, true) => {
+                for seq in keys {
+                    let _ = writeln!(io::stdout(), "\"{}\": {}", seq, name);
+                }
+                Ok(())
+            }
+            (false, false) => writeln!(
+                io::stdout(),
+                "{} can be found on {}.",
+                name,
+                keys.iter()
+                    .map(|k| format!("\"{}\"", k))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+        };
+    }
+}
+
+/// Key sequences bound to a function in a keymap, with the ones the line editor binds in emacs keymap.
+fn function_keys(rush: &RuSh, keymap: &str, function: &str) -> Vec<String> {
+    let bindings = rush.inputrc.bindings(keymap);
+    let defaults: &[(KeyPress, &str)] = match keymap {
+        "emacs" => inputrc::EMACS,
+        _ => &[],
+    };
+    let binding = Binding::Function(function.to_string());
+    bindings
+        .iter()
+        .filter(|(_, b)| *b == binding)
+        .map(|(k, _)| *k)
+        .chain(
+            defaults
+                .iter()
+                .filter(|(k, f)| *f == function && !bindings.iter().any(|(b, _)| b == k))
+                .map(|(k, _)| *k),
+        )
+        .map(inputrc::keyseq)
+        .collect()
+}
+
+/// bind builtin: set line editor key bindings and variables, the way inputrc does.
+/// Only single keys can be bound, and shell commands cannot. Bindings the line editor has in emacs keymap are
+/// listed too, but -u and -r only remove the ones inputrc or bind made.
+pub fn bind(rush: &mut RuSh, args: &[String]) -> i32 {
+    let usage = "bind [-lpsvPSVX] [-m keymap] [-f filename] [-q name] [-u name] [-r keyseq] [-x keyseq:shell-command] [keyseq:readline-function or readline-command]";
+    let (opts, first) = match options("bind", args, "lpsvPSVXm:f:q:u:r:x:", usage) {
+        Some(parsed) => parsed,
+        None => return 2,
+    };
+    let mut keymap = inputrc::current_keymap(rush);
+    if let Some((_, name)) = opts.iter().rev().find(|(c, _)| *c == 'm') {
+        match inputrc::keymap(name) {
+            Some(k) => keymap = k.to_string(),
+            None => {
+                eprintln!("rush: bind: `{}': invalid keymap name", name);
+                return 1;
+            }
+        }
+    }
+    let mut status = 0;
+    for (c, arg) in &opts {
+        match c {
+            'l' => {
+                for name in inputrc::FUNCTIONS {
+                    let _ = writeln!(io::stdout(), "{}", name);
+                }
+            }
+            'p' | 'P' => print_bindings(rush, &keymap, false, *c == 'p'),
+            's' | 'S' => print_bindings(rush, &keymap, true, *c == 's'),
+            'v' | 'V' => {
+                let mut names: Vec<String> = inputrc::VARIABLES
+                    .iter()
+                    .map(|(n, _)| n.to_string())
+                    .chain(rush.inputrc.variables.keys().cloned())
+                    .collect();
+                names.sort();
+                names.dedup();
+                for name in names {
+                    let value = inputrc::variable(rush, &name);
+                    if *c == 'v' {
+                        let _ = writeln!(io::stdout(), "set {} {}", name, value);
+                    } else {
+                        let _ = writeln!(io::stdout(), "{} is set to `{}'", name, value);
+                    }
+                }
+            }
+            'f' if !inputrc::read(rush, arg, Some(&keymap)) => {
+                eprintln!(
+                    "rush: bind: {}: cannot read: {}",
+                    arg,
+                    io::Error::last_os_error()
+                );
+                status = 1;
+            }
+            'q' | 'u' => {
+                if !inputrc::FUNCTIONS.contains(&arg.as_str()) {
+                    eprintln!("rush: bind: `{}': unknown function name", arg);
+                    return 1;
+                }
+                if *c == 'u' {
+                    if let Some(bindings) = rush.inputrc.keymaps.get_mut(&keymap) {
+                        bindings.retain(|(_, b)| *b != Binding::Function(arg.clone()));
+                    }
+                    continue;
+                }
+                let keys = function_keys(rush, &keymap, arg);
+                if keys.is_empty() {
+                    let _ = writeln!(io::stdout(), "{} is not bound to any keys.", arg);
+                    status = 1;
+                } else {
+                    let keys: Vec<String> = keys.iter().map(|k| format!("\"{}\"", k)).collect();
+                    let _ = writeln!(
+                        io::stdout(),
+                        "{} can be invoked via {}.",
+                        arg,
+                        keys.join(", ")
+                    );
+                }
+            }
+            'r' => match inputrc::key(&inputrc::unescape(arg)) {
+                Ok(key) => {
+                    rush.inputrc.unbind(&keymap, key);
+                }
+                Err(e) => {
+                    eprintln!("rush: bind: {}", e);
+                    status = 1;
+                }
+            },
+            'x' => {
+                eprintln!("rush: bind: {}: shell commands cannot be bound", arg);
+                status = 1;
+            }
+            _ => (),
+        }
+    }
+    for arg in &args[first..] {
+        if let Err(e) = inputrc::line(rush, arg, Some(&keymap)) {
+            eprintln!("rush: bind: {}", e);
+            status = 1;
+        }
+    }
+    status
+}
@@ -0,0 +1,1093 @@
+//
+// completion.rs
+//
+// Copyright 2015-2019 Laurent Wandrebeck <l.wandrebeck@quelquesmots.fr>
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston,
+// MA 02110-1301, USA.
+//
+
+//! RuSh programmable completion.
+//!
+//! completion.rs keeps completion specifications defined by the complete builtin, generates the matches
+//! they describe (compgen builtin uses it too), and completes the word under the cursor of an interactive line.
+//! Shell functions given with `complete -F` are called the bash way: COMP_WORDS, COMP_CWORD, COMP_LINE, COMP_POINT,
+//! COMP_TYPE and COMP_KEY are set while they run, and they give matches back in COMPREPLY array, usually with
+//! `COMPREPLY=( $(compgen ...) )`. They are run by RuSh, so they are limited to what it supports: bash-completion
+//! scripts relying on `case` or `[[ ]]` do not work yet.
+
+use crate::builtins;
+use crate::execute;
+use crate::expand;
+use crate::parse;
+use crate::rush::RuSh;
+use crate::variables::{Access, Value, Variable};
+use rustyline::completion::Pair;
+use std::fs;
+use std::path::Path;
+
+/// Name the specification used for commands without one is stored under (`complete -D`).
+pub const DEFAULT: &str = "_DefaultCmD_";
+/// Name the specification used on an empty line is stored under (`complete -E`).
+pub const EMPTY: &str = "_EmptycmD_";
+/// Name the specification used for the command word is stored under (`complete -I`).
+pub const INITIAL: &str = "_InitialWorD_";
+
+/// Completion options, given with `-o`.
+pub const OPTIONS: &[&str] = &[
+    "bashdefault",
+    "default",
+    "dirnames",
+    "filenames",
+    "noquote",
+    "nosort",
+    "nospace",
+    "plusdirs",
+];
+
+/// Completion actions, given with `-A`, with their short option if any.
+pub const ACTIONS: &[(&str, Option<char>)] = &[
+    ("alias", Some('a')),
+    ("arrayvar", None),
+    ("binding", None),
+    ("builtin", Some('b')),
+    ("command", Some('c')),
+    ("directory", Some('d')),
+    ("disabled", None),
+    ("enabled", None),
+    ("export", Some('e')),
+    ("file", Some('f')),
+    ("function", None),
+    ("group", Some('g')),
+    ("helptopic", None),
+    ("hostname", None),
+    ("job", Some('j')),
+    ("keyword", Some('k')),
+    ("running", None),
+    ("service", Some('s')),
+    ("setopt", None),
+    ("shopt", None),
+    ("signal", None),
+    ("stopped", None),
+    ("user", Some('u')),
+    ("variable", Some('v')),
+];
+
+/// Shell reserved words.
+pub const KEYWORDS: &[&str] = &[
+    "!", "[[", "]]", "case", "coproc", "do", "done", "elif", "else", "esac", "fi", "for",
+    "function", "if", "in", "select", "then", "time", "until", "while", "{", "}",
+];
+
+/// Signal names.
+const SIGNALS: &[&str] = &[
+    "SIGHUP",
+    "SIGINT",
+    "SIGQUIT",
+    "SIGILL",
+    "SIGTRAP",
+    "SIGABRT",
+    "SIGBUS",
+    "SIGFPE",
+    "SIGKILL",
+    "SIGUSR1",
+    "SIGSEGV",
+    "SIGUSR2",
+    "SIGPIPE",
+    "SIGALRM",
+    "SIGTERM",
+    "SIGSTKFLT",
+    "SIGCHLD",
+    "SIGCONT",
+    "SIGSTOP",
+    "SIGTSTP",
+    "SIGTTIN",
+    "SIGTTOU",
+    "SIGURG",
+    "SIGXCPU",
+    "SIGXFSZ",
+    "SIGVTALRM",
+    "SIGPROF",
+    "SIGWINCH",
+    "SIGIO",
+    "SIGPWR",
+    "SIGSYS",
+];
+
+/// A completion specification, as defined by complete builtin.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CompSpec {
+    /// actions (`-A` names) generating matches.
+    pub actions: Vec<String>,
+    /// completion options (`-o` names).
+    pub options: Vec<String>,
+    /// `-G` pathname expansion pattern.
+    pub glob: Option<String>,
+    /// `-W` word list, expanded then split.
+    pub words: Option<String>,
+    /// `-F` shell function, giving matches back in COMPREPLY.
+    pub function: Option<String>,
+    /// `-C` command, whose output lines are matches.
+    pub command: Option<String>,
+    /// `-X` pattern removing matches, or keeping them only if it starts with `!`.
+    pub filter: Option<String>,
+    /// `-P` prefix added to matches.
+    pub prefix: Option<String>,
+    /// `-S` suffix added to matches.
+    pub suffix: Option<String>,
+}
+
+impl CompSpec {
+    /// Is completion option `name` set ?
+    pub fn option(&self, name: &str) -> bool {
+        self.options.iter().any(|o| o == name)
+    }
+
+    /// Set or unset completion option `name`.
+    pub fn set_option(&mut self, name: &str, set: bool) {
+        self.options.retain(|o| o != name);
+        if set {
+            self.options.push(name.to_string());
+        }
+    }
+
+    /// Give back the complete command defining this specification for `name`, the way `complete -p` prints it.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use rush::completion::{CompSpec, DEFAULT};
+    ///
+    /// let spec = CompSpec {
+    ///     actions: vec![String::from("directory"), String::from("signal")],
+    ///     options: vec![String::from("nospace")],
+    ///     words: Some(String::from("start stop")),
+    ///     function: Some(String::from("_f")),
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(spec.print("cmd"), "complete -o nospace -d -A signal -W 'start stop' -F _f cmd");
+    /// assert_eq!(CompSpec::default().print(DEFAULT), "complete -D");
+    /// ```
+    pub fn print(&self, name: &str) -> String {
+        let mut s = String::from("complete");
+        for option in OPTIONS.iter().filter(|o| self.option(o)) {
+            s.push_str(" -o ");
+            s.push_str(option);
+        }
+        let has = |action: &str| self.actions.iter().any(|a| a == action);
+        for (action, letter) in ACTIONS {
+            if let Some(letter) = letter.filter(|_| has(action)) {
+                s.push_str(" -");
+                s.push(letter);
+            }
+        }
+        for (action, _) in ACTIONS.iter().filter(|(a, l)| l.is_none() && has(a)) {
+            s.push_str(" -A ");
+            s.push_str(action);
+        }
+        let quoted = [
+            ("-G", &self.glob),
+            ("-W", &self.words),
+            ("-X", &self.filter),
+            ("-P", &self.prefix),
+            ("-S", &self.suffix),
+        ];
+        for (flag, value) in quoted.iter() {
+            if let Some(value) = value {
+                s.push_str(&format!(" {} '{}'", flag, value.replace('\'', "'\\''")));
+            }
+        }
+        for (flag, value) in [("-F", &self.function), ("-C", &self.command)].iter() {
+            if let Some(value) = value {
+                s.push_str(&format!(" {} {}", flag, value));
+            }
+        }
+        match name {
+            DEFAULT => s.push_str(" -D"),
+            EMPTY => s.push_str(" -E"),
+            INITIAL => s.push_str(" -I"),
+            _ => {
+                s.push(' ');
+                s.push_str(name);
+            }
+        }
+        s
+    }
+}
+
+/// The command being completed, as given to completion functions.
+#[derive(Debug, PartialEq)]
+pub struct Line {
+    /// command text, COMP_LINE.
+    pub text: String,
+    /// cursor position in characters, COMP_POINT.
+    pub point: usize,
+    /// command words, COMP_WORDS.
+    pub words: Vec<String>,
+    /// index of the word the cursor is in, COMP_CWORD.
+    pub cword: usize,
+}
+
+/// Find the command the cursor at `pos` is in: it is delimited by unquoted `;`, `|`, `&`, `(`, `)` or newlines.
+/// Returns its start and end positions, leading blanks being skipped.
+fn command_bounds(line: &str, pos: usize) -> (usize, usize) {
+    let mut start = 0;
+    let mut end = line.len();
+    let mut quote: Option<char> = None;
+    let mut escape = false;
+    for (i, c) in line.char_indices() {
+        if escape {
+            escape = false;
+        } else if let Some(q) = quote {
+            if c == q {
+                quote = None;
+            } else if c == '\\' && q == '"' {
+                escape = true;
+            }
+        } else if c == '\\' {
+            escape = true;
+        } else if c == '"' || c == '\'' {
+            quote = Some(c);
+        } else if ";|&()\n".contains(c) {
+            if i < pos {
+                start = i + 1;
+            } else {
+                end = i;
+                break;
+            }
+        }
+    }
+    while start < pos && line[start..].starts_with([' ', '\t']) {
+        start += 1;
+    }
+    (start, end)
+}
+
+/// Split a command into words the way readline does: blanks separate words, unquoted characters from
+/// `breaks` (COMP_WORDBREAKS) are words of their own. Returns words positions, and index of the word
+/// the cursor at `point` is in, an empty word being added if it is in none.
+///
+/// # Examples
+/// ```rust
+/// use rush::completion::split;
+///
+/// let (words, cword) = split("ls -l a=b 'c d'", 9, "=");
+/// assert_eq!(words, vec![(0, 2), (3, 5), (6, 7), (7, 8), (8, 9), (10, 15)]);
+/// assert_eq!(cword, 4);
+/// let (words, cword) = split("ls  -l", 3, "=");
+/// assert_eq!(words, vec![(0, 2), (3, 3), (4, 6)]);
+/// assert_eq!(cword, 1);
+/// ```
+pub fn split(text: &str, point: usize, breaks: &str) -> (Vec<(usize, usize)>, usize) {
+    let mut words: Vec<(usize, usize, bool)> = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut in_break = false;
+    let mut quote: Option<char> = None;
+    let mut escape = false;
+    for (i, c) in text.char_indices() {
+        if escape {
+            escape = false;
+            continue;
+        }
+        if let Some(q) = quote {
+            if c == q {
+                quote = None;
+            } else if c == '\\' && q == '"' {
+                escape = true;
+            }
+            continue;
+        }
+        if c == ' ' || c == '\t' || c == '\n' {
+            if let Some(s) = start.take() {
+                words.push((s, i, in_break));
+            }
+            continue;
+        }
+        // quotes start quoted text, even if they are word breaks.
+        let is_break = breaks.contains(c) && c != '"' && c != '\'';
+        if start.is_some() && is_break != in_break {
+            words.push((start.unwrap(), i, in_break));
+            start = None;
+        }
+        if start.is_none() {
+            start = Some(i);
+            in_break = is_break;
+        }
+        if c == '\\' {
+            escape = true;
+        } else if c == '"' || c == '\'' {
+            quote = Some(c);
+        }
+    }
+    if let Some(s) = start {
+        words.push((s, text.len(), in_break));
+    }
+    // a cursor right after a word break starts a new word.
+    let cword = words
+        .iter()
+        .position(|(s, e, brk)| *s <= point && point <= *e && !(*brk && *e == point));
+    let cword = match cword {
+        Some(c) => c,
+        None => {
+            let c = words.iter().filter(|(s, _, _)| *s < point).count();
+            words.insert(c, (point, point, false));
+            c
+        }
+    };
+    (words.iter().map(|(s, e, _)| (*s, *e)).collect(), cword)
+}
+
+/// Remove quotes and backslashes from a word being completed.
+///
+/// # Examples
+/// ```rust
+/// use rush::completion::dequote;
+///
+/// assert_eq!(dequote("'a b'\\ c\"d"), "a b cd");
+/// ```
+pub fn dequote(word: &str) -> String {
+    let mut s = String::with_capacity(word.len());
+    let mut quote: Option<char> = None;
+    let mut escape = false;
+    for c in word.chars() {
+        if escape {
+            s.push(c);
+            escape = false;
+        } else if quote == Some(c) {
+            quote = None;
+        } else if c == '\\' && quote != Some('\'') {
+            escape = true;
+        } else if quote.is_none() && (c == '"' || c == '\'') {
+            quote = Some(c);
+        } else {
+            s.push(c);
+        }
+    }
+    s
+}
+
+/// Replace a leading `~` with HOME, and a leading `~user` with the user home directory, so that a path can be read.
+fn tilde(rush: &RuSh, path: &str) -> String {
+    let rest = match path.strip_prefix('~') {
+        Some(rest) => rest,
+        None => return path.to_string(),
+    };
+    let (user, rest) = match rest.find('/') {
+        Some(i) => rest.split_at(i),
+        None => (rest, ""),
+    };
+    let home = if user.is_empty() {
+        rush.shell_vars.get("HOME").map(|h| h.value.to_string())
+    } else {
+        fs::read_to_string("/etc/passwd").ok().and_then(|passwd| {
+            passwd
+                .lines()
+                .map(|l| l.split(':').collect::<Vec<&str>>())
+                .find(|fields| fields.len() > 5 && fields[0] == user)
+                .map(|fields| fields[5].to_string())
+        })
+    };
+    match home {
+        Some(home) => format!("{}{}", home, rest),
+        None => path.to_string(),
+    }
+}
+
+/// Is `path` (where `~` stands for HOME) a directory ?
+fn is_dir(rush: &RuSh, path: &str) -> bool {
+    Path::new(&tilde(rush, path)).is_dir()
+}
+
+/// File names starting with `word`, directories only if `dirs` is set. Hidden files are matched too.
+fn files(rush: &RuSh, word: &str, dirs: bool) -> Vec<String> {
+    let (dir, base) = match word.rfind('/') {
+        Some(i) => (&word[..=i], &word[i + 1..]),
+        None => ("", word),
+    };
+    let path = if dir.is_empty() {
+        String::from(".")
+    } else {
+        tilde(rush, dir)
+    };
+    let entries = match fs::read_dir(&path) {
+        Ok(e) => e,
+        Err(_) => return Vec::new(),
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| !dirs || e.path().is_dir())
+        .filter_map(|e| e.file_name().into_string().ok())
+        .filter(|name| name.starts_with(base))
+        .map(|name| format!("{}{}", dir, name))
+        .collect();
+    names.sort();
+    names
+}
+
+/// Command names starting with `word`: aliases, builtins, functions, reserved words and executable files in PATH.
+/// A word containing a slash is completed with executable files and directories.
+fn commands(rush: &RuSh, word: &str) -> Vec<String> {
+    if word.contains('/') {
+        return files(rush, word, false)
+            .into_iter()
+            .filter(|f| {
+                let path = tilde(rush, f);
+                let path = Path::new(&path);
+                path.is_dir() || is_executable(path)
+            })
+            .collect();
+    }
+    let mut names: Vec<String> = builtins::aliases(&rush.shell_array_vars)
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    names.extend(builtins::NAMES.iter().map(|n| n.to_string()));
+    names.extend(rush.functions.keys().cloned());
+    names.extend(KEYWORDS.iter().map(|k| k.to_string()));
+    let path = match rush.shell_vars.get("PATH") {
+        Some(p) => p.value.to_string(),
+        None => String::new(),
+    };
+    for dir in path.split(':') {
+        let dir = if dir.is_empty() { "." } else { dir };
+        if let Ok(entries) = fs::read_dir(dir) {
+            for entry in entries.filter_map(|e| e.ok()) {
+                if let Ok(name) = entry.file_name().into_string() {
+                    if name.starts_with(word) && is_executable(&entry.path()) {
+                        names.push(name);
+                    }
+                }
+            }
+        }
+    }
+    names.retain(|n| n.starts_with(word));
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Is `path` an executable file ?
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    match fs::metadata(path) {
+        Ok(meta) => meta.is_file() && meta.permissions().mode() & 0o111 != 0,
+        Err(_) => false,
+    }
+}
+
+/// First field of each line of a colon separated database (/etc/passwd, /etc/group).
+fn database(path: &str) -> Vec<String> {
+    match fs::read_to_string(path) {
+        Ok(contents) => contents
+            .lines()
+            .filter(|l| !l.starts_with('#'))
+            .filter_map(|l| l.split(':').next())
+            .filter(|name| !name.is_empty())
+            .map(String::from)
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Service names, from /etc/services.
+fn services() -> Vec<String> {
+    match fs::read_to_string("/etc/services") {
+        Ok(contents) => contents
+            .lines()
+            .filter_map(|l| l.split('#').next())
+            .filter_map(|l| l.split_whitespace().next())
+            .map(String::from)
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Host names, from the file named by HOSTFILE, or /etc/hosts.
+pub fn hosts(rush: &RuSh) -> Vec<String> {
+    let path = match rush.shell_vars.get("HOSTFILE") {
+        Some(v) if !v.value.to_string().is_empty() => v.value.to_string(),
+        _ => String::from("/etc/hosts"),
+    };
+    match fs::read_to_string(path) {
+        Ok(contents) => contents
+            .lines()
+            .filter_map(|l| l.split('#').next())
+            .flat_map(|l| l.split_whitespace().skip(1))
+            .map(String::from)
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Names an action gives, the ones starting with `word`.
+fn action(rush: &RuSh, name: &str, word: &str) -> Vec<String> {
+    let names: Vec<String> = match name {
+        "alias" => builtins::aliases(&rush.shell_array_vars)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect(),
+        "arrayvar" => rush.shell_array_vars.arrayvars.keys().cloned().collect(),
+        "builtin" | "enabled" | "helptopic" => {
+            builtins::NAMES.iter().map(|n| n.to_string()).collect()
+        }
+        "command" => return commands(rush, word),
+        "directory" => return files(rush, word, true),
+        "export" => rush.shell_vars.exported(),
+        "file" => return files(rush, word, false),
+        "function" => rush.functions.keys().cloned().collect(),
+        "group" => database("/etc/group"),
+        "hostname" => hosts(rush),
+        "keyword" => KEYWORDS.iter().map(|k| k.to_string()).collect(),
+        "service" => services(),
+        "setopt" => rush.set_options.names(),
+        "shopt" => rush.shopt_options.names(),
+        "signal" => SIGNALS.iter().map(|s| s.to_string()).collect(),
+        "user" => database("/etc/passwd"),
+        "variable" => {
+            let mut names = rush.shell_vars.names();
+            names.extend(rush.shell_array_vars.arrayvars.keys().cloned());
+            names
+        }
+        // there are no jobs, disabled builtins nor key bindings names yet.
+        _ => Vec::new(),
+    };
+    let mut names: Vec<String> = names.into_iter().filter(|n| n.starts_with(word)).collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Pathname expansion of a `-G` pattern. Hidden files are matched only if the pattern component starts with a dot.
+fn glob(rush: &RuSh, pattern: &str) -> Vec<String> {
+    let pattern = tilde(rush, pattern);
+    let (mut paths, rest) = match pattern.strip_prefix('/') {
+        Some(rest) => (vec![String::from("/")], rest),
+        None => (vec![String::new()], pattern.as_str()),
+    };
+    let components: Vec<&str> = rest.split('/').collect();
+    for (i, component) in components.iter().enumerate() {
+        let last = i + 1 == components.len();
+        let mut next = Vec::new();
+        for path in &paths {
+            if !component.contains(['*', '?', '[']) {
+                let candidate = format!("{}{}", path, component);
+                if last {
+                    if Path::new(&candidate).exists() {
+                        next.push(candidate);
+                    }
+                } else {
+                    next.push(format!("{}/", candidate));
+                }
+                continue;
+            }
+            let dir = if path.is_empty() { "." } else { path.as_str() };
+            if let Ok(entries) = fs::read_dir(dir) {
+                for entry in entries.filter_map(|e| e.ok()) {
+                    let name = match entry.file_name().into_string() {
+                        Ok(n) => n,
+                        Err(_) => continue,
+                    };
+                    if name.starts_with('.') && !component.starts_with('.') {
+                        continue;
+                    }
+                    if !expand::pattern_match(component, &name) {
+                        continue;
+                    }
+                    if last {
+                        next.push(format!("{}{}", path, name));
+                    } else if entry.path().is_dir() {
+                        next.push(format!("{}{}/", path, name));
+                    }
+                }
+            }
+        }
+        paths = next;
+    }
+    paths.sort();
+    paths
+}
+
+/// Set a completion variable.
+fn set_var(rush: &mut RuSh, name: &str, value: Value, export: bool) {
+    rush.shell_vars.set(
+        name.to_string(),
+        Variable {
+            value,
+            access: Access::ReadWrite,
+            export,
+        },
+    );
+}
+
+/// Set COMP_LINE, COMP_POINT, COMP_TYPE and COMP_KEY, exported for `-C` commands. Tab completion is the only kind there is.
+fn set_vars(rush: &mut RuSh, line: &Line, export: bool) {
+    set_var(rush, "COMP_LINE", Value::S(line.text.clone()), export);
+    set_var(rush, "COMP_POINT", Value::I(line.point as i64), export);
+    set_var(rush, "COMP_TYPE", Value::I(9), export);
+    set_var(rush, "COMP_KEY", Value::I(9), export);
+}
+
+/// Unset completion variables once a function or command has run.
+fn unset_vars(rush: &mut RuSh) {
+    for name in &[
+        "COMP_LINE",
+        "COMP_POINT",
+        "COMP_TYPE",
+        "COMP_KEY",
+        "COMP_CWORD",
+    ] {
+        rush.shell_vars.unset(name.to_string());
+    }
+    rush.shell_array_vars.arrayvars.remove("COMP_WORDS");
+}
+
+/// Call a `-F` completion function with command name, word being completed and previous word as arguments.
+/// Returns matches it put in COMPREPLY, and its exit status.
+fn call_function(
+    rush: &mut RuSh,
+    function: &str,
+    word: &str,
+    line: Option<&Line>,
+) -> (Vec<String>, i32) {
+    let mut args = vec![function.to_string()];
+    match line {
+        Some(line) => {
+            set_vars(rush, line, false);
+            set_var(rush, "COMP_CWORD", Value::I(line.cword as i64), false);
+            rush.shell_array_vars.clear("COMP_WORDS");
+            for (i, w) in line.words.iter().enumerate() {
+                rush.shell_array_vars.set(
+                    "COMP_WORDS",
+                    crate::arrays::Index::I(i),
+                    Value::S(w.clone()),
+                );
+            }
+            args.push(line.words[0].clone());
+            args.push(word.to_string());
+            args.push(match line.cword {
+                0 => String::new(),
+                c => line.words[c - 1].clone(),
+            });
+        }
+        None => args.extend(vec![String::new(), word.to_string(), String::new()]),
+    }
+    rush.shell_array_vars.arrayvars.remove("COMPREPLY");
+    let status = match execute::function(rush, &args) {
+        Some(status) => status,
+        None => {
+            eprintln!("rush: completion: function `{}' not found", function);
+            0
+        }
+    };
+    if line.is_some() {
+        unset_vars(rush);
+    }
+    (expand::array_values(rush, "COMPREPLY"), status)
+}
+
+/// Run a `-C` command with command name, word being completed and previous word as arguments.
+/// Returns its output lines.
+fn call_command(rush: &mut RuSh, command: &str, word: &str, line: Option<&Line>) -> Vec<String> {
+    let (name, prev) = match line {
+        Some(line) => {
+            set_vars(rush, line, true);
+            let prev = match line.cword {
+                0 => String::new(),
+                c => line.words[c - 1].clone(),
+            };
+            (line.words[0].clone(), prev)
+        }
+        None => (String::new(), String::new()),
+    };
+    let input = format!(
+        "{} {} {} {}",
+        command,
+        builtins::squote(&name),
+        builtins::squote(word),
+        builtins::squote(&prev)
+    );
+    let output = match execute::spawn_substitution(rush, &input) {
+        Some((fd, pid)) => execute::collect_substitution(fd, pid).0,
+        None => String::new(),
+    };
+    if line.is_some() {
+        unset_vars(rush);
+    }
+    output.lines().map(String::from).collect()
+}
+
+/// Remove matches according to a `-X` pattern, where `&` stands for the word being completed.
+fn filter(matches: &mut Vec<String>, pattern: &str, word: &str) {
+    let (negate, pattern) = match pattern.strip_prefix('!') {
+        Some(p) => (true, p),
+        None => (false, pattern),
+    };
+    let mut p = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'&') => {
+                chars.next();
+                p.push('&');
+            }
+            '&' => {
+                for w in word.chars() {
+                    if "*?[\\".contains(w) {
+                        p.push('\\');
+                    }
+                    p.push(w);
+                }
+            }
+            _ => p.push(c),
+        }
+    }
+    matches.retain(|m| expand::pattern_match(&p, m) == negate);
+}
+
+/// Generate matches a completion specification describes for `word`, the way compgen does.
+/// `line` is the command being completed, None for compgen. Returns None when a `-F` function
+/// exited with status 124, asking for completion to be tried again.
+///
+/// # Examples
+/// ```rust
+/// use rush::RuSh;
+/// use rush::completion::{generate, CompSpec};
+///
+/// let mut rush = RuSh::default();
+/// let spec = CompSpec {
+///     actions: vec![String::from("keyword")],
+///     words: Some(String::from("wait write")),
+///     prefix: Some(String::from("<")),
+///     ..Default::default()
+/// };
+/// assert_eq!(generate(&mut rush, &spec, "w", None), Some(vec![String::from("<while"), String::from("<wait"), String::from("<write")]));
+/// ```
+pub fn generate(
+    rush: &mut RuSh,
+    spec: &CompSpec,
+    word: &str,
+    line: Option<&Line>,
+) -> Option<Vec<String>> {
+    let mut matches = Vec::new();
+    for name in &spec.actions {
+        matches.extend(action(rush, name, word));
+    }
+    if let Some(pattern) = &spec.glob {
+        matches.extend(glob(rush, pattern));
+    }
+    if let Some(words) = &spec.words {
+        matches.extend(
+            parse::expand_words(rush, words)
+                .into_iter()
+                .filter(|w| !w.is_empty() && w.starts_with(word)),
+        );
+    }
+    if let Some(function) = &spec.function {
+        let (replies, status) = call_function(rush, function, word, line);
+        if status == 124 {
+            return None;
+        }
+        matches.extend(replies);
+    }
+    if let Some(command) = &spec.command {
+        matches.extend(call_command(rush, command, word, line));
+    }
+    if let Some(pattern) = &spec.filter {
+        filter(&mut matches, pattern, word);
+    }
+    if spec.prefix.is_some() || spec.suffix.is_some() {
+        let prefix = spec.prefix.clone().unwrap_or_default();
+        let suffix = spec.suffix.clone().unwrap_or_default();
+        for m in matches.iter_mut() {
+            *m = format!("{}{}{}", prefix, m, suffix);
+        }
+    }
+    Some(matches)
+}
+
+/// Apply `-o dirnames`, `-o default`, `-o bashdefault` and `-o plusdirs` to matches a specification generated.
+/// Returns true if file names were added.
+pub fn fallback(rush: &RuSh, spec: &CompSpec, word: &str, matches: &mut Vec<String>) -> bool {
+    let mut added = false;
+    if matches.is_empty() && spec.option("dirnames") {
+        matches.extend(files(rush, word, true));
+        added = true;
+    }
+    if matches.is_empty() && (spec.option("default") || spec.option("bashdefault")) {
+        matches.extend(files(rush, word, false));
+        added = true;
+    }
+    if spec.option("plusdirs") {
+        matches.extend(files(rush, word, true));
+        added = true;
+    }
+    added
+}
+
+/// Quote a match so that it is read back as is, a leading `~` being kept for tilde expansion.
+fn quote(m: &str) -> String {
+    match m.strip_prefix('~') {
+        Some(rest) if !rest.is_empty() => format!("~{}", builtins::quote(rest)),
+        Some(_) => m.to_string(),
+        None => builtins::quote(m),
+    }
+}
+
+/// Default completion, used when no specification applies, and with `-o bashdefault`: words starting with `$` or `${`
+/// complete variable names, words starting with `~` user names, words starting with (or following) `@` host names
+/// when hostcomplete option is set. Failing that, the command name completes commands, unless it is empty and
+/// no_empty_cmd_completion option is set, and other words file names.
+/// Returns matches, whether they are file names, and whether they are to be quoted.
+///
+/// # Examples
+/// ```rust
+/// use rush::RuSh;
+/// use rush::completion;
+/// use rush::parse::parse;
+///
+/// let mut rush = RuSh::default();
+/// parse(&mut rush, "rush_default=1");
+/// assert_eq!(completion::default(&rush, "${rush_def", false, false), (vec![String::from("${rush_default}")], false, false));
+/// assert_eq!(completion::default(&rush, "~roo", false, false), (vec![String::from("~root")], true, true));
+/// parse(&mut rush, "shopt -s no_empty_cmd_completion");
+/// assert!(completion::default(&rush, "", false, true).0.is_empty());
+/// ```
+pub fn default(
+    rush: &RuSh,
+    word: &str,
+    after_at: bool,
+    command: bool,
+) -> (Vec<String>, bool, bool) {
+    let shopt = |name| match rush.shopt_options.get(name) {
+        Some(opt) => opt.get(),
+        None => false,
+    };
+    if let Some(name) = word.strip_prefix('$') {
+        let (open, name, close) = match name.strip_prefix('{') {
+            Some(name) => ("${", name, "}"),
+            None => ("$", name, ""),
+        };
+        let matches: Vec<String> = action(rush, "variable", name)
+            .into_iter()
+            .map(|v| format!("{}{}{}", open, v, close))
+            .collect();
+        if !matches.is_empty() {
+            return (matches, false, false);
+        }
+    }
+    if let Some(user) = word.strip_prefix('~').filter(|u| !u.contains('/')) {
+        let matches: Vec<String> = action(rush, "user", user)
+            .into_iter()
+            .map(|u| format!("~{}", u))
+            .collect();
+        if !matches.is_empty() {
+            return (matches, true, true);
+        }
+    }
+    if shopt("hostcomplete") {
+        let (at, host) = match word.strip_prefix('@') {
+            Some(host) => ("@", host),
+            None => ("", word),
+        };
+        if after_at || !at.is_empty() {
+            let matches: Vec<String> = action(rush, "hostname", host)
+                .into_iter()
+                .map(|h| format!("{}{}", at, h))
+                .collect();
+            if !matches.is_empty() {
+                return (matches, false, true);
+            }
+        }
+    }
+    if command {
+        if word.is_empty() && shopt("no_empty_cmd_completion") {
+            return (Vec::new(), false, true);
+        }
+        return (commands(rush, word), word.contains('/'), true);
+    }
+    (files(rush, word, false), true, true)
+}
+
+/// Remove file names ending with a FIGNORE suffix. If they all do, they are kept unless force_fignore option is set.
+fn fignore(rush: &RuSh, matches: &mut Vec<String>) {
+    let suffixes = match rush.shell_vars.get("FIGNORE") {
+        Some(v) => v.value.to_string(),
+        None => return,
+    };
+    let suffixes: Vec<&str> = suffixes.split(':').filter(|s| !s.is_empty()).collect();
+    let ignored = |m: &String| {
+        suffixes
+            .iter()
+            .any(|s| m.len() > s.len() && m.ends_with(s) && !m[..m.len() - s.len()].ends_with('/'))
+    };
+    let force = match rush.shopt_options.get("force_fignore") {
+        Some(opt) => opt.get(),
+        None => false,
+    };
+    if force || !matches.iter().all(ignored) {
+        matches.retain(|m| !ignored(m));
+    }
+}
+
+/// Find the specification completing the command being completed, with the name it is stored under.
+fn specification(rush: &RuSh, line: &str, comp_line: &Line) -> Option<(String, CompSpec)> {
+    let names = if line.trim().is_empty() && rush.completions.contains_key(EMPTY) {
+        vec![EMPTY.to_string()]
+    } else if comp_line.cword == 0 {
+        vec![INITIAL.to_string()]
+    } else {
+        let command = dequote(&comp_line.words[0]);
+        let base = match command.rfind('/') {
+            Some(i) => command[i + 1..].to_string(),
+            None => command.clone(),
+        };
+        vec![command, base, DEFAULT.to_string()]
+    };
+    names
+        .into_iter()
+        .find_map(|n| rush.completions.get(&n).map(|spec| (n, spec.clone())))
+}
+
+/// Complete the word before position `pos` in `line`. Returns where the replaced word starts, and candidates.
+/// The command name gets `complete -I` specification if any (`complete -E` one on an empty line).
+/// Arguments get the specification of the command (or of its base name), or `complete -D` one.
+/// Without a specification, default completion is used.
+///
+/// # Examples
+/// ```rust
+/// use rush::RuSh;
+/// use rush::completion;
+/// use rush::parse::parse;
+///
+/// let mut rush = RuSh::default();
+/// parse(&mut rush, "_f() { COMPREPLY=(\"$1:$2:$3:$COMP_CWORD\"); }; complete -F _f cmd");
+/// let (start, candidates) = completion::complete(&mut rush, "echo; cmd a b", 13);
+/// assert_eq!(start, 12);
+/// assert_eq!(candidates[0].replacement, "cmd:b:a:2 ");
+/// ```
+pub fn complete(rush: &mut RuSh, line: &str, pos: usize) -> (usize, Vec<Pair>) {
+    let (start, end) = command_bounds(line, pos);
+    let text = &line[start..end];
+    let point = pos - start;
+    let breaks = match rush.shell_vars.get("COMP_WORDBREAKS") {
+        Some(v) => v.value.to_string(),
+        None => String::new(),
+    };
+    let (spans, cword) = split(text, point, &breaks);
+    let words: Vec<String> = spans
+        .iter()
+        .map(|(s, e)| text[*s..*e].to_string())
+        .collect();
+    let word_start = spans[cword].0;
+    let word = dequote(&text[word_start..point]);
+    let comp_line = Line {
+        text: text.to_string(),
+        point: text[..point].chars().count(),
+        words,
+        cword,
+    };
+    let after_at = text[..word_start].ends_with('@');
+    let mut found = specification(rush, line, &comp_line);
+    let mut tries = 0;
+    let mut filenames;
+    let mut spec;
+    let mut matches;
+    loop {
+        match found {
+            Some((name, found_spec)) => {
+                spec = found_spec.clone();
+                rush.completion = Some(spec.clone());
+                let generated = generate(rush, &spec, &word, Some(&comp_line));
+                if let Some(current) = rush.completion.take() {
+                    spec.options = current.options;
+                }
+                matches = match generated {
+                    Some(m) => m,
+                    None => {
+                        // a function exiting with 124 after changing the specification has completion tried again.
+                        tries += 1;
+                        let again = specification(rush, line, &comp_line);
+                        if tries < 8 && again != Some((name, found_spec)) {
+                            found = again;
+                            continue;
+                        }
+                        Vec::new()
+                    }
+                };
+                filenames = spec.option("filenames")
+                    || spec.actions.iter().any(|a| a == "file" || a == "directory");
+                if matches.is_empty() && spec.option("bashdefault") {
+                    let (m, f, quote) = default(rush, &word, after_at, cword == 0);
+                    matches = m;
+                    filenames |= f;
+                    if !quote {
+                        spec.set_option("noquote", true);
+                    }
+                }
+                filenames |= fallback(rush, &spec, &word, &mut matches);
+            }
+            None => {
+                spec = CompSpec::default();
+                let (m, f, quote) = default(rush, &word, after_at, cword == 0);
+                matches = m;
+                filenames = f;
+                if !quote {
+                    spec.set_option("noquote", true);
+                }
+            }
+        }
+        break;
+    }
+    if filenames {
+        fignore(rush, &mut matches);
+    }
+    if !spec.option("nosort") {
+        matches.sort();
+        matches.dedup();
+    }
+    let single = matches.len() == 1;
+    let candidates = matches
+        .into_iter()
+        .map(|m| {
+            let dir = filenames && is_dir(rush, &m);
+            let mut display = if filenames {
+                let trimmed = m.trim_end_matches('/');
+                match trimmed.rfind('/') {
+                    Some(i) => trimmed[i + 1..].to_string(),
+                    None => trimmed.to_string(),
+                }
+            } else {
+                m.clone()
+            };
+            let mut replacement = if spec.option("noquote") {
+                m.clone()
+            } else {
+                quote(&m)
+            };
+            if dir {
+                display.push('/');
+                if !m.ends_with('/') {
+                    replacement.push('/');
+                }
+            } else if single && !spec.option("nospace") {
+                replacement.push(' ');
+            }
+            Pair {
+                display,
+                replacement,
+            }
+        })
+        .collect();
+    (start + word_start, candidates)
+}
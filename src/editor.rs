@@ -0,0 +1,269 @@
+//
+// editor.rs
+//
+// Copyright 2015-2019 Laurent Wandrebeck <l.wandrebeck@quelquesmots.fr>
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston,
+// MA 02110-1301, USA.
+//
+
+//! RuSh line editor helper.
+//!
+//! editor.rs plugs RuSh into rustyline. The helper is lent the shell while a line is being read,
+//! so that completion can expand words and call shell functions, and highlighting can look commands up.
+
+use crate::completion;
+use crate::highlight::{self, Colors};
+use crate::inputrc;
+use crate::prompt::Prompt;
+use crate::rush::RuSh;
+use rustyline::completion::{Completer, Pair};
+use rustyline::config::{BellStyle, Configurer};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Cmd, Context, EditMode, Editor, KeyPress, Movement};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::env;
+use std::io::{self, Write};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// rustyline helper, holding the shell while a line is read.
+#[derive(Default)]
+pub struct Helper {
+    rush: RefCell<RuSh>,
+    /// syntax highlighting colors, None if the terminal has none.
+    colors: Option<Colors>,
+    /// keys bound in the editor by `configure`.
+    keys: Vec<KeyPress>,
+    /// prompt being displayed.
+    prompt: Prompt,
+    /// right prompt displayed along with it, if any.
+    rprompt: Prompt,
+}
+
+impl Helper {
+    /// Create a helper. It holds a shell of its own until `readline` lends it the real one.
+    /// Colors are the ones of the terminal TERM names.
+    pub fn new() -> Helper {
+        Helper {
+            colors: Colors::from_env(),
+            ..Default::default()
+        }
+    }
+}
+
+impl Completer for Helper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        Ok(completion::complete(&mut self.rush.borrow_mut(), line, pos))
+    }
+}
+
+/// RPS1 is displayed as a hint, right-aligned, as long as the line leaves room for it.
+/// The editor drops it once the line is accepted, and `configure` keeps Right from inserting it.
+impl Hinter for Helper {
+    fn hint(&self, line: &str, _pos: usize, _ctx: &Context<'_>) -> Option<String> {
+        let right = &self.rprompt.visible;
+        if right.is_empty() || right.contains('\n') || line.contains('\n') {
+            return None;
+        }
+        let prompt = self.prompt.visible.rsplit('\n').next().unwrap_or("");
+        let used = prompt.width() + line.width() + right.width();
+        // last column is left empty, so that the terminal does not wrap.
+        let cols = columns();
+        if used >= cols {
+            return None;
+        }
+        Some(format!("{}{}", " ".repeat(cols - used - 1), right))
+    }
+}
+
+impl Highlighter for Helper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        match &self.colors {
+            Some(colors) => Cow::Owned(highlight::highlight(&self.rush.borrow(), line, colors)),
+            None => Cow::Borrowed(line),
+        }
+    }
+
+    fn highlight_prompt<'b, 's: 'b, 'p: 'b>(
+        &'s self,
+        prompt: &'p str,
+        default: bool,
+    ) -> Cow<'b, str> {
+        // the editor is given the visible part of the prompt only, so that it knows its width.
+        if default {
+            Cow::Borrowed(&self.prompt.prompt)
+        } else {
+            Cow::Borrowed(prompt)
+        }
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        match hint.strip_suffix(self.rprompt.visible.as_str()) {
+            Some(padding) if !self.rprompt.visible.is_empty() => {
+                Cow::Owned(format!("{}{}", padding, self.rprompt.prompt))
+            }
+            _ => Cow::Borrowed(hint),
+        }
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize) -> bool {
+        self.colors.is_some()
+    }
+}
+
+impl Validator for Helper {}
+
+impl rustyline::Helper for Helper {}
+
+/// Read a line with `prompt`, and `rush.rprompt` on the right, the editor helper being lent `rush` meanwhile.
+/// `initial` is text given back for edition.
+pub fn readline(
+    rl: &mut Editor<Helper>,
+    rush: &mut RuSh,
+    prompt: &Prompt,
+    initial: Option<String>,
+) -> rustyline::Result<String> {
+    if let Some(helper) = rl.helper_mut() {
+        helper.prompt = prompt.clone();
+        helper.rprompt = rush.rprompt.clone();
+        std::mem::swap(rush, helper.rush.get_mut());
+    }
+    let line = match initial {
+        Some(text) => rl.readline_with_initial(&prompt.visible, (&text, "")),
+        None => rl.readline(&prompt.visible),
+    };
+    if let Some(helper) = rl.helper_mut() {
+        std::mem::swap(rush, helper.rush.get_mut());
+    }
+    line
+}
+
+/// Set the editor up from `rush` settings: editing mode, readline variables and key bindings.
+/// In vi mode, vi-insert keymap is used, with vi-command bindings of keys that do not insert a character.
+pub fn configure(rl: &mut Editor<Helper>, rush: &RuSh) {
+    let vi = inputrc::variable(rush, "editing-mode") == "vi";
+    rl.set_edit_mode(if vi { EditMode::Vi } else { EditMode::Emacs });
+    if let Some(ms) = rush.inputrc.variables.get("keyseq-timeout") {
+        if let Ok(ms) = ms.parse() {
+            rl.set_keyseq_timeout(ms);
+        }
+    }
+    rl.set_bell_style(match inputrc::variable(rush, "bell-style").as_str() {
+        "none" | "off" => BellStyle::None,
+        "visible" => BellStyle::Visible,
+        _ => BellStyle::Audible,
+    });
+    if let Ok(items) = inputrc::variable(rush, "completion-query-items").parse() {
+        rl.set_completion_prompt_limit(items);
+    }
+    let mut bindings: Vec<_> = Vec::new();
+    if vi {
+        bindings.extend(rush.inputrc.bindings("vi-insert"));
+        bindings.extend(
+            rush.inputrc
+                .bindings("vi-command")
+                .iter()
+                .filter(|(k, _)| !matches!(k, KeyPress::Char(_))),
+        );
+    } else {
+        bindings.extend(rush.inputrc.bindings("emacs"));
+    }
+    let old = match rl.helper_mut() {
+        Some(helper) => std::mem::take(&mut helper.keys),
+        None => Vec::new(),
+    };
+    for key in old {
+        rl.unbind_sequence(key);
+    }
+    let mut keys = Vec::new();
+    for (key, binding) in bindings {
+        if let Some(cmd) = inputrc::command(binding, *key) {
+            if !keys.contains(key) {
+                rl.bind_sequence(*key, cmd);
+                keys.push(*key);
+            }
+        }
+    }
+    // Right accepts a hint at end of line, and RPS1 is displayed as one: it only moves the cursor then.
+    if !rush.rprompt.visible.is_empty() && !keys.contains(&KeyPress::Right) {
+        rl.bind_sequence(KeyPress::Right, Cmd::Move(Movement::ForwardChar(1)));
+        keys.push(KeyPress::Right);
+    }
+    if let Some(helper) = rl.helper_mut() {
+        helper.keys = keys;
+    }
+}
+
+/// Transient prompt: once `line` is accepted, the `prompt` it was read with is rewritten to the compact
+/// RUSH_TRANSIENT_PROMPT, if set. Only done when the editor draws the line itself on a terminal.
+pub fn transient(rl: &Editor<Helper>, rush: &mut RuSh, prompt: &Prompt, line: &str) {
+    match rush.shell_vars.get("RUSH_TRANSIENT_PROMPT") {
+        Some(var) if !var.value.to_string().is_empty() => (),
+        _ => return,
+    }
+    let term = env::var("TERM").unwrap_or_default();
+    let tty = unsafe { libc::isatty(0) == 1 && libc::isatty(1) == 1 };
+    if !tty || ["dumb", "cons25", "emacs"].contains(&term.as_str()) {
+        return;
+    }
+    // rows the prompt and the line take, the way the editor lays them out, plus the one the cursor went to.
+    let cols = columns();
+    let (mut row, mut col) = (0, 0);
+    for c in prompt.visible.chars().chain(line.chars()) {
+        if c == '\n' {
+            row += 1;
+            col = 0;
+            continue;
+        }
+        let w = c.width().unwrap_or(0);
+        col += w;
+        if col > cols {
+            row += 1;
+            col = w;
+        }
+    }
+    if col == cols {
+        row += 1;
+    }
+    let compact = Prompt::get(rush, "RUSH_TRANSIENT_PROMPT");
+    let line = match rl.helper().and_then(|helper| helper.colors.as_ref()) {
+        Some(colors) => highlight::highlight(rush, line, colors),
+        None => line.to_string(),
+    };
+    print!("\x1b[{}A\r\x1b[J{}{}\n", row + 1, compact.prompt, line);
+    let _ = io::stdout().flush();
+}
+
+/// Width of the terminal, COLUMNS or 80 if it cannot be known.
+pub fn columns() -> usize {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    if unsafe { libc::ioctl(1, libc::TIOCGWINSZ, &mut size) } == 0 && size.ws_col > 0 {
+        return usize::from(size.ws_col);
+    }
+    match env::var("COLUMNS").ok().and_then(|c| c.parse().ok()) {
+        Some(cols) if cols > 0 => cols,
+        _ => 80,
+    }
+}
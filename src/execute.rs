@@ -0,0 +1,748 @@
+//
+// execute.rs
+//
+// Copyright 2015-2019 Laurent Wandrebeck <l.wandrebeck@quelquesmots.fr>
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston,
+// MA 02110-1301, USA.
+//
+
+//! RuSh commands execution is located in this module.
+//!
+//! execute.rs walks the tree parsed with rush.pest, expands words and runs commands.
+//! Builtins are run by the shell itself, other commands in a forked child process
+//! which is given exported variables as environment.
+
+use crate::arrays::Index;
+use crate::builtins;
+use crate::expand;
+use crate::parse::{self, Rule};
+use crate::prompt::Prompt;
+use crate::rush::RuSh;
+use crate::variables::{Access, Value, Variable};
+use libc::c_char;
+use pest::iterators::Pair;
+use std::ffi::CString;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::path::Path;
+use std::ptr;
+
+/// An assignment ready to be done: variable name, array index if any, and value.
+pub struct Assignment {
+    /// variable name.
+    pub name: String,
+    /// array index, if an array element is assigned.
+    pub index: Option<String>,
+    /// expanded value.
+    pub value: String,
+    /// expanded words of a `name=(word...)` compound assignment, which sets an indexed array.
+    pub array: Option<Vec<String>>,
+}
+
+/// Execute a `program` pair, ie a list of commands. Returns exit status of the last one.
+pub fn program(rush: &mut RuSh, p: Pair<Rule>) -> i32 {
+    list(rush, p)
+}
+
+/// Has execution to stop, because the shell exits, current input is aborted, or return was used ?
+pub fn stopped(rush: &RuSh) -> bool {
+    rush.exit.is_some() || rush.abort || rush.returning.is_some()
+}
+
+/// Execute a list of `andor` pairs (`program` or `compoundlist`). Returns exit status of the last one.
+fn list(rush: &mut RuSh, p: Pair<Rule>) -> i32 {
+    for andor in p.into_inner() {
+        if andor.as_rule() == Rule::andor {
+            rush.last_status = and_or(rush, andor);
+            if stopped(rush) {
+                break;
+            }
+        }
+    }
+    rush.last_status
+}
+
+/// Execute pipelines separated by && and ||. errexit is ignored for every pipeline but the last one.
+fn and_or(rush: &mut RuSh, p: Pair<Rule>) -> i32 {
+    let mut inner = p.into_inner().peekable();
+    let mut status = 0;
+    let mut run = true;
+    while let Some(pipe) = inner.next() {
+        let last = inner.peek().is_none();
+        if run {
+            if !last {
+                rush.errexit_ignored += 1;
+            }
+            status = pipeline(rush, pipe);
+            if !last {
+                rush.errexit_ignored -= 1;
+            }
+            rush.last_status = status;
+            if stopped(rush) {
+                break;
+            }
+        }
+        // next pipeline is run if && follows a success, or || a failure.
+        if let Some(op) = inner.next() {
+            run = (op.as_str() == "&&") == (status == 0);
+        }
+    }
+    status
+}
+
+/// Execute a pipeline. Every command of a multi-command pipeline is run in its own process.
+/// Exit status is the one of the last command (or the last failing one with pipefail), inverted by `!`.
+fn pipeline(rush: &mut RuSh, p: Pair<Rule>) -> i32 {
+    let mut inner = p.into_inner().peekable();
+    let negated = match inner.peek() {
+        Some(b) => b.as_rule() == Rule::bang,
+        None => false,
+    };
+    if negated {
+        inner.next();
+        rush.errexit_ignored += 1;
+    }
+    let commands: Vec<Pair<Rule>> = inner.collect();
+    // compound commands do not trigger errexit themselves, commands they contain do.
+    let simple = commands.len() > 1
+        || commands[0].clone().into_inner().next().unwrap().as_rule() == Rule::simplecommand;
+    let status = if commands.len() == 1 {
+        command(rush, commands[0].clone())
+    } else {
+        let pipefail = match rush.set_options.get("pipefail") {
+            Some(opt) => opt.get(),
+            None => false,
+        };
+        let statuses = fork_pipeline(rush, commands);
+        match statuses.iter().rev().find(|s| pipefail && **s != 0) {
+            Some(s) => *s,
+            None => *statuses.last().unwrap_or(&0),
+        }
+    };
+    if negated {
+        rush.errexit_ignored -= 1;
+        return if status == 0 { 1 } else { 0 };
+    }
+    if status != 0 && simple && rush.errexit_ignored == 0 && rush.exit.is_none() {
+        if let Some(opt) = rush.set_options.get("errexit") {
+            if opt.get() {
+                rush.exit = Some(status);
+            }
+        }
+    }
+    status
+}
+
+/// Run each command of a pipeline in a child process, stdout of one being connected to stdin of the next.
+/// Returns exit status of every command.
+fn fork_pipeline(rush: &mut RuSh, commands: Vec<Pair<Rule>>) -> Vec<i32> {
+    let count = commands.len();
+    let mut pids = Vec::with_capacity(count);
+    let mut input = -1;
+    let _ = io::stdout().flush();
+    for (i, cmd) in commands.into_iter().enumerate() {
+        let mut fds = [-1; 2];
+        if i + 1 < count && unsafe { libc::pipe(fds.as_mut_ptr()) } == -1 {
+            eprintln!("rush: pipe: {}", os_error(&io::Error::last_os_error()));
+            break;
+        }
+        match unsafe { libc::fork() } {
+            -1 => {
+                eprintln!("rush: fork: {}", os_error(&io::Error::last_os_error()));
+                break;
+            }
+            0 => unsafe {
+                if input != -1 {
+                    libc::dup2(input, 0);
+                    libc::close(input);
+                }
+                if fds[1] != -1 {
+                    libc::dup2(fds[1], 1);
+                    libc::close(fds[1]);
+                    libc::close(fds[0]);
+                }
+                let status = command(rush, cmd);
+                let _ = io::stdout().flush();
+                libc::_exit(rush.exit.unwrap_or(status));
+            },
+            pid => pids.push(pid),
+        }
+        unsafe {
+            if input != -1 {
+                libc::close(input);
+            }
+            if fds[1] != -1 {
+                libc::close(fds[1]);
+            }
+        }
+        input = fds[0];
+    }
+    if input != -1 {
+        unsafe { libc::close(input) };
+    }
+    pids.into_iter().map(wait).collect()
+}
+
+/// Command substitution: run `input` in a child process and give back what it wrote on its standard output,
+/// trailing newlines removed. `$?` is set to the child exit status.
+pub fn substitute(rush: &mut RuSh, input: &str) -> String {
+    match spawn_substitution(rush, input) {
+        Some((fd, pid)) => {
+            let (output, status) = collect_substitution(fd, pid);
+            rush.last_status = status;
+            output
+        }
+        None => String::new(),
+    }
+}
+
+/// Start a command substitution: `input` is run in a child process whose standard output is a pipe.
+/// Returns the pipe read end and the child pid, None if it could not be started.
+pub fn spawn_substitution(rush: &mut RuSh, input: &str) -> Option<(RawFd, libc::pid_t)> {
+    let mut fds = [-1; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } == -1 {
+        eprintln!("rush: pipe: {}", os_error(&io::Error::last_os_error()));
+        return None;
+    }
+    let _ = io::stdout().flush();
+    match unsafe { libc::fork() } {
+        -1 => {
+            eprintln!("rush: fork: {}", os_error(&io::Error::last_os_error()));
+            unsafe {
+                libc::close(fds[0]);
+                libc::close(fds[1]);
+            }
+            None
+        }
+        0 => unsafe {
+            libc::dup2(fds[1], 1);
+            libc::close(fds[1]);
+            libc::close(fds[0]);
+            rush.nesting += 1;
+            parse::parse(rush, input);
+            let _ = io::stdout().flush();
+            libc::_exit(rush.exit.unwrap_or(rush.last_status));
+        },
+        pid => unsafe {
+            libc::close(fds[1]);
+            // commands run meanwhile do not get it.
+            libc::fcntl(fds[0], libc::F_SETFD, libc::FD_CLOEXEC);
+            Some((fds[0], pid))
+        },
+    }
+}
+
+/// Read the output of a command substitution started by `spawn_substitution`, and wait for its end.
+/// Returns its output, trailing newlines removed, and its exit status.
+pub fn collect_substitution(fd: RawFd, pid: libc::pid_t) -> (String, i32) {
+    let mut output = Vec::new();
+    let _ = unsafe { fs::File::from_raw_fd(fd) }.read_to_end(&mut output);
+    let status = wait(pid);
+    let mut output = String::from_utf8_lossy(&output).into_owned();
+    output.truncate(output.trim_end_matches('\n').len());
+    (output, status)
+}
+
+/// Execute a `command` pair.
+fn command(rush: &mut RuSh, p: Pair<Rule>) -> i32 {
+    let inner = p.into_inner().next().unwrap();
+    match inner.as_rule() {
+        Rule::simplecommand => simple_command(rush, inner),
+        Rule::ifclause => if_clause(rush, inner),
+        Rule::whileclause => while_clause(rush, inner, false),
+        Rule::untilclause => while_clause(rush, inner, true),
+        Rule::bracegroup => list(rush, inner.into_inner().next().unwrap()),
+        Rule::functiondef => define_function(rush, inner),
+        _ => unreachable!(),
+    }
+}
+
+/// Define a shell function: its body is kept as text, and parsed again each time it is called.
+fn define_function(rush: &mut RuSh, p: Pair<Rule>) -> i32 {
+    let mut inner = p.into_inner();
+    let name = inner.next().unwrap().as_str().to_string();
+    let body = inner.next().unwrap();
+    let (line, _) = body.as_span().start_pos().line_col();
+    rush.functions.insert(
+        name,
+        (body.as_str().to_string(), rush.lineno + line as i64 - 1),
+    );
+    0
+}
+
+/// Call a shell function, `args[0]` being its name, with other arguments as positional parameters.
+/// FUNCNAME array lists functions being executed, innermost first.
+/// Returns exit status, the one given to return if it was used, None if there is no such function.
+///
+/// # Examples
+/// ```rust
+/// use rush::RuSh;
+/// use rush::execute;
+/// use rush::parse::parse;
+///
+/// let mut rush = RuSh::default();
+/// parse(&mut rush, "f() { n=$#; name=$FUNCNAME; return 3; n=0; }");
+/// let args = vec![String::from("f"), String::from("a"), String::from("b")];
+/// assert_eq!(execute::function(&mut rush, &args), Some(3));
+/// assert_eq!(rush.shell_vars.get("n").unwrap().geti(), 2);
+/// assert_eq!(rush.shell_vars.get("name").unwrap().value.to_string(), "f");
+/// assert_eq!(execute::function(&mut rush, &[String::from("g")]), None);
+/// ```
+pub fn function(rush: &mut RuSh, args: &[String]) -> Option<i32> {
+    let (body, line) = rush.functions.get(&args[0])?.clone();
+    let mut positional = vec![rush.positional[0].clone()];
+    positional.extend_from_slice(&args[1..]);
+    let saved = std::mem::replace(&mut rush.positional, positional);
+    rush.funcname.push(args[0].clone());
+    update_funcname(rush);
+    rush.nesting += 1;
+    rush.returnable += 1;
+    let status = parse::reparse(rush, &body, line);
+    rush.returnable -= 1;
+    rush.nesting -= 1;
+    let status = rush.returning.take().unwrap_or(status);
+    rush.funcname.pop();
+    update_funcname(rush);
+    rush.positional = saved;
+    Some(status)
+}
+
+/// Update FUNCNAME array from the functions stack, innermost function first.
+fn update_funcname(rush: &mut RuSh) {
+    rush.shell_array_vars.clear("FUNCNAME");
+    for (i, name) in rush.funcname.iter().rev().enumerate() {
+        rush.shell_array_vars
+            .set("FUNCNAME", Index::I(i), Value::S(name.clone()));
+    }
+}
+
+/// Execute a condition list, errexit being ignored. Returns true if it succeeds.
+fn condition(rush: &mut RuSh, p: Pair<Rule>) -> bool {
+    rush.errexit_ignored += 1;
+    let status = list(rush, p);
+    rush.errexit_ignored -= 1;
+    status == 0
+}
+
+/// Execute an if clause: if list; then list; [elif list; then list;]... [else list;] fi
+fn if_clause(rush: &mut RuSh, p: Pair<Rule>) -> i32 {
+    let mut inner = p.into_inner();
+    let cond = inner.next().unwrap();
+    let body = inner.next().unwrap();
+    if condition(rush, cond) {
+        return list(rush, body);
+    }
+    if stopped(rush) {
+        return rush.last_status;
+    }
+    for part in inner {
+        match part.as_rule() {
+            Rule::elifpart => {
+                let mut elif = part.into_inner();
+                let cond = elif.next().unwrap();
+                if condition(rush, cond) {
+                    return list(rush, elif.next().unwrap());
+                }
+                if stopped(rush) {
+                    return rush.last_status;
+                }
+            }
+            Rule::elsepart => return list(rush, part.into_inner().next().unwrap()),
+            _ => unreachable!(),
+        }
+    }
+    // no condition succeeded and there is no else part.
+    0
+}
+
+/// Execute a while (`until` false) clause: while list; do list; done
+fn while_clause(rush: &mut RuSh, p: Pair<Rule>, until: bool) -> i32 {
+    let mut inner = p.into_inner();
+    let cond = inner.next().unwrap();
+    let body = inner.next().unwrap();
+    let mut status = 0;
+    loop {
+        let success = condition(rush, cond.clone());
+        if stopped(rush) || success == until {
+            break;
+        }
+        status = list(rush, body.clone());
+        if stopped(rush) {
+            break;
+        }
+    }
+    status
+}
+
+/// Execute a simple command: assignments only, a special builtin, a function, a builtin or an external command.
+fn simple_command(rush: &mut RuSh, p: Pair<Rule>) -> i32 {
+    // LINENO is the command line number in its source.
+    let (line, _) = p.as_span().start_pos().line_col();
+    let line = rush.lineno + line as i64 - 1;
+    rush.shell_vars.set(
+        String::from("LINENO"),
+        Variable {
+            value: Value::I(line),
+            access: Access::ReadWrite,
+            export: false,
+        },
+    );
+    // a command made of assignments only exits with the status of the last command substitution, if any.
+    let substituted = p
+        .clone()
+        .into_inner()
+        .flatten()
+        .any(|i| i.as_rule() == Rule::cmdsubst || i.as_rule() == Rule::btquoted);
+    let mut assignments = Vec::new();
+    let mut args: Vec<String> = Vec::new();
+    let mut values = Vec::new();
+    for inner in p.into_inner() {
+        match inner.as_rule() {
+            Rule::assignment => assignments.push(assignment(rush, inner)),
+            Rule::word => {
+                let fields = expand::word(rush, inner.clone());
+                if fields.len() == 1 {
+                    values.push(expand::typed(rush, &inner, &fields[0]));
+                } else {
+                    values.extend(fields.iter().map(|f| Value::S(f.clone())));
+                }
+                args.extend(fields);
+            }
+            _ => unreachable!(),
+        }
+    }
+    // an expansion error aborts the command.
+    if stopped(rush) {
+        return rush.exit.unwrap_or(1);
+    }
+    if let Some(opt) = rush.set_options.get("xtrace") {
+        if opt.get() {
+            xtrace(rush, &assignments, &args);
+        }
+    }
+    // no command name, variables are set in the shell itself.
+    if args.is_empty() {
+        let mut status = if substituted { rush.last_status } else { 0 };
+        for a in assignments {
+            if !set(rush, a) {
+                status = 1;
+            }
+        }
+        return status;
+    }
+    let is_function = rush.functions.contains_key(&args[0]);
+    match builtins::get(&args[0]) {
+        // assignments preceding special builtins remain in effect.
+        Some(builtin) if builtins::is_special(&args[0]) => {
+            for a in assignments {
+                set(rush, a);
+            }
+            rush.values = values;
+            builtin(rush, &args)
+        }
+        // assignments preceding functions and other builtins are exported during their execution only.
+        builtin if is_function || builtin.is_some() => {
+            let assignments: Vec<Assignment> = assignments
+                .into_iter()
+                .filter(|a| a.index.is_none() && a.array.is_none())
+                .collect();
+            // nothing is assigned if one of the variables is readonly.
+            for a in &assignments {
+                if rush.shell_vars.get_access(&a.name) == Some(Access::ReadOnly) {
+                    eprintln!("rush: {}: readonly variable", a.name);
+                    return 1;
+                }
+            }
+            let mut saved = Vec::with_capacity(assignments.len());
+            for a in assignments {
+                saved.push((a.name.clone(), rush.shell_vars.get(&a.name)));
+                rush.shell_vars.set(
+                    a.name,
+                    Variable {
+                        value: parse::value(&a.value),
+                        access: Access::ReadWrite,
+                        export: true,
+                    },
+                );
+            }
+            let status = match builtin {
+                Some(builtin) if !is_function => {
+                    rush.values = values;
+                    builtin(rush, &args)
+                }
+                _ => function(rush, &args).unwrap_or(0),
+            };
+            for (name, old) in saved.into_iter().rev() {
+                rush.shell_vars.unset(name.clone());
+                if let Some(var) = old {
+                    rush.shell_vars.set(name, var);
+                }
+            }
+            status
+        }
+        // assignments preceding external commands only go to the child environment.
+        _ => {
+            let overrides: Vec<(String, String)> = assignments
+                .into_iter()
+                .filter(|a| a.index.is_none() && a.array.is_none())
+                .map(|a| (a.name, a.value))
+                .collect();
+            spawn(rush, &args, &overrides)
+        }
+    }
+}
+
+/// Print an expanded simple command, prefixed by PS4, to stderr or to the file descriptor given by RUSH_XTRACEFD.
+/// PS4 first character is repeated to show nesting level.
+fn xtrace(rush: &mut RuSh, assignments: &[Assignment], args: &[String]) {
+    let ps4 = Prompt::get(rush, "PS4").prompt;
+    let mut line = String::with_capacity(ps4.len() + 64);
+    if let Some(c) = ps4.chars().next() {
+        for _ in 0..rush.nesting {
+            line.push(c);
+        }
+    }
+    line.push_str(&ps4);
+    let mut words = Vec::with_capacity(assignments.len() + args.len());
+    for a in assignments {
+        words.push(match (&a.index, &a.array) {
+            (_, Some(array)) => format!(
+                "{}=({})",
+                a.name,
+                array
+                    .iter()
+                    .map(|w| builtins::squote(w))
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            ),
+            (Some(i), None) => format!("{}[{}]={}", a.name, i, builtins::squote(&a.value)),
+            (None, None) => format!("{}={}", a.name, builtins::squote(&a.value)),
+        });
+    }
+    words.extend(args.iter().map(|a| builtins::squote(a)));
+    line.push_str(&words.join(" "));
+    line.push('\n');
+    let fd = match rush.shell_vars.get("RUSH_XTRACEFD") {
+        Some(v) => v.value.to_string().parse::<i32>().unwrap_or(2),
+        None => 2,
+    };
+    if fd == 2 {
+        eprint!("{}", line);
+    } else {
+        let _ = io::stdout().flush();
+        // an invalid file descriptor falls back to stderr.
+        if unsafe { libc::write(fd, line.as_ptr() as *const libc::c_void, line.len()) } < 0 {
+            eprint!("{}", line);
+        }
+    }
+}
+
+/// Expand an `assignment` pair.
+fn assignment(rush: &mut RuSh, p: Pair<Rule>) -> Assignment {
+    let mut inner = p.into_inner();
+    let name = expand::param_name(rush, inner.next().unwrap());
+    let mut array = None;
+    let value = match inner.next() {
+        Some(words) if words.as_rule() == Rule::arraywords => {
+            array = Some(
+                words
+                    .into_inner()
+                    .flat_map(|word| expand::word(rush, word))
+                    .collect(),
+            );
+            String::new()
+        }
+        Some(word) => expand::word_nosplit(rush, word),
+        None => String::new(),
+    };
+    match name.find('[') {
+        Some(pos) => Assignment {
+            name: name[..pos].to_string(),
+            index: Some(name[pos + 1..name.len() - 1].to_string()),
+            value,
+            array,
+        },
+        None => Assignment {
+            name,
+            index: None,
+            value,
+            array,
+        },
+    }
+}
+
+/// Carry out an assignment in the shell itself. A compound assignment replaces every element of the array with
+/// its words, indexed from 0. Returns false if a list is assigned to an array element.
+fn set(rush: &mut RuSh, a: Assignment) -> bool {
+    match (a.index, a.array) {
+        (Some(i), Some(_)) => {
+            eprintln!(
+                "rush: {}[{}]: cannot assign list to array member",
+                a.name, i
+            );
+            return false;
+        }
+        (None, Some(words)) => {
+            rush.shell_array_vars.clear(&a.name);
+            for (i, word) in words.iter().enumerate() {
+                rush.shell_array_vars
+                    .set(&a.name, Index::I(i), parse::value(word));
+            }
+        }
+        (index, None) => assign(rush, &a.name, index, &a.value),
+    }
+    true
+}
+
+/// Assign a value to a variable (or an array element), its type being autodetected.
+/// The variable is exported if allexport option is set.
+pub fn assign(rush: &mut RuSh, name: &str, index: Option<String>, value: &str) {
+    match index {
+        Some(i) => rush
+            .shell_array_vars
+            .set(name, expand::array_index(&i), parse::value(value)),
+        None => {
+            let export = match rush.set_options.get("allexport") {
+                Some(opt) => opt.get(),
+                None => false,
+            };
+            rush.shell_vars.set(
+                name.to_string(),
+                Variable {
+                    value: parse::value(value),
+                    access: Access::ReadWrite,
+                    export,
+                },
+            )
+        }
+    }
+}
+
+/// Search a command in PATH directories. A name containing a slash is used as is.
+pub fn find_command(rush: &RuSh, name: &str) -> Option<String> {
+    if name.contains('/') {
+        return Some(name.to_string());
+    }
+    let path = match rush.shell_vars.get("PATH") {
+        Some(p) => p.value.to_string(),
+        None => return None,
+    };
+    for dir in path.split(':') {
+        // an empty PATH element means current directory.
+        let dir = if dir.is_empty() { "." } else { dir };
+        let candidate = Path::new(dir).join(name);
+        if let Ok(meta) = fs::metadata(&candidate) {
+            if meta.is_file() && meta.permissions().mode() & 0o111 != 0 {
+                return candidate.to_str().map(String::from);
+            }
+        }
+    }
+    None
+}
+
+/// Run an external command in a child process and wait for it.
+/// `overrides` are variables given to the child environment only.
+fn spawn(rush: &mut RuSh, args: &[String], overrides: &[(String, String)]) -> i32 {
+    let restricted = match rush.shopt_options.get("restricted_shell") {
+        Some(opt) => opt.get(),
+        None => false,
+    };
+    if restricted && args[0].contains('/') {
+        eprintln!(
+            "rush: {}: restricted: cannot specify `/' in command names",
+            args[0]
+        );
+        return 1;
+    }
+    let path = match find_command(rush, &args[0]) {
+        Some(p) => p,
+        None => {
+            eprintln!("rush: {}: command not found", args[0]);
+            return 127;
+        }
+    };
+    // everything is allocated before fork, child only has to call execve.
+    let cpath = match CString::new(path) {
+        Ok(c) => c,
+        Err(_) => return 127,
+    };
+    let argv: Vec<CString> = args
+        .iter()
+        .filter_map(|a| CString::new(a.as_str()).ok())
+        .collect();
+    let envp = rush.shell_vars.envp(overrides);
+    let mut argvp: Vec<*const c_char> = argv.iter().map(|a| a.as_ptr()).collect();
+    argvp.push(ptr::null());
+    let mut envpp: Vec<*const c_char> = envp.iter().map(|e| e.as_ptr()).collect();
+    envpp.push(ptr::null());
+    let _ = io::stdout().flush();
+    match unsafe { libc::fork() } {
+        -1 => {
+            eprintln!("rush: fork: {}", os_error(&io::Error::last_os_error()));
+            1
+        }
+        0 => unsafe {
+            libc::execve(cpath.as_ptr(), argvp.as_ptr(), envpp.as_ptr());
+            let err = io::Error::last_os_error();
+            eprintln!("rush: {}: {}", args[0], os_error(&err));
+            libc::_exit(if err.kind() == io::ErrorKind::NotFound {
+                127
+            } else {
+                126
+            });
+        },
+        pid => wait(pid),
+    }
+}
+
+/// Get an OS error message the way the C library gives it, without Rust `(os error N)` suffix.
+///
+/// # Examples
+/// ```rust
+/// use std::io;
+/// use rush::execute::os_error;
+///
+/// assert_eq!(os_error(&io::Error::from_raw_os_error(2)), "No such file or directory");
+/// ```
+pub fn os_error(err: &io::Error) -> String {
+    let msg = err.to_string();
+    match msg.find(" (os error") {
+        Some(pos) => msg[..pos].to_string(),
+        None => msg,
+    }
+}
+
+/// Wait for a child process. Returns its exit status, 128 + signal number if it was killed.
+pub fn wait(pid: libc::pid_t) -> i32 {
+    let mut status = 0;
+    loop {
+        if unsafe { libc::waitpid(pid, &mut status, 0) } != -1 {
+            break;
+        }
+        if io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+            return 1;
+        }
+    }
+    if libc::WIFEXITED(status) {
+        libc::WEXITSTATUS(status)
+    } else if libc::WIFSIGNALED(status) {
+        128 + libc::WTERMSIG(status)
+    } else {
+        1
+    }
+}
@@ -0,0 +1,790 @@
+//
+// expand.rs
+//
+// Copyright 2015-2019 Laurent Wandrebeck <l.wandrebeck@quelquesmots.fr>
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston,
+// MA 02110-1301, USA.
+//
+
+//! RuSh words expansion is located in this module.
+//!
+//! expand.rs turns `word` pairs parsed with rush.pest into fields.
+//! Tilde expansion, parameter expansion, command substitution, arithmetic expansion, quote removal and word splitting
+//! on IFS are done here.
+
+use crate::arith;
+use crate::arrays::Index;
+use crate::execute;
+use crate::parse::{self, Rule};
+use crate::rush::RuSh;
+use crate::segments;
+use crate::variables::{Access, Value, Variable};
+use pest::iterators::Pair;
+
+/// Default value of IFS when the variable is not set.
+const DEFAULT_IFS: &str = " \t\n";
+
+/// Fields being built while expanding a word.
+struct Fields {
+    /// fields already delimited.
+    fields: Vec<String>,
+    /// field being built.
+    current: String,
+    /// current field contains quoted parts, so it is kept even when empty.
+    quoted: bool,
+}
+
+/// Methods for `Fields`.
+impl Fields {
+    fn new() -> Fields {
+        Fields {
+            fields: Vec::new(),
+            current: String::new(),
+            quoted: false,
+        }
+    }
+
+    /// Add quoted text, never split.
+    fn quoted(&mut self, s: &str) {
+        self.current.push_str(s);
+        self.quoted = true;
+    }
+
+    /// Add unquoted text which is not the result of an expansion, never split either.
+    fn literal(&mut self, s: &str) {
+        self.current.push_str(s);
+    }
+
+    /// Add the result of an unquoted expansion, split on IFS characters.
+    fn split(&mut self, s: &str, ifs: &str) {
+        for c in s.chars() {
+            if ifs.contains(c) {
+                if !self.current.is_empty() || self.quoted {
+                    self.delimit();
+                }
+            } else {
+                self.current.push(c);
+            }
+        }
+    }
+
+    /// End current field.
+    fn delimit(&mut self) {
+        self.fields.push(std::mem::take(&mut self.current));
+        self.quoted = false;
+    }
+
+    /// Get back every field.
+    fn finish(mut self) -> Vec<String> {
+        if !self.current.is_empty() || self.quoted {
+            self.delimit();
+        }
+        self.fields
+    }
+}
+
+/// Get IFS value, default one if the variable is not set.
+pub fn ifs(rush: &RuSh) -> String {
+    match rush.shell_vars.get("IFS") {
+        Some(v) => v.value.to_string(),
+        None => DEFAULT_IFS.to_string(),
+    }
+}
+
+/// Expand a `word` pair into fields: tilde, parameters, quote removal and word splitting.
+pub fn word(rush: &mut RuSh, p: Pair<Rule>) -> Vec<String> {
+    let ifs = ifs(rush);
+    let mut fields = Fields::new();
+    for (pos, part) in p.into_inner().enumerate() {
+        match part.as_rule() {
+            Rule::literal => {
+                if pos == 0 {
+                    fields.literal(&tilde(rush, part.as_str()));
+                } else {
+                    fields.literal(part.as_str());
+                }
+            }
+            Rule::escaped => fields.quoted(&unescape(part.as_str())),
+            Rule::squoted => fields.quoted(&squoted(part)),
+            Rule::dquoted => dquoted(rush, part, &mut fields),
+            Rule::varvalue => match positional_list(&part) {
+                Some(_) => {
+                    let params: Vec<String> = rush.positional.iter().skip(1).cloned().collect();
+                    for (i, param) in params.iter().enumerate() {
+                        if i > 0 {
+                            fields.delimit();
+                        }
+                        fields.split(param, &ifs);
+                    }
+                }
+                None => {
+                    let value = varvalue(rush, part);
+                    fields.split(&value, &ifs);
+                }
+            },
+            Rule::arithexp | Rule::cmdsubst | Rule::btquoted => {
+                let value = substitution(rush, part);
+                fields.split(&value, &ifs);
+            }
+            Rule::dollar => fields.literal("$"),
+            _ => unreachable!(),
+        }
+    }
+    fields.finish()
+}
+
+/// Expand a `word` pair into a single string, without word splitting (assignments, here strings...).
+pub fn word_nosplit(rush: &mut RuSh, p: Pair<Rule>) -> String {
+    let mut s = String::new();
+    for (pos, part) in p.into_inner().enumerate() {
+        match part.as_rule() {
+            Rule::literal => {
+                if pos == 0 {
+                    s.push_str(&tilde(rush, part.as_str()));
+                } else {
+                    s.push_str(part.as_str());
+                }
+            }
+            Rule::escaped => s.push_str(&unescape(part.as_str())),
+            Rule::squoted => s.push_str(&squoted(part)),
+            Rule::dquoted => {
+                let mut fields = Fields::new();
+                dquoted(rush, part, &mut fields);
+                s.push_str(&fields.finish().join(" "));
+            }
+            Rule::varvalue => s.push_str(&varvalue(rush, part)),
+            Rule::arithexp | Rule::cmdsubst | Rule::btquoted => {
+                s.push_str(&substitution(rush, part))
+            }
+            Rule::dollar => s.push('$'),
+            _ => unreachable!(),
+        }
+    }
+    s
+}
+
+/// Value of a word expanded into `field`. A word made of a lone `$name` or `${name}`, quoted or not, keeps the type
+/// of the variable it comes from, so that builtins like printf can use integers and floats as they are.
+pub fn typed(rush: &RuSh, p: &Pair<Rule>, field: &str) -> Value {
+    let mut part = p.clone();
+    loop {
+        let mut inner = part.clone().into_inner();
+        match (inner.next(), inner.next()) {
+            (Some(only), None) if part.as_rule() != Rule::varvalue => part = only,
+            _ => break,
+        }
+    }
+    if part.as_rule() == Rule::varvalue {
+        let mut inner = part.into_inner();
+        if let (Some(name), None) = (inner.next(), inner.next()) {
+            if let Rule::noarrayvarname | Rule::varname = name.as_rule() {
+                if let Some(var) = rush.shell_vars.get(name.as_str()) {
+                    if var.value.to_string() == field {
+                        return var.value;
+                    }
+                }
+            }
+        }
+    }
+    Value::S(field.to_string())
+}
+
+/// Expand an `arithexp`, `cmdsubst` or `btquoted` pair: value of the arithmetic expression, or output of the
+/// command. Within backquotes, a backslash only escapes `` ` ``, `$` and another backslash.
+fn substitution(rush: &mut RuSh, p: Pair<Rule>) -> String {
+    let s = p.as_str();
+    match p.as_rule() {
+        Rule::arithexp => {
+            let expr = parse::expand_string(rush, &s[3..s.len() - 2]);
+            match arith::eval(rush, &expr) {
+                Ok(value) => value.to_string(),
+                Err(msg) => {
+                    eprintln!("rush: {}: {}", expr.trim(), msg);
+                    error(rush, 1);
+                    String::new()
+                }
+            }
+        }
+        Rule::cmdsubst => command_substitution(rush, &s[2..s.len() - 1]),
+        _ => {
+            let mut command = String::new();
+            let mut chars = s[1..s.len() - 1].chars().peekable();
+            while let Some(c) = chars.next() {
+                match (c, chars.peek()) {
+                    ('\\', Some('`')) | ('\\', Some('$')) | ('\\', Some('\\')) => {
+                        command.push(chars.next().unwrap())
+                    }
+                    _ => command.push(c),
+                }
+            }
+            command_substitution(rush, &command)
+        }
+    }
+}
+
+/// Run a command substitution, in background when an interactive prompt is expanded.
+fn command_substitution(rush: &mut RuSh, command: &str) -> String {
+    if rush.segments.active {
+        segments::substitute(rush, command)
+    } else {
+        execute::substitute(rush, command)
+    }
+}
+
+/// Tilde expansion of the beginning of an unquoted literal: ~, ~+, ~- and ~/path.
+fn tilde(rush: &RuSh, s: &str) -> String {
+    if !s.starts_with('~') {
+        return s.to_string();
+    }
+    let end = s.find('/').unwrap_or(s.len());
+    let var = match &s[1..end] {
+        "" => "HOME",
+        "+" => "PWD",
+        "-" => "OLDPWD",
+        _ => return s.to_string(),
+    };
+    match rush.shell_vars.get(var) {
+        Some(v) => format!("{}{}", v.value, &s[end..]),
+        None => s.to_string(),
+    }
+}
+
+/// Remove backslash of an unquoted escaped character. An escaped newline disappears.
+fn unescape(s: &str) -> String {
+    match &s[1..] {
+        "\n" => String::new(),
+        c => c.to_string(),
+    }
+}
+
+/// Contents of a single quoted string.
+fn squoted(p: Pair<Rule>) -> String {
+    let s = p.as_str();
+    s[1..s.len() - 1].to_string()
+}
+
+/// Expand double quoted string contents into `fields`. "$@" is the only way to get several fields.
+fn dquoted(rush: &mut RuSh, p: Pair<Rule>, fields: &mut Fields) {
+    let parts: Vec<Pair<Rule>> = p.into_inner().collect();
+    // "$@" with no positional parameters gives no field at all.
+    let only_at = parts.len() == 1 && positional_list(&parts[0]) == Some(true);
+    if !only_at || rush.positional.len() > 1 {
+        fields.quoted("");
+    }
+    for part in parts {
+        match part.as_rule() {
+            Rule::nodquotedstring => fields.quoted(part.as_str()),
+            Rule::dqescaped => {
+                let c = &part.as_str()[1..];
+                match c {
+                    "$" | "`" | "\"" | "\\" => fields.quoted(c),
+                    "\n" => (),
+                    _ => fields.quoted(part.as_str()),
+                }
+            }
+            Rule::varvalue => match positional_list(&part) {
+                Some(true) => {
+                    let params: Vec<String> = rush.positional.iter().skip(1).cloned().collect();
+                    for (i, param) in params.iter().enumerate() {
+                        if i > 0 {
+                            fields.delimit();
+                        }
+                        fields.quoted(param);
+                    }
+                }
+                _ => {
+                    let value = varvalue(rush, part);
+                    fields.quoted(&value);
+                }
+            },
+            Rule::arithexp | Rule::cmdsubst | Rule::btquoted => {
+                let value = substitution(rush, part);
+                fields.quoted(&value);
+            }
+            Rule::dollar => fields.quoted("$"),
+            Rule::dquote => fields.quoted("\""),
+            Rule::EOI => (),
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// Expand an `expandstring` pair the way double quoted contents are, into a single string.
+pub fn string(rush: &mut RuSh, p: Pair<Rule>) -> String {
+    let mut fields = Fields::new();
+    dquoted(rush, p, &mut fields);
+    fields.finish().join(" ")
+}
+
+/// Is the varvalue `$@` / `${@}` (Some(true)) or `$*` / `${*}` (Some(false)) ?
+fn positional_list(p: &Pair<Rule>) -> Option<bool> {
+    match p.as_str() {
+        "$@" | "${@}" => Some(true),
+        "$*" | "${*}" => Some(false),
+        _ => None,
+    }
+}
+
+/// Get a parameter value from its name: positional and special parameters, variables and array elements.
+/// Returns None when the parameter is not set.
+pub fn param(rush: &RuSh, name: &str) -> Option<String> {
+    if let Some(pos) = name.find('[') {
+        let (array, index) = (&name[..pos], &name[pos + 1..name.len() - 1]);
+        return match index {
+            "@" | "*" => {
+                let values = array_values(rush, array);
+                if values.is_empty() {
+                    None
+                } else {
+                    Some(values.join(" "))
+                }
+            }
+            _ => rush
+                .shell_array_vars
+                .get(array, &array_index(index))
+                .map(|v| v.to_string()),
+        };
+    }
+    match name {
+        "#" => Some((rush.positional.len().max(1) - 1).to_string()),
+        "@" | "*" => {
+            if rush.positional.len() > 1 {
+                Some(rush.positional[1..].join(" "))
+            } else {
+                None
+            }
+        }
+        "?" => Some(rush.last_status.to_string()),
+        "-" => Some(rush.set_options.flags()),
+        "$" => rush.shell_vars.get("RUSHPID").map(|v| v.value.to_string()),
+        _ => {
+            if let Ok(n) = name.parse::<usize>() {
+                return rush.positional.get(n).cloned();
+            }
+            match rush.shell_vars.get(name) {
+                Some(v) => Some(v.value.to_string()),
+                // $array is ${array[0]}
+                None => rush
+                    .shell_array_vars
+                    .get(name, &Index::I(0))
+                    .map(|v| v.to_string()),
+            }
+        }
+    }
+}
+
+/// Get an `Index` from its textual representation.
+pub fn array_index(index: &str) -> Index {
+    match index.trim().parse::<usize>() {
+        Ok(i) => Index::I(i),
+        Err(_) => Index::A(index.to_string()),
+    }
+}
+
+/// Values of an array, indexed ones sorted by index.
+pub fn array_values(rush: &RuSh, array: &str) -> Vec<String> {
+    match rush.shell_array_vars.arrayvars.get(array) {
+        Some((_access, hm)) => {
+            let mut values: Vec<(&Index, &Value)> = hm.iter().collect();
+            values.sort_by(|a, b| match (a.0, b.0) {
+                (Index::I(x), Index::I(y)) => x.cmp(y),
+                _ => std::cmp::Ordering::Equal,
+            });
+            values.iter().map(|(_, v)| v.to_string()).collect()
+        }
+        None => Vec::new(),
+    }
+}
+
+/// Get the name of the parameter referenced by a varname or assignname pair, `name[index]` for array elements.
+/// Index part is expanded.
+pub fn param_name(rush: &mut RuSh, p: Pair<Rule>) -> String {
+    let s = p.as_str().to_string();
+    match p.into_inner().find(|i| i.as_rule() == Rule::index) {
+        Some(index) => {
+            let name = &s[..s.find('[').unwrap_or(s.len())];
+            let i = index.into_inner().next().unwrap();
+            let value = match i.as_rule() {
+                Rule::varvalue => varvalue(rush, i),
+                Rule::dquoted => {
+                    let mut fields = Fields::new();
+                    dquoted(rush, i, &mut fields);
+                    fields.finish().join(" ")
+                }
+                Rule::squoted => squoted(i),
+                _ => i.as_str().to_string(),
+            };
+            format!("{}[{}]", name, value)
+        }
+        None => s,
+    }
+}
+
+/// Expand the value given to a ${parameter<op>value} expansion, without word splitting.
+fn opvalue(rush: &mut RuSh, p: Pair<Rule>) -> String {
+    let mut s = String::new();
+    for part in p.into_inner() {
+        match part.as_rule() {
+            Rule::varvalue => s.push_str(&varvalue(rush, part)),
+            Rule::dquoted => {
+                let mut fields = Fields::new();
+                dquoted(rush, part, &mut fields);
+                s.push_str(&fields.finish().join(" "));
+            }
+            Rule::squoted => s.push_str(&squoted(part)),
+            Rule::noclosingbracketstring | Rule::noslashstring => {
+                let mut escape = false;
+                for c in part.as_str().chars() {
+                    if c == '\\' && !escape {
+                        escape = true;
+                    } else {
+                        s.push(c);
+                        escape = false;
+                    }
+                }
+            }
+            Rule::dollar => s.push('$'),
+            _ => s.push_str(part.as_str()),
+        }
+    }
+    s
+}
+
+/// Set a variable from ${parameter=value} expansion.
+fn assign(rush: &mut RuSh, name: &str, value: &str) {
+    match name.find('[') {
+        Some(pos) => rush.shell_array_vars.set(
+            &name[..pos],
+            array_index(&name[pos + 1..name.len() - 1]),
+            Value::S(value.to_string()),
+        ),
+        None => rush.shell_vars.set(
+            name.to_string(),
+            Variable {
+                value: Value::S(value.to_string()),
+                access: Access::ReadWrite,
+                export: false,
+            },
+        ),
+    }
+}
+
+/// Expand a `varvalue` pair: $name, ${name}, ${name<op>value}...
+pub fn varvalue(rush: &mut RuSh, p: Pair<Rule>) -> String {
+    let mut inner = p.into_inner();
+    let first = match inner.next() {
+        Some(f) => f,
+        None => return String::new(),
+    };
+    match first.as_rule() {
+        Rule::noarrayvarname => match param(rush, first.as_str()) {
+            Some(v) => v,
+            None => unset(rush, first.as_str()),
+        },
+        // ${#name}
+        Rule::varlen => {
+            let name = param_name(rush, inner.next().unwrap());
+            match name.as_str() {
+                "@" | "*" => (rush.positional.len().max(1) - 1).to_string(),
+                _ if name.ends_with("[@]") || name.ends_with("[*]") => {
+                    array_values(rush, &name[..name.len() - 3])
+                        .len()
+                        .to_string()
+                }
+                _ => match param(rush, &name) {
+                    Some(v) => v.chars().count().to_string(),
+                    None => unset(rush, &name).chars().count().to_string(),
+                },
+            }
+        }
+        // ${!prefix*} ${!prefix@}
+        Rule::varmatch => {
+            let prefix = inner.next().unwrap().as_str().to_string();
+            let names: Vec<String> = rush
+                .shell_vars
+                .names()
+                .into_iter()
+                .filter(|n| n.starts_with(&prefix))
+                .collect();
+            names.join(" ")
+        }
+        Rule::varname => {
+            let name = param_name(rush, first);
+            let value = param(rush, &name);
+            let op = match inner.next() {
+                Some(op) => op,
+                None => {
+                    return match value {
+                        Some(v) => v,
+                        None => unset(rush, &name),
+                    }
+                }
+            };
+            // with a colon, a null parameter is treated as an unset one.
+            let colon = op.as_str().starts_with(':');
+            let set = match &value {
+                Some(v) => !(colon && v.is_empty()),
+                None => false,
+            };
+            let value = value.unwrap_or_default();
+            match op.as_rule() {
+                Rule::varusedefault => {
+                    let word = opvalue(rush, inner.next().unwrap());
+                    if set {
+                        value
+                    } else {
+                        word
+                    }
+                }
+                Rule::varsetdefault => {
+                    let word = opvalue(rush, inner.next().unwrap());
+                    if set {
+                        value
+                    } else {
+                        assign(rush, &name, &word);
+                        word
+                    }
+                }
+                Rule::varaltvalue => {
+                    let word = opvalue(rush, inner.next().unwrap());
+                    if set {
+                        word
+                    } else {
+                        String::new()
+                    }
+                }
+                Rule::varerrmsg => {
+                    let word = opvalue(rush, inner.next().unwrap());
+                    if set {
+                        value
+                    } else {
+                        if word.is_empty() {
+                            eprintln!("rush: {}: parameter null or not set", name);
+                        } else {
+                            eprintln!("rush: {}: {}", name, word);
+                        }
+                        error(rush, 127);
+                        String::new()
+                    }
+                }
+                Rule::varshortfrontmatch | Rule::varlongfrontmatch => {
+                    let pattern = opvalue(rush, inner.next().unwrap());
+                    let chars: Vec<char> = value.chars().collect();
+                    let mut ends: Vec<usize> = (0..=chars.len()).collect();
+                    if op.as_rule() == Rule::varlongfrontmatch {
+                        ends.reverse();
+                    }
+                    for end in ends {
+                        let front: String = chars[..end].iter().collect();
+                        if pattern_match(&pattern, &front) {
+                            return chars[end..].iter().collect();
+                        }
+                    }
+                    value
+                }
+                Rule::varshortbackmatch | Rule::varlongbackmatch => {
+                    let pattern = opvalue(rush, inner.next().unwrap());
+                    let chars: Vec<char> = value.chars().collect();
+                    let mut starts: Vec<usize> = (0..=chars.len()).rev().collect();
+                    if op.as_rule() == Rule::varlongbackmatch {
+                        starts.reverse();
+                    }
+                    for start in starts {
+                        let back: String = chars[start..].iter().collect();
+                        if pattern_match(&pattern, &back) {
+                            return chars[..start].iter().collect();
+                        }
+                    }
+                    value
+                }
+                Rule::varrepl | Rule::varreplall | Rule::varsubstfront | Rule::varsubstback => {
+                    let pattern = opvalue(rush, inner.next().unwrap());
+                    let repl = match inner.next() {
+                        Some(r) => opvalue(rush, r),
+                        None => String::new(),
+                    };
+                    replace(&value, &pattern, &repl, op.as_rule())
+                }
+                Rule::varsubstr => {
+                    let offset = substr_arg(rush, inner.next().unwrap());
+                    let length = inner.next().map(|l| substr_arg(rush, l));
+                    let chars: Vec<char> = value.chars().collect();
+                    let len = chars.len() as i64;
+                    let start = if offset < 0 {
+                        (len + offset).max(0)
+                    } else {
+                        offset.min(len)
+                    };
+                    let end = match length {
+                        Some(l) if l < 0 => (len + l).max(start),
+                        Some(l) => (start + l).min(len),
+                        None => len,
+                    };
+                    chars[start as usize..end as usize].iter().collect()
+                }
+                Rule::varlowe => value.to_lowercase(),
+                Rule::varupp => value.to_uppercase(),
+                Rule::varlow | Rule::varup => {
+                    let mut chars = value.chars();
+                    match chars.next() {
+                        Some(c) if op.as_rule() == Rule::varlow => {
+                            c.to_lowercase().chain(chars).collect()
+                        }
+                        Some(c) => c.to_uppercase().chain(chars).collect(),
+                        None => value,
+                    }
+                }
+                _ => unreachable!(),
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Value of an unset parameter: null string, unless nounset option is set, which makes it an error.
+/// $@ and $* are never considered unbound.
+fn unset(rush: &mut RuSh, name: &str) -> String {
+    if let Some(opt) = rush.set_options.get("nounset") {
+        if opt.get() && name != "@" && name != "*" {
+            eprintln!("rush: {}: unbound variable", name);
+            error(rush, 127);
+        }
+    }
+    String::new()
+}
+
+/// An expansion error aborts current input execution in an interactive shell, and makes a non interactive one exit
+/// with `status`: 1 for an arithmetic error, 127 for an unset parameter.
+fn error(rush: &mut RuSh, status: i32) {
+    if rush.interactive {
+        rush.abort = true;
+        rush.last_status = 1;
+    } else {
+        rush.exit = Some(status);
+    }
+}
+
+/// Get integer argument of ${parameter:offset:length}.
+fn substr_arg(rush: &mut RuSh, p: Pair<Rule>) -> i64 {
+    let v = match p.into_inner().next() {
+        Some(i) if i.as_rule() == Rule::varvalue => varvalue(rush, i),
+        Some(i) => i.as_str().to_string(),
+        None => String::new(),
+    };
+    v.trim().parse::<i64>().unwrap_or(0)
+}
+
+/// Pattern substitution for ${parameter/pattern/string} and its variants.
+fn replace(value: &str, pattern: &str, repl: &str, op: Rule) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    let len = chars.len();
+    let slice = |a: usize, b: usize| -> String { chars[a..b].iter().collect() };
+    match op {
+        Rule::varsubstfront => match (0..=len)
+            .rev()
+            .find(|&end| pattern_match(pattern, &slice(0, end)))
+        {
+            Some(end) => format!("{}{}", repl, slice(end, len)),
+            None => value.to_string(),
+        },
+        Rule::varsubstback => {
+            match (0..=len).find(|&start| pattern_match(pattern, &slice(start, len))) {
+                Some(start) => format!("{}{}", slice(0, start), repl),
+                None => value.to_string(),
+            }
+        }
+        _ => {
+            let mut result = String::with_capacity(value.len());
+            let mut start = 0;
+            while start < len {
+                // longest non empty match starting at `start`.
+                match (start + 1..=len)
+                    .rev()
+                    .find(|&end| pattern_match(pattern, &slice(start, end)))
+                {
+                    Some(end) => {
+                        result.push_str(repl);
+                        start = end;
+                        if op == Rule::varrepl {
+                            result.push_str(&slice(start, len));
+                            return result;
+                        }
+                    }
+                    None => {
+                        result.push(chars[start]);
+                        start += 1;
+                    }
+                }
+            }
+            result
+        }
+    }
+}
+
+/// Shell pattern matching: `*`, `?`, `[...]` bracket expressions (with `!` or `^` negation) and `\` escapes.
+///
+/// # Examples
+/// ```rust
+/// use rush::expand::pattern_match;
+///
+/// assert!(pattern_match("*.rs", "main.rs"));
+/// assert!(pattern_match("[!a]?c", "bbc"));
+/// assert!(!pattern_match("a\\*", "ab"));
+/// ```
+pub fn pattern_match(pattern: &str, text: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let t: Vec<char> = text.chars().collect();
+    glob(&p, &t)
+}
+
+fn glob(p: &[char], t: &[char]) -> bool {
+    match p.first() {
+        None => t.is_empty(),
+        Some('*') => (0..=t.len()).any(|i| glob(&p[1..], &t[i..])),
+        Some('?') => !t.is_empty() && glob(&p[1..], &t[1..]),
+        Some('[') if t.is_empty() => false,
+        Some('[') => match bracket(&p[1..], t[0]) {
+            Some((true, used)) => glob(&p[1 + used..], &t[1..]),
+            Some((false, _)) => false,
+            // no closing bracket, [ is a literal character.
+            None => t[0] == '[' && glob(&p[1..], &t[1..]),
+        },
+        Some('\\') if p.len() > 1 => t.first() == Some(&p[1]) && glob(&p[2..], &t[1..]),
+        Some(c) => t.first() == Some(c) && glob(&p[1..], &t[1..]),
+    }
+}
+
+/// Match a character against a bracket expression (after the `[`).
+/// Returns whether it matched and how many pattern characters were used, None if there is no closing `]`.
+fn bracket(p: &[char], c: char) -> Option<(bool, usize)> {
+    let negate = matches!(p.first(), Some('!') | Some('^'));
+    let mut i = if negate { 1 } else { 0 };
+    let first = i;
+    let mut matched = false;
+    while i < p.len() {
+        if p[i] == ']' && i > first {
+            return Some((matched != negate, i + 1));
+        }
+        if i + 2 < p.len() && p[i + 1] == '-' && p[i + 2] != ']' {
+            matched |= p[i] <= c && c <= p[i + 2];
+            i += 3;
+        } else {
+            matched |= p[i] == c;
+            i += 1;
+        }
+    }
+    None
+}
@@ -0,0 +1,349 @@
+//
+// highlight.rs
+//
+// Copyright 2015-2019 Laurent Wandrebeck <l.wandrebeck@quelquesmots.fr>
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston,
+// MA 02110-1301, USA.
+//
+
+//! RuSh syntax highlighting.
+//!
+//! highlight.rs colorizes the line being edited from its syntax tree, as rush.pest grammar parses it:
+//! reserved words, builtins, quoted strings, parameter expansions, comments and numbers.
+//! Unknown commands and unbalanced quotes are shown in red. Colors come from the terminfo entry of the terminal.
+
+use crate::builtins;
+use crate::completion::KEYWORDS;
+use crate::execute;
+use crate::parse::{self, Rule};
+use crate::rush::RuSh;
+use crate::variables::Value;
+use pest::iterators::Pair;
+use std::path::Path;
+use term::terminfo::parm::Param;
+use term::terminfo::TermInfo;
+
+/// Kinds of highlighted text.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    /// reserved words: if, then, while...
+    Keyword,
+    /// builtins, functions and aliases used as command names.
+    Builtin,
+    /// quoted strings.
+    String,
+    /// parameter expansions.
+    Variable,
+    /// comments.
+    Comment,
+    /// numbers.
+    Number,
+    /// unknown commands and unbalanced quotes.
+    Error,
+}
+
+/// Terminal escape sequences each kind of text is written with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Colors {
+    pub keyword: String,
+    pub builtin: String,
+    pub string: String,
+    pub variable: String,
+    pub comment: String,
+    pub number: String,
+    pub error: String,
+    /// back to normal text.
+    pub reset: String,
+}
+
+impl Colors {
+    /// Get colors from the terminfo entry of TERM. None if there is none, or if the terminal has less than 8 colors.
+    pub fn from_env() -> Option<Colors> {
+        match TermInfo::from_env() {
+            Ok(info) => Colors::from_terminfo(&info),
+            Err(_) => None,
+        }
+    }
+
+    /// Get colors from a terminfo entry. None if the terminal has less than 8 colors.
+    pub fn from_terminfo(info: &TermInfo) -> Option<Colors> {
+        let colors = info.numbers.get("colors").cloned().unwrap_or(0);
+        if colors < 8 {
+            return None;
+        }
+        let cap = |name: &str, color: i32| {
+            let mut out = Vec::new();
+            info.apply_cap(name, &[Param::Number(color)], &mut out)
+                .ok()
+                .map(|_| String::from_utf8_lossy(&out).into_owned())
+        };
+        let setaf = |color| cap("setaf", color);
+        let mut reset = Vec::new();
+        info.reset(&mut reset).ok()?;
+        Some(Colors {
+            keyword: setaf(4)?,
+            builtin: setaf(2)?,
+            string: setaf(3)?,
+            variable: setaf(6)?,
+            // bright black if there is one.
+            comment: setaf(if colors >= 16 { 8 } else { 4 })?,
+            number: setaf(5)?,
+            error: setaf(1)?,
+            reset: String::from_utf8_lossy(&reset).into_owned(),
+        })
+    }
+
+    /// ANSI colors, whatever the terminal is.
+    pub fn ansi() -> Colors {
+        Colors {
+            keyword: String::from("\x1b[34m"),
+            builtin: String::from("\x1b[32m"),
+            string: String::from("\x1b[33m"),
+            variable: String::from("\x1b[36m"),
+            comment: String::from("\x1b[90m"),
+            number: String::from("\x1b[35m"),
+            error: String::from("\x1b[31m"),
+            reset: String::from("\x1b[0m"),
+        }
+    }
+
+    /// Escape sequence for a kind of text.
+    fn get(&self, kind: Kind) -> &str {
+        match kind {
+            Kind::Keyword => &self.keyword,
+            Kind::Builtin => &self.builtin,
+            Kind::String => &self.string,
+            Kind::Variable => &self.variable,
+            Kind::Comment => &self.comment,
+            Kind::Number => &self.number,
+            Kind::Error => &self.error,
+        }
+    }
+}
+
+/// Paint text from `start` to `end` with `kind`.
+fn paint(kinds: &mut [Option<Kind>], start: usize, end: usize, kind: Kind) {
+    let end = end.min(kinds.len());
+    if start < end {
+        for k in &mut kinds[start..end] {
+            *k = Some(kind);
+        }
+    }
+}
+
+/// Paint comments and reserved words found in text between syntax tree nodes, starting at `offset`.
+fn gap(text: &str, offset: usize, kinds: &mut [Option<Kind>]) {
+    let mut start: Option<usize> = None;
+    let mut comment = false;
+    for (i, c) in text
+        .char_indices()
+        .chain(std::iter::once((text.len(), '\n')))
+    {
+        if comment {
+            if c == '\n' {
+                paint(kinds, offset + start.unwrap(), offset + i, Kind::Comment);
+                comment = false;
+                start = None;
+            }
+            continue;
+        }
+        if c == ' ' || c == '\t' || c == '\n' || ";|&()".contains(c) {
+            if let Some(s) = start.take() {
+                if KEYWORDS.contains(&&text[s..i]) {
+                    paint(kinds, offset + s, offset + i, Kind::Keyword);
+                }
+            }
+        } else if start.is_none() {
+            start = Some(i);
+            comment = c == '#';
+        }
+    }
+}
+
+/// Kind of a command name: builtins, functions and aliases, nothing for existing commands,
+/// an error for unknown ones. Names given by expansions are not looked for.
+fn command_kind(rush: &RuSh, word: Pair<Rule>) -> Option<Kind> {
+    if word
+        .clone()
+        .into_inner()
+        .any(|p| p.as_rule() != Rule::literal)
+    {
+        return None;
+    }
+    let name = word.as_str();
+    let alias = builtins::aliases(&rush.shell_array_vars)
+        .iter()
+        .any(|(a, _)| a == name);
+    if builtins::get(name).is_some() || rush.functions.contains_key(name) || alias {
+        return Some(Kind::Builtin);
+    }
+    if KEYWORDS.contains(&name) {
+        return Some(Kind::Keyword);
+    }
+    let found = if name.contains('/') {
+        Path::new(name).is_file()
+    } else {
+        execute::find_command(rush, name).is_some()
+    };
+    if found {
+        None
+    } else {
+        Some(Kind::Error)
+    }
+}
+
+/// Paint a syntax tree node and its children, inner nodes painting over outer ones.
+fn walk(rush: &RuSh, p: Pair<Rule>, kinds: &mut [Option<Kind>]) {
+    let (start, end) = (p.as_span().start(), p.as_span().end());
+    match p.as_rule() {
+        Rule::dquoted | Rule::squoted | Rule::btquoted => {
+            paint(kinds, start, end, Kind::String);
+        }
+        Rule::varvalue | Rule::cmdsubst | Rule::arithexp => {
+            paint(kinds, start, end, Kind::Variable);
+            return;
+        }
+        Rule::bang => {
+            paint(kinds, start, end, Kind::Keyword);
+            return;
+        }
+        Rule::word => {
+            let literal = p.clone().into_inner().all(|w| w.as_rule() == Rule::literal);
+            if literal {
+                if let Value::I(_) | Value::F(_) = parse::value(p.as_str()) {
+                    paint(kinds, start, end, Kind::Number);
+                }
+            }
+        }
+        Rule::simplecommand => {
+            let mut name = true;
+            for child in p.into_inner() {
+                if name && child.as_rule() == Rule::word {
+                    name = false;
+                    if let Some(kind) = command_kind(rush, child.clone()) {
+                        let span = child.as_span();
+                        paint(kinds, span.start(), span.end(), kind);
+                    }
+                    for part in child.into_inner() {
+                        walk(rush, part, kinds);
+                    }
+                } else {
+                    walk(rush, child, kinds);
+                }
+            }
+            return;
+        }
+        _ => (),
+    }
+    let compound = matches!(
+        p.as_rule(),
+        Rule::program
+            | Rule::compoundlist
+            | Rule::andor
+            | Rule::pipeline
+            | Rule::command
+            | Rule::ifclause
+            | Rule::elifpart
+            | Rule::elsepart
+            | Rule::whileclause
+            | Rule::untilclause
+            | Rule::bracegroup
+            | Rule::functiondef
+            | Rule::functionbody
+    );
+    let text = p.as_str();
+    let mut pos = start;
+    for child in p.into_inner() {
+        let span = child.as_span();
+        if compound {
+            gap(&text[pos - start..span.start() - start], pos, kinds);
+        }
+        pos = span.end();
+        walk(rush, child, kinds);
+    }
+    if compound {
+        gap(&text[pos - start..], pos, kinds);
+    }
+}
+
+/// Kinds of text found in a line, as `(start, end, kind)` spans. An incomplete line is completed
+/// (quote closed, compound command ended) so that what has been typed so far can be highlighted.
+///
+/// # Examples
+/// ```rust
+/// use rush::RuSh;
+/// use rush::highlight::{spans, Kind};
+///
+/// let rush = RuSh::default();
+/// assert_eq!(spans(&rush, "echo \"$a\" 'b'"), vec![(0, 4, Kind::Builtin), (5, 6, Kind::String), (6, 8, Kind::Variable), (8, 9, Kind::String), (10, 13, Kind::String)]);
+/// assert_eq!(spans(&rush, "echo 'b"), vec![(0, 4, Kind::Builtin), (5, 7, Kind::Error)]);
+/// ```
+pub fn spans(rush: &RuSh, line: &str) -> Vec<(usize, usize, Kind)> {
+    let mut kinds: Vec<Option<Kind>> = vec![None; line.len()];
+    let quote = parse::open_quote(line);
+    let mut text = line.to_string();
+    if let Some(q) = quote {
+        text.push_str(&line[q..=q]);
+    }
+    for end in &["", "\nfi", "\ndone", "\n}"] {
+        let input = format!("{}{}", text, end);
+        if let Some(program) = parse::tree(&input) {
+            walk(rush, program, &mut kinds);
+            break;
+        }
+    }
+    if let Some(q) = quote {
+        paint(&mut kinds, q, line.len(), Kind::Error);
+    }
+    let mut spans: Vec<(usize, usize, Kind)> = Vec::new();
+    for (i, kind) in kinds.iter().enumerate() {
+        if let Some(kind) = kind {
+            match spans.last_mut() {
+                Some((_, end, k)) if *end == i && k == kind => *end = i + 1,
+                _ => spans.push((i, i + 1, *kind)),
+            }
+        }
+    }
+    spans
+}
+
+/// Highlight a line with `colors`.
+///
+/// # Examples
+/// ```rust
+/// use rush::RuSh;
+/// use rush::highlight::{highlight, Colors};
+///
+/// let rush = RuSh::default();
+/// let colors = Colors::ansi();
+/// assert_eq!(
+///     highlight(&rush, "if true; then x; fi", &colors),
+///     "\x1b[34mif\x1b[0m true; \x1b[34mthen\x1b[0m \x1b[31mx\x1b[0m; \x1b[34mfi\x1b[0m"
+/// );
+/// ```
+pub fn highlight(rush: &RuSh, line: &str, colors: &Colors) -> String {
+    let mut s = String::with_capacity(line.len() * 2);
+    let mut pos = 0;
+    for (start, end, kind) in spans(rush, line) {
+        s.push_str(&line[pos..start]);
+        s.push_str(colors.get(kind));
+        s.push_str(&line[start..end]);
+        s.push_str(&colors.reset);
+        pos = end;
+    }
+    s.push_str(&line[pos..]);
+    s
+}
@@ -0,0 +1,1134 @@
+//
+// history.rs
+//
+// Copyright 2015-2019 Laurent Wandrebeck <l.wandrebeck@quelquesmots.fr>
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston,
+// MA 02110-1301, USA.
+//
+
+//! RuSh history management.
+//!
+//! history.rs contains the history list, which interactive commands are added to according to HISTCONTROL,
+//! HISTIGNORE and HISTSIZE, and which is loaded from and saved to HISTFILE.
+//! It also contains csh-style history expansion: event designators (`!!`, `!n`, `!-n`, `!string`, `!?string?`,
+//! `^old^new`), word designators (`:0`, `:n`, `^`, `$`, `*`, `x-y`, `%`) and modifiers (`:h`, `:t`, `:r`, `:e`,
+//! `:p`, `:q`, `:x`, `:s/old/new/`, `:&`, `:g`, `:G`).
+
+use crate::execute;
+use crate::expand::pattern_match;
+use crate::parse;
+use crate::rush::RuSh;
+use crate::variables::{Access, Value, Variable};
+use std::convert::TryFrom;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+
+/// Default HISTSIZE and HISTFILESIZE.
+const SIZE: i64 = 500;
+
+/// Characters that do not start a history expansion when following `!`.
+const NO_EXPAND: &str = " \t\n\r=(";
+
+/// Characters ending a `!string` event designator.
+const STRING_END: &str = " \t\n\r:;&|<>()\"'`";
+
+/// Words after which lines of a multi-line command are joined with a space rather than a semicolon.
+const NO_SEMICOLON: [&str; 11] = [
+    "if", "then", "else", "elif", "do", "while", "until", "in", "{", "!", "time",
+];
+
+/// A history entry.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    /// command line.
+    pub line: String,
+    /// time it was entered, in seconds since epoch, if known.
+    pub time: Option<i64>,
+}
+
+/// Command history list. Entries are numbered from `base`, which grows as the oldest ones are dropped.
+#[derive(Debug)]
+pub struct History {
+    /// entries, oldest first.
+    pub entries: Vec<Entry>,
+    /// history number of the first entry.
+    pub base: usize,
+    /// index of the first entry not written to history file yet.
+    pub unsaved: usize,
+    /// number of history file entries read or written, for history -n.
+    pub file_entries: usize,
+    /// true if the last entry is the command being executed, which history -s and fc replace.
+    pub current: bool,
+    /// number of removals, after which a copy of history list, like the editor one, has to be rebuilt.
+    pub removals: usize,
+}
+
+impl Default for History {
+    fn default() -> History {
+        History::new()
+    }
+}
+
+impl History {
+    /// Create an empty history list, whose first entry is number 1.
+    pub fn new() -> History {
+        History {
+            entries: Vec::new(),
+            base: 1,
+            unsaved: 0,
+            file_entries: 0,
+            current: false,
+            removals: 0,
+        }
+    }
+
+    /// Add a line, timestamped with current time, whatever history options are.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use rush::history::History;
+    ///
+    /// let mut history = History::new();
+    /// history.add("ls");
+    /// history.add("ls");
+    /// assert_eq!(history.len(), 2);
+    /// assert_eq!(history.get(1).unwrap().line, "ls");
+    /// assert_eq!(history.number(), 3);
+    /// ```
+    pub fn add(&mut self, line: &str) {
+        self.entries.push(Entry {
+            line: line.to_string(),
+            time: Some(chrono::Local::now().timestamp()),
+        });
+    }
+
+    /// Number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// true if there is no entry.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterate over entries, oldest first.
+    pub fn iter(&self) -> std::slice::Iter<'_, Entry> {
+        self.entries.iter()
+    }
+
+    /// History number the next entry gets.
+    pub fn number(&self) -> usize {
+        self.base + self.entries.len()
+    }
+
+    /// Entry with history number `n`.
+    pub fn get(&self, n: usize) -> Option<&Entry> {
+        n.checked_sub(self.base).and_then(|i| self.entries.get(i))
+    }
+
+    /// Remove entry with history number `n`, following entries being renumbered. Returns false if there is none.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use rush::history::History;
+    ///
+    /// let mut history = History::new();
+    /// history.add("a");
+    /// history.add("b");
+    /// assert!(history.remove(1));
+    /// assert!(!history.remove(2));
+    /// assert_eq!(history.get(1).unwrap().line, "b");
+    /// ```
+    pub fn remove(&mut self, n: usize) -> bool {
+        match n.checked_sub(self.base).filter(|i| *i < self.entries.len()) {
+            Some(i) => {
+                self.entries.remove(i);
+                self.removals += 1;
+                if i == self.entries.len() {
+                    self.current = false;
+                }
+                if i < self.unsaved {
+                    self.unsaved -= 1;
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove every entry, numbering starting again from 1.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.base = 1;
+        self.unsaved = 0;
+        self.current = false;
+        self.removals += 1;
+    }
+
+    /// Remove the command being executed from history, so that it can be replaced.
+    pub fn remove_current(&mut self) {
+        if self.current {
+            self.remove(self.number() - 1);
+        }
+    }
+
+    /// Keep at most `max` entries, dropping oldest ones.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use rush::history::History;
+    ///
+    /// let mut history = History::new();
+    /// for line in ["a", "b", "c"] {
+    ///     history.add(line);
+    /// }
+    /// history.truncate(2);
+    /// assert_eq!(history.base, 2);
+    /// assert_eq!(history.get(2).unwrap().line, "b");
+    /// ```
+    pub fn truncate(&mut self, max: usize) {
+        if self.entries.len() > max {
+            let dropped = self.entries.len() - max;
+            self.entries.drain(..dropped);
+            self.base += dropped;
+            self.unsaved = self.unsaved.saturating_sub(dropped);
+        }
+    }
+}
+
+/// Value of a shell option.
+fn option(rush: &RuSh, shopt: bool, name: &str) -> bool {
+    let options = if shopt {
+        &rush.shopt_options
+    } else {
+        &rush.set_options
+    };
+    match options.get(name) {
+        Some(opt) => opt.get(),
+        None => false,
+    }
+}
+
+/// Value of a history size variable, None meaning no limit: it is unset, not a number or negative.
+fn limit(rush: &RuSh, name: &str) -> Option<usize> {
+    rush.shell_vars
+        .get(name)
+        .and_then(|v| v.value.to_string().parse::<i64>().ok())
+        .and_then(|n| usize::try_from(n).ok())
+}
+
+/// History file, None if HISTFILE is unset or empty.
+pub fn file(rush: &RuSh) -> Option<String> {
+    rush.shell_vars
+        .get("HISTFILE")
+        .map(|v| v.value.to_string())
+        .filter(|f| !f.is_empty())
+}
+
+/// History entries a command is saved as. A multi-line command is saved as a single entry with cmdhist option,
+/// its lines being joined with semicolons, or with a newline after a comment, or kept as they are with lithist option.
+///
+/// # Examples
+/// ```rust
+/// use rush::RuSh;
+/// use rush::history;
+/// use rush::parse::parse;
+///
+/// let mut rush = RuSh::default();
+/// assert_eq!(history::lines(&rush, "if true\nthen ls\nfi"), vec!["if true; then ls; fi"]);
+/// assert_eq!(history::lines(&rush, "ls |\nwc"), vec!["ls | wc"]);
+/// assert_eq!(history::lines(&rush, "echo 'a\nb'"), vec!["echo 'a\nb'"]);
+/// assert_eq!(history::lines(&rush, "echo 'a\n\nb'\n\nls"), vec!["echo 'a\n\nb'; ls"]);
+/// assert_eq!(history::lines(&rush, "if true # c\nthen echo b\nfi"), vec!["if true # c\nthen echo b; fi"]);
+/// assert_eq!(history::lines(&rush, "echo a#b\nls"), vec!["echo a#b; ls"]);
+/// parse(&mut rush, "shopt -s lithist");
+/// assert_eq!(history::lines(&rush, "ls\npwd"), vec!["ls\npwd"]);
+/// parse(&mut rush, "shopt -u cmdhist");
+/// assert_eq!(history::lines(&rush, "ls\n\npwd"), vec!["ls", "pwd"]);
+/// ```
+pub fn lines(rush: &RuSh, command: &str) -> Vec<String> {
+    let command = command.trim_end_matches('\n');
+    if !command.contains('\n') {
+        return vec![command.to_string()];
+    }
+    // blank lines are dropped, unless they are part of a quoted string.
+    let mut read = String::with_capacity(command.len());
+    let mut kept = Vec::new();
+    for line in command.lines() {
+        if !line.trim().is_empty() || parse::open_quote(&read).is_some() {
+            kept.push(line);
+        }
+        read.push_str(line);
+        read.push('\n');
+    }
+    let lines = kept.into_iter();
+    if !option(rush, true, "cmdhist") {
+        return lines.map(String::from).collect();
+    }
+    if option(rush, true, "lithist") {
+        return vec![command.to_string()];
+    }
+    let mut joined = String::new();
+    for line in lines {
+        if parse::open_quote(&joined).is_some() || comment(&joined) {
+            // newline is part of a quoted string, or ends a comment.
+            joined.push('\n');
+        } else if let Some(stripped) = joined.strip_suffix('\\') {
+            // backslash-newline is a line continuation.
+            joined = stripped.to_string();
+        } else if !joined.is_empty() {
+            let previous = joined.trim_end();
+            let word = previous.split_whitespace().next_back().unwrap_or("");
+            if NO_SEMICOLON.contains(&word) || previous.ends_with(|c| ";&|(".contains(c)) {
+                joined.push(' ');
+            } else {
+                joined.push_str("; ");
+            }
+        }
+        joined.push_str(line);
+    }
+    vec![joined]
+}
+
+/// Whether a command ends in an unquoted comment, which only a newline ends.
+fn comment(command: &str) -> bool {
+    let mut quote = None;
+    let mut escape = false;
+    let mut comment = false;
+    let mut previous = '\n';
+    for c in command.chars() {
+        if comment {
+            comment = c != '\n';
+        } else if escape {
+            // an escaped character is part of a word.
+            escape = false;
+            previous = '\\';
+            continue;
+        } else if let Some(q) = quote {
+            if c == q {
+                quote = None;
+            } else if c == '\\' && q != '\'' {
+                escape = true;
+            }
+        } else if c == '\\' {
+            escape = true;
+        } else if c == '"' || c == '\'' || c == '`' {
+            quote = Some(c);
+        } else if c == '#' && (previous.is_whitespace() || ";&|()<>".contains(previous)) {
+            comment = true;
+        }
+        previous = c;
+    }
+    comment
+}
+
+/// Split HISTIGNORE value on colons not preceded by a backslash.
+fn ignore_patterns(value: &str) -> Vec<String> {
+    let mut patterns = vec![String::new()];
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some(next) = chars.next() {
+                    if next != ':' {
+                        patterns.last_mut().unwrap().push(c);
+                    }
+                    patterns.last_mut().unwrap().push(next);
+                }
+            }
+            ':' => patterns.push(String::new()),
+            _ => patterns.last_mut().unwrap().push(c),
+        }
+    }
+    patterns
+}
+
+/// true if a line matches a HISTIGNORE pattern. `&` in a pattern stands for the previous history line.
+fn ignored(rush: &RuSh, line: &str) -> bool {
+    let value = match rush.shell_vars.get("HISTIGNORE") {
+        Some(v) => v.value.to_string(),
+        None => return false,
+    };
+    let previous = rush.history.entries.last().map(|e| e.line.as_str());
+    ignore_patterns(&value)
+        .iter()
+        .filter(|p| !p.is_empty())
+        .any(|p| match (p.contains('&'), previous) {
+            (false, _) => pattern_match(p, line),
+            (true, Some(previous)) => pattern_match(&p.replace('&', previous), line),
+            (true, None) => false,
+        })
+}
+
+/// Add a command entered interactively to history, if history option is set. Lines are saved as given by `lines`,
+/// unless HISTCONTROL or HISTIGNORE says otherwise. History is then truncated to HISTSIZE, and HISTCMD updated.
+/// Returns true if something was added.
+///
+/// # Examples
+/// ```rust
+/// use rush::RuSh;
+/// use rush::history;
+/// use rush::parse::parse;
+///
+/// let mut rush = RuSh::default();
+/// parse(&mut rush, "set -o history; HISTCONTROL=ignoreboth; HISTIGNORE='ls*:&'");
+/// assert!(history::add(&mut rush, "pwd"));
+/// assert!(!history::add(&mut rush, "pwd"));
+/// assert!(!history::add(&mut rush, " cd"));
+/// assert!(!history::add(&mut rush, "ls -l"));
+/// assert_eq!(rush.history.len(), 1);
+/// ```
+pub fn add(rush: &mut RuSh, command: &str) -> bool {
+    if !option(rush, false, "history") || command.trim().is_empty() {
+        return false;
+    }
+    let control = match rush.shell_vars.get("HISTCONTROL") {
+        Some(v) => v.value.to_string(),
+        None => String::new(),
+    };
+    let control: Vec<&str> = control.split(':').collect();
+    let has =
+        |name| control.contains(&name) || (name != "erasedups" && control.contains(&"ignoreboth"));
+    if has("ignorespace") && command.starts_with(' ') {
+        return false;
+    }
+    let mut added = false;
+    for line in lines(rush, command) {
+        if ignored(rush, &line)
+            || (has("ignoredups") && rush.history.entries.last().is_some_and(|e| e.line == line))
+        {
+            continue;
+        }
+        if has("erasedups") {
+            let mut n = rush.history.base;
+            while n < rush.history.number() {
+                if rush.history.get(n).is_some_and(|e| e.line == line) {
+                    rush.history.remove(n);
+                } else {
+                    n += 1;
+                }
+            }
+        }
+        rush.history.add(&line);
+        added = true;
+    }
+    if let Some(max) = limit(rush, "HISTSIZE") {
+        rush.history.truncate(max);
+    }
+    rush.history.current = added;
+    // HISTCMD is the history number of the command being executed.
+    let number = rush.history.number() - if added { 1 } else { 0 };
+    rush.shell_vars.set(
+        String::from("HISTCMD"),
+        Variable {
+            value: Value::I(i64::try_from(number).unwrap_or(0)),
+            access: Access::ReadWrite,
+            export: false,
+        },
+    );
+    added
+}
+
+/// Timestamp of a history file line, which is `#` followed by seconds since epoch.
+fn timestamp(line: &str) -> Option<i64> {
+    match line.strip_prefix('#') {
+        Some(t) if !t.is_empty() && t.chars().all(|c| c.is_ascii_digit()) => t.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Read history entries from a file. A line made of `#` followed by digits is the timestamp of next entry.
+/// With `multiline`, an entry following a timestamp goes on until next timestamp, so that lithist entries are kept.
+pub fn read(path: &str, multiline: bool) -> io::Result<Vec<Entry>> {
+    let contents = String::from_utf8_lossy(&fs::read(path)?).into_owned();
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut time = None;
+    // last entry gets following lines.
+    let mut open = false;
+    for line in contents.lines() {
+        if let Some(t) = timestamp(line) {
+            time = Some(t);
+            open = false;
+        } else if open {
+            if let Some(e) = entries.last_mut() {
+                e.line.push('\n');
+                e.line.push_str(line);
+            }
+        } else {
+            open = multiline && time.is_some();
+            entries.push(Entry {
+                line: line.to_string(),
+                time: time.take(),
+            });
+        }
+    }
+    Ok(entries)
+}
+
+/// Write history entries to a file, appending them to it with `append`. With `timestamps`,
+/// entries are preceded by their timestamp, if they have one.
+pub fn write(path: &str, entries: &[Entry], append: bool, timestamps: bool) -> io::Result<()> {
+    let mut contents = String::new();
+    for entry in entries {
+        if let (true, Some(time)) = (timestamps, entry.time) {
+            contents.push_str(&format!("#{}\n", time));
+        }
+        contents.push_str(&entry.line);
+        contents.push('\n');
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(append)
+        .truncate(!append)
+        .open(path)?;
+    file.write_all(contents.as_bytes())
+}
+
+/// true if timestamps are written to history file, which is the case when HISTTIMEFORMAT is set.
+pub fn timestamps(rush: &RuSh) -> bool {
+    rush.shell_vars.get("HISTTIMEFORMAT").is_some()
+}
+
+/// Keep at most HISTFILESIZE entries in history file, timestamps being kept.
+fn truncate_file(rush: &mut RuSh, path: &str) -> io::Result<()> {
+    if let Some(max) = limit(rush, "HISTFILESIZE") {
+        let entries = read(path, option(rush, true, "lithist"))?;
+        if entries.len() > max {
+            write(path, &entries[entries.len() - max..], false, true)?;
+        }
+        rush.history.file_entries = rush.history.file_entries.min(max);
+    }
+    Ok(())
+}
+
+/// Append history file entries to history list, truncated to HISTSIZE then. With `new`, only the entries
+/// added to the file since it was last read or written are, for history -n.
+pub fn read_file(rush: &mut RuSh, path: &str, new: bool) -> io::Result<()> {
+    let entries = read(path, option(rush, true, "lithist"))?;
+    let skip = if new { rush.history.file_entries } else { 0 };
+    rush.history.file_entries = entries.len();
+    rush.history.entries.extend(entries.into_iter().skip(skip));
+    if let Some(max) = limit(rush, "HISTSIZE") {
+        rush.history.truncate(max);
+    }
+    Ok(())
+}
+
+/// Write history list to a file, or append to it the entries not written yet with `append`, for history -w and -a.
+/// The file is then truncated to HISTFILESIZE.
+pub fn write_file(rush: &mut RuSh, path: &str, append: bool) -> io::Result<()> {
+    let first = if append { rush.history.unsaved } else { 0 };
+    write(
+        path,
+        &rush.history.entries[first..],
+        append,
+        timestamps(rush),
+    )?;
+    rush.history.file_entries = if append {
+        rush.history.file_entries + rush.history.len() - first
+    } else {
+        rush.history.len()
+    };
+    rush.history.unsaved = rush.history.len();
+    truncate_file(rush, path)
+}
+
+/// Set history variables an interactive shell starts with, unless already set: HISTFILE is ~/.rush_history and
+/// HISTSIZE is 500.
+pub fn defaults(rush: &mut RuSh) {
+    if rush.shell_vars.get("HISTSIZE").is_none() {
+        rush.shell_vars.set(
+            String::from("HISTSIZE"),
+            Variable {
+                value: Value::I(SIZE),
+                access: Access::ReadWrite,
+                export: false,
+            },
+        );
+    }
+    if rush.shell_vars.get("HISTFILE").is_none() {
+        if let Some(home) = rush.shell_vars.get("HOME") {
+            let path = format!(
+                "{}/.rush_history",
+                home.value.to_string().trim_end_matches('/')
+            );
+            rush.shell_vars.set(
+                String::from("HISTFILE"),
+                Variable {
+                    value: Value::S(path),
+                    access: Access::ReadWrite,
+                    export: false,
+                },
+            );
+        }
+    }
+}
+
+/// Load history file once an interactive shell has read its startup files. HISTFILESIZE defaults to HISTSIZE,
+/// or 500 if it is unset, the file is truncated to it, and history list to HISTSIZE.
+pub fn load(rush: &mut RuSh) {
+    if rush.shell_vars.get("HISTFILESIZE").is_none() {
+        let size = rush.shell_vars.get("HISTSIZE").unwrap_or(Variable {
+            value: Value::I(SIZE),
+            access: Access::ReadWrite,
+            export: false,
+        });
+        rush.shell_vars.set(String::from("HISTFILESIZE"), size);
+    }
+    let path = match file(rush) {
+        Some(path) => path,
+        None => return,
+    };
+    if fs::metadata(&path).is_err() {
+        return;
+    }
+    if let Err(e) = truncate_file(rush, &path).and_then(|_| read_file(rush, &path, false)) {
+        eprintln!("rush: {}: {}", path, execute::os_error(&e));
+        return;
+    }
+    rush.history.unsaved = rush.history.len();
+}
+
+/// Save history when an interactive shell exits: with histappend, entries of this session are appended to
+/// history file, which is overwritten with history list otherwise.
+pub fn save(rush: &mut RuSh) {
+    let path = match file(rush) {
+        Some(path) if option(rush, false, "history") => path,
+        _ => return,
+    };
+    let append = option(rush, true, "histappend");
+    if let Err(e) = write_file(rush, &path, append) {
+        eprintln!("rush: {}: {}", path, execute::os_error(&e));
+    }
+}
+
+/// Result of history expansion of a line.
+#[derive(Debug, PartialEq)]
+pub struct Expansion {
+    /// expanded line.
+    pub line: String,
+    /// true if the line was changed by expansion.
+    pub changed: bool,
+    /// true if the :p modifier asked to print the line instead of executing it.
+    pub print: bool,
+}
+
+/// State of an expansion in progress.
+struct Expander<'a> {
+    /// history entries, oldest first.
+    entries: Vec<&'a str>,
+    /// history number of the first entry.
+    base: usize,
+    /// history number of the line being expanded.
+    number: usize,
+    /// line being expanded.
+    chars: Vec<char>,
+    /// last substitution, for :& and empty old strings.
+    subst: Option<(String, String)>,
+    /// last string searched with !?string?, for the % word designator.
+    searched: Option<String>,
+    /// :p was found.
+    print: bool,
+}
+
+/// Expand history references in a line, against history of `rush`.
+/// Returns an error message if an event is not found or a designator is invalid.
+///
+/// # Examples
+/// ```rust
+/// use rush::RuSh;
+/// use rush::history;
+///
+/// let mut rush = RuSh::default();
+/// rush.history.add("ls -l /usr/share/doc.tar.gz");
+/// rush.history.add("echo a b c");
+/// assert_eq!(history::expand(&rush, "!! d").unwrap().line, "echo a b c d");
+/// assert_eq!(history::expand(&rush, "x !$ !^ !*").unwrap().line, "x c a a b c");
+/// assert_eq!(history::expand(&rush, "!ls:2:h").unwrap().line, "/usr/share");
+/// assert_eq!(history::expand(&rush, "!-2:$:t:r").unwrap().line, "doc.tar");
+/// assert_eq!(history::expand(&rush, "^b^B").unwrap().line, "echo a B c");
+/// assert_eq!(history::expand(&rush, "echo '!!' \\!!").unwrap().changed, false);
+/// assert!(history::expand(&rush, "!:p").unwrap().print);
+/// assert_eq!(history::expand(&rush, "!nope").unwrap_err(), "!nope: event not found");
+/// ```
+pub fn expand(rush: &RuSh, line: &str) -> Result<Expansion, String> {
+    let mut exp = Expander {
+        entries: rush.history.iter().map(|e| e.line.as_str()).collect(),
+        base: rush.history.base,
+        number: rush.history.number(),
+        chars: line.chars().collect(),
+        subst: None,
+        searched: None,
+        print: false,
+    };
+    let mut out = String::new();
+    let mut changed = false;
+    let mut i = 0;
+    // ^old^new^ is !!:s^old^new^
+    if exp.chars.first() == Some(&'^') {
+        let last = exp.event_text("!!", exp.number - 1)?;
+        let (text, next) = exp.modifiers(last, "^", 0, true)?;
+        out.push_str(&text);
+        i = next;
+        changed = true;
+    }
+    let (mut squote, mut dquote) = (false, false);
+    while i < exp.chars.len() {
+        let c = exp.chars[i];
+        match c {
+            '\\' if !squote => {
+                out.push(c);
+                if let Some(next) = exp.chars.get(i + 1) {
+                    out.push(*next);
+                    i += 1;
+                }
+            }
+            '\'' if !dquote => {
+                squote = !squote;
+                out.push(c);
+            }
+            '"' if !squote => {
+                dquote = !dquote;
+                out.push(c);
+            }
+            '!' if !squote
+                && exp
+                    .chars
+                    .get(i + 1)
+                    .is_some_and(|n| !(NO_EXPAND.contains(*n) || dquote && *n == '"')) =>
+            {
+                let (text, next) = exp.event(i, &out)?;
+                out.push_str(&text);
+                i = next;
+                changed = true;
+                continue;
+            }
+            _ => out.push(c),
+        }
+        i += 1;
+    }
+    Ok(Expansion {
+        line: out,
+        changed,
+        print: exp.print,
+    })
+}
+
+/// Split a history line into words: blank separated, quotes kept together, shell operators being words of their own.
+///
+/// # Examples
+/// ```rust
+/// use rush::history::words;
+///
+/// assert_eq!(words("echo 'a b'>f&&ls"), vec!["echo", "'a b'", ">", "f", "&&", "ls"]);
+/// ```
+pub fn words(line: &str) -> Vec<String> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+        } else if ";&|<>()".contains(c) {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            let mut op = c.to_string();
+            while i + 1 < chars.len() && ";&|<>".contains(chars[i + 1]) && chars[i + 1] == c {
+                op.push(c);
+                i += 1;
+            }
+            words.push(op);
+        } else if c == '\\' {
+            word.push(c);
+            if let Some(next) = chars.get(i + 1) {
+                word.push(*next);
+                i += 1;
+            }
+        } else if c == '\'' || c == '"' || c == '`' {
+            word.push(c);
+            i += 1;
+            while i < chars.len() && chars[i] != c {
+                if c != '\'' && chars[i] == '\\' && i + 1 < chars.len() {
+                    word.push(chars[i]);
+                    i += 1;
+                }
+                word.push(chars[i]);
+                i += 1;
+            }
+            if i < chars.len() {
+                word.push(c);
+            }
+        } else {
+            word.push(c);
+        }
+        i += 1;
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+impl<'a> Expander<'a> {
+    /// Expand the history reference starting with `!` at position `start`. `current` is the line expanded so far,
+    /// for `!#`. Returns expanded text and position following the reference.
+    fn event(&mut self, start: usize, current: &str) -> Result<(String, usize), String> {
+        let chars = &self.chars;
+        let mut j = start + 1;
+        let entry: String = match chars[j] {
+            '!' => {
+                j += 1;
+                self.event_text("!!", self.number - 1)?
+            }
+            '#' => {
+                j += 1;
+                current.to_string()
+            }
+            '0'..='9' | '-'
+                if chars[j] != '-' || chars.get(j + 1).is_some_and(|c| c.is_ascii_digit()) =>
+            {
+                let negative = chars[j] == '-';
+                if negative {
+                    j += 1;
+                }
+                let begin = j;
+                while j < chars.len() && chars[j].is_ascii_digit() {
+                    j += 1;
+                }
+                let text: String = chars[start..j].iter().collect();
+                let n: usize = chars[begin..j]
+                    .iter()
+                    .collect::<String>()
+                    .parse()
+                    .map_err(|_| format!("{}: event not found", text))?;
+                // !n is history entry number n, !-n the nth previous one.
+                let index = if negative {
+                    self.number.checked_sub(n)
+                } else {
+                    Some(n)
+                };
+                match index {
+                    Some(i) => self.event_text(&text, i)?,
+                    None => return Err(format!("{}: event not found", text)),
+                }
+            }
+            '?' => {
+                j += 1;
+                let begin = j;
+                while j < chars.len() && chars[j] != '?' && chars[j] != '\n' {
+                    j += 1;
+                }
+                let search: String = chars[begin..j].iter().collect();
+                if j < chars.len() && chars[j] == '?' {
+                    j += 1;
+                }
+                let text: String = chars[start..j].iter().collect();
+                let found = self
+                    .entries
+                    .iter()
+                    .rev()
+                    .find(|e| e.contains(search.as_str()));
+                self.searched = Some(search);
+                match found {
+                    Some(e) => e.to_string(),
+                    None => return Err(format!("{}: event not found", text)),
+                }
+            }
+            // !$, !^, !*, !:x and !%x refer to previous command.
+            '$' | '^' | '*' | ':' | '%' => self.event_text("!!", self.number - 1)?,
+            _ => {
+                let begin = j;
+                while j < chars.len() && !STRING_END.contains(chars[j]) {
+                    j += 1;
+                }
+                let prefix: String = chars[begin..j].iter().collect();
+                let text: String = chars[start..j].iter().collect();
+                match self
+                    .entries
+                    .iter()
+                    .rev()
+                    .find(|e| e.starts_with(prefix.as_str()))
+                {
+                    Some(e) => e.to_string(),
+                    None => return Err(format!("{}: event not found", text)),
+                }
+            }
+        };
+        let (text, next) = self.designator(entry, start, j)?;
+        self.modifiers(text, "", next, false)
+    }
+
+    /// History entry number `n`.
+    fn event_text(&self, text: &str, n: usize) -> Result<String, String> {
+        match n.checked_sub(self.base).and_then(|i| self.entries.get(i)) {
+            Some(e) => Ok(e.to_string()),
+            None => Err(format!("{}: event not found", text)),
+        }
+    }
+
+    /// Apply the word designator found at position `j`, if any, to `entry`.
+    /// Returns selected words and position following the designator.
+    fn designator(&self, entry: String, start: usize, j: usize) -> Result<(String, usize), String> {
+        let chars = &self.chars;
+        let mut k = j;
+        match chars.get(k) {
+            Some(':')
+                if chars
+                    .get(k + 1)
+                    .is_some_and(|c| c.is_ascii_digit() || "^$*-%".contains(*c)) =>
+            {
+                k += 1
+            }
+            Some(c) if "^$*-%".contains(*c) => (),
+            _ => return Ok((entry, j)),
+        }
+        let words = words(&entry);
+        let last = words.len().saturating_sub(1);
+        let number = |k: &mut usize| -> Option<usize> {
+            let begin = *k;
+            while *k < chars.len() && chars[*k].is_ascii_digit() {
+                *k += 1;
+            }
+            chars[begin..*k].iter().collect::<String>().parse().ok()
+        };
+        let (from, to) = match chars[k] {
+            '^' => {
+                k += 1;
+                (1, Some(1))
+            }
+            '$' => {
+                k += 1;
+                (last, Some(last))
+            }
+            '*' => {
+                k += 1;
+                // no argument at all is not an error.
+                if words.len() < 2 {
+                    return Ok((String::new(), k));
+                }
+                (1, Some(last))
+            }
+            '%' => {
+                k += 1;
+                let search = self.searched.as_deref().unwrap_or("");
+                match words
+                    .iter()
+                    .position(|w| !search.is_empty() && w.contains(search))
+                {
+                    Some(w) => (w, Some(w)),
+                    None => (usize::MAX, None),
+                }
+            }
+            _ => {
+                let from = number(&mut k).unwrap_or(0);
+                match chars.get(k) {
+                    Some('*') => {
+                        k += 1;
+                        (from, Some(last))
+                    }
+                    Some('-') => {
+                        k += 1;
+                        match chars.get(k) {
+                            Some('$') => {
+                                k += 1;
+                                (from, Some(last))
+                            }
+                            Some(c) if c.is_ascii_digit() => (from, number(&mut k)),
+                            // x- leaves out last word.
+                            _ => (from, last.checked_sub(1)),
+                        }
+                    }
+                    _ => (from, Some(from)),
+                }
+            }
+        };
+        match to {
+            Some(to) if from <= to && to < words.len() => Ok((words[from..=to].join(" "), k)),
+            _ => {
+                let text: String = chars[start..k].iter().collect();
+                Err(format!("{}: bad word specifier", text))
+            }
+        }
+    }
+
+    /// Apply modifiers found at position `j` to `text`. With `quick`, `text` is substituted first using the
+    /// `^old^new^` syntax starting at `j`, `delim` being `^`.
+    /// Returns modified text and position following modifiers.
+    fn modifiers(
+        &mut self,
+        mut text: String,
+        delim: &str,
+        mut j: usize,
+        quick: bool,
+    ) -> Result<(String, usize), String> {
+        if quick {
+            let d = delim.chars().next().unwrap_or('^');
+            let (t, next) = self.substitute(&text, d, j + 1, false, false)?;
+            text = t;
+            j = next;
+        }
+        while self.chars.get(j) == Some(&':') {
+            let mut k = j + 1;
+            let mut global = false;
+            let mut each = false;
+            match self.chars.get(k) {
+                Some('g') | Some('a') => {
+                    global = true;
+                    k += 1;
+                }
+                Some('G') => {
+                    each = true;
+                    k += 1;
+                }
+                _ => (),
+            }
+            let m = match self.chars.get(k) {
+                Some(m) => *m,
+                None => break,
+            };
+            k += 1;
+            match m {
+                'h' => {
+                    if let Some(pos) = text.rfind('/') {
+                        text.truncate(pos.max(if text.starts_with('/') { 1 } else { 0 }));
+                    }
+                }
+                't' => {
+                    if let Some(pos) = text.rfind('/') {
+                        text = text[pos + 1..].to_string();
+                    }
+                }
+                'r' | 'e' => {
+                    let base = text.rfind('/').map_or(0, |p| p + 1);
+                    if let Some(pos) = text[base..].rfind('.') {
+                        text = if m == 'r' {
+                            text[..base + pos].to_string()
+                        } else {
+                            text[base + pos..].to_string()
+                        };
+                    } else if m == 'e' {
+                        text.clear();
+                    }
+                }
+                'p' => self.print = true,
+                'q' => text = format!("'{}'", text.replace('\'', "'\\''")),
+                'x' => {
+                    text = words(&text)
+                        .iter()
+                        .map(|w| format!("'{}'", w.replace('\'', "'\\''")))
+                        .collect::<Vec<String>>()
+                        .join(" ")
+                }
+                's' | '&' => {
+                    let (t, next) = if m == 's' {
+                        match self.chars.get(k) {
+                            Some(d) => {
+                                let d = *d;
+                                self.substitute(&text, d, k + 1, global, each)?
+                            }
+                            None => return Err(format!("{}: substitution failed", text)),
+                        }
+                    } else {
+                        let (old, new) = match &self.subst {
+                            Some(s) => s.clone(),
+                            None => return Err(format!("{}: no previous substitution", text)),
+                        };
+                        (replace(&text, &old, &new, global, each)?, k)
+                    };
+                    text = t;
+                    k = next;
+                }
+                _ => return Err(format!(":{}: unrecognized history modifier", m)),
+            }
+            j = k;
+        }
+        Ok((text, j))
+    }
+
+    /// Read `old` and `new` strings of a substitution from position `j`, separated and ended by `delim`
+    /// (the final one being optional at end of line), then substitute them in `text`.
+    fn substitute(
+        &mut self,
+        text: &str,
+        delim: char,
+        mut j: usize,
+        global: bool,
+        each: bool,
+    ) -> Result<(String, usize), String> {
+        let mut parts = [String::new(), String::new()];
+        for (n, part) in parts.iter_mut().enumerate() {
+            while j < self.chars.len() && self.chars[j] != delim && self.chars[j] != '\n' {
+                if self.chars[j] == '\\' && self.chars.get(j + 1) == Some(&delim) {
+                    j += 1;
+                }
+                part.push(self.chars[j]);
+                j += 1;
+            }
+            if self.chars.get(j) == Some(&delim) {
+                j += 1;
+            } else if n == 0 {
+                break;
+            }
+        }
+        let [old, new] = parts;
+        // an empty old string reuses previous one, & in new stands for old.
+        let old = if old.is_empty() {
+            match &self.subst {
+                Some((o, _)) => o.clone(),
+                None => return Err(format!("{}: no previous substitution", text)),
+            }
+        } else {
+            old
+        };
+        let mut expanded = String::new();
+        let mut chars = new.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' if chars.peek() == Some(&'&') => expanded.push(chars.next().unwrap()),
+                '&' => expanded.push_str(&old),
+                _ => expanded.push(c),
+            }
+        }
+        self.subst = Some((old.clone(), expanded.clone()));
+        Ok((replace(text, &old, &expanded, global, each)?, j))
+    }
+}
+
+/// Replace first occurrence of `old` by `new` in `text`, every one of them with `global`,
+/// first one in every word with `each`.
+fn replace(text: &str, old: &str, new: &str, global: bool, each: bool) -> Result<String, String> {
+    if !text.contains(old) {
+        return Err(format!("{}: substitution failed", old));
+    }
+    Ok(if global {
+        text.replace(old, new)
+    } else if each {
+        words(text)
+            .iter()
+            .map(|w| w.replacen(old, new, 1))
+            .collect::<Vec<String>>()
+            .join(" ")
+    } else {
+        text.replacen(old, new, 1)
+    })
+}
@@ -0,0 +1,755 @@
+//
+// inputrc.rs
+//
+// Copyright 2015-2019 Laurent Wandrebeck <l.wandrebeck@quelquesmots.fr>
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston,
+// MA 02110-1301, USA.
+//
+
+//! RuSh line editor settings.
+//!
+//! inputrc.rs reads readline init files (INPUTRC, ~/.inputrc or /etc/inputrc) and the lines bind builtin is given.
+//! Only what the line editor can do is kept: bell-style, completion-query-items, editing-mode and keyseq-timeout
+//! variables, and single keys bound to readline functions or to macros, which insert their text.
+//! Other variables are remembered, but have no effect.
+
+use crate::rush::RuSh;
+use rustyline::{Anchor, At, Cmd, KeyPress, Movement, Word};
+use std::collections::HashMap;
+use std::fs;
+
+/// Readline functions keys can be bound to.
+pub const FUNCTIONS: &[&str] = &[
+    "abort",
+    "accept-line",
+    "backward-char",
+    "backward-delete-char",
+    "backward-kill-line",
+    "backward-kill-word",
+    "backward-word",
+    "beginning-of-history",
+    "beginning-of-line",
+    "capitalize-word",
+    "clear-screen",
+    "complete",
+    "delete-char",
+    "downcase-word",
+    "end-of-file",
+    "end-of-history",
+    "end-of-line",
+    "forward-char",
+    "forward-search-history",
+    "forward-word",
+    "history-search-backward",
+    "history-search-forward",
+    "kill-line",
+    "kill-whole-line",
+    "kill-word",
+    "next-history",
+    "possible-completions",
+    "previous-history",
+    "quoted-insert",
+    "reverse-search-history",
+    "self-insert",
+    "transpose-chars",
+    "transpose-words",
+    "undo",
+    "unix-line-discard",
+    "unix-word-rubout",
+    "upcase-word",
+    "yank",
+    "yank-pop",
+];
+
+/// Readline variables the line editor uses, with their default value.
+pub const VARIABLES: &[(&str, &str)] = &[
+    ("bell-style", "audible"),
+    ("completion-query-items", "100"),
+    ("editing-mode", "emacs"),
+    ("keymap", "emacs"),
+    ("keyseq-timeout", "500"),
+];
+
+/// Keys the line editor binds to readline functions in emacs keymap, unless they are bound otherwise.
+/// Printable characters insert themselves.
+pub const EMACS: &[(KeyPress, &str)] = &[
+    (KeyPress::Ctrl('A'), "beginning-of-line"),
+    (KeyPress::Ctrl('B'), "backward-char"),
+    (KeyPress::Ctrl('D'), "end-of-file"),
+    (KeyPress::Ctrl('E'), "end-of-line"),
+    (KeyPress::Ctrl('F'), "forward-char"),
+    (KeyPress::Ctrl('G'), "abort"),
+    (KeyPress::Ctrl('H'), "backward-delete-char"),
+    (KeyPress::Tab, "complete"),
+    (KeyPress::Ctrl('J'), "accept-line"),
+    (KeyPress::Ctrl('K'), "kill-line"),
+    (KeyPress::Ctrl('L'), "clear-screen"),
+    (KeyPress::Enter, "accept-line"),
+    (KeyPress::Ctrl('N'), "next-history"),
+    (KeyPress::Ctrl('P'), "previous-history"),
+    (KeyPress::Ctrl('Q'), "quoted-insert"),
+    (KeyPress::Ctrl('R'), "reverse-search-history"),
+    (KeyPress::Ctrl('S'), "forward-search-history"),
+    (KeyPress::Ctrl('T'), "transpose-chars"),
+    (KeyPress::Ctrl('U'), "unix-line-discard"),
+    (KeyPress::Ctrl('V'), "quoted-insert"),
+    (KeyPress::Ctrl('W'), "unix-word-rubout"),
+    (KeyPress::Ctrl('Y'), "yank"),
+    (KeyPress::Ctrl('_'), "undo"),
+    (KeyPress::Backspace, "backward-delete-char"),
+    (KeyPress::Meta('\x7f'), "backward-kill-word"),
+    (KeyPress::Meta('<'), "beginning-of-history"),
+    (KeyPress::Meta('>'), "end-of-history"),
+    (KeyPress::Meta('b'), "backward-word"),
+    (KeyPress::Meta('c'), "capitalize-word"),
+    (KeyPress::Meta('d'), "kill-word"),
+    (KeyPress::Meta('f'), "forward-word"),
+    (KeyPress::Meta('l'), "downcase-word"),
+    (KeyPress::Meta('t'), "transpose-words"),
+    (KeyPress::Meta('u'), "upcase-word"),
+    (KeyPress::Meta('y'), "yank-pop"),
+    (KeyPress::Up, "previous-history"),
+    (KeyPress::Down, "next-history"),
+    (KeyPress::Right, "forward-char"),
+    (KeyPress::Left, "backward-char"),
+    (KeyPress::Home, "beginning-of-line"),
+    (KeyPress::End, "end-of-line"),
+    (KeyPress::Delete, "delete-char"),
+];
+
+/// What a key is bound to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Binding {
+    /// a readline function.
+    Function(String),
+    /// text inserted when the key is pressed.
+    Macro(String),
+}
+
+/// Readline settings and key bindings.
+#[derive(Clone, Debug, Default)]
+pub struct Inputrc {
+    /// variables set, by name. editing-mode is kept as vi and emacs set options.
+    pub variables: HashMap<String, String>,
+    /// key bindings by keymap: emacs, vi-insert or vi-command.
+    pub keymaps: HashMap<String, Vec<(KeyPress, Binding)>>,
+    /// keymap set with `set keymap`, None for the one of the editing mode.
+    pub keymap: Option<String>,
+}
+
+impl Inputrc {
+    /// Bind a key in a keymap, replacing its previous binding.
+    pub fn bind(&mut self, keymap: &str, key: KeyPress, binding: Binding) {
+        let bindings = self.keymaps.entry(keymap.to_string()).or_default();
+        match bindings.iter_mut().find(|(k, _)| *k == key) {
+            Some(b) => b.1 = binding,
+            None => bindings.push((key, binding)),
+        }
+    }
+
+    /// Remove the binding of a key in a keymap. Returns false if it had none.
+    pub fn unbind(&mut self, keymap: &str, key: KeyPress) -> bool {
+        match self.keymaps.get_mut(keymap) {
+            Some(bindings) => {
+                let len = bindings.len();
+                bindings.retain(|(k, _)| *k != key);
+                bindings.len() != len
+            }
+            None => false,
+        }
+    }
+
+    /// Key bindings of a keymap.
+    pub fn bindings(&self, keymap: &str) -> &[(KeyPress, Binding)] {
+        match self.keymaps.get(keymap) {
+            Some(bindings) => bindings,
+            None => &[],
+        }
+    }
+}
+
+/// Keymap a name stands for, None if there is none. vi and vi-move are vi-command, emacs-standard is emacs.
+///
+/// # Examples
+/// ```rust
+/// use rush::inputrc::keymap;
+///
+/// assert_eq!(keymap("vi"), Some("vi-command"));
+/// assert_eq!(keymap("emacs-standard"), Some("emacs"));
+/// assert_eq!(keymap("emacs-meta"), None);
+/// ```
+pub fn keymap(name: &str) -> Option<&'static str> {
+    match name {
+        "emacs" | "emacs-standard" => Some("emacs"),
+        "vi" | "vi-move" | "vi-command" => Some("vi-command"),
+        "vi-insert" => Some("vi-insert"),
+        _ => None,
+    }
+}
+
+/// Is vi editing mode on ?
+fn vi(rush: &RuSh) -> bool {
+    match rush.set_options.get("vi") {
+        Some(opt) => opt.get(),
+        None => false,
+    }
+}
+
+/// Keymap bindings go to when none is given: the one set with `set keymap`, or the one of the editing mode.
+pub fn current_keymap(rush: &RuSh) -> String {
+    match &rush.inputrc.keymap {
+        Some(keymap) => keymap.clone(),
+        None if vi(rush) => String::from("vi-insert"),
+        None => String::from("emacs"),
+    }
+}
+
+/// Value of a readline variable.
+///
+/// # Examples
+/// ```rust
+/// use rush::RuSh;
+/// use rush::inputrc::variable;
+/// use rush::parse::parse;
+///
+/// let mut rush = RuSh::default();
+/// assert_eq!(variable(&rush, "bell-style"), "audible");
+/// parse(&mut rush, "set -o vi");
+/// assert_eq!(variable(&rush, "editing-mode"), "vi");
+/// ```
+pub fn variable(rush: &RuSh, name: &str) -> String {
+    match name {
+        "editing-mode" => String::from(if vi(rush) { "vi" } else { "emacs" }),
+        "keymap" => current_keymap(rush),
+        _ => match rush.inputrc.variables.get(name) {
+            Some(value) => value.clone(),
+            None => VARIABLES
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, v)| v.to_string())
+                .unwrap_or_default(),
+        },
+    }
+}
+
+/// Set a readline variable. editing-mode switches vi and emacs set options, keymap the keymap bindings go to.
+fn set_variable(rush: &mut RuSh, name: &str, value: &str) -> Result<(), String> {
+    match name {
+        "editing-mode" => match value {
+            "vi" | "emacs" => {
+                rush.set_options.update("vi", value == "vi");
+                rush.set_options.update("emacs", value == "emacs");
+            }
+            _ => return Err(format!("{}: invalid editing mode", value)),
+        },
+        "keymap" => match keymap(value) {
+            Some(k) => rush.inputrc.keymap = Some(k.to_string()),
+            None => return Err(format!("{}: invalid keymap name", value)),
+        },
+        _ => {
+            rush.inputrc
+                .variables
+                .insert(name.to_string(), value.to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Translate readline escapes: `\C-x` and `\M-x` modifiers, `\e`, `\\`, `\"`, `\'`, `\a`, `\b`, `\d`, `\f`, `\n`,
+/// `\r`, `\t`, `\v`, octal `\nnn` and hexadecimal `\xHH` characters. Meta characters are escape prefixed.
+///
+/// # Examples
+/// ```rust
+/// use rush::inputrc::unescape;
+///
+/// assert_eq!(unescape("\\C-a\\M-b\\e[A"), "\x01\x1bb\x1b[A");
+/// assert_eq!(unescape("a\\tb\\101\\x42\\\""), "a\tbAB\"");
+/// ```
+pub fn unescape(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut s = String::new();
+    let mut i = 0;
+    // pending modifiers.
+    let (mut ctrl, mut meta) = (false, false);
+    while i < chars.len() {
+        let mut c = chars[i];
+        i += 1;
+        if c == '\\' && i < chars.len() {
+            let e = chars[i];
+            i += 1;
+            if (e == 'C' || e == 'M') && chars.get(i) == Some(&'-') && i + 1 < chars.len() {
+                i += 1;
+                if e == 'C' {
+                    ctrl = true;
+                } else {
+                    meta = true;
+                }
+                continue;
+            }
+            c = match e {
+                'e' => '\x1b',
+                'a' => '\x07',
+                'b' => '\x08',
+                'd' => '\x7f',
+                'f' => '\x0c',
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                'v' => '\x0b',
+                '0'..='7' => {
+                    let mut n = e.to_digit(8).unwrap();
+                    let mut count = 1;
+                    while count < 3 && i < chars.len() && chars[i].is_digit(8) {
+                        n = n * 8 + chars[i].to_digit(8).unwrap();
+                        i += 1;
+                        count += 1;
+                    }
+                    std::char::from_u32(n).unwrap_or('\0')
+                }
+                'x' if i < chars.len() && chars[i].is_ascii_hexdigit() => {
+                    let mut n = 0;
+                    let mut count = 0;
+                    while count < 2 && i < chars.len() && chars[i].is_ascii_hexdigit() {
+                        n = n * 16 + chars[i].to_digit(16).unwrap();
+                        i += 1;
+                        count += 1;
+                    }
+                    std::char::from_u32(n).unwrap_or('\0')
+                }
+                _ => e,
+            };
+        }
+        if ctrl {
+            c = control(c);
+            ctrl = false;
+        }
+        if meta {
+            s.push('\x1b');
+            meta = false;
+        }
+        s.push(c);
+    }
+    s
+}
+
+/// Control character of a character: `\C-a` is 0x01, `\C-?` is rubout.
+fn control(c: char) -> char {
+    match c {
+        '?' => '\x7f',
+        _ if c.is_ascii() => ((c.to_ascii_uppercase() as u8) & 0x1f) as char,
+        _ => c,
+    }
+}
+
+/// Escape sequences of keys the line editor knows.
+const SEQUENCES: &[(&str, KeyPress)] = &[
+    ("\x1b[A", KeyPress::Up),
+    ("\x1b[B", KeyPress::Down),
+    ("\x1b[C", KeyPress::Right),
+    ("\x1b[D", KeyPress::Left),
+    ("\x1b[H", KeyPress::Home),
+    ("\x1b[F", KeyPress::End),
+    ("\x1b[Z", KeyPress::BackTab),
+    ("\x1b[2~", KeyPress::Insert),
+    ("\x1b[3~", KeyPress::Delete),
+    ("\x1b[5~", KeyPress::PageUp),
+    ("\x1b[6~", KeyPress::PageDown),
+    ("\x1bOP", KeyPress::F(1)),
+    ("\x1bOQ", KeyPress::F(2)),
+    ("\x1bOR", KeyPress::F(3)),
+    ("\x1bOS", KeyPress::F(4)),
+    ("\x1b[15~", KeyPress::F(5)),
+    ("\x1b[17~", KeyPress::F(6)),
+    ("\x1b[18~", KeyPress::F(7)),
+    ("\x1b[19~", KeyPress::F(8)),
+    ("\x1b[20~", KeyPress::F(9)),
+    ("\x1b[21~", KeyPress::F(10)),
+    ("\x1b[23~", KeyPress::F(11)),
+    ("\x1b[24~", KeyPress::F(12)),
+    // other terminals.
+    ("\x1bOA", KeyPress::Up),
+    ("\x1bOB", KeyPress::Down),
+    ("\x1bOC", KeyPress::Right),
+    ("\x1bOD", KeyPress::Left),
+    ("\x1bOH", KeyPress::Home),
+    ("\x1bOF", KeyPress::End),
+    ("\x1b[1~", KeyPress::Home),
+    ("\x1b[4~", KeyPress::End),
+];
+
+/// Key a character is read as.
+fn key_press(c: char) -> KeyPress {
+    match c {
+        '\0' => KeyPress::Ctrl(' '),
+        '\x08' | '\x7f' => KeyPress::Backspace,
+        '\t' => KeyPress::Tab,
+        '\r' => KeyPress::Enter,
+        '\x1b' => KeyPress::Esc,
+        '\x01'..='\x1f' => KeyPress::Ctrl((c as u8 + 0x40) as char),
+        _ => KeyPress::Char(c),
+    }
+}
+
+/// Key a key sequence stands for, once unescaped. The line editor binds single keys only: a character,
+/// a control or meta character, or a known escape sequence.
+///
+/// # Examples
+/// ```rust
+/// use rush::inputrc::key;
+/// use rustyline::KeyPress;
+///
+/// assert_eq!(key("\x01"), Ok(KeyPress::Ctrl('A')));
+/// assert_eq!(key("\x1bf"), Ok(KeyPress::Meta('f')));
+/// assert_eq!(key("\x1b[A"), Ok(KeyPress::Up));
+/// assert!(key("\x18\x12").is_err());
+/// ```
+pub fn key(seq: &str) -> Result<KeyPress, String> {
+    let chars: Vec<char> = seq.chars().collect();
+    match chars.as_slice() {
+        [c] => return Ok(key_press(*c)),
+        ['\x1b', '\x1b'] => return Ok(KeyPress::Esc),
+        ['\x1b', c] if *c != '[' && *c != 'O' => return Ok(KeyPress::Meta(*c)),
+        _ => (),
+    }
+    match SEQUENCES.iter().find(|(s, _)| *s == seq) {
+        Some((_, key)) => Ok(*key),
+        None => Err(format!(
+            "\"{}\": key sequence not supported by the line editor",
+            escape(seq)
+        )),
+    }
+}
+
+/// Write characters back with readline escapes.
+fn escape(text: &str) -> String {
+    let mut s = String::new();
+    for c in text.chars() {
+        match c {
+            '\x1b' => s.push_str("\\e"),
+            '\x7f' => s.push_str("\\C-?"),
+            '\\' | '"' => {
+                s.push('\\');
+                s.push(c);
+            }
+            '\0'..='\x1f' => {
+                s.push_str("\\C-");
+                s.push(((c as u8) | 0x60) as char);
+            }
+            _ => s.push(c),
+        }
+    }
+    s
+}
+
+/// Key sequence of a key, the way bind builtin prints it.
+///
+/// # Examples
+/// ```rust
+/// use rush::inputrc::keyseq;
+/// use rustyline::KeyPress;
+///
+/// assert_eq!(keyseq(KeyPress::Ctrl('A')), "\\C-a");
+/// assert_eq!(keyseq(KeyPress::Meta('f')), "\\ef");
+/// assert_eq!(keyseq(KeyPress::Up), "\\e[A");
+/// ```
+pub fn keyseq(key: KeyPress) -> String {
+    match key {
+        KeyPress::Char(c) => escape(&c.to_string()),
+        KeyPress::Ctrl(' ') => String::from("\\C-@"),
+        KeyPress::Ctrl(c) => format!("\\C-{}", c.to_ascii_lowercase()),
+        KeyPress::Meta(c) => format!("\\e{}", escape(&c.to_string())),
+        KeyPress::Tab => String::from("\\C-i"),
+        KeyPress::Enter => String::from("\\C-m"),
+        KeyPress::Esc => String::from("\\e"),
+        KeyPress::Backspace => String::from("\\C-?"),
+        _ => match SEQUENCES.iter().find(|(_, k)| *k == key) {
+            Some((s, _)) => escape(s),
+            None => String::new(),
+        },
+    }
+}
+
+/// Key written as a readline key name, with `Control-` and `Meta-` modifiers: `Control-u`, `Meta-Rubout`, `TAB`…
+fn key_name(name: &str) -> Result<KeyPress, String> {
+    let (mut ctrl, mut meta) = (false, false);
+    let mut rest = name;
+    loop {
+        let lower = rest.to_ascii_lowercase();
+        if let Some(len) = ["control-", "c-"].iter().find_map(|p| {
+            if lower.starts_with(p) && rest.len() > p.len() {
+                Some(p.len())
+            } else {
+                None
+            }
+        }) {
+            ctrl = true;
+            rest = &rest[len..];
+        } else if let Some(len) = ["meta-", "m-"].iter().find_map(|p| {
+            if lower.starts_with(p) && rest.len() > p.len() {
+                Some(p.len())
+            } else {
+                None
+            }
+        }) {
+            meta = true;
+            rest = &rest[len..];
+        } else {
+            break;
+        }
+    }
+    let mut c = match rest.to_ascii_lowercase().as_str() {
+        "del" | "rubout" => '\x7f',
+        "esc" | "escape" => '\x1b',
+        "lfd" | "newline" => '\n',
+        "ret" | "return" => '\r',
+        "space" | "spc" => ' ',
+        "tab" => '\t',
+        _ => {
+            let mut chars = rest.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => c,
+                _ => return Err(format!("{}: unknown key name", name)),
+            }
+        }
+    };
+    if ctrl {
+        c = control(c);
+    }
+    let seq = if meta {
+        format!("\x1b{}", c)
+    } else {
+        c.to_string()
+    };
+    key(&seq)
+}
+
+/// End of a quoted string starting at `start`, a backslash escaping next character.
+fn closing_quote(text: &str, start: usize) -> Option<usize> {
+    let quote = text[start..].chars().next()?;
+    let mut escape = false;
+    for (i, c) in text[start + 1..].char_indices() {
+        if escape {
+            escape = false;
+        } else if c == '\\' {
+            escape = true;
+        } else if c == quote {
+            return Some(start + 1 + i);
+        }
+    }
+    None
+}
+
+/// Line editor command a binding stands for. self-insert inserts the key it is bound to.
+pub fn command(binding: &Binding, key: KeyPress) -> Option<Cmd> {
+    let name = match binding {
+        Binding::Macro(text) => return Some(Cmd::Insert(1, text.clone())),
+        Binding::Function(name) => name.as_str(),
+    };
+    let emacs_word = Movement::ForwardWord(1, At::AfterEnd, Word::Emacs);
+    Some(match name {
+        "abort" => Cmd::Abort,
+        "accept-line" => Cmd::AcceptLine,
+        "backward-char" => Cmd::Move(Movement::BackwardChar(1)),
+        "backward-delete-char" => Cmd::Kill(Movement::BackwardChar(1)),
+        "backward-kill-line" | "unix-line-discard" => Cmd::Kill(Movement::BeginningOfLine),
+        "backward-kill-word" => Cmd::Kill(Movement::BackwardWord(1, Word::Emacs)),
+        "backward-word" => Cmd::Move(Movement::BackwardWord(1, Word::Emacs)),
+        "beginning-of-history" => Cmd::BeginningOfHistory,
+        "beginning-of-line" => Cmd::Move(Movement::BeginningOfLine),
+        "capitalize-word" => Cmd::CapitalizeWord,
+        "clear-screen" => Cmd::ClearScreen,
+        "complete" | "possible-completions" => Cmd::Complete,
+        "delete-char" => Cmd::Kill(Movement::ForwardChar(1)),
+        "downcase-word" => Cmd::DowncaseWord,
+        "end-of-file" => Cmd::EndOfFile,
+        "end-of-history" => Cmd::EndOfHistory,
+        "end-of-line" => Cmd::Move(Movement::EndOfLine),
+        "forward-char" => Cmd::Move(Movement::ForwardChar(1)),
+        "forward-search-history" => Cmd::ForwardSearchHistory,
+        "forward-word" => Cmd::Move(emacs_word),
+        "history-search-backward" => Cmd::HistorySearchBackward,
+        "history-search-forward" => Cmd::HistorySearchForward,
+        "kill-line" => Cmd::Kill(Movement::EndOfLine),
+        "kill-whole-line" => Cmd::Kill(Movement::WholeLine),
+        "kill-word" => Cmd::Kill(emacs_word),
+        "next-history" => Cmd::NextHistory,
+        "previous-history" => Cmd::PreviousHistory,
+        "quoted-insert" => Cmd::QuotedInsert,
+        "reverse-search-history" => Cmd::ReverseSearchHistory,
+        "self-insert" => match key {
+            KeyPress::Char(c) => Cmd::SelfInsert(1, c),
+            _ => return None,
+        },
+        "transpose-chars" => Cmd::TransposeChars,
+        "transpose-words" => Cmd::TransposeWords(1),
+        "undo" => Cmd::Undo(1),
+        "unix-word-rubout" => Cmd::Kill(Movement::BackwardWord(1, Word::Big)),
+        "upcase-word" => Cmd::UpcaseWord,
+        "yank" => Cmd::Yank(1, Anchor::Before),
+        "yank-pop" => Cmd::YankPop,
+        _ => return None,
+    })
+}
+
+/// Execute an init file line: `set variable value`, or a key sequence or name bound to a function or a macro,
+/// `"\C-x": function`, `Meta-f: "text"`. Bindings go to `keymap`, or to the current keymap if None.
+///
+/// # Examples
+/// ```rust
+/// use rush::RuSh;
+/// use rush::inputrc::{line, variable, Binding};
+/// use rustyline::KeyPress;
+///
+/// let mut rush = RuSh::default();
+/// line(&mut rush, "set bell-style none", None).unwrap();
+/// assert_eq!(variable(&rush, "bell-style"), "none");
+/// line(&mut rush, "\"\\C-t\": backward-word", None).unwrap();
+/// line(&mut rush, "Meta-l: \"ls -l\"", None).unwrap();
+/// assert_eq!(
+///     rush.inputrc.bindings("emacs"),
+///     &[(KeyPress::Ctrl('T'), Binding::Function(String::from("backward-word"))), (KeyPress::Meta('l'), Binding::Macro(String::from("ls -l")))]
+/// );
+/// assert!(line(&mut rush, "\"\\C-t\": no-such-function", None).is_err());
+/// ```
+pub fn line(rush: &mut RuSh, text: &str, keymap: Option<&str>) -> Result<(), String> {
+    let text = text.trim();
+    if text.is_empty() || text.starts_with('#') {
+        return Ok(());
+    }
+    if let Some(rest) = text.strip_prefix("set ") {
+        let mut words = rest.split_whitespace();
+        return match (words.next(), words.next()) {
+            (Some(name), Some(value)) => set_variable(rush, name, value),
+            _ => Err(format!("{}: missing value", text)),
+        };
+    }
+    let (key, rest) = if text.starts_with('"') {
+        match closing_quote(text, 0) {
+            Some(end) => {
+                let rest = text[end + 1..].trim_start();
+                match rest.strip_prefix(':') {
+                    Some(rest) => (key(&unescape(&text[1..end]))?, rest),
+                    None => return Err(format!("{}: no colon after key sequence", text)),
+                }
+            }
+            None => return Err(format!("{}: unterminated key sequence", text)),
+        }
+    } else {
+        match text.find(':') {
+            Some(colon) => (key_name(text[..colon].trim())?, &text[colon + 1..]),
+            None => return Err(format!("{}: no key sequence terminator", text)),
+        }
+    };
+    let rest = rest.trim();
+    let binding = if rest.starts_with('"') || rest.starts_with('\'') {
+        let end = closing_quote(rest, 0).unwrap_or(rest.len());
+        Binding::Macro(unescape(&rest[1..end]))
+    } else {
+        let name = rest.split_whitespace().next().unwrap_or("");
+        if !FUNCTIONS.contains(&name) {
+            return Err(format!("`{}': unknown function name", name));
+        }
+        Binding::Function(name.to_string())
+    };
+    let keymap = match keymap {
+        Some(keymap) => keymap.to_string(),
+        None => current_keymap(rush),
+    };
+    rush.inputrc.bind(&keymap, key, binding);
+    Ok(())
+}
+
+/// Does a `$if` condition hold ? `mode=`, `term=` and application name tests are known.
+fn condition(rush: &RuSh, test: &str) -> bool {
+    let test = test.trim();
+    if let Some(mode) = test.strip_prefix("mode=") {
+        return variable(rush, "editing-mode") == mode.trim();
+    }
+    if let Some(name) = test.strip_prefix("term=") {
+        let term = match rush.shell_vars.get("TERM") {
+            Some(v) => v.value.to_string(),
+            None => String::new(),
+        };
+        let name = name.trim();
+        return term == name || term.split('-').next() == Some(name);
+    }
+    test.eq_ignore_ascii_case("rush")
+}
+
+/// Read an init file. Lines that cannot be executed are ignored, the way readline does.
+/// Returns false if the file cannot be read.
+pub fn read(rush: &mut RuSh, path: &str, keymap: Option<&str>) -> bool {
+    read_nested(rush, path, keymap, 0)
+}
+
+/// Read an init file, `depth` being the number of files including it.
+fn read_nested(rush: &mut RuSh, path: &str, keymap: Option<&str>, depth: usize) -> bool {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(_) => return false,
+    };
+    // for each $if being read: are its lines executed ?
+    let mut conditions: Vec<bool> = Vec::new();
+    for text in contents.lines() {
+        let text = text.trim();
+        let active = conditions.iter().all(|c| *c);
+        if let Some(test) = text.strip_prefix("$if") {
+            conditions.push(active && condition(rush, test));
+        } else if text.starts_with("$else") {
+            let outer =
+                conditions.len() < 2 || conditions[..conditions.len() - 1].iter().all(|c| *c);
+            if let Some(c) = conditions.last_mut() {
+                *c = outer && !*c;
+            }
+        } else if text.starts_with("$endif") {
+            conditions.pop();
+        } else if !active {
+            continue;
+        } else if let Some(file) = text.strip_prefix("$include") {
+            let file = file.trim();
+            let file = match (file.strip_prefix("~/"), rush.shell_vars.get("HOME")) {
+                (Some(rest), Some(home)) => {
+                    format!("{}/{}", home.value.to_string().trim_end_matches('/'), rest)
+                }
+                _ => file.to_string(),
+            };
+            if depth < 16 {
+                read_nested(rush, &file, keymap, depth + 1);
+            }
+        } else {
+            let _ = line(rush, text, keymap);
+        }
+    }
+    true
+}
+
+/// Read the init file of an interactive shell: INPUTRC, or ~/.inputrc, or /etc/inputrc.
+pub fn startup(rush: &mut RuSh) {
+    if let Some(path) = rush.shell_vars.get("INPUTRC") {
+        let path = path.value.to_string();
+        if !path.is_empty() {
+            read(rush, &path, None);
+            return;
+        }
+    }
+    if let Some(home) = rush.shell_vars.get("HOME") {
+        let path = format!("{}/.inputrc", home.value.to_string().trim_end_matches('/'));
+        if read(rush, &path, None) {
+            return;
+        }
+    }
+    read(rush, "/etc/inputrc", None);
+}
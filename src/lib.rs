@@ -4,8 +4,28 @@ extern crate chrono;
 extern crate pest;
 extern crate rand;
 
+/// Include aliases expansion.
+pub mod alias;
+/// Include arithmetic evaluation.
+pub mod arith;
 /// Include arrays management.
 pub mod arrays;
+/// Include builtins.
+pub mod builtins;
+/// Include programmable completion.
+pub mod completion;
+/// Include line editor helper.
+pub mod editor;
+/// Include commands execution.
+pub mod execute;
+/// Include words expansion.
+pub mod expand;
+/// Include syntax highlighting.
+pub mod highlight;
+/// Include history management.
+pub mod history;
+/// Include line editor settings and key bindings.
+pub mod inputrc;
 /// Include options management (shopt, set)
 pub mod opt;
 /// Include parse routine
@@ -14,7 +34,13 @@ pub mod parse;
 pub mod prompt;
 /// Include rush core.
 pub mod rush;
+/// Include scripts execution.
+pub mod script;
+/// Include prompt segments computed in background.
+pub mod segments;
 /// Include variables management.
 pub mod variables;
+/// Include version control status.
+pub mod vcs;
 
 pub use self::rush::RuSh;
@@ -37,20 +37,351 @@ extern crate rustyline;
 extern crate term;
 
 // pub for use is there so doc is generated.
+use rush::alias;
 pub use rush::arrays::{Array, Index};
-pub use rush::opt::Opt;
+use rush::editor::{self, Helper};
+use rush::history;
+use rush::inputrc;
+pub use rush::opt::{Opt, OptionRW, SET_FLAGS};
+use rush::parse::incomplete;
 pub use rush::parse::parse;
-pub use rush::prompt::Prompt;
+pub use rush::prompt::{self, Prompt};
 pub use rush::rush::RuSh;
+pub use rush::script;
 pub use rush::variables::{Access, Value, Variable, Variables};
+use rustyline::error::ReadlineError;
+use std::env;
+use std::process;
+
+/// Invocation usage, printed by --help and on invalid options.
+const USAGE: &str = "Usage:\trush [long option] [option] ...
+\trush [long option] [option] script-file ...
+\trush [long option] [option] -c command [name [argument ...]]
+Long options:
+\t--help
+\t--init-file file
+\t--login
+\t--noprofile
+\t--norc
+\t--posix
+\t--rcfile file
+\t--restricted
+\t--verbose
+\t--version
+Shell options:
+\t-ilrs or -c command or -O shopt_option\t\t(invocation only)
+\t-abefhkmnptuvxBCEHPT or -o option";
+
+/// How rush was invoked, from its command line arguments.
+struct Invocation {
+    /// -l, --login, or $0 starting with `-`: profile files are read.
+    login: bool,
+    /// -i: interactive, whatever standard input is.
+    interactive: bool,
+    /// -c: first operand is a command string.
+    command: bool,
+    /// -s: commands are read from standard input, operands are positional parameters.
+    stdin: bool,
+    /// -r, --restricted: restricted shell.
+    restricted: bool,
+    /// false with --noprofile.
+    profile: bool,
+    /// false with --norc.
+    rc: bool,
+    /// --rcfile file, used instead of ~/.rushrc.
+    rcfile: Option<String>,
+    /// -O and +O shopt options, applied once defaults depending on interactivity are set.
+    shopts: Vec<(String, bool)>,
+    /// arguments following options.
+    operands: Vec<String>,
+}
+
+/// Print an invocation error followed by usage, and exit.
+fn invalid(message: &str) -> ! {
+    eprintln!("rush: {}", message);
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+/// Parse command line arguments. set options are applied to `rush` as they are met,
+/// so that they are in effect when startup files are read. Long options have to come first.
+fn invocation(rush: &mut RuSh, args: &[String]) -> Invocation {
+    let mut inv = Invocation {
+        login: args.first().is_some_and(|a| a.starts_with('-')),
+        interactive: false,
+        command: false,
+        stdin: false,
+        restricted: false,
+        profile: true,
+        rc: true,
+        rcfile: None,
+        shopts: Vec::new(),
+        operands: Vec::new(),
+    };
+    let mut i = 1;
+    while let Some(arg) = args.get(i).filter(|a| a.starts_with("--") && a.len() > 2) {
+        i += 1;
+        match arg.as_str() {
+            "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            "--version" => {
+                let (major, minor, patch, build, release, machtype) = &rush.versinfo;
+                println!(
+                    "RuSh, version {}.{}.{}({})-{} ({})",
+                    major, minor, patch, build, release, machtype
+                );
+                println!(
+                    "License GPLv3+: GNU GPL version 3 or later <http://gnu.org/licenses/gpl.html>"
+                );
+                process::exit(0);
+            }
+            "--login" => inv.login = true,
+            "--noprofile" => inv.profile = false,
+            "--norc" => inv.rc = false,
+            "--posix" => {
+                rush.set_options.update("posix", true);
+            }
+            "--restricted" => inv.restricted = true,
+            "--verbose" => {
+                rush.set_options.update("verbose", true);
+            }
+            "--rcfile" | "--init-file" => match args.get(i) {
+                Some(f) => {
+                    inv.rcfile = Some(f.clone());
+                    i += 1;
+                }
+                None => invalid(&format!("{}: option requires an argument", arg)),
+            },
+            _ => invalid(&format!("{}: invalid option", arg)),
+        }
+    }
+    while let Some(arg) = args.get(i) {
+        if arg == "--" || arg == "-" {
+            i += 1;
+            break;
+        }
+        if arg.len() < 2 || !(arg.starts_with('-') || arg.starts_with('+')) {
+            break;
+        }
+        i += 1;
+        let set = arg.starts_with('-');
+        for c in arg[1..].chars() {
+            match c {
+                'c' if set => inv.command = true,
+                'i' if set => inv.interactive = true,
+                'l' if set => inv.login = true,
+                'r' if set => inv.restricted = true,
+                's' if set => inv.stdin = true,
+                'o' | 'O' => {
+                    let name = match args.get(i) {
+                        Some(name) => name,
+                        None => invalid(&format!("-{}: option requires an argument", c)),
+                    };
+                    i += 1;
+                    if c == 'o' && !rush.set_options.update(name, set) {
+                        invalid(&format!("{}: invalid option name", name));
+                    }
+                    if c == 'O' {
+                        if rush.shopt_options.get(name).is_none() {
+                            invalid(&format!("{}: invalid shell option name", name));
+                        }
+                        inv.shopts.push((name.clone(), set));
+                    }
+                }
+                _ => match SET_FLAGS.iter().find(|(flag, _)| *flag == c) {
+                    Some((_, name)) => {
+                        rush.set_options.update(name, set);
+                    }
+                    None => invalid(&format!("{}{}: invalid option", &arg[..1], c)),
+                },
+            }
+        }
+    }
+    inv.operands = args[i..].to_vec();
+    inv
+}
+
+/// Set a read only shell option, that only invocation can change.
+fn set_readonly_opt(rush: &mut RuSh, name: &str) {
+    rush.shopt_options.set(
+        name.to_string(),
+        OptionRW {
+            set: true,
+            access: Access::ReadOnly,
+        },
+    );
+}
+
+/// Apply history expansion to an interactive input line, and add it to history.
+/// Returns the line to execute, None if it is not to be executed (expansion failed, histverify is set, or :p was used).
+/// `initial` gets the line to edit again, if any.
+fn history_expand(rush: &mut RuSh, input: String, initial: &mut Option<String>) -> Option<String> {
+    let shopt = |rush: &RuSh, name| match rush.shopt_options.get(name) {
+        Some(opt) => opt.get(),
+        None => false,
+    };
+    let histexpand = match rush.set_options.get("histexpand") {
+        Some(opt) => opt.get(),
+        None => false,
+    };
+    let line = if histexpand {
+        match history::expand(rush, &input) {
+            Ok(exp) => {
+                if exp.changed && shopt(rush, "histverify") && !exp.print {
+                    *initial = Some(exp.line);
+                    return None;
+                }
+                if exp.changed || exp.print {
+                    println!("{}", exp.line);
+                }
+                if exp.print {
+                    history::add(rush, &exp.line);
+                    return None;
+                }
+                exp.line
+            }
+            Err(e) => {
+                eprintln!("rush: {}", e);
+                if shopt(rush, "histreedit") {
+                    *initial = Some(input);
+                }
+                return None;
+            }
+        }
+    } else {
+        input
+    };
+    Some(line)
+}
+
+/// Copy history list to the editor one, so that it can be browsed. `synced` is the number of removals from
+/// history list and the history number of its next entry when last copied: only entries added since are copied
+/// then, unless some were removed meanwhile.
+fn editor_history(rush: &RuSh, rl: &mut rustyline::Editor<Helper>, synced: &mut (usize, usize)) {
+    let history = rl.history_mut();
+    let mut next = synced.1;
+    if synced.0 != rush.history.removals {
+        history.clear();
+        next = rush.history.base;
+    }
+    history.set_max_len(usize::MAX);
+    for n in next.max(rush.history.base)..rush.history.number() {
+        if let Some(entry) = rush.history.get(n) {
+            history.add(entry.line.as_str());
+        }
+    }
+    // entries dropped from history list are dropped from the editor one too.
+    history.set_max_len(rush.history.len().max(1));
+    if rush.history.is_empty() {
+        history.clear();
+    }
+    *synced = (rush.history.removals, rush.history.number());
+}
+
+/// Read logout file, save history and exit an interactive shell.
+fn quit(rush: &mut RuSh, status: i32) -> ! {
+    script::logout(rush);
+    history::save(rush);
+    process::exit(status);
+}
 
 /// This is the main function. Initializes RuSh structure and starts the shell.
+/// `rush -c command [name [arguments]]` runs a command string, `rush script [arguments]` a script file.
+/// Otherwise commands are read from standard input, interactively if it is a terminal or if -i is given.
+/// Login shells (invoked as `-rush`, or with -l) read profile files, interactive ones ~/.rushrc.
 fn main() {
     let mut rush = RuSh::default();
+    let args: Vec<String> = env::args().collect();
+    if let Some(arg0) = args.first() {
+        rush.positional[0] = arg0.clone();
+    }
+    let inv = invocation(&mut rush, &args);
+    if inv.login {
+        set_readonly_opt(&mut rush, "login_shell");
+    }
+    let command = if inv.command {
+        match inv.operands.first() {
+            Some(c) => Some(c.clone()),
+            None => invalid("-c: option requires an argument"),
+        }
+    } else {
+        None
+    };
+    // $0 and positional parameters.
+    let script = if inv.command {
+        if inv.operands.len() > 1 {
+            rush.positional = inv.operands[1..].to_vec();
+        }
+        None
+    } else if inv.stdin || inv.operands.is_empty() {
+        rush.positional.truncate(1);
+        rush.positional.extend_from_slice(&inv.operands);
+        None
+    } else {
+        Some(inv.operands[0].clone())
+    };
+    rush.interactive = inv.interactive
+        || (command.is_none()
+            && script.is_none()
+            && unsafe { libc::isatty(0) == 1 && libc::isatty(2) == 1 });
+    // aliases are expanded in interactive shells only, unless asked otherwise. History is kept by interactive ones.
+    if rush.interactive {
+        rush.set_options.update("history", true);
+    } else {
+        rush.shopt_options.update("expand_aliases", false);
+    }
+    for (name, set) in &inv.shopts {
+        rush.shopt_options.update(name, *set);
+    }
+    rush.update_opts_vars();
+    if rush.interactive {
+        history::defaults(&mut rush);
+        inputrc::startup(&mut rush);
+    }
+    if inv.login && inv.profile {
+        script::profile(&mut rush, script::SYSTEM_PROFILE);
+    }
+    if rush.interactive && !inv.login && inv.rc {
+        script::rc(&mut rush, inv.rcfile.as_deref());
+    }
+    if rush.interactive {
+        history::load(&mut rush);
+    }
+    // restrictions apply once startup files are read.
+    if inv.restricted {
+        set_readonly_opt(&mut rush, "restricted_shell");
+        rush.update_opts_vars();
+        for name in &["PATH", "SHELL", "ENV", "RUSH_ENV"] {
+            if rush.shell_vars.get(name).is_some() {
+                rush.shell_vars
+                    .set_access(name.to_string(), Access::ReadOnly);
+            }
+        }
+    }
+    if let Some(command) = command {
+        process::exit(script::string(&mut rush, &command));
+    }
+    if let Some(path) = script {
+        process::exit(script::file(&mut rush, &path, &inv.operands[1..]));
+    }
+    if !rush.interactive {
+        process::exit(script::stdin(&mut rush));
+    }
     //rush.prompt = Prompt::get(&mut rush.shell_vars, "PS1");
+    prompt::command(&mut rush);
     rush.prompt = Prompt::get(&mut rush, "PS1");
+    rush.rprompt = Prompt::get(&mut rush, "RPS1");
     //let mut stdin = io::stdin();
-    let mut rl = rustyline::Editor::<()>::new();
+    // history list is managed by rush, the editor gets a copy of it. Completion lists matches the bash way.
+    let mut rl = rustyline::Editor::<Helper>::with_config(
+        rustyline::Config::builder()
+            .history_ignore_dups(false)
+            .completion_type(rustyline::CompletionType::List)
+            .build(),
+    );
+    rl.set_helper(Some(Helper::new()));
     // take care of SECOND env var
     //~ let child = thread::spawn(move ||  {
     //~ loop {
@@ -61,26 +392,73 @@ fn main() {
     //~ }
     //~ }
     //~ });
+    // line given back to the user for edition, with histverify and histreedit.
+    let mut initial: Option<String> = None;
+    // lines of a command still incomplete, continued with PS2 prompt.
+    let mut pending = String::new();
+    // history list state when last copied to the editor.
+    let mut synced = (usize::MAX, 0);
     // main loop. display prompt, wait for input, parse, etc.
     loop {
-        let line = rl.readline(&rush.prompt.prompt);
-        // (very) Basic parsing for now. To be moved in parser.rs later on.
+        editor_history(&rush, &mut rl, &mut synced);
+        editor::configure(&mut rl, &rush);
+        let prompt = rush.prompt.clone();
+        let line = editor::readline(&mut rl, &mut rush, &prompt, initial.take());
         match line {
-            Ok(input) => {
-                // TODO fix history management
-                // rl.add_history_entry(&input);
-                parse(&mut rush, &input);
-                rush.cmd_nb += 1;
+            Ok(mut input) => {
+                editor::transient(&rl, &mut rush, &prompt, &input);
+                // lines read when standard input is not a terminal keep their newline.
+                if input.ends_with('\n') {
+                    input.pop();
+                }
+                if let Some(input) = history_expand(&mut rush, input, &mut initial) {
+                    pending.push_str(&input);
+                    pending.push('\n');
+                    if incomplete(&alias::expand(&rush, &pending)) {
+                        rush.line_case = 2;
+                    } else {
+                        rush.line_case = 1;
+                        let command = std::mem::take(&mut pending);
+                        history::add(&mut rush, command.trim_end_matches('\n'));
+                        parse(&mut rush, &command);
+                        rush.cmd_nb += 1;
+                        if let Some(status) = rush.exit {
+                            quit(&mut rush, status);
+                        }
+                    }
+                }
+            }
+            // Ctrl-C drops the command being typed.
+            Err(ReadlineError::Interrupted) => {
+                pending.clear();
+                rush.line_case = 1;
+            }
+            Err(_) => {
+                // end of input in the middle of a command: report it.
+                if !pending.is_empty() {
+                    history::add(&mut rush, pending.trim_end_matches('\n'));
+                    parse(&mut rush, &pending);
+                }
+                break;
             }
-            Err(_) => break,
         }
-        // Use correct variable to define next prompt display.
+        // Use correct variable to define next prompt display. RPS1 only goes along with PS1.
+        rush.rprompt = Prompt::default();
         match rush.line_case {
-            1 => rush.prompt = Prompt::get(&mut rush, "PS1"),
+            1 => {
+                prompt::command(&mut rush);
+                if let Some(status) = rush.exit {
+                    quit(&mut rush, status);
+                }
+                rush.prompt = Prompt::get(&mut rush, "PS1");
+                rush.rprompt = Prompt::get(&mut rush, "RPS1");
+            }
             2 => rush.prompt = Prompt::get(&mut rush, "PS2"),
             3 => rush.prompt = Prompt::get(&mut rush, "PS3"),
             4 => rush.prompt = Prompt::get(&mut rush, "PS4"),
             _ => panic!("wrong line_case value."),
         }
     }
+    let status = rush.last_status;
+    quit(&mut rush, status);
 }
@@ -42,6 +42,29 @@ impl OptionRW {
     }
 }
 
+/// set builtin short flags and the options they stand for, in $- order.
+pub const SET_FLAGS: [(char, &str); 19] = [
+    ('a', "allexport"),
+    ('b', "notify"),
+    ('e', "errexit"),
+    ('f', "noglob"),
+    ('h', "hashall"),
+    ('k', "keyword"),
+    ('m', "monitor"),
+    ('n', "noexec"),
+    ('p', "privileged"),
+    ('t', "onecmd"),
+    ('u', "nounset"),
+    ('v', "verbose"),
+    ('x', "xtrace"),
+    ('B', "braceexpand"),
+    ('C', "noclobber"),
+    ('E', "errtrace"),
+    ('H', "histexpand"),
+    ('P', "physical"),
+    ('T', "functrace"),
+];
+
 /// Opt structure is defined here to store options status (setopt and set)
 //#[derive(Hash, Eq, PartialEq, Debug)]
 pub struct Opt {
@@ -92,10 +115,83 @@ impl Opt {
     /// ```
     /// # Tests
 
+    #[allow(clippy::empty_line_after_doc_comments)]
     pub fn set(&mut self, key: String, value: OptionRW) {
         self.opt.insert(key, value);
     }
 
+    /// Update the value of an existing option, if its access allows it.
+    /// Returns false if the option does not exist or is `Access::ReadOnly`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use rush::opt::Opt;
+    /// let mut o = Opt::init_shopt_options();
+    /// assert!(o.update("autocd", true));
+    /// assert!(o.get("autocd").unwrap().get());
+    /// assert!(!o.update("login_shell", true));
+    /// assert!(!o.get("login_shell").unwrap().get());
+    /// assert!(!o.update("doesnotexist", true));
+    /// ```
+    pub fn update(&mut self, key: &str, set: bool) -> bool {
+        match self.opt.get_mut(key) {
+            Some(OptionRW {
+                set: value,
+                access: Access::ReadWrite,
+            }) => {
+                *value = set;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Get every option name, sorted.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use rush::opt::Opt;
+    /// let o = Opt::init_set_options();
+    /// assert_eq!(o.names()[0], "allexport");
+    /// ```
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.opt.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Get names of set options, sorted and colon separated, as stored in SHELLOPTS and RUSHOPTS.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use rush::opt::Opt;
+    /// let o = Opt::init_set_options();
+    /// assert_eq!(o.enabled(), "braceexpand:hashall:histexpand:monitor");
+    /// ```
+    pub fn enabled(&self) -> String {
+        self.names()
+            .into_iter()
+            .filter(|name| self.opt[name].set)
+            .collect::<Vec<String>>()
+            .join(":")
+    }
+
+    /// Get set options short flags, as given back by $-.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use rush::opt::Opt;
+    /// let o = Opt::init_set_options();
+    /// assert_eq!(o.flags(), "hmBH");
+    /// ```
+    pub fn flags(&self) -> String {
+        SET_FLAGS
+            .iter()
+            .filter(|(_, name)| self.opt.get(*name).is_some_and(|o| o.set))
+            .map(|(flag, _)| *flag)
+            .collect()
+    }
+
     /// Initialize default shopt_options(&mut self). Returns `Opt`.
     pub fn init_shopt_options() -> Opt {
         //! 43 shopt entries. Allocate a big enough HashMap.
@@ -386,7 +482,7 @@ impl Opt {
             "login_shell".to_string(),
             OptionRW {
                 set: false,
-                access: Access::ReadWrite,
+                access: Access::ReadOnly,
             },
         );
         // If set, and a file that rush is checking for mail has been accessed since the last time it was checked, the message ``The mail in mailfile has been read'' is displayed.
@@ -450,7 +546,7 @@ impl Opt {
             "restricted_shell".to_string(),
             OptionRW {
                 set: false,
-                access: Access::ReadWrite,
+                access: Access::ReadOnly,
             },
         );
         // If set, the shift builtin prints an error message when the shift count exceeds the number of positional parameters.
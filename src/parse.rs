@@ -25,13 +25,15 @@
 //!
 //! Every functions related to parsing of shell input and files are located in that file
 
-use crate::arrays::{Array, Index};
+use crate::alias;
+use crate::execute;
+use crate::expand;
 use crate::rush::RuSh;
-use crate::variables::{Access, Value, Variable};
-use std::convert::TryInto;
+use crate::variables::Value;
+use pest::error::InputLocation;
+use pest::iterators::Pair;
 use std::str::FromStr;
 
-use pest::iterators::Pair;
 use pest::Parser;
 use pest_derive::Parser;
 //~ use pest_consume::{Error, Parser, match_nodes};
@@ -188,269 +190,215 @@ struct Script;
 //~ }
 //~ }
 
-fn varname(p: &Pair<Rule>, name: String) -> (String, Option<Index>) {
-    //for i in p.as_rule() {
-    match p.as_rule() {
-        Rule::index => (p.as_span().as_str().to_string(), Some(index(p))),
-        Rule::arg => (p.as_span().as_str().to_string(), None), //FIXME
-        Rule::numarg => (p.as_span().as_str().to_string(), None), //FIXME
-        Rule::allarg => (p.as_span().as_str().to_string(), None), //FIXME
-        Rule::exitstatus => (p.as_span().as_str().to_string(), None), //FIXME
-        Rule::pid => (p.as_span().as_str().to_string(), None), //FIXME
-        // FIXME ensure we cover everything correctly
-        _ => panic!(),
+/// Get a typed `Value` from an assigned string. Integers and floats are autodetected, as long as they
+/// are written the way they are displayed back, so that `$var` always expands to what was assigned.
+///
+/// # Examples
+/// ```rust
+/// use rush::parse::value;
+/// use rush::variables::Value;
+///
+/// assert_eq!(value("-42"), Value::I(-42));
+/// assert_eq!(value("3.5"), Value::F(3.5));
+/// assert_eq!(value("3.50"), Value::S("3.50".to_string()));
+/// assert_eq!(value("0x2A"), Value::S("0x2A".to_string()));
+/// assert_eq!(value("RuSh"), Value::S("RuSh".to_string()));
+/// ```
+pub fn value(s: &str) -> Value {
+    if let Ok(mut pairs) = Script::parse(Rule::number, s) {
+        let number = pairs.next().unwrap();
+        if number.as_str() == s {
+            let inner = number.into_inner().next().unwrap();
+            match inner.as_rule() {
+                Rule::int => {
+                    if let Ok(i) = i64::from_str(s) {
+                        return Value::I(i);
+                    }
+                }
+                Rule::float => {
+                    if let Ok(f) = f64::from_str(s) {
+                        if f.to_string() == s {
+                            return Value::F(f);
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
     }
-    //}
-    //~ let mut v:Option<Value>;
-    //~ if arr {
-    //~ v = rush::shell_array_vars.get(p.into_span().as_str().unwrap(), i);
-    //~ } else {
-    //~ v = rush::shell_vars.get(p.into_span().as_str().unwrap());
-    //~ }
-    //~ match v {
-    //~ Some(v) => v,
-    //~ None => Value::S("")
-    //~ }
+    Value::S(s.to_string())
 }
 
-fn varops(p: &Pair<Rule>) -> Value {
-    match p.as_rule() {
-        Rule::varmatch => unimplemented!(),
-        Rule::varlen => unimplemented!(),
-        Rule::varreplall => unimplemented!(),
-        Rule::varrepl => unimplemented!(),
-        Rule::varsubstback => unimplemented!(),
-        Rule::varsubstfront => unimplemented!(),
-        Rule::varlongbackmatch => unimplemented!(),
-        Rule::varshortbackmatch => unimplemented!(),
-        Rule::varlongfrontmatch => unimplemented!(),
-        Rule::varshortfrontmatch => unimplemented!(),
-        Rule::varsubstrlen => unimplemented!(),
-        Rule::varsubstr => unimplemented!(),
-        Rule::varerrmsg => unimplemented!(),
-        Rule::varaltvalue => unimplemented!(),
-        Rule::varsetdefault => unimplemented!(),
-        Rule::varusedefault => unimplemented!(),
-        // FIXME ensure we cover everything correctly
-        _ => panic!(),
+/// Parse input (a command line or a whole script) and execute it. Aliases are expanded first.
+/// Exit status of the last command is stored in `RuSh::last_status`.
+/// With verbose option set, input is printed to stderr as it is read.
+/// An aborted execution (interactive shell expansion error) gives back 1, `RuSh::exit` is set when the shell has to exit.
+/// Returns false if input has a syntax error, in which case nothing is executed and status is 2.
+/// With noexec option set, a non interactive shell only checks syntax.
+pub fn parse(rush: &mut RuSh, input: &str) -> bool {
+    if let Some(opt) = rush.set_options.get("verbose") {
+        if opt.get() {
+            eprintln!("{}", input);
+        }
     }
-}
-
-fn index(p: &Pair<Rule>) -> Index {
-    let mut index: Index;
-    match p.as_rule() {
-        // FIXME index::I is usize and need to be correctly parsed (ie -1 is last element)
-        Rule::hexnum => Index::I(hexnum(p).try_into().unwrap()),
-        Rule::binnum => Index::I(binnum(p).try_into().unwrap()),
-        Rule::octnum => Index::I(octnum(p).try_into().unwrap()),
-        Rule::int => Index::I(int(p).try_into().unwrap()),
-        Rule::varvalue => unimplemented!(),
-        Rule::dquoted => unimplemented!(),
-        Rule::squoted => unimplemented!(),
-        Rule::btquoted => unimplemented!(),
-        Rule::nonquoted => unimplemented!(),
-        // FIXME ensure we cover everything correctly
-        _ => panic!(),
+    let noexec = match rush.set_options.get("noexec") {
+        Some(opt) => opt.get() && !rush.interactive,
+        None => false,
+    };
+    let input = alias::expand(rush, input);
+    match Script::parse(Rule::program, &input) {
+        Ok(_) if noexec => true,
+        Ok(mut program) => {
+            execute::program(rush, program.next().unwrap());
+            // an aborted execution goes back to top level.
+            if rush.abort && rush.nesting == 0 {
+                rush.abort = false;
+                rush.last_status = 1;
+            }
+            true
+        }
+        Err(e) => {
+            eprintln!("rush: syntax error: {}", e);
+            rush.last_status = 2;
+            false
+        }
     }
 }
 
-fn hexnum(p: &Pair<Rule>) -> i64 {
-    i64::from_str_radix(&p.as_span().as_str()[2..], 16).unwrap()
-}
-
-fn octnum(p: &Pair<Rule>) -> i64 {
-    i64::from_str_radix(&p.as_span().as_str()[1..], 8).unwrap()
+/// Parse input without executing it. Returns the program syntax tree, None on a syntax error.
+///
+/// # Examples
+/// ```rust
+/// use rush::parse::tree;
+///
+/// assert!(tree("if true; then echo a; fi").is_some());
+/// assert!(tree("if true").is_none());
+/// ```
+pub fn tree(input: &str) -> Option<Pair<'_, Rule>> {
+    Script::parse(Rule::program, input)
+        .ok()
+        .and_then(|mut program| program.next())
 }
 
-fn binnum(p: &Pair<Rule>) -> i64 {
-    i64::from_str_radix(&p.as_span().as_str()[2..], 2).unwrap()
+/// Parse and execute a function body found at `line`. Its aliases were expanded when it was defined.
+/// Returns exit status, 2 on a syntax error.
+pub fn reparse(rush: &mut RuSh, input: &str, line: i64) -> i32 {
+    match Script::parse(Rule::program, input) {
+        Ok(mut program) => {
+            let lineno = rush.lineno;
+            rush.lineno = line;
+            let status = execute::program(rush, program.next().unwrap());
+            rush.lineno = lineno;
+            status
+        }
+        Err(e) => {
+            eprintln!("rush: syntax error: {}", e);
+            2
+        }
+    }
 }
 
-fn int(p: &Pair<Rule>) -> i64 {
-    i64::from_str(&p.as_span().as_str()).unwrap()
+/// Is input an incomplete command, that following lines could complete ? It is the case when parsing fails
+/// at the very end of input (open quote, compound command without its closing word, trailing `|` or `&&`…),
+/// or when last line ends with a backslash.
+///
+/// # Examples
+/// ```rust
+/// use rush::parse::incomplete;
+///
+/// assert!(incomplete("if true; then\n"));
+/// assert!(incomplete("echo 'a\n"));
+/// assert!(incomplete("echo a |\n"));
+/// assert!(incomplete("echo a \\\n"));
+/// assert!(!incomplete("echo a\n"));
+/// assert!(!incomplete("fi\n"));
+/// ```
+pub fn incomplete(input: &str) -> bool {
+    if let Some(line) = input.strip_suffix('\n') {
+        if line.chars().rev().take_while(|c| *c == '\\').count() % 2 == 1 {
+            return true;
+        }
+    }
+    match Script::parse(Rule::program, input) {
+        Ok(_) => false,
+        Err(e) => match e.location {
+            InputLocation::Pos(pos) => pos >= input.len(),
+            InputLocation::Span((_, end)) => end >= input.len(),
+        },
+    }
 }
 
-fn float(p: &Pair<Rule>) -> f64 {
-    f64::from_str(&p.as_span().as_str()).unwrap()
+/// Position of the quote left open at the end of input, if any.
+///
+/// # Examples
+/// ```rust
+/// use rush::parse::open_quote;
+///
+/// assert_eq!(open_quote("echo \"a 'b"), Some(5));
+/// assert_eq!(open_quote("echo 'a \"b"), Some(5));
+/// assert_eq!(open_quote("echo \\'a"), None);
+/// ```
+pub fn open_quote(line: &str) -> Option<usize> {
+    let mut quote: Option<(char, usize)> = None;
+    let mut escape = false;
+    for (i, c) in line.char_indices() {
+        if escape {
+            escape = false;
+        } else if let Some((q, _)) = quote {
+            if c == q {
+                quote = None;
+            } else if c == '\\' && q != '\'' {
+                escape = true;
+            }
+        } else if c == '\\' {
+            escape = true;
+        } else if c == '"' || c == '\'' || c == '`' {
+            quote = Some((c, i));
+        }
+    }
+    quote.map(|(_, i)| i)
 }
 
-fn varassign(rush: &mut RuSh, p: &Pair<Rule>) {
-    let mut var: (String, Option<Index>);
-    var = (String::new(), None);
-    let mut val = Value::I(i64::from(0));
-    let mut local: bool = false;
-    print!("{:?}", p);
-    //~ for inner in p.into_inner() {
-    //~ match inner.as_rule() {
-    //~ Rule::localvar => local = true,
-    //~ Rule::varname => var = varname(p, inner.as_span().as_str().to_string()),
-    //~ Rule::float => val = Value::F(float(&inner)),
-    //~ Rule::hexnum => val = Value::I(hexnum(&inner)),
-    //~ Rule::octnum => val = Value::I(octnum(&inner)),
-    //~ Rule::binnum => val = Value::I(binnum(&inner)),
-    //~ Rule::int => val = Value::I(int(&inner)),
-    //~ // FIXME ensure we cover everything correctly
-    //~ _ => panic!()
-    //~ }
-    //~ }
-    //FIXME take care of local variables
-    match var.1 {
-        // array case
-        Some(i) => rush.shell_array_vars.set(&var.0, i, val),
-        // simple variable
-        None => rush.shell_vars.set(
-            var.0,
-            Variable {
-                value: val,
-                access: Access::ReadWrite,
-            },
-        ),
+/// Expand a string the way double quoted contents are: parameter expansion and backslash escaping of `$`, `` ` ``,
+/// `"` and `\\`. Used for ENV and prompts. A string that cannot be parsed is given back as is.
+///
+/// # Examples
+/// ```rust
+/// use rush::RuSh;
+/// use rush::parse::{expand_string, parse};
+///
+/// let mut rush = RuSh::default();
+/// parse(&mut rush, "dir=/tmp");
+/// assert_eq!(expand_string(&mut rush, "\"$dir/rc\" \\$dir"), "\"/tmp/rc\" $dir");
+/// ```
+pub fn expand_string(rush: &mut RuSh, s: &str) -> String {
+    match Script::parse(Rule::expandstring, s) {
+        Ok(mut pairs) => expand::string(rush, pairs.next().unwrap()),
+        Err(_) => s.to_string(),
     }
 }
 
-/// Parsing loop - To be rewritten
-pub fn parse(rush: &mut RuSh, input: &str) {
-    // fn parse(rule: R, input: &str) -> Result<Pairs<R>, Error<R>>
-    let pest = Script::parse(Rule::bla, input).unwrap_or_else(|e| panic!("{}", e));
-    for line in pest.flatten() {
-        match line.as_rule() {
-            Rule::float => println!("float: {}", line.as_span().as_str()),
-            Rule::binnum => println!("binnum: {}", line.as_span().as_str()),
-            Rule::hexnum => println!("hexnum: {}", line.as_span().as_str()),
-            Rule::octnum => println!("octnum: {}", line.as_span().as_str()),
-            Rule::int => println!("int: {}", line.as_span().as_str()),
-            Rule::squoted => println!("squoted: {}", line.as_span().as_str()),
-            Rule::dquoted => println!("dquoted: {}", line.as_span().as_str()),
-            Rule::btquoted => println!("btquoted: {}", line.as_span().as_str()),
-            Rule::nonquoted => println!("nonquoted: {:#?}", line),
-            Rule::shebang => println!("shebang: {}", line.as_span().as_str()),
-            Rule::comment => println!("comment: {}", line.as_span().as_str()),
-            Rule::varassign => println!("varassign: {:?}", line.as_span().as_str()), //varassign(rush, &line),
-
-            //println!("varassing: {}", line.as_span().as_str()),
-            //~ Rule::floatvarassign => {
-            //~ let mut var = String::new();
-            //~ let mut val = 0.0;
-            //~ let mut index: Option<Index> = None;
-            //~ for inner in line.into_inner() {
-            //~ match inner.as_rule() {
-            //~ Rule::varname => var = inner.as_span().as_str().to_string(),
-            //~ Rule::float => val = f64::from_str(inner.as_span().as_str()).unwrap(),
-            //~ // FIXME - index management needs to be implemented.
-            //~ Rule::index => {
-            //~ index = Some(Index::I(0));
-            //~ unimplemented!();
-            //~ }
-            //~ _ => unreachable!(),
-            //~ }
-            //~ }
-            //~ rush.shell_vars.set(
-            //~ var,
-            //~ Variable {
-            //~ value: Value::F(val),
-            //~ access: Access::ReadWrite,
-            //~ },
-            //~ );
-            //~ }
-            //~ Rule::binvarassign => println!("binvarassign: {}", line.as_span().as_str()),
-            //~ Rule::intvarassign => {
-            //~ let mut var = String::new();
-            //~ let mut val = 0;
-            //~ for inner in line.into_inner() {
-            //~ match inner.as_rule() {
-            //~ Rule::varname => var = inner.as_span().as_str().to_string(),
-            //~ Rule::int => val = i64::from_str(inner.as_span().as_str()).unwrap(),
-            //~ _ => unreachable!(),
-            //~ }
-            //~ }
-            //~ rush.shell_vars.set(
-            //~ var,
-            //~ Variable {
-            //~ value: Value::I(val),
-            //~ access: Access::ReadWrite,
-            //~ },
-            //~ );
-            //~ }
-            //~ Rule::hexvarassign => println!("hexvarassign: {}", line.as_span().as_str()),
-            //~ Rule::octvarassign => println!("octvarassign: {}", line.as_span().as_str()),
-            //~ Rule::stringvarassign => {
-            //~ let mut var = String::new();
-            //~ let mut val = String::new();
-            //~ for inner in line.into_inner() {
-            //~ match inner.as_rule() {
-            //~ Rule::varname => var = inner.as_span().as_str().to_string(),
-            //~ // FIXME: add variables management
-            //~ Rule::dquoted => val = inner.as_span().as_str().to_string(),
-            //~ Rule::squoted => val = inner.as_span().as_str().to_string(),
-            //~ // FIXME: add execution management
-            //~ Rule::btquoted => val = inner.as_span().as_str().to_string(),
-            //~ Rule::nonquoted => val = inner.as_span().as_str().to_string(),
-            //~ _ => unreachable!(),
-            //~ }
-            //~ }
-            //~ rush.shell_vars.set(
-            //~ var,
-            //~ Variable {
-            //~ value: Value::S(val),
-            //~ access: Access::ReadWrite,
-            //~ },
-            //~ );
-            //~ }
-            Rule::nop => println!("nop: {}", line.as_span().as_str()),
-
-            //~ Rule::localfloatvarassign => {
-            //~ println!("localfloatvarassign: {}", line.as_span().as_str())
-            //~ }
-            //~ Rule::localbinvarassign => println!("localbinvarassign: {}", line.as_span().as_str()),
-            //~ Rule::localintvarassign => println!("localintvarassign: {}", line.as_span().as_str()),
-            //~ Rule::localhexvarassign => println!("localhexvarassign: {}", line.as_span().as_str()),
-            //~ Rule::localoctvarassign => println!("localoctvarassign: {}", line.as_span().as_str()),
-            //~ Rule::localstringvarassign => {
-            //~ println!("localstringvarassign: {}", line.as_span().as_str())
-            //~ }
-            Rule::alnum => println!("alnum: {}", line.as_span().as_str()),
-            Rule::alph => println!("alph: {}", line.as_span().as_str()),
-            Rule::blank => println!("blank: {}", line.as_span().as_str()),
-            Rule::cntrl => println!("cntrl: {}", line.as_span().as_str()),
-            Rule::digi => println!("digi: {}", line.as_span().as_str()),
-            Rule::graph => println!("graph: {}", line.as_span().as_str()),
-            Rule::lower => println!("lower: {}", line.as_span().as_str()),
-            Rule::prin => println!("prin: {}", line.as_span().as_str()),
-            Rule::space => println!("space: {}", line.as_span().as_str()),
-            Rule::upper => println!("upper: {}", line.as_span().as_str()),
-            Rule::xdigit => println!("xdigit: {}", line.as_span().as_str()),
-            Rule::brea => println!("break: {}", line.as_span().as_str()),
-            Rule::continu => println!("continue: {}", line.as_span().as_str()),
-
-            //Rule::pwd => println!("pwd: {}", line.as_span().as_str()),
-            Rule::exit => println!("exit: {}", line.as_span().as_str()),
-            Rule::tru => println!("true: {}", line.as_span().as_str()),
-            Rule::fals => println!("false: {}", line.as_span().as_str()),
-
-            //Rule::help => println!("help: {}", line.as_span().as_str()),
-            //Rule::bg => println!("bg: {}", line.as_span().as_str()),
-            //Rule::fg => println!("fg: {}", line.as_span().as_str()),
-            Rule::logout => println!("logout: {}", line.as_span().as_str()),
-            Rule::echo => {
-                for inner in line.into_inner() {
-                    match inner.as_rule() {
-                        Rule::echooptne => println!("echooptne: {}", inner.as_span().as_str()),
-                        Rule::echooptnE => println!("echooptnE: {}", inner.as_span().as_str()),
-                        Rule::echoopte => println!("echoopte: {}", inner.as_span().as_str()),
-                        Rule::echooptE => println!("echooptE: {}", inner.as_span().as_str()),
-                        Rule::echooptn => println!("echooptn: {}", inner.as_span().as_str()),
-                        Rule::dquoted => println!("echo dquoted:  {}", inner.as_span().as_str()),
-                        Rule::squoted => println!("echo squoted:  {}", inner.as_span().as_str()),
-                        Rule::btquoted => println!("echo btquoted:  {}", inner.as_span().as_str()),
-                        Rule::nonquoted => {
-                            println!("echo nonquoted:  {}", inner.as_span().as_str());
-                            println!("{:#?}", inner.into_inner());
-                        }
-                        _ => unreachable!(),
-                    }
-                }
-            }
-            _ => unreachable!(), // ident rule is silent and cannot be reached
-        };
+/// Expand a list of words the way a command line does: quotes are honored and expansions split into fields.
+/// Used for compgen -W. Words that cannot be parsed are only split on blanks.
+///
+/// # Examples
+/// ```rust
+/// use rush::RuSh;
+/// use rush::parse::{expand_words, parse};
+///
+/// let mut rush = RuSh::default();
+/// parse(&mut rush, "w='c d'");
+/// assert_eq!(expand_words(&mut rush, "a\\ b $w 'e f'"), vec!["a b", "c", "d", "e f"]);
+/// ```
+pub fn expand_words(rush: &mut RuSh, s: &str) -> Vec<String> {
+    let list = format!("({})", s);
+    match Script::parse(Rule::arraywords, &list) {
+        Ok(mut pairs) if pairs.as_str().len() == list.len() => pairs
+            .next()
+            .unwrap()
+            .into_inner()
+            .flat_map(|word| expand::word(rush, word))
+            .collect(),
+        _ => s.split_whitespace().map(String::from).collect(),
     }
 }
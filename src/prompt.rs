@@ -24,11 +24,17 @@
 //! prompt.rs contains prompt affiliated methods.
 //! prompt is parsed here too.
 
+use crate::expand;
+use crate::parse;
 use crate::rush::RuSh;
+use crate::segments;
 use crate::variables::{Access, Value, Variable};
+use crate::vcs;
 use chrono::*;
 use pest::Parser;
 use pest_derive::Parser;
+use std::ffi::CStr;
+use std::fmt::Write;
 
 /// pest grammar inclusion. dummy const so that .pest file changes are taken care of.
 #[derive(Parser)]
@@ -36,9 +42,12 @@ use pest_derive::Parser;
 struct Script;
 
 /// Public structure Prompt
+#[derive(Clone, Default)]
 pub struct Prompt {
     /// prompt is stored in a String.
     pub prompt: String,
+    /// prompt without its non-printing characters, marked with \[ and \]. The line editor computes its width from it.
+    pub visible: String,
 }
 
 /// Methods for Prompt.
@@ -52,7 +61,7 @@ impl Prompt {
     /// use rush::variables::{Variables, Variable, Value};
     /// let mut r = RuSh::default();
     /// let mut p = Prompt::get(&mut r, "PS2");
-    /// assert_eq!(p.prompt, ">");
+    /// assert_eq!(p.prompt, "> ");
     /// p = Prompt::get(&mut r, "PS3");
     /// assert_eq!(p.prompt, ">");
     /// p = Prompt::get(&mut r, "PS4");
@@ -66,6 +75,7 @@ impl Prompt {
                     Variable {
                         value: Value::S(s),
                         access: Access::ReadWrite,
+                        ..
                     } => s,
                     _ => String::from(""),
                 },
@@ -76,9 +86,10 @@ impl Prompt {
                         Variable {
                             value: Value::S(ps1),
                             access: Access::ReadWrite,
+                            export: false,
                         },
                     );
-                    "[\\u@\\h \\W]$ ".to_string()
+                    "[\\u@\\h \\W]\\$ ".to_string()
                 }
             },
             "PS2" => match rush.shell_vars.get(p) {
@@ -86,19 +97,21 @@ impl Prompt {
                     Variable {
                         value: Value::S(s),
                         access: Access::ReadWrite,
+                        ..
                     } => s,
                     _ => String::from(""),
                 },
                 None => {
-                    let ps2 = ">".to_string();
+                    let ps2 = "> ".to_string();
                     rush.shell_vars.set(
                         String::from("PS2"),
                         Variable {
                             value: Value::S(ps2),
                             access: Access::ReadWrite,
+                            export: false,
                         },
                     );
-                    ">".to_string()
+                    "> ".to_string()
                 }
             },
             "PS3" => match rush.shell_vars.get(p) {
@@ -106,6 +119,7 @@ impl Prompt {
                     Variable {
                         value: Value::S(s),
                         access: Access::ReadWrite,
+                        ..
                     } => s,
                     _ => String::from(""),
                 },
@@ -116,6 +130,7 @@ impl Prompt {
                         Variable {
                             value: Value::S(ps3),
                             access: Access::ReadWrite,
+                            export: false,
                         },
                     );
                     ">".to_string()
@@ -126,6 +141,7 @@ impl Prompt {
                     Variable {
                         value: Value::S(s),
                         access: Access::ReadWrite,
+                        ..
                     } => s,
                     _ => String::from(""),
                 },
@@ -136,89 +152,276 @@ impl Prompt {
                         Variable {
                             value: Value::S(ps4),
                             access: Access::ReadWrite,
+                            export: false,
                         },
                     );
                     ">".to_string()
                 }
             },
+            // RuSh prompts, empty unless set.
+            "RPS1" | "RUSH_TRANSIENT_PROMPT" => match rush.shell_vars.get(p) {
+                Some(var) => var.value.to_string(),
+                None => String::new(),
+            },
             _ => {
                 panic!("prompt env var should not have that value !");
             }
         };
+        // with promptvars, the decoded prompt then goes through parameter expansion, command substitution and
+        // arithmetic expansion. What escapes give is quoted so that it is kept as is.
+        let promptvars = match rush.shopt_options.get("promptvars") {
+            Some(opt) => opt.get(),
+            None => false,
+        };
         // Parse prompt variable value element by element, and evaluate each when needed.
+        // \[ and \] are marked with \x01 and \x02 until expansion is done.
         let pest = Script::parse(Rule::prompt, &ps).unwrap_or_else(|e| panic!("{}", e));
         for element in pest {
-            match element.as_rule() {
-                Rule::normal_prompt => pt.push_str(element.as_span().as_str()),
-                Rule::prompt_date => {
-                    let dt = Local::now();
-                    pt.push_str(&dt.format("%a %b %e").to_string());
-                }
-                Rule::prompt_host => {
-                    let host = &rush.shell_vars.get("HOSTNAME").unwrap().gets();
-                    let pos = host.find('.').unwrap_or_else(|| host.len());
-                    pt.push_str(&host[..pos]);
-                }
-                Rule::prompt_hostname => {
-                    pt.push_str(&rush.shell_vars.get("HOSTNAME").unwrap().gets())
+            let text = match element.as_rule() {
+                Rule::normal_prompt => {
+                    pt.push_str(element.as_str());
+                    continue;
                 }
-                Rule::prompt_jobs => unimplemented!(),
-                Rule::prompt_term_dev_basename => {
-                    pt.push_str(&rush.shell_vars.get("TERM").unwrap().gets())
-                }
-                Rule::prompt_time_s24 => {
-                    let dt = Local::now();
-                    pt.push_str(&dt.format("%H:%M:%S").to_string());
-                }
-                Rule::prompt_time_s12 => {
-                    let dt = Local::now();
-                    pt.push_str(&dt.format("%I:%M:%S").to_string());
+                Rule::prompt_strftime => {
+                    let format = element.into_inner().next().map_or("", |f| f.as_str());
+                    let format = if format.is_empty() { "%X" } else { format };
+                    // an invalid format gives an empty string.
+                    let mut date = String::new();
+                    if write!(date, "{}", Local::now().format(format)).is_err() {
+                        date.clear();
+                    }
+                    date
                 }
-                Rule::prompt_time_12 => {
-                    let dt = Local::now();
-                    pt.push_str(&dt.format("%I:%M:%S%P").to_string());
+                Rule::prompt_date => Local::now().format("%a %b %d").to_string(),
+                Rule::prompt_host => {
+                    let host = rush.shell_vars.get("HOSTNAME").unwrap().value.to_string();
+                    host.split('.').next().unwrap_or("").to_string()
                 }
-                Rule::prompt_username => {
-                    pt.push_str(&rush.shell_vars.get("USERNAME").unwrap().gets())
+                Rule::prompt_hostname => rush.shell_vars.get("HOSTNAME").unwrap().value.to_string(),
+                // there is no job control yet.
+                Rule::prompt_jobs => String::from("0"),
+                Rule::prompt_term_dev_basename => terminal(),
+                Rule::prompt_shellname => {
+                    let name = &rush.positional[0];
+                    name.rsplit('/').next().unwrap_or(name).to_string()
                 }
-                Rule::prompt_version => pt.push_str("0.0.0.0-alpha0-x86_64-redhat-linux-gnu"), // FIXME
-                Rule::prompt_version_patch => pt.push_str("0.0.0.0-alpha0-x86_64-redhat-linux-gnu"), // FIXME
-                Rule::prompt_pwd => pt.push_str(&rush.shell_vars.get("PWD").unwrap().gets()),
+                Rule::prompt_time_s24 => Local::now().format("%H:%M:%S").to_string(),
+                Rule::prompt_time_s12 => Local::now().format("%I:%M:%S").to_string(),
+                Rule::prompt_time_12 => Local::now().format("%I:%M %p").to_string(),
+                Rule::prompt_time_24 => Local::now().format("%H:%M").to_string(),
+                Rule::prompt_username => user_name(rush),
+                Rule::prompt_version => format!("{}.{}", rush.versinfo.0, rush.versinfo.1),
+                Rule::prompt_version_patch => format!(
+                    "{}.{}.{}",
+                    rush.versinfo.0, rush.versinfo.1, rush.versinfo.2
+                ),
+                Rule::prompt_pwd => pwd(rush),
                 Rule::prompt_pwd_basename => {
-                    let path = &rush.shell_vars.get("PWD").unwrap().gets();
-                    let pos = path.rfind('/').unwrap_or(0);
-                    if pos == 0 {
-                        pt.push_str(&path[pos..]);
+                    let path = pwd_tilde(rush);
+                    if path == "/" || path == "~" {
+                        path
                     } else {
-                        pt.push_str(&path[pos + 1..]);
+                        path.rsplit('/').next().unwrap_or("").to_string()
                     }
                 }
-                Rule::prompt_history_command_number => match rush.shell_vars.get("HISTCMD") {
-                    Some(phcn) => pt.push_str(&phcn.geti().to_string()),
-                    None => pt.push_str("0"),
-                },
-                Rule::prompt_command_number => pt.push_str(&rush.cmd_nb.to_string()),
-                Rule::prompt_is_root => match rush.shell_vars.get("UID").unwrap().geti() {
-                    0 => pt.push_str("#"),
-                    _ => pt.push_str("$"),
+                Rule::prompt_vcs => {
+                    let dir = match rush.shell_vars.get("PWD") {
+                        Some(v) => v.value.to_string(),
+                        None => String::new(),
+                    };
+                    // git status of interactive prompts is read in background.
+                    if rush.interactive && p != "PS2" && p != "PS3" && p != "PS4" {
+                        segments::vcs(rush, dir)
+                    } else {
+                        vcs::prompt(&dir)
+                    }
+                }
+                Rule::prompt_history_command_number => rush.history.number().to_string(),
+                Rule::prompt_command_number => (rush.cmd_nb + 1).to_string(),
+                Rule::prompt_is_root => match rush.shell_vars.get("EUID") {
+                    Some(euid) if euid.geti() == 0 => String::from("#"),
+                    _ => String::from("$"),
                 },
-                Rule::prompt_octal => pt.push_str(
-                    &u8::from_str_radix(element.as_span().as_str(), 8)
-                        .unwrap()
-                        .to_string(),
-                ),
-                Rule::prompt_newline => pt.push_str("\n"),
-                Rule::prompt_car_ret => pt.push_str("\r"),
-                // 33 is octal value of ascii espace character
-                Rule::prompt_esc => pt.push_str(&u8::from_str_radix("33", 8).unwrap().to_string()),
-                // 7 is octal value of ascii bell character
-                Rule::prompt_bell => pt.push_str(&u8::from_str_radix("7", 8).unwrap().to_string()),
-                Rule::prompt_backslash2 => pt.push_str("\\"),
-                Rule::prompt_non_print => unimplemented!(),
-                Rule::prompt_end_non_print => unimplemented!(),
-                _ => panic!(),
+                Rule::prompt_octal => {
+                    let code = u32::from_str_radix(element.as_str(), 8).unwrap();
+                    std::char::from_u32(code).unwrap_or('\0').to_string()
+                }
+                Rule::prompt_newline => String::from("\n"),
+                Rule::prompt_car_ret => String::from("\r"),
+                Rule::prompt_esc => String::from("\x1b"),
+                Rule::prompt_bell => String::from("\x07"),
+                Rule::prompt_backslash2 => String::from("\\"),
+                Rule::prompt_non_print => String::from("\x01"),
+                Rule::prompt_end_non_print => String::from("\x02"),
+                Rule::prompt_unknown => format!("\\{}", element.as_str()),
+                Rule::prompt_lone_backslash => String::from("\\"),
+                _ => unreachable!(),
             };
+            if promptvars {
+                for c in text.chars() {
+                    if let '$' | '`' | '"' | '\\' = c {
+                        pt.push('\\');
+                    }
+                    pt.push(c);
+                }
+            } else {
+                pt.push_str(&text);
+            }
+        }
+        if promptvars {
+            // command substitutions in the prompt do not change $?.
+            // command substitutions of interactive prompts are run in background.
+            rush.segments.active = rush.interactive && p != "PS2" && p != "PS3" && p != "PS4";
+            let status = rush.last_status;
+            pt = parse::expand_string(rush, &pt);
+            rush.last_status = status;
+            rush.segments.active = false;
+        }
+        let mut prompt = String::new();
+        let mut visible = String::new();
+        // inside a \[ \] sequence of non-printing characters ?
+        let mut non_print = false;
+        for c in pt.chars() {
+            match c {
+                '\x01' => non_print = true,
+                '\x02' => non_print = false,
+                _ => {
+                    prompt.push(c);
+                    if !non_print {
+                        visible.push(c);
+                    }
+                }
+            }
+        }
+        Prompt { prompt, visible }
+    }
+}
+
+/// Run PROMPT_COMMAND before PS1 is displayed: the variable value, or each element of the array in turn.
+/// `$?` is kept as the last command left it.
+///
+/// # Examples
+/// ```rust
+/// use rush::RuSh;
+/// use rush::parse::parse;
+/// use rush::prompt;
+///
+/// let mut rush = RuSh::default();
+/// parse(&mut rush, "PROMPT_COMMAND='n=$((n + 1))'");
+/// parse(&mut rush, "false");
+/// prompt::command(&mut rush);
+/// prompt::command(&mut rush);
+/// assert_eq!(rush.shell_vars.get("n").unwrap().value.to_string(), "2");
+/// assert_eq!(rush.last_status, 1);
+/// ```
+pub fn command(rush: &mut RuSh) {
+    let commands = if rush
+        .shell_array_vars
+        .arrayvars
+        .contains_key("PROMPT_COMMAND")
+    {
+        expand::array_values(rush, "PROMPT_COMMAND")
+    } else {
+        match rush.shell_vars.get("PROMPT_COMMAND") {
+            Some(var) => vec![var.value.to_string()],
+            None => return,
+        }
+    };
+    let status = rush.last_status;
+    for command in commands.iter().filter(|c| !c.trim().is_empty()) {
+        parse::parse(rush, command);
+        if rush.exit.is_some() {
+            return;
+        }
+    }
+    rush.last_status = status;
+}
+
+/// Name of the user the shell runs as, USERNAME if there is none in password database.
+fn user_name(rush: &RuSh) -> String {
+    unsafe {
+        let pw = libc::getpwuid(libc::getuid());
+        if !pw.is_null() {
+            if let Ok(name) = CStr::from_ptr((*pw).pw_name).to_str() {
+                return name.to_string();
+            }
+        }
+    }
+    match rush.shell_vars.get("USERNAME") {
+        Some(v) => v.value.to_string(),
+        None => String::new(),
+    }
+}
+
+/// Basename of the terminal device standard input is, `tty` if it is none.
+fn terminal() -> String {
+    let name = unsafe { libc::ttyname(0) };
+    if name.is_null() {
+        return String::from("tty");
+    }
+    let name = unsafe { CStr::from_ptr(name) }
+        .to_string_lossy()
+        .into_owned();
+    name.rsplit('/').next().unwrap_or("").to_string()
+}
+
+/// Current directory, HOME being replaced with `~`.
+fn pwd_tilde(rush: &RuSh) -> String {
+    let pwd = match rush.shell_vars.get("PWD") {
+        Some(v) => v.value.to_string(),
+        None => String::new(),
+    };
+    if let Some(home) = rush.shell_vars.get("HOME") {
+        let home = home.value.to_string();
+        let home = home.trim_end_matches('/');
+        if !home.is_empty() {
+            if pwd == home {
+                return String::from("~");
+            }
+            if let Some(rest) = pwd.strip_prefix(home) {
+                if rest.starts_with('/') {
+                    return format!("~{}", rest);
+                }
+            }
+        }
+    }
+    pwd
+}
+
+/// Current directory for \w: HOME is replaced with `~`, and only PROMPT_DIRTRIM trailing directories are kept,
+/// leading ones being replaced with `...` when it makes it shorter.
+fn pwd(rush: &RuSh) -> String {
+    let path = pwd_tilde(rush);
+    let trim = match rush.shell_vars.get("PROMPT_DIRTRIM") {
+        Some(v) => v.value.to_string().parse::<usize>().unwrap_or(0),
+        None => 0,
+    };
+    if trim == 0 {
+        return path;
+    }
+    // ~ stays in front of the trimmed path.
+    let start = if path.starts_with('~') {
+        match path.find('/') {
+            Some(slash) => slash + 1,
+            None => return path,
+        }
+    } else {
+        0
+    };
+    let dirs = path[start..].matches('/').count();
+    if dirs < trim {
+        return path;
+    }
+    // position of the trim-th separator from the end.
+    let mut count = 0;
+    let tail = path[start..].rfind(|c| {
+        if c == '/' {
+            count += 1;
         }
-        Prompt { prompt: pt }
+        count == trim
+    });
+    match tail {
+        Some(tail) if tail > 3 => format!("{}...{}", &path[..start], &path[start + tail..]),
+        _ => path,
     }
 }
@@ -25,11 +25,17 @@
 //! default method is implemented here (called when RuSh is launched).
 
 pub use crate::arrays::Array;
+pub use crate::completion::CompSpec;
+pub use crate::history::History;
+pub use crate::inputrc::Inputrc;
 pub use crate::opt::Opt;
 pub use crate::prompt::Prompt;
-pub use crate::variables::Variables;
+pub use crate::segments::Segments;
+pub use crate::variables::{Value, Variables};
 #[allow(unused_imports)]
 use pest_derive::Parser;
+use std::collections::HashMap;
+use std::env;
 /// pest grammar inclusion. dummy const so that .pest file changes are taken care of.
 #[derive(Parser)]
 #[grammar = "rush.pest"]
@@ -46,22 +52,67 @@ pub struct RuSh {
     pub shell_vars: Variables,
     /// shell_array_vars: RUSH_VERSINFO, RUSH_ALIASES and other shell variables defined as array
     pub shell_array_vars: Array,
-    /// Command history. Stored as History from rustyline
-    pub history: rustyline::history::History,
+    /// Command history list.
+    pub history: History,
     /// line case, needed for prompt management
     pub line_case: u8,
     /// command number, may be needed by prompt
     pub cmd_nb: u64,
-    /// prompt contents. Stored as Prompt { prompt: String }
+    /// prompt contents. Stored as Prompt { prompt: String, visible: String }
     pub prompt: Prompt,
+    /// right prompt contents, from RPS1, shown along with PS1.
+    pub rprompt: Prompt,
     /// information about RuSh version: major minor patch build release MACHTYPE
     pub versinfo: (u8, u8, u8, u8, String, String),
+    /// exit status of the last executed command, $?
+    pub last_status: i32,
+    /// positional parameters, $0 being stored at index 0.
+    pub positional: Vec<String>,
+    /// execution nesting level (eval, sourced files, command substitution…). xtrace repeats PS4 first character accordingly.
+    pub nesting: usize,
+    /// is the shell interactive ?
+    pub interactive: bool,
+    /// set when the shell has to exit (errexit, expansion error in a non interactive shell…), with given status.
+    pub exit: Option<i32>,
+    /// set when execution of current input has to be aborted (expansion error in an interactive shell).
+    pub abort: bool,
+    /// errexit is ignored when greater than 0 (if/while/until conditions, && and || lists, negated pipelines).
+    pub errexit_ignored: usize,
+    /// shell invocation time, in seconds since epoch.
+    pub start_time: i64,
+    /// line number, in its source, of the first line of the input being executed. LINENO is computed from it.
+    pub lineno: i64,
+    /// stack of sourced files (script included) with line number they were sourced from, innermost last.
+    /// RUSH_SOURCE and RUSH_LINENO arrays reflect it, innermost first.
+    pub sources: Vec<(String, i64)>,
+    /// getopts state: OPTIND value it last set, position of next option letter in current argument (0 if none),
+    /// and that argument.
+    pub getopts: (i64, usize, String),
+    /// shell functions: body text, and line number it starts at in its source.
+    pub functions: HashMap<String, (String, i64)>,
+    /// functions being executed, innermost last. FUNCNAME array reflects it, innermost first.
+    pub funcname: Vec<String>,
+    /// number of functions and sourced files being executed, which return builtin can leave.
+    pub returnable: usize,
+    /// set by return builtin, with given status, until the function or sourced file is left.
+    pub returning: Option<i32>,
+    /// completion specifications defined by complete builtin, by command name.
+    pub completions: HashMap<String, CompSpec>,
+    /// specification of the completion being generated, that compopt changes when given no name.
+    pub completion: Option<CompSpec>,
+    /// line editor settings and key bindings, from inputrc files and bind builtin.
+    pub inputrc: Inputrc,
+    /// prompt segments computed in background.
+    pub segments: Segments,
+    /// typed values of the arguments of the builtin being run.
+    pub values: Vec<Value>,
 }
 
 /// Default method for RuSh
 impl Default for RuSh {
+    #[allow(unused_doc_comments)]
     fn default() -> RuSh {
-        RuSh {
+        let mut rush = RuSh {
             /// 46 shopt options by default, so let’s have a big enough HashMap to store these.
             shopt_options: Opt::init_shopt_options(),
             /// 27 set options by default, so let’s have a big enough HashMap to store these.
@@ -70,10 +121,8 @@ impl Default for RuSh {
             shell_vars: Variables::init_shell_vars(),
             /// initialize array variables.
             shell_array_vars: Array::init_shell_array_vars(),
-            // TODO set history size
-            // rl.set_history_max_len(1000);
-            /// Manage commands history with rustyline crate.
-            history: rustyline::history::History::new(),
+            /// History is kept by interactive shells, and sized by HISTSIZE.
+            history: History::new(),
             /// Variable line_case allows to know which PS[1234] variable to use to display prompt.
             line_case: 1,
             /// Command number in this session. Can be used in prompt.
@@ -81,7 +130,9 @@ impl Default for RuSh {
             /// Variable prompt contains interpreted definition of PS[1234].
             prompt: Prompt {
                 prompt: String::from(""),
+                visible: String::from(""),
             },
+            rprompt: Prompt::default(),
             /// actual RuSh version informations
             versinfo: (
                 0,
@@ -91,6 +142,75 @@ impl Default for RuSh {
                 "alpha0".to_string(),
                 "x86_64-redhat-linux-gnu".to_string(),
             ), //FIXME MACHTYPE
+            /// Nothing has been executed yet.
+            last_status: 0,
+            /// $0 is the shell name, there are no positional parameters by default.
+            positional: vec!["rush".to_string()],
+            /// Commands are run at top level.
+            nesting: 0,
+            /// main decides whether the shell is interactive.
+            interactive: false,
+            /// Nothing asked the shell to exit or to abort execution yet.
+            exit: None,
+            abort: false,
+            errexit_ignored: 0,
+            start_time: chrono::Local::now().timestamp(),
+            /// Input starts at line 1, no file is being sourced.
+            lineno: 1,
+            sources: Vec::new(),
+            /// getopts starts with first argument.
+            getopts: (1, 0, String::new()),
+            /// No function is defined or being executed.
+            functions: HashMap::new(),
+            funcname: Vec::new(),
+            returnable: 0,
+            returning: None,
+            /// No completion is defined or being generated.
+            completions: HashMap::new(),
+            completion: None,
+            /// Line editor defaults, until inputrc is read.
+            inputrc: Inputrc::default(),
+            /// No prompt segment has been computed yet.
+            segments: Segments::default(),
+            /// No builtin is being run.
+            values: Vec::new(),
+        };
+        // options listed in SHELLOPTS and RUSHOPTS environment variables are enabled upon startup.
+        if let Ok(opts) = env::var("SHELLOPTS") {
+            for name in opts.split(':') {
+                rush.set_options.update(name, true);
+            }
         }
+        if let Ok(opts) = env::var("RUSHOPTS") {
+            for name in opts.split(':') {
+                rush.shopt_options.update(name, true);
+            }
+        }
+        rush.update_opts_vars();
+        rush
+    }
+}
+
+/// Methods for RuSh.
+impl RuSh {
+    /// Update SHELLOPTS and RUSHOPTS readonly variables, to be called each time set or shopt options change.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use rush::RuSh;
+    /// let mut r = RuSh::default();
+    /// r.set_options.update("xtrace", true);
+    /// r.update_opts_vars();
+    /// assert!(r.shell_vars.get("SHELLOPTS").unwrap().gets().contains("xtrace"));
+    /// ```
+    pub fn update_opts_vars(&mut self) {
+        self.shell_vars.set_readonly(
+            String::from("SHELLOPTS"),
+            Value::S(self.set_options.enabled()),
+        );
+        self.shell_vars.set_readonly(
+            String::from("RUSHOPTS"),
+            Value::S(self.shopt_options.enabled()),
+        );
     }
 }
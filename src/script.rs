@@ -0,0 +1,350 @@
+//
+// script.rs
+//
+// Copyright 2015-2019 Laurent Wandrebeck <l.wandrebeck@quelquesmots.fr>
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston,
+// MA 02110-1301, USA.
+//
+
+//! RuSh non interactive input is executed here.
+//!
+//! script.rs runs script files, `-c` command strings, standard input when it is not a terminal,
+//! files read by the source (.) builtin, and startup files.
+//! Input is executed one complete command at a time, so that a syntax error only stops execution where it is found,
+//! and so that commands can read standard input following them.
+
+use crate::alias;
+use crate::arrays::Index;
+use crate::builtins::{self, Input};
+use crate::execute;
+use crate::parse;
+use crate::rush::RuSh;
+use crate::variables::{Access, Value, Variable};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+/// Execute lines given by `next_line` one complete command at a time, until there are no more lines
+/// or the shell has to stop. Returns false if a syntax error was found.
+pub fn run(rush: &mut RuSh, next_line: &mut dyn FnMut() -> Option<String>) -> bool {
+    let mut input = String::new();
+    // line number of input first line, and number of lines it is made of.
+    let mut first = 1;
+    let mut count = 0;
+    loop {
+        let line = next_line();
+        let eof = line.is_none();
+        if let Some(line) = line {
+            input.push_str(&line);
+            count += 1;
+            // an alias may open a compound command that next lines complete.
+            if parse::incomplete(&alias::expand(rush, &input)) {
+                continue;
+            }
+        }
+        if !input.is_empty() {
+            rush.lineno = first;
+            if !parse::parse(rush, &input) {
+                return false;
+            }
+            input.clear();
+            first += count;
+            count = 0;
+        }
+        if eof || execute::stopped(rush) {
+            return true;
+        }
+    }
+}
+
+/// Execute a string, `-c` command string or sourced file contents.
+fn run_str(rush: &mut RuSh, input: &str) -> bool {
+    let mut lines = input.split_inclusive('\n');
+    run(rush, &mut || lines.next().map(String::from))
+}
+
+/// Execute a `-c` command string, which is available as RUSH_EXECUTION_STRING. Returns exit status.
+///
+/// # Examples
+/// ```rust
+/// use rush::RuSh;
+/// use rush::script;
+///
+/// let mut rush = RuSh::default();
+/// assert_eq!(script::string(&mut rush, "set -- a b\nfalse"), 1);
+/// assert_eq!(rush.positional, vec!["rush", "a", "b"]);
+/// assert_eq!(script::string(&mut rush, "if true"), 2);
+/// ```
+pub fn string(rush: &mut RuSh, command: &str) -> i32 {
+    rush.shell_vars.set(
+        String::from("RUSH_EXECUTION_STRING"),
+        Variable {
+            value: Value::S(command.to_string()),
+            access: Access::ReadWrite,
+            export: false,
+        },
+    );
+    if !run_str(rush, command) {
+        return 2;
+    }
+    rush.exit.unwrap_or(rush.last_status)
+}
+
+/// Execute a script file, with given positional parameters, $0 being the script name. Returns exit status.
+pub fn file(rush: &mut RuSh, path: &str, args: &[String]) -> i32 {
+    let contents = match fs::read(path) {
+        Ok(c) => String::from_utf8_lossy(&c).into_owned(),
+        Err(e) => {
+            eprintln!("rush: {}: {}", path, execute::os_error(&e));
+            return if e.kind() == std::io::ErrorKind::NotFound {
+                127
+            } else {
+                126
+            };
+        }
+    };
+    rush.positional = Some(path.to_string())
+        .into_iter()
+        .chain(args.iter().cloned())
+        .collect();
+    push_source(rush, path, 0);
+    if !run_str(rush, &contents) {
+        return 2;
+    }
+    rush.exit.unwrap_or(rush.last_status)
+}
+
+/// Execute commands read from standard input, which is not a terminal. Lines are read a byte at a time,
+/// so that commands reading standard input get what follows them. Returns exit status.
+pub fn stdin(rush: &mut RuSh) -> i32 {
+    let mut next_line = || {
+        let mut line = Vec::new();
+        while let Input::Byte(b) = builtins::read_byte(0, None) {
+            line.push(b);
+            if b == b'\n' {
+                break;
+            }
+        }
+        if line.is_empty() {
+            None
+        } else {
+            Some(String::from_utf8_lossy(&line).into_owned())
+        }
+    };
+    if !run(rush, &mut next_line) {
+        return 2;
+    }
+    rush.exit.unwrap_or(rush.last_status)
+}
+
+/// Find a file to source: names without a slash are looked for in PATH when sourcepath option is set,
+/// then in current directory.
+///
+/// # Examples
+/// ```rust
+/// use rush::RuSh;
+/// use rush::script;
+///
+/// let mut rush = RuSh::default();
+/// assert_eq!(script::find_source(&rush, "/etc/passwd"), "/etc/passwd");
+/// assert_eq!(script::find_source(&rush, "doesnotexist"), "doesnotexist");
+/// ```
+pub fn find_source(rush: &RuSh, name: &str) -> String {
+    let sourcepath = match rush.shopt_options.get("sourcepath") {
+        Some(opt) => opt.get(),
+        None => false,
+    };
+    if name.contains('/') || !sourcepath {
+        return name.to_string();
+    }
+    let path = match rush.shell_vars.get("PATH") {
+        Some(p) => p.value.to_string(),
+        None => String::new(),
+    };
+    for dir in path.split(':').filter(|d| !d.is_empty()) {
+        let candidate = Path::new(dir).join(name);
+        if let Ok(meta) = fs::metadata(&candidate) {
+            if meta.is_file() && meta.permissions().mode() & 0o444 != 0 {
+                if let Some(c) = candidate.to_str() {
+                    return c.to_string();
+                }
+            }
+        }
+    }
+    name.to_string()
+}
+
+/// Execute a file in the current shell, for the source (.) builtin. Positional parameters are replaced
+/// during its execution if arguments are given. Returns exit status of the last command executed.
+pub fn source(rush: &mut RuSh, path: &str, args: &[String]) -> i32 {
+    let contents = match fs::read(path) {
+        Ok(c) => String::from_utf8_lossy(&c).into_owned(),
+        Err(e) => {
+            eprintln!("rush: {}: {}", path, execute::os_error(&e));
+            return 1;
+        }
+    };
+    let saved = if args.is_empty() {
+        None
+    } else {
+        let mut positional = vec![rush.positional[0].clone()];
+        positional.extend_from_slice(args);
+        Some(std::mem::replace(&mut rush.positional, positional))
+    };
+    // RUSH_LINENO gets the line the file is sourced from.
+    let line = match rush.shell_vars.get("LINENO") {
+        Some(v) => v.value.to_string().parse::<i64>().unwrap_or(0),
+        None => 0,
+    };
+    let lineno = rush.lineno;
+    push_source(rush, path, line);
+    rush.nesting += 1;
+    rush.returnable += 1;
+    // a file without any command gives back 0.
+    let empty = contents.lines().all(|l| {
+        let l = l.trim();
+        l.is_empty() || l.starts_with('#')
+    });
+    let status = if !run_str(rush, &contents) {
+        2
+    } else if empty {
+        0
+    } else {
+        rush.last_status
+    };
+    let status = rush.returning.take().unwrap_or(status);
+    rush.returnable -= 1;
+    rush.nesting -= 1;
+    rush.sources.pop();
+    update_source_vars(rush);
+    rush.lineno = lineno;
+    if let Some(positional) = saved {
+        rush.positional = positional;
+    }
+    status
+}
+
+/// Source a startup file if it exists. Returns true if it does.
+fn startup_file(rush: &mut RuSh, path: &str) -> bool {
+    if !Path::new(path).is_file() {
+        return false;
+    }
+    source(rush, path, &[]);
+    true
+}
+
+/// Path of a file in home directory, None if HOME is not set.
+fn home_file(rush: &RuSh, name: &str) -> Option<String> {
+    rush.shell_vars
+        .get("HOME")
+        .map(|home| format!("{}/{}", home.value.to_string().trim_end_matches('/'), name))
+}
+
+/// System wide login shell startup file.
+pub const SYSTEM_PROFILE: &str = "/etc/profile";
+
+/// Login shell startup files: `system`, SYSTEM_PROFILE for a login shell, then the first existing of
+/// ~/.rush_profile and ~/.profile. In posix mode, ~/.profile only is looked for.
+pub fn profile(rush: &mut RuSh, system: &str) {
+    startup_file(rush, system);
+    let posix = match rush.set_options.get("posix") {
+        Some(opt) => opt.get(),
+        None => false,
+    };
+    let names: &[&str] = if posix {
+        &[".profile"]
+    } else {
+        &[".rush_profile", ".profile"]
+    };
+    for name in names {
+        if let Some(path) = home_file(rush, name) {
+            if startup_file(rush, &path) {
+                break;
+            }
+        }
+    }
+}
+
+/// Interactive shell startup file: `rcfile` if given, ~/.rushrc otherwise.
+/// In posix mode, the file named by expanding ENV is sourced instead.
+///
+/// # Examples
+/// ```rust
+/// use rush::RuSh;
+/// use rush::parse::parse;
+/// use rush::script;
+///
+/// let path = std::env::temp_dir().join(format!("rush_rc_doc_{}", std::process::id()));
+/// std::fs::write(&path, "fromrc=1\n").unwrap();
+/// let mut rush = RuSh::default();
+/// parse(&mut rush, &format!("set -o posix; ENV={}", path.display()));
+/// script::rc(&mut rush, None);
+/// assert_eq!(rush.shell_vars.get("fromrc").unwrap().geti(), 1);
+/// std::fs::remove_file(&path).unwrap();
+/// ```
+pub fn rc(rush: &mut RuSh, rcfile: Option<&str>) {
+    let posix = match rush.set_options.get("posix") {
+        Some(opt) => opt.get(),
+        None => false,
+    };
+    let path = if posix {
+        match rush.shell_vars.get("ENV") {
+            Some(env) => Some(parse::expand_string(rush, &env.value.to_string())),
+            None => None,
+        }
+    } else {
+        match rcfile {
+            Some(f) => Some(f.to_string()),
+            None => home_file(rush, ".rushrc"),
+        }
+    };
+    if let Some(path) = path {
+        if !path.is_empty() {
+            startup_file(rush, &path);
+        }
+    }
+}
+
+/// Source ~/.rush_logout when a login shell exits.
+pub fn logout(rush: &mut RuSh) {
+    let login = match rush.shopt_options.get("login_shell") {
+        Some(opt) => opt.get(),
+        None => false,
+    };
+    if login {
+        if let Some(path) = home_file(rush, ".rush_logout") {
+            startup_file(rush, &path);
+        }
+    }
+}
+
+/// Push a file on the sources stack.
+fn push_source(rush: &mut RuSh, path: &str, line: i64) {
+    rush.sources.push((path.to_string(), line));
+    update_source_vars(rush);
+}
+
+/// Update RUSH_SOURCE and RUSH_LINENO arrays from the sources stack, innermost file first.
+fn update_source_vars(rush: &mut RuSh) {
+    rush.shell_array_vars.clear("RUSH_SOURCE");
+    rush.shell_array_vars.clear("RUSH_LINENO");
+    for (i, (path, line)) in rush.sources.iter().rev().enumerate() {
+        rush.shell_array_vars
+            .set("RUSH_SOURCE", Index::I(i), Value::S(path.clone()));
+        rush.shell_array_vars
+            .set("RUSH_LINENO", Index::I(i), Value::I(*line));
+    }
+}
@@ -0,0 +1,210 @@
+//
+// segments.rs
+//
+// Copyright 2015-2019 Laurent Wandrebeck <l.wandrebeck@quelquesmots.fr>
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston,
+// MA 02110-1301, USA.
+//
+
+//! RuSh prompt segments computed in background.
+//!
+//! segments.rs runs the expensive parts of interactive prompts (command substitutions, VCS status…) on threads of
+//! their own, so that displaying a prompt never waits longer than RUSH_PROMPT_TIMEOUT milliseconds. A segment which
+//! is not ready in time keeps the value it had at the previous prompt, and is updated once its job is done.
+
+use crate::execute;
+use crate::rush::RuSh;
+use crate::vcs;
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Default time a prompt waits for its segments, in milliseconds.
+pub const DEFAULT_TIMEOUT: u64 = 100;
+
+/// Number of segment values kept: the least recently displayed ones are dropped beyond.
+pub const MAX_VALUES: usize = 64;
+
+/// A job computing the value of a segment.
+pub type Job = Box<dyn FnOnce() -> String + Send>;
+
+/// Segments values, and jobs computing them, each on a thread of its own.
+#[derive(Default)]
+pub struct Segments {
+    /// set while an interactive prompt is expanded: command substitutions then run in background.
+    pub active: bool,
+    /// sender given to job threads, None until a job is submitted.
+    done: Option<Sender<(String, String)>>,
+    /// values computed by job threads.
+    results: Option<Receiver<(String, String)>>,
+    /// segments whose job is not done yet.
+    pending: HashSet<String>,
+    /// last known value of each segment, with the time it was last asked for.
+    values: HashMap<String, (String, u64)>,
+    /// parts of git statuses only read again once the files they depend on changed, by segment.
+    walked: Arc<Mutex<HashMap<String, vcs::Walked>>>,
+    /// incremented each time a value is asked for.
+    clock: u64,
+}
+
+impl Segments {
+    /// Is the job computing segment `key` not done yet ?
+    pub fn pending(&self, key: &str) -> bool {
+        self.pending.contains(key)
+    }
+
+    /// Have `job` compute segment `key` on a thread of its own. Nothing is done while a previous job for `key`
+    /// is still running, so that a slow segment never has more than one thread.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use rush::segments::Segments;
+    /// use std::time::Duration;
+    ///
+    /// let mut segments = Segments::default();
+    /// segments.submit("answer", Box::new(|| String::from("42")));
+    /// assert_eq!(segments.get("answer", Duration::from_secs(5)), "42");
+    /// assert!(!segments.pending("answer"));
+    /// ```
+    pub fn submit(&mut self, key: &str, job: Job) {
+        if self.pending(key) {
+            return;
+        }
+        if self.done.is_none() {
+            let (done, results) = channel();
+            self.done = Some(done);
+            self.results = Some(results);
+        }
+        if let Some(done) = self.done.clone() {
+            let name = key.to_string();
+            let spawned = thread::Builder::new().spawn(move || {
+                let _ = done.send((name, job()));
+            });
+            if spawned.is_ok() {
+                self.pending.insert(key.to_string());
+            }
+        }
+    }
+
+    /// Value of segment `key`, its job being waited for `timeout` at most. Value it had before if the job
+    /// is not done in time, empty if it never had any.
+    pub fn get(&mut self, key: &str, timeout: Duration) -> String {
+        let deadline = Instant::now() + timeout;
+        self.clock += 1;
+        loop {
+            // results of other segments are stored along the way, without waiting.
+            let left = if self.pending.contains(key) {
+                deadline.saturating_duration_since(Instant::now())
+            } else {
+                Duration::from_secs(0)
+            };
+            match self
+                .results
+                .as_ref()
+                .map(|results| results.recv_timeout(left))
+            {
+                Some(Ok((done, value))) => {
+                    self.pending.remove(&done);
+                    self.values.insert(done, (value, self.clock));
+                }
+                _ => break,
+            }
+        }
+        let value = match self.values.get_mut(key) {
+            Some((value, used)) => {
+                *used = self.clock;
+                value.clone()
+            }
+            None => String::new(),
+        };
+        self.evict();
+        value
+    }
+
+    /// Drop least recently asked for values beyond MAX_VALUES.
+    fn evict(&mut self) {
+        while self.values.len() > MAX_VALUES {
+            let oldest = self
+                .values
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(key, _)| key.clone());
+            match oldest {
+                Some(key) => {
+                    self.values.remove(&key);
+                    if let Ok(mut walked) = self.walked.lock() {
+                        walked.remove(&key);
+                    }
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+/// Time a prompt waits for its segments: RUSH_PROMPT_TIMEOUT milliseconds, DEFAULT_TIMEOUT if it is not a number.
+pub fn timeout(rush: &RuSh) -> Duration {
+    let ms = match rush.shell_vars.get("RUSH_PROMPT_TIMEOUT") {
+        Some(var) => var.value.to_string().parse().unwrap_or(DEFAULT_TIMEOUT),
+        None => DEFAULT_TIMEOUT,
+    };
+    Duration::from_millis(ms)
+}
+
+/// Command substitution within an interactive prompt: the command is started at once, and its output collected
+/// by a thread. It is not started again while previous run is not done.
+pub fn substitute(rush: &mut RuSh, input: &str) -> String {
+    let key = format!("$({})", input);
+    if !rush.segments.pending(&key) {
+        if let Some((fd, pid)) = execute::spawn_substitution(rush, input) {
+            rush.segments.submit(
+                &key,
+                Box::new(move || execute::collect_substitution(fd, pid).0),
+            );
+        }
+    }
+    let timeout = timeout(rush);
+    rush.segments.get(&key, timeout)
+}
+
+/// git status of directory `dir` within an interactive prompt, read by a thread. Values are kept per
+/// directory, so that changing directory never displays the status of another repository. Worktree files are
+/// looked at each time, objects are only walked again once index or refs changed.
+pub fn vcs(rush: &mut RuSh, dir: String) -> String {
+    let key = format!("\\g {}", dir);
+    if !rush.segments.pending(&key) {
+        let walked = Arc::clone(&rush.segments.walked);
+        let name = key.clone();
+        rush.segments.submit(
+            &key,
+            Box::new(move || {
+                let mut previous = match walked.lock() {
+                    Ok(mut walked) => walked.remove(&name).unwrap_or_default(),
+                    Err(_) => vcs::Walked::default(),
+                };
+                let prompt = vcs::prompt_since(&dir, &mut previous);
+                if let Ok(mut walked) = walked.lock() {
+                    walked.insert(name, previous);
+                }
+                prompt
+            }),
+        );
+    }
+    let timeout = timeout(rush);
+    rush.segments.get(&key, timeout)
+}
@@ -25,12 +25,13 @@
 //! `Variable` and `Variables` are defined here.
 //! variables (un)setting, update methods for classical variables.
 
+use crate::opt::Opt;
 use libc::{c_char, c_int, geteuid, getgid, getlogin, getpid, getppid, getuid, size_t};
 use rand::Rng;
 use std::collections::hash_map::Entry::{Occupied, Vacant};
 use std::collections::HashMap;
-use std::ffi::CStr;
-use std::{env, str};
+use std::ffi::{CStr, CString};
+use std::{env, fmt, str};
 
 /// Access can be ReadWrite or ReadOnly
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
@@ -48,6 +49,17 @@ pub enum Value {
     S(String),
 }
 
+/// Display `Value` the way it is expanded by the shell.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::I(i) => write!(f, "{}", i),
+            Value::F(fl) => write!(f, "{}", fl),
+            Value::S(s) => write!(f, "{}", s),
+        }
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Clone, Debug, PartialEq)]
 /// Variable Structure.
@@ -56,6 +68,8 @@ pub struct Variable {
     pub value: Value,
     /// Is the variable rw or ro.
     pub access: Access,
+    /// Is the variable exported to the environment of child processes.
+    pub export: bool,
 }
 
 /// Methods for Variable structure.
@@ -65,7 +79,7 @@ impl Variable {
     /// # Examples
     /// ```rust
     /// use rush::variables::{Access, Variable, Value};
-    /// let var = Variable { value: Value::I(-42), access: Access::ReadWrite };
+    /// let var = Variable { value: Value::I(-42), access: Access::ReadWrite, export: false };
     /// assert_eq!(var.geti(), -42);
     /// ```
     pub fn geti(&self) -> i64 {
@@ -80,7 +94,7 @@ impl Variable {
     /// # Examples
     /// ```rust
     /// use rush::variables::{Access, Variable, Value};
-    /// let var = Variable { value: Value::F(-42.5), access: Access::ReadWrite };
+    /// let var = Variable { value: Value::F(-42.5), access: Access::ReadWrite, export: false };
     /// assert_eq!(var.getf(), -42.5);
     /// ```
     pub fn getf(&self) -> f64 {
@@ -95,7 +109,7 @@ impl Variable {
     /// # Examples
     /// ```rust
     /// use rush::variables::{Access, Variable, Value};
-    /// let var = Variable { value: Value::S("Forty two".to_string()), access: Access::ReadWrite };
+    /// let var = Variable { value: Value::S("Forty two".to_string()), access: Access::ReadWrite, export: false };
     /// assert_eq!(var.gets(), "Forty two");
     /// ```
     pub fn gets(&self) -> String {
@@ -126,10 +140,10 @@ impl Variables {
     ///     None => panic!("RUSH_COMMAND should be defined.")
     /// }
     /// match vars.get("HISTSIZE") {
-    ///     Some(v) => assert_eq!(v.geti(), 1000),
+    ///     Some(v) => assert_eq!(v.geti(), 500),
     ///     None => panic!("HISTSIZE should be defined.")
     /// }
-    /// vars.set(String::from("TEST"), Variable { value: Value::F(-49.3), access: Access::ReadWrite });
+    /// vars.set(String::from("TEST"), Variable { value: Value::F(-49.3), access: Access::ReadWrite, export: false });
     /// match vars.get("TEST") {
     ///     Some(v) => assert_eq!(v.getf(), -49.3),
     ///     None => panic!("TEST variable should be defined.")
@@ -141,6 +155,7 @@ impl Variables {
                 let var = Variable {
                     value: val.value.clone(),
                     access: val.access.clone(),
+                    export: val.export,
                 };
                 Some(var)
             }
@@ -148,6 +163,26 @@ impl Variables {
         }
     }
 
+    /// Check that a string is a valid variable name: a letter or underscore, then letters, digits or underscores.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use rush::variables::Variables;
+    ///
+    /// assert!(Variables::is_name("_RUSH_42"));
+    /// assert!(!Variables::is_name("42"));
+    /// assert!(!Variables::is_name("BASH_FUNC_ls%%"));
+    /// ```
+    pub fn is_name(name: &str) -> bool {
+        let mut chars = name.chars();
+        match chars.next() {
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+            }
+            _ => false,
+        }
+    }
+
     /// Get Access from Variable
     ///
     /// # Examples
@@ -168,6 +203,7 @@ impl Variables {
     ///     None => assert!(true),
     /// }
     /// ```
+    #[allow(clippy::manual_map)]
     pub fn get_access(&self, key: &str) -> Option<Access> {
         match self.vars.get(key) {
             Some(val) => Some(val.access.clone()),
@@ -176,23 +212,24 @@ impl Variables {
     }
 
     /// Set a variable value for a given name. Variable is created if needed, otherwise value is updated if rw.
+    /// An exported variable stays exported when its value is updated.
     ///
     /// # Examples
     /// ```rust
     /// use rush::variables::{Access, Variable, Variables, Value};
     ///
     /// let mut vars = Variables::init_shell_vars();
-    /// vars.set(String::from("TESTF"), Variable { value: Value::F(-49.3), access: Access::ReadWrite });
+    /// vars.set(String::from("TESTF"), Variable { value: Value::F(-49.3), access: Access::ReadWrite, export: false });
     /// match vars.get("TESTF") {
     ///     Some(v) => assert_eq!(v.getf(), -49.3),
     ///     None => panic!("TESTF should be defined.")
     /// }
-    /// vars.set(String::from("TESTI"), Variable { value: Value::I(-42), access: Access::ReadWrite });
+    /// vars.set(String::from("TESTI"), Variable { value: Value::I(-42), access: Access::ReadWrite, export: false });
     /// match vars.get("TESTI") {
     ///     Some(v) => assert_eq!(v.geti(), -42),
     ///     None => panic!("TESTI should be defined.")
     /// }
-    /// vars.set(String::from("TESTS"), Variable { value: Value::S(String::from("RuSh will rock (one day)")), access: Access::ReadWrite });
+    /// vars.set(String::from("TESTS"), Variable { value: Value::S(String::from("RuSh will rock (one day)")), access: Access::ReadWrite, export: false });
     /// match vars.get("TESTS") {
     ///     Some(v) => assert_eq!(v.gets(), "RuSh will rock (one day)"),
     ///     None => panic!("TESTS variable should be defined.")
@@ -204,7 +241,11 @@ impl Variables {
             Occupied(mut entry) => {
                 let contents = entry.get_mut();
                 match contents.access {
-                    Access::ReadWrite => *contents = v,
+                    Access::ReadWrite => {
+                        let export = contents.export || v.export;
+                        *contents = v;
+                        contents.export = export;
+                    }
                     Access::ReadOnly => println!("readonly variable"),
                 }
             }
@@ -249,11 +290,154 @@ impl Variables {
                 entry.insert(Variable {
                     value: Value::S("".to_string()),
                     access: v,
+                    export: false,
+                });
+            }
+        }
+    }
+
+    /// Set the value of a variable even if it is `Access::ReadOnly`, for the shell own use
+    /// (SHELLOPTS, RUSHOPTS…). The variable is created as `Access::ReadOnly` if needed.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use rush::variables::{Access, Variables, Value};
+    ///
+    /// let mut vars = Variables::init_shell_vars();
+    /// vars.set_readonly(String::from("SHELLOPTS"), Value::S(String::from("braceexpand")));
+    /// match vars.get("SHELLOPTS") {
+    ///     Some(v) => { assert_eq!(v.gets(), "braceexpand"); assert_eq!(v.access, Access::ReadOnly); },
+    ///     None => panic!("SHELLOPTS should be defined.")
+    /// }
+    /// ```
+    pub fn set_readonly(&mut self, key: String, value: Value) {
+        match self.vars.entry(key) {
+            Occupied(mut entry) => entry.get_mut().value = value,
+            Vacant(entry) => {
+                entry.insert(Variable {
+                    value,
+                    access: Access::ReadOnly,
+                    export: false,
+                });
+            }
+        }
+    }
+
+    /// Get every variable name, sorted.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use rush::variables::Variables;
+    ///
+    /// let vars = Variables::init_shell_vars();
+    /// assert!(vars.names().contains(&"RUSH_COMMAND".to_string()));
+    /// ```
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.vars.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Get export attribute from Variable
+    ///
+    /// # Examples
+    /// ```rust
+    /// use rush::variables::{Access, Variable, Variables, Value};
+    ///
+    /// let mut vars = Variables::init_shell_vars();
+    /// assert_eq!(vars.get_export("SHLVL"), Some(true));
+    /// assert_eq!(vars.get_export("RUSH_COMMAND"), Some(false));
+    /// assert_eq!(vars.get_export("nonexistingvar"), None);
+    /// ```
+    pub fn get_export(&self, key: &str) -> Option<bool> {
+        self.vars.get(key).map(|val| val.export)
+    }
+
+    /// Set variable export attribute. If variable does not exist, it is created as Value::S("")
+    ///
+    /// # Examples
+    /// ```rust
+    /// use rush::variables::{Access, Variable, Variables, Value};
+    ///
+    /// let mut vars = Variables::init_shell_vars();
+    /// vars.set_export("TEST".to_string(), true);
+    /// assert_eq!(vars.get_export("TEST"), Some(true));
+    /// vars.set_export("TEST".to_string(), false);
+    /// assert_eq!(vars.get_export("TEST"), Some(false));
+    /// ```
+    pub fn set_export(&mut self, key: String, export: bool) {
+        match self.vars.entry(key) {
+            Occupied(mut entry) => entry.get_mut().export = export,
+            Vacant(entry) => {
+                entry.insert(Variable {
+                    value: Value::S("".to_string()),
+                    access: Access::ReadWrite,
+                    export,
                 });
             }
         }
     }
 
+    /// Get names of exported variables, sorted.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use rush::variables::Variables;
+    ///
+    /// let vars = Variables::init_shell_vars();
+    /// assert!(vars.exported().contains(&"SHLVL".to_string()));
+    /// assert!(!vars.exported().contains(&"RUSH_COMMAND".to_string()));
+    /// ```
+    pub fn exported(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .vars
+            .iter()
+            .filter(|(_, v)| v.export)
+            .map(|(k, _)| k.to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Build the environment given to a child process as `NAME=value` C strings, ready for `execve`.
+    /// `overrides` are temporary assignments (`VAR=val cmd`) only visible to the child: they take
+    /// precedence over (and are exported even if) existing variables of the same name.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use std::ffi::CString;
+    /// use rush::variables::{Access, Variable, Variables, Value};
+    ///
+    /// let mut vars = Variables::init_shell_vars();
+    /// vars.set(String::from("NOTEXPORTED"), Variable { value: Value::I(1), access: Access::ReadWrite, export: false });
+    /// vars.set(String::from("EXPORTED"), Variable { value: Value::F(2.5), access: Access::ReadWrite, export: true });
+    /// let envp = vars.envp(&[(String::from("SHLVL"), String::from("42"))]);
+    /// assert!(envp.contains(&CString::new("EXPORTED=2.5").unwrap()));
+    /// assert!(envp.contains(&CString::new("SHLVL=42").unwrap()));
+    /// assert!(!envp.contains(&CString::new("NOTEXPORTED=1").unwrap()));
+    /// ```
+    pub fn envp(&self, overrides: &[(String, String)]) -> Vec<CString> {
+        let mut envp = Vec::with_capacity(self.vars.len() + overrides.len());
+        for (key, var) in &self.vars {
+            if var.export && !overrides.iter().any(|(k, _)| k == key) {
+                let mut entry = String::with_capacity(key.len() + 16);
+                entry.push_str(key);
+                entry.push('=');
+                entry.push_str(&var.value.to_string());
+                // a NUL byte cannot be passed through execve, skip such variable.
+                if let Ok(c) = CString::new(entry) {
+                    envp.push(c);
+                }
+            }
+        }
+        for (key, val) in overrides {
+            if let Ok(c) = CString::new(format!("{}={}", key, val)) {
+                envp.push(c);
+            }
+        }
+        envp
+    }
+
     /// Unset a variable name and its value. So is the associated environment variable and value.
     ///
     /// # Examples
@@ -292,6 +476,22 @@ impl Variables {
         let mut vars = Variables {
             vars: HashMap::with_capacity(200),
         };
+        // Import the whole inherited environment, every variable being exported back to children.
+        // Names that cannot be shell variables (BASH_FUNC_foo%%, etc.) are ignored.
+        for (key, val) in env::vars_os() {
+            if let (Ok(key), Ok(val)) = (key.into_string(), val.into_string()) {
+                if Variables::is_name(&key) {
+                    vars.set(
+                        key,
+                        Variable {
+                            value: Value::S(val),
+                            access: Access::ReadWrite,
+                            export: true,
+                        },
+                    );
+                }
+            }
+        }
         // see man bash (Shell vars)
         // Expands to the full filename used to invoke this instance of rush.
         match env::current_exe() {
@@ -300,12 +500,20 @@ impl Variables {
                 Variable {
                     value: Value::S(ce.into_os_string().into_string().unwrap()),
                     access: Access::ReadWrite,
+                    export: false,
                 },
             ),
             Err(e) => panic!("Unable to get current_exe ! {}", e),
         }
         // A colon-separated list of enabled shell options. Each word in the list is a valid argument for the -s option to the shopt builtin command. The options appearing in RUSHOPTS are those reported as on by shopt. If this variable is in the environment when rush starts up, each shell option in the list will be enabled before reading any startup files. This variable is read-only.
-        // TODO RUSHOPTS
+        vars.set(
+            String::from("RUSHOPTS"),
+            Variable {
+                value: Value::S(Opt::init_shopt_options().enabled()),
+                access: Access::ReadOnly,
+                export: false,
+            },
+        );
         // Expands to the process ID of the current rush process. This differs from $$ under certain circumstances, such as subshells that do not require rush to be re-initialized.
         unsafe {
             let pid = getpid();
@@ -314,6 +522,7 @@ impl Variables {
                 Variable {
                     value: Value::I(i64::from(pid)),
                     access: Access::ReadWrite,
+                    export: false,
                 },
             );
         }
@@ -331,22 +540,24 @@ impl Variables {
             Variable {
                 value: Value::S(String::from("")),
                 access: Access::ReadWrite,
+                export: false,
             },
         );
         // The command argument to the -c invocation option.
-        // TODO RUSH_EXECUTION_STRING
+        // RUSH_EXECUTION_STRING is set by script::string.
         // An array variable whose members are the line numbers in source files where each corresponding member of FUNCNAME was invoked.  ${RUSH_LINENO[$i]} is the line number in the source file (${RUSH_SOURCE[$i+1]}) where ${FUNCNAME[$i]} was called (or ${RUSH_LINENO[$i-1]} if referenced within another shell function). Use LINENO to obtain the current line number.
-        // TODO RUSH_LINENO
+        // RUSH_LINENO is an array maintained by script module.
         // An array variable whose members are assigned by the =~ binary operator to the [[ conditional command. The element with index 0 is the portion of the string matching the entire regular expression.  The element with index n is the portion of the string matching the nth parenthesized subexpression. This variable is read-only.
         // TODO RUSH_REMATCH
         // An array variable whose members are the source filenames where the corresponding shell function names in the FUNCNAME array variable are defined. The shell function ${FUNCNAME[$i]} is defined in the file ${RUSH_SOURCE[$i]} and called from ${RUSH_SOURCE[$i+1]}.
-        // TODO RUSH_SOURCE
+        // RUSH_SOURCE is an array maintained by script module.
         // Incremented by one within each subshell or subshell environment when the shell begins executing in that environment. The initial value is 0.
         vars.set(
             String::from("RUSH_SUBSHELL"),
             Variable {
                 value: Value::I(0),
                 access: Access::ReadWrite,
+                export: false,
             },
         );
         // A readonly array variable whose members hold version information for this instance of rush.  The values assigned to the array members are as follows:
@@ -358,33 +569,41 @@ impl Variables {
         // RUSH_VERSINFO[5]        The value of MACHTYPE.
         // TODO RUSH_VERSINFO -> need MACHTYPE, which needs HOSTTYPE, VENDOR, OSTYPE
         // Expands to a string describing the version of this instance of bash
-        let _versinfo = vec!["0", "0", "0", "0", "alpha0", "x86_64-redhat-linux-gnu"]; // FIXME -> needs internal array support which is not yet implemented
+        let _versinfo = ["0", "0", "0", "0", "alpha0", "x86_64-redhat-linux-gnu"]; // FIXME -> needs internal array support which is not yet implemented
         vars.set(
             String::from("RUSH_VERSION"),
             Variable {
                 value: Value::S(String::from("0.0.0.0-alpha0-x86_64-redhat-linux-gnu")),
                 access: Access::ReadOnly,
+                export: false,
             },
         ); // FIXME -> use some global var.
            // An index into ${COMP_WORDS} of the word containing the current cursor position. This variable is available only in shell functions invoked by the programmable completion facilities.
-           // TODO COMP_CWORD
+           // COMP_CWORD is set by completion module.
            // The key (or final key of a key sequence) used to invoke the current completion function.
-           // TODO COMP_KEY
+           // COMP_KEY is set by completion module.
            // The current command line.  This variable is available only in shell functions and external commands invoked by the programmable completion facilities.
-           // TODO COMP_LINE
+           // COMP_LINE is set by completion module.
            // The index of the current cursor position relative to the beginning of the current command. If the current cursor position is at the end of the current command, the value of this variable is equal to ${#COMP_LINE}.  This variable is available only in shell functions and external commands invoked by the programmable completion facilities.
-           // TODO COMP_POINT
+           // COMP_POINT is set by completion module.
            // Set to an integer value corresponding to the type of completion attempted that caused a completion function to be called: TAB, for normal completion, ?, for listing completions after successive tabs, !, for listing alternatives on partial word completion, @, to list completions if the word is not unmodified, or %, for menu completion. This variable is available only in shell functions and external commands invoked by the programmable completion facilities.
-           // TODO COMP_TYPE
+           // COMP_TYPE is set by completion module.
            // The  set of characters that the readline library treats as word separators when performing word completion. If COMP_WORDBREAKS is unset, it loses its special properties, even if it is subsequently reset.
-           // TODO COMP_WORDBREAKS
-           // An array variable consisting of the individual words in the current command line. The line is split into words as readline would split it, using COMP_WORDBREAKS as described above.  This variable is available only in shell functions invoked by the programmable completion facilities.
-           // TODO COMP_WORDS
-           // An array variable created to hold the file descriptors for output from and input to an unnamed coprocess.
-           // TODO COPROC
-           // An  array  variable (see Arrays below) containing the current contents of the directory stack. Directories appear in the stack in the order they are displayed by the dirs builtin. Assigning to members of this array variable may be used to modify directories already in the stack, but the pushd and popd builtins must be used to add and remove directories. Assignment to this variable will not change the current directory. If DIRSTACK is unset, it loses its special properties, even if it is subsequently reset.
-           // TODO DIRSTACK
-           // Expands to the effective user ID of the current user, initialized at shell startup. This variable is readonly.
+        vars.set(
+            String::from("COMP_WORDBREAKS"),
+            Variable {
+                value: Value::S(String::from(" \t\n\"'@><=;|&(:")),
+                access: Access::ReadWrite,
+                export: false,
+            },
+        );
+        // An array variable consisting of the individual words in the current command line. The line is split into words as readline would split it, using COMP_WORDBREAKS as described above.  This variable is available only in shell functions invoked by the programmable completion facilities.
+        // COMP_WORDS is set by completion module.
+        // An array variable created to hold the file descriptors for output from and input to an unnamed coprocess.
+        // TODO COPROC
+        // An  array  variable (see Arrays below) containing the current contents of the directory stack. Directories appear in the stack in the order they are displayed by the dirs builtin. Assigning to members of this array variable may be used to modify directories already in the stack, but the pushd and popd builtins must be used to add and remove directories. Assignment to this variable will not change the current directory. If DIRSTACK is unset, it loses its special properties, even if it is subsequently reset.
+        // TODO DIRSTACK
+        // Expands to the effective user ID of the current user, initialized at shell startup. This variable is readonly.
         unsafe {
             let euid = geteuid();
             vars.set(
@@ -392,6 +611,7 @@ impl Variables {
                 Variable {
                     value: Value::I(i64::from(euid)),
                     access: Access::ReadOnly,
+                    export: false,
                 },
             );
         }
@@ -400,7 +620,7 @@ impl Variables {
         // An array variable containing the list of groups of which the current user is a member.  Assignments to GROUPS have no effect and return an error status.  If GROUPS is unset, it loses its special properties, even if it is subsequently reset.
         // TODO GROUPS
         // The history number, or index in the history list, of the current command.  If HISTCMD is unset, it loses its special properties, even if it is subsequently reset.
-        // TODO HISTCMD
+        // HISTCMD is maintained by history module.
         // Automatically set to the name of the current host.
         let mut bufc = vec![0u8; 40];
         extern "C" {
@@ -417,6 +637,7 @@ impl Variables {
                         .unwrap_or_else(|_| String::from("wtf")),
                 ),
                 access: Access::ReadWrite,
+                export: false,
             },
         );
         // Automatically set to a string that uniquely describes the type of machine on which rush is executing.  The default is system-dependent.
@@ -427,6 +648,7 @@ impl Variables {
             Variable {
                 value: Value::I(1),
                 access: Access::ReadWrite,
+                export: false,
             },
         );
         // Automatically set to a string that fully describes the system type on which rush is executing, in the standard GNU cpu-company-system format. The default is system-dependent.
@@ -439,12 +661,29 @@ impl Variables {
             Variable {
                 value: Value::S(String::from(".")),
                 access: Access::ReadWrite,
+                export: false,
             },
         );
         // The value of the last option argument processed by the getopts builtin command.
-        // TODO OPTARG
+        // OPTARG is set by getopts.
         // The index of the next argument to be processed by the getopts builtin command.
-        // TODO OPTIND
+        vars.set(
+            String::from("OPTIND"),
+            Variable {
+                value: Value::I(1),
+                access: Access::ReadWrite,
+                export: false,
+            },
+        );
+        // If set to the value 1, rush displays error messages generated by the getopts builtin command.
+        vars.set(
+            String::from("OPTERR"),
+            Variable {
+                value: Value::I(1),
+                access: Access::ReadWrite,
+                export: false,
+            },
+        );
         // Automatically set to a string that describes the operating system on which rush is executing.  The default is system-dependent.
         // TODO OSTYPE
         // An array variable containing a list of exit status values from the processes in the most-recently-executed foreground pipeline (which may contain only a single command).
@@ -457,6 +696,7 @@ impl Variables {
                 Variable {
                     value: Value::I(i64::from(ppid)),
                     access: Access::ReadOnly,
+                    export: false,
                 },
             );
         }
@@ -470,6 +710,7 @@ impl Variables {
             Variable {
                 value: Value::S(pwd),
                 access: Access::ReadWrite,
+                export: false,
             },
         );
         // Each time this parameter is referenced, a random integer between 0 and 32767 is generated. The sequence of random numbers may be initialized by assigning a value to RANDOM. If RANDOM is unset, it loses its special properties, even if it is subsequently reset.
@@ -480,6 +721,7 @@ impl Variables {
                 Variable {
                     value: Value::I(i64::from(rng.gen::<i16>())),
                     access: Access::ReadWrite,
+                    export: false,
                 },
             );
         }
@@ -495,6 +737,7 @@ impl Variables {
             Variable {
                 value: Value::I(0),
                 access: Access::ReadWrite,
+                export: false,
             },
         );
         // The full pathname to the shell is kept in this environment variable.  If it is not set when the shell starts, rush assigns to it the full pathname of the current user's login shell.
@@ -508,36 +751,39 @@ impl Variables {
             Variable {
                 value: Value::S(cexe),
                 access: Access::ReadWrite,
+                export: false,
             },
         );
         // A colon-separated list of enabled shell options. Each word in the list is a valid argument for the -o option to the set builtin command. The options appearing in SHELLOPTS are those reported as  on by set -o. If this variable is in the environment when rush starts up, each shell option in the list will be enabled before reading any startup files. This variable is read-only.
-        // TODO SHELLOPTS
+        vars.set(
+            String::from("SHELLOPTS"),
+            Variable {
+                value: Value::S(Opt::init_set_options().enabled()),
+                access: Access::ReadOnly,
+                export: false,
+            },
+        );
         // Incremented by one each time an instance of rush is started.
-        match vars.get(&String::from("SHLVL")) {
-            Some(lvl) => {
-                if let Variable {
-                    value: Value::I(mut val),
-                    access: Access::ReadWrite,
-                } = lvl
-                {
-                    val += 1;
-                    vars.set(
-                        String::from("SHLVL"),
-                        Variable {
-                            value: Value::I(val),
-                            access: Access::ReadWrite,
-                        },
-                    );
-                }
-            }
-            None => vars.set(
-                String::from("SHLVL"),
-                Variable {
-                    value: Value::I(1),
-                    access: Access::ReadWrite,
-                },
-            ),
+        // SHLVL is inherited as a string from the environment, an invalid value restarts at 1.
+        let shlvl = match vars.get("SHLVL") {
+            Some(Variable {
+                value: Value::I(val),
+                ..
+            }) => val + 1,
+            Some(Variable {
+                value: Value::S(val),
+                ..
+            }) => val.trim().parse::<i64>().unwrap_or(0) + 1,
+            _ => 1,
         };
+        vars.set(
+            String::from("SHLVL"),
+            Variable {
+                value: Value::I(shlvl),
+                access: Access::ReadWrite,
+                export: true,
+            },
+        );
         // Expands to the user ID of the current user, initialized at shell startup. This variable is readonly.
         unsafe {
             let id = getuid();
@@ -546,6 +792,7 @@ impl Variables {
                 Variable {
                     value: Value::I(i64::from(id)),
                     access: Access::ReadOnly,
+                    export: false,
                 },
             );
         }
@@ -557,27 +804,32 @@ impl Variables {
                 Variable {
                     value: Value::I(i64::from(id)),
                     access: Access::ReadOnly,
+                    export: false,
                 },
             );
         }
-        unsafe {
-            let log = getlogin();
-            vars.set(
-                String::from("USERNAME"),
-                Variable {
-                    value: Value::S(
-                        String::from_utf8(CStr::from_ptr(log).to_bytes().to_owned())
-                            .unwrap_or_else(|_| "no login".to_owned()),
-                    ),
-                    access: Access::ReadOnly,
-                },
-            );
-        }
+        // getlogin gives back NULL when there is no controlling terminal, fall back on USER then.
+        let log = unsafe { getlogin() };
+        let username = if log.is_null() {
+            env::var("USER").unwrap_or_else(|_| "no login".to_owned())
+        } else {
+            unsafe { String::from_utf8(CStr::from_ptr(log).to_bytes().to_owned()) }
+                .unwrap_or_else(|_| "no login".to_owned())
+        };
+        vars.set(
+            String::from("USERNAME"),
+            Variable {
+                value: Value::S(username),
+                access: Access::ReadOnly,
+                export: false,
+            },
+        );
         vars.set(
             String::from("HISTSIZE"),
             Variable {
-                value: Value::I(1000),
+                value: Value::I(500),
                 access: Access::ReadWrite,
+                export: false,
             },
         );
         vars
@@ -0,0 +1,1042 @@
+//
+// vcs.rs
+//
+// Copyright 2015-2019 Laurent Wandrebeck <l.wandrebeck@quelquesmots.fr>
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston,
+// MA 02110-1301, USA.
+//
+
+//! RuSh version control status, shown by the \g prompt escape.
+//!
+//! vcs.rs reads git repositories files directly instead of running git: current branch or detached HEAD commit,
+//! operation in progress (rebase, am, merge, cherry-pick, revert, bisect), files changed in the worktree since the
+//! index recorded them, changes staged in the index since HEAD commit, and how many commits the branch is ahead and
+//! behind its upstream. Commits and trees are read from loose objects and pack files, which are inflated here. Status
+//! is displayed the way git-prompt.sh does: `main *+|MERGING u+1-2`.
+
+use std::collections::{BinaryHeap, HashMap};
+use std::fmt;
+use std::fs::{self, File};
+use std::os::unix::fs::{FileExt, MetadataExt};
+use std::path::{Path, PathBuf};
+
+/// Most commits read to count how many are ahead and behind upstream.
+const MAX_COMMITS: usize = 10000;
+
+/// Most deltas followed to rebuild an object from a pack file.
+const MAX_DELTAS: usize = 64;
+
+/// A commit object name.
+type Sha = [u8; 20];
+
+/// Status of a git repository.
+#[derive(Debug, Default, PartialEq)]
+pub struct Status {
+    /// current branch name, or abbreviated commit name between parentheses when HEAD is detached.
+    pub branch: String,
+    /// have files of the worktree changed since the index recorded them ?
+    pub dirty: bool,
+    /// have changes been staged in the index since HEAD commit ?
+    pub staged: bool,
+    /// operation in progress, with its progress when known: `REBASE 2/5`, `MERGING`…
+    pub operation: Option<String>,
+    /// commits the branch is ahead and behind its upstream, None if it has none.
+    pub upstream: Option<(usize, usize)>,
+}
+
+/// Parts of a status read by walking objects, staged changes and upstream distance, with the stamp of the files
+/// they were read from. They are only read again once one of these files changed.
+#[derive(Clone, Debug, Default)]
+pub struct Walked {
+    /// modification times of index, HEAD, config, packed refs, and branch and upstream refs.
+    stamp: String,
+    /// have changes been staged in the index since HEAD commit ?
+    staged: bool,
+    /// commits the branch is ahead and behind its upstream.
+    upstream: Option<(usize, usize)>,
+}
+
+/// Status as git-prompt.sh displays it, with GIT_PS1_SHOWDIRTYSTATE and GIT_PS1_SHOWUPSTREAM=verbose.
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.branch)?;
+        match (self.dirty, self.staged) {
+            (true, true) => write!(f, " *+")?,
+            (true, false) => write!(f, " *")?,
+            (false, true) => write!(f, " +")?,
+            (false, false) => (),
+        }
+        if let Some(operation) = &self.operation {
+            write!(f, "|{}", operation)?;
+        }
+        match self.upstream {
+            Some((0, 0)) => write!(f, " u="),
+            Some((ahead, 0)) => write!(f, " u+{}", ahead),
+            Some((0, behind)) => write!(f, " u-{}", behind),
+            Some((ahead, behind)) => write!(f, " u+{}-{}", ahead, behind),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Status of the repository `dir` belongs to, None if it is not within a git worktree.
+pub fn status(dir: &Path) -> Option<Status> {
+    status_since(dir, &mut Walked::default())
+}
+
+/// Status of the repository `dir` belongs to, staged changes and upstream distance being taken from `walked` if
+/// the files they depend on did not change since, and kept there otherwise. Worktree files are always looked at.
+pub fn status_since(dir: &Path, walked: &mut Walked) -> Option<Status> {
+    let mut repo = Repo::find(dir)?;
+    let head = repo.read("HEAD")?;
+    let mut status = Status::default();
+    let symbolic = head.strip_prefix("ref: ").map(str::to_string);
+    status.branch = match &symbolic {
+        Some(name) => name.trim_start_matches("refs/heads/").to_string(),
+        None => format!("({}...)", &head[..head.len().min(7)]),
+    };
+    // operation in progress, the way git-prompt.sh finds it.
+    let (mut operation, mut step, mut total) = (None, None, None);
+    if repo.git.join("rebase-merge").is_dir() {
+        if let Some(name) = repo.read("rebase-merge/head-name") {
+            status.branch = name.trim_start_matches("refs/heads/").to_string();
+        }
+        step = repo.read("rebase-merge/msgnum");
+        total = repo.read("rebase-merge/end");
+        operation = Some("REBASE");
+    } else if repo.git.join("rebase-apply").is_dir() {
+        step = repo.read("rebase-apply/next");
+        total = repo.read("rebase-apply/last");
+        operation = if repo.git.join("rebase-apply/rebasing").exists() {
+            if let Some(name) = repo.read("rebase-apply/head-name") {
+                status.branch = name.trim_start_matches("refs/heads/").to_string();
+            }
+            Some("REBASE")
+        } else if repo.git.join("rebase-apply/applying").exists() {
+            Some("AM")
+        } else {
+            Some("AM/REBASE")
+        };
+    } else {
+        for (file, name) in &[
+            ("MERGE_HEAD", "MERGING"),
+            ("CHERRY_PICK_HEAD", "CHERRY-PICKING"),
+            ("REVERT_HEAD", "REVERTING"),
+            ("BISECT_LOG", "BISECTING"),
+        ] {
+            if repo.git.join(file).exists() {
+                operation = Some(name);
+                break;
+            }
+        }
+    }
+    status.operation = operation.map(|operation| match (step, total) {
+        (Some(step), Some(total)) => format!("{} {}/{}", operation, step, total),
+        _ => operation.to_string(),
+    });
+    let index = repo.index();
+    if let Some((entries, _)) = &index {
+        status.dirty = repo.dirty(entries);
+    }
+    let upstream = symbolic.as_deref().and_then(|name| {
+        repo.upstream(name.trim_start_matches("refs/heads/"))
+            .map(|upstream| (name.to_string(), upstream))
+    });
+    let stamp = repo.stamp(&symbolic, &upstream);
+    if walked.stamp != stamp {
+        walked.staged = match &index {
+            Some((entries, cached)) => repo.staged(entries, *cached),
+            None => false,
+        };
+        walked.upstream = None;
+        if let Some((name, upstream)) = upstream {
+            if let (Some(local), Some(upstream)) = (repo.resolve(&name), repo.resolve(&upstream)) {
+                walked.upstream = repo.ahead_behind(local, upstream);
+            }
+        }
+        walked.stamp = stamp;
+    }
+    status.staged = walked.staged;
+    status.upstream = walked.upstream;
+    Some(status)
+}
+
+/// Text \g displays for directory `dir`: its repository status, empty outside of git worktrees.
+pub fn prompt(dir: &str) -> String {
+    prompt_since(dir, &mut Walked::default())
+}
+
+/// Text \g displays for directory `dir`, what `walked` keeps being read again only once it changed.
+pub fn prompt_since(dir: &str, walked: &mut Walked) -> String {
+    match status_since(Path::new(dir), walked) {
+        Some(status) => status.to_string(),
+        None => String::new(),
+    }
+}
+
+/// A git repository.
+struct Repo {
+    /// worktree top directory.
+    work: PathBuf,
+    /// repository directory: HEAD, index and state of operations in progress.
+    git: PathBuf,
+    /// directory shared by all worktrees: objects, refs and config. Same as `git` but in linked worktrees.
+    common: PathBuf,
+    /// pack files, opened when an object is first looked for.
+    packs: Option<Vec<Pack>>,
+    /// commits already read: parents and commit time.
+    commits: HashMap<Sha, (Vec<Sha>, i64)>,
+}
+
+impl Repo {
+    /// Find the repository `dir` belongs to: `.git` directory, or `.git` file naming it, of `dir` or its parents.
+    fn find(dir: &Path) -> Option<Repo> {
+        for work in dir.ancestors() {
+            let dotgit = work.join(".git");
+            let git = if dotgit.is_file() {
+                let text = fs::read_to_string(&dotgit).ok()?;
+                work.join(text.trim().strip_prefix("gitdir: ")?)
+            } else {
+                dotgit
+            };
+            if !git.join("HEAD").is_file() {
+                continue;
+            }
+            let common = match fs::read_to_string(git.join("commondir")) {
+                Ok(text) => git.join(text.trim()),
+                Err(_) => git.clone(),
+            };
+            return Some(Repo {
+                work: work.to_path_buf(),
+                git,
+                common,
+                packs: None,
+                commits: HashMap::new(),
+            });
+        }
+        None
+    }
+
+    /// Contents of a file of the repository directory, trimmed.
+    fn read(&self, name: &str) -> Option<String> {
+        fs::read_to_string(self.git.join(name))
+            .ok()
+            .map(|text| text.trim().to_string())
+    }
+
+    /// Modification times of the files staged changes and upstream distance depend on: index, HEAD, config,
+    /// packed refs, and the loose refs of branch `head` and of its upstream.
+    fn stamp(&self, head: &Option<String>, upstream: &Option<(String, String)>) -> String {
+        let mut files = vec![
+            self.git.join("index"),
+            self.git.join("HEAD"),
+            self.common.join("config"),
+            self.common.join("packed-refs"),
+        ];
+        files.extend(head.iter().map(|name| self.common.join(name)));
+        files.extend(upstream.iter().map(|(_, name)| self.common.join(name)));
+        let mut stamp = self.git.to_string_lossy().into_owned();
+        for file in files {
+            match fs::metadata(file) {
+                Ok(meta) => stamp.push_str(&format!(" {}.{}", meta.mtime(), meta.mtime_nsec())),
+                Err(_) => stamp.push_str(" -"),
+            }
+        }
+        stamp
+    }
+
+    /// Commit a ref names, symbolic refs being followed: loose ref file, or packed-refs entry.
+    fn resolve(&self, name: &str) -> Option<Sha> {
+        let mut name = name.to_string();
+        for _ in 0..8 {
+            let dir = if name.starts_with("refs/") {
+                &self.common
+            } else {
+                &self.git
+            };
+            let text = match fs::read_to_string(dir.join(&name)) {
+                Ok(text) => text.trim().to_string(),
+                Err(_) => {
+                    let packed = fs::read_to_string(self.common.join("packed-refs")).ok()?;
+                    return packed
+                        .lines()
+                        .filter(|line| !line.starts_with('#') && !line.starts_with('^'))
+                        .find_map(|line| match line.split_once(' ') {
+                            Some((sha, r)) if r == name => unhex(sha),
+                            _ => None,
+                        });
+                }
+            };
+            match text.strip_prefix("ref: ") {
+                Some(target) => name = target.to_string(),
+                None => return unhex(&text),
+            }
+        }
+        None
+    }
+
+    /// Ref of the upstream of `branch`, from branch.<name>.remote and branch.<name>.merge configuration.
+    fn upstream(&self, branch: &str) -> Option<String> {
+        let config = fs::read_to_string(self.common.join("config")).ok()?;
+        let section = format!("[branch \"{}\"]", branch);
+        let (mut remote, mut merge) = (None, None);
+        let mut within = false;
+        for line in config.lines().map(str::trim) {
+            if line.starts_with('[') {
+                within = line == section;
+            } else if within {
+                if let Some((key, value)) = line.split_once('=') {
+                    let value = value.trim().trim_matches('"').to_string();
+                    match key.trim().to_lowercase().as_str() {
+                        "remote" => remote = Some(value),
+                        "merge" => merge = Some(value),
+                        _ => (),
+                    }
+                }
+            }
+        }
+        let (remote, merge) = (remote?, merge?);
+        if remote == "." {
+            return Some(merge);
+        }
+        Some(format!(
+            "refs/remotes/{}/{}",
+            remote,
+            merge.trim_start_matches("refs/heads/")
+        ))
+    }
+
+    /// Entries of the index, and the tree its cache tree extension records for all of them, if it is valid.
+    /// None if there is no index, or it cannot be read.
+    fn index(&self) -> Option<(Vec<Entry>, Option<Sha>)> {
+        let index = fs::read(self.git.join("index")).ok()?;
+        if index.len() < 12 || &index[..4] != b"DIRC" {
+            return None;
+        }
+        let version = be32(&index, 4)?;
+        let count = be32(&index, 8)?;
+        let mut entries = Vec::with_capacity(count as usize);
+        let mut pos = 12;
+        let mut name: Vec<u8> = Vec::new();
+        for _ in 0..count {
+            let entry = index.get(pos..pos + 62)?;
+            let mut sha = [0; 20];
+            sha.copy_from_slice(&entry[40..60]);
+            let flags = u16::from_be_bytes([entry[60], entry[61]]);
+            let mut start = pos + 62;
+            let (mut skip, mut intent) = (flags & 0x8000 != 0, false);
+            if version >= 3 && flags & 0x4000 != 0 {
+                let extended = index.get(start..start + 2)?;
+                skip |= extended[0] & 0x40 != 0;
+                intent = extended[0] & 0x20 != 0;
+                start += 2;
+            }
+            if version >= 4 {
+                // path is the end of the previous one cut by some bytes, followed by a suffix.
+                let (strip, len) = offset(&index[start..])?;
+                name.truncate(name.len().saturating_sub(strip as usize));
+                start += len;
+                let end = start + index[start..].iter().position(|b| *b == 0)?;
+                name.extend_from_slice(&index[start..end]);
+                pos = end + 1;
+            } else {
+                let len = usize::from(flags & 0xfff);
+                let end = start + index[start..].iter().position(|b| *b == 0)?;
+                name = index[start..end].to_vec();
+                pos += (start - pos + len + 8) & !7;
+            }
+            entries.push(Entry {
+                name: name.clone(),
+                mtime: be32(entry, 8)?,
+                mtime_nsec: be32(entry, 12)?,
+                mode: be32(entry, 24)?,
+                size: be32(entry, 36)?,
+                sha,
+                stage: (flags >> 12) & 3,
+                skip,
+                intent,
+            });
+        }
+        // extensions follow entries, up to the final checksum. TREE starts with the root, whose path is empty.
+        let mut cached = None;
+        while pos + 8 <= index.len().saturating_sub(20) {
+            let size = be32(&index, pos + 4)? as usize;
+            let data = index.get(pos + 8..pos + 8 + size)?;
+            if &index[pos..pos + 4] == b"TREE" && data.first() == Some(&0) {
+                let line = data.iter().position(|b| *b == b'\n')?;
+                let valid = !data[1..line].starts_with(b"-");
+                if valid {
+                    let mut sha = [0; 20];
+                    sha.copy_from_slice(data.get(line + 1..line + 21)?);
+                    cached = Some(sha);
+                }
+            }
+            pos += 8 + size;
+        }
+        Some((entries, cached))
+    }
+
+    /// Have files of the worktree changed since the index recorded them ? Their size, modification time and
+    /// executable bit are compared with the ones the index holds, as git does before looking at contents.
+    /// Unmerged entries make the worktree dirty too.
+    fn dirty(&self, entries: &[Entry]) -> bool {
+        for entry in entries {
+            if entry.stage != 0 || entry.intent {
+                return true;
+            }
+            // assume-unchanged, skip-worktree and submodule entries are not looked at.
+            if entry.skip || entry.mode & 0o170000 == 0o160000 {
+                continue;
+            }
+            let path = self
+                .work
+                .join(String::from_utf8_lossy(&entry.name).as_ref());
+            match fs::symlink_metadata(&path) {
+                Ok(meta) => {
+                    let changed = meta.size() as u32 != entry.size
+                        || meta.mtime() as u32 != entry.mtime
+                        || meta.mtime_nsec() as u32 != entry.mtime_nsec
+                        || (entry.mode & 0o170000 == 0o100000
+                            && (meta.mode() & 0o111 != 0) != (entry.mode & 0o111 != 0));
+                    if changed {
+                        return true;
+                    }
+                }
+                Err(_) => return true,
+            }
+        }
+        false
+    }
+
+    /// Have changes been staged in the index since HEAD commit ? Index entries are compared with HEAD tree,
+    /// unless the cache tree of the index is valid and names it. Unmerged entries count as staged changes, and
+    /// any entry does when there is no commit yet.
+    fn staged(&mut self, entries: &[Entry], cached: Option<Sha>) -> bool {
+        let tree = match self.resolve("HEAD").and_then(|head| self.object(&head, 0)) {
+            Some((1, data)) => String::from_utf8_lossy(&data)
+                .lines()
+                .next()
+                .and_then(|line| line.strip_prefix("tree "))
+                .and_then(unhex),
+            _ => return entries.iter().any(|e| !e.intent),
+        };
+        let tree = match tree {
+            Some(tree) => tree,
+            None => return false,
+        };
+        if cached == Some(tree) {
+            return false;
+        }
+        let mut files = HashMap::new();
+        if self.tree(&tree, &mut Vec::new(), &mut files).is_none() {
+            return false;
+        }
+        let mut found = 0;
+        for entry in entries.iter().filter(|e| !e.intent) {
+            if entry.stage != 0 {
+                return true;
+            }
+            match files.get(&entry.name) {
+                Some((mode, sha)) if *mode == entry.mode && *sha == entry.sha => found += 1,
+                _ => return true,
+            }
+        }
+        found != files.len()
+    }
+
+    /// Add files of tree `sha` to `files`, with their mode and object name, their path being prefixed by `prefix`.
+    /// Subtrees are read too.
+    fn tree(
+        &mut self,
+        sha: &Sha,
+        prefix: &mut Vec<u8>,
+        files: &mut HashMap<Vec<u8>, (u32, Sha)>,
+    ) -> Option<()> {
+        let (kind, data) = self.object(sha, 0)?;
+        if kind != 2 {
+            return None;
+        }
+        let mut pos = 0;
+        while pos < data.len() {
+            let space = pos + data[pos..].iter().position(|b| *b == b' ')?;
+            let nul = space + data[space..].iter().position(|b| *b == 0)?;
+            let mode = u32::from_str_radix(&String::from_utf8_lossy(&data[pos..space]), 8).ok()?;
+            let mut object = [0; 20];
+            object.copy_from_slice(data.get(nul + 1..nul + 21)?);
+            let len = prefix.len();
+            prefix.extend_from_slice(&data[space + 1..nul]);
+            if mode == 0o40000 {
+                prefix.push(b'/');
+                self.tree(&object, prefix, files)?;
+            } else {
+                files.insert(prefix.clone(), (mode, object));
+            }
+            prefix.truncate(len);
+            pos = nul + 21;
+        }
+        Some(())
+    }
+
+    /// Commits reachable from `local` and not from `upstream`, and conversely. Commits are walked newest first from
+    /// both, until all of those left to walk are reachable from both. None if there are too many to read.
+    fn ahead_behind(&mut self, local: Sha, upstream: Sha) -> Option<(usize, usize)> {
+        if local == upstream {
+            return Some((0, 0));
+        }
+        let mut flags: HashMap<Sha, u8> = HashMap::new();
+        let mut queue = BinaryHeap::new();
+        for (sha, flag) in [(local, 1), (upstream, 2)].iter() {
+            flags.insert(*sha, *flag);
+            queue.push((self.commit(sha)?.1, *sha));
+        }
+        let mut walked = 0;
+        while queue.iter().any(|(_, sha)| flags[sha] != 3) {
+            let (_, sha) = queue.pop()?;
+            walked += 1;
+            if walked > MAX_COMMITS {
+                return None;
+            }
+            let flag = flags[&sha];
+            for parent in self.commit(&sha)?.0 {
+                let old = *flags.get(&parent).unwrap_or(&0);
+                if old | flag != old {
+                    flags.insert(parent, old | flag);
+                    queue.push((self.commit(&parent)?.1, parent));
+                }
+            }
+        }
+        let ahead = flags.values().filter(|f| **f == 1).count();
+        let behind = flags.values().filter(|f| **f == 2).count();
+        Some((ahead, behind))
+    }
+
+    /// Parents and commit time of a commit.
+    fn commit(&mut self, sha: &Sha) -> Option<(Vec<Sha>, i64)> {
+        if let Some(commit) = self.commits.get(sha) {
+            return Some(commit.clone());
+        }
+        let (kind, data) = self.object(sha, 0)?;
+        if kind != 1 {
+            return None;
+        }
+        let (mut parents, mut time) = (Vec::new(), 0);
+        for line in String::from_utf8_lossy(&data).lines() {
+            if line.is_empty() {
+                break;
+            }
+            if let Some(parent) = line.strip_prefix("parent ") {
+                parents.push(unhex(parent)?);
+            } else if let Some(committer) = line.strip_prefix("committer ") {
+                time = committer
+                    .rsplit(' ')
+                    .nth(1)
+                    .and_then(|t| t.parse().ok())
+                    .unwrap_or(0);
+            }
+        }
+        self.commits.insert(*sha, (parents.clone(), time));
+        Some((parents, time))
+    }
+
+    /// Type (1 commit, 2 tree, 3 blob, 4 tag) and contents of an object, loose or packed.
+    fn object(&mut self, sha: &Sha, depth: usize) -> Option<(u8, Vec<u8>)> {
+        let name = hex(sha);
+        let loose = self
+            .common
+            .join("objects")
+            .join(&name[..2])
+            .join(&name[2..]);
+        if let Ok(data) = fs::read(loose) {
+            let raw = inflate(&data)?;
+            let nul = raw.iter().position(|b| *b == 0)?;
+            let kind = match raw[..nul].split(|b| *b == b' ').next()? {
+                b"commit" => 1,
+                b"tree" => 2,
+                b"blob" => 3,
+                b"tag" => 4,
+                _ => return None,
+            };
+            return Some((kind, raw[nul + 1..].to_vec()));
+        }
+        if self.packs.is_none() {
+            self.packs = Some(Pack::open_all(&self.common.join("objects/pack")));
+        }
+        let found = self
+            .packs
+            .as_ref()?
+            .iter()
+            .enumerate()
+            .find_map(|(i, pack)| pack.find(sha).map(|offset| (i, offset)))?;
+        self.packed(found.0, found.1, depth)
+    }
+
+    /// Type and contents of the object at `offset` in pack number `pack`, deltas applied.
+    fn packed(&mut self, pack: usize, offset: u64, depth: usize) -> Option<(u8, Vec<u8>)> {
+        if depth > MAX_DELTAS {
+            return None;
+        }
+        // compressed size is unknown: read more of the pack until the object can be inflated.
+        let mut chunk = 4096;
+        loop {
+            let data = self.packs.as_ref()?.get(pack)?.read(offset, chunk)?;
+            let mut pos = 0;
+            let mut c = *data.get(pos)?;
+            let kind = (c >> 4) & 7;
+            while c & 0x80 != 0 {
+                pos += 1;
+                c = *data.get(pos)?;
+            }
+            pos += 1;
+            let base = match kind {
+                6 => {
+                    let (back, len) = offset_delta(data.get(pos..)?)?;
+                    pos += len;
+                    Some(Err(offset.checked_sub(back)?))
+                }
+                7 => {
+                    let mut sha = [0; 20];
+                    sha.copy_from_slice(data.get(pos..pos + 20)?);
+                    pos += 20;
+                    Some(Ok(sha))
+                }
+                _ => None,
+            };
+            let inflated = match inflate(data.get(pos..)?) {
+                Some(inflated) => inflated,
+                None if data.len() == chunk => {
+                    chunk *= 4;
+                    continue;
+                }
+                None => return None,
+            };
+            return match base {
+                None => Some((kind, inflated)),
+                Some(base) => {
+                    let (kind, base) = match base {
+                        Err(base) => self.packed(pack, base, depth + 1)?,
+                        Ok(sha) => self.object(&sha, depth + 1)?,
+                    };
+                    Some((kind, apply_delta(&base, &inflated)?))
+                }
+            };
+        }
+    }
+}
+
+/// An index entry.
+struct Entry {
+    /// path, relative to worktree top directory.
+    name: Vec<u8>,
+    /// modification time, in seconds.
+    mtime: u32,
+    /// nanoseconds of modification time.
+    mtime_nsec: u32,
+    /// file type and permissions.
+    mode: u32,
+    /// file size.
+    size: u32,
+    /// object name of the contents.
+    sha: Sha,
+    /// merge stage, 0 unless the entry is unmerged.
+    stage: u16,
+    /// assume-unchanged or skip-worktree.
+    skip: bool,
+    /// intent-to-add entry, whose contents are not staged yet.
+    intent: bool,
+}
+
+/// A pack file, and its version 2 index.
+struct Pack {
+    idx: File,
+    pack: File,
+    /// number of objects.
+    count: u32,
+}
+
+impl Pack {
+    /// Open every pack of a pack directory.
+    fn open_all(dir: &Path) -> Vec<Pack> {
+        let mut packs = Vec::new();
+        if let Ok(entries) = fs::read_dir(dir) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.extension().is_some_and(|e| e == "idx") {
+                    if let Some(pack) = Pack::open(&path) {
+                        packs.push(pack);
+                    }
+                }
+            }
+        }
+        packs
+    }
+
+    /// Open a pack index and its pack.
+    fn open(idx: &Path) -> Option<Pack> {
+        let file = File::open(idx).ok()?;
+        let mut header = [0; 8];
+        file.read_exact_at(&mut header, 0).ok()?;
+        if header != [0xff, b't', b'O', b'c', 0, 0, 0, 2] {
+            return None;
+        }
+        let mut count = [0; 4];
+        file.read_exact_at(&mut count, 8 + 255 * 4).ok()?;
+        Some(Pack {
+            pack: File::open(idx.with_extension("pack")).ok()?,
+            idx: file,
+            count: u32::from_be_bytes(count),
+        })
+    }
+
+    /// `len` bytes of the pack from `offset`, less at its end.
+    fn read(&self, offset: u64, len: usize) -> Option<Vec<u8>> {
+        let mut data = vec![0; len];
+        let mut read = 0;
+        while read < len {
+            match self.pack.read_at(&mut data[read..], offset + read as u64) {
+                Ok(0) => break,
+                Ok(n) => read += n,
+                Err(_) => return None,
+            }
+        }
+        data.truncate(read);
+        Some(data)
+    }
+
+    /// Offset of an object in the pack, found by binary search among the names the index sorts.
+    fn find(&self, sha: &Sha) -> Option<u64> {
+        let be32_at = |offset: u64| -> Option<u32> {
+            let mut buf = [0; 4];
+            self.idx.read_exact_at(&mut buf, offset).ok()?;
+            Some(u32::from_be_bytes(buf))
+        };
+        let first = if sha[0] == 0 {
+            0
+        } else {
+            be32_at(8 + (u64::from(sha[0]) - 1) * 4)?
+        };
+        let (mut low, mut high) = (first, be32_at(8 + u64::from(sha[0]) * 4)?);
+        let names = 8 + 256 * 4;
+        let mut name = [0; 20];
+        while low < high {
+            let mid = low + (high - low) / 2;
+            self.idx
+                .read_exact_at(&mut name, names + u64::from(mid) * 20)
+                .ok()?;
+            match name.cmp(sha) {
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+                std::cmp::Ordering::Equal => {
+                    let count = u64::from(self.count);
+                    let offsets = names + count * 24;
+                    let offset = be32_at(offsets + u64::from(mid) * 4)?;
+                    if offset & 0x8000_0000 == 0 {
+                        return Some(u64::from(offset));
+                    }
+                    // large offsets follow, on 8 bytes.
+                    let mut buf = [0; 8];
+                    let large = offsets + count * 4 + u64::from(offset & 0x7fff_ffff) * 8;
+                    self.idx.read_exact_at(&mut buf, large).ok()?;
+                    return Some(u64::from_be_bytes(buf));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Big endian 32 bits number at `pos`.
+fn be32(data: &[u8], pos: usize) -> Option<u32> {
+    let bytes = data.get(pos..pos + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Number encoded the way pack offset deltas and index version 4 path prefixes are, with its length.
+fn offset(data: &[u8]) -> Option<(u64, usize)> {
+    let mut pos = 0;
+    let mut c = *data.get(pos)?;
+    let mut n = u64::from(c & 0x7f);
+    while c & 0x80 != 0 {
+        pos += 1;
+        c = *data.get(pos)?;
+        n = ((n + 1) << 7) | u64::from(c & 0x7f);
+    }
+    Some((n, pos + 1))
+}
+
+/// Offset of the base of an offset delta, back from the delta itself, with its length.
+fn offset_delta(data: &[u8]) -> Option<(u64, usize)> {
+    offset(data)
+}
+
+/// Size of a delta source or target: 7 bits per byte, least significant first. Returns it with its length.
+fn delta_size(data: &[u8]) -> Option<(usize, usize)> {
+    let (mut size, mut shift, mut pos) = (0, 0, 0);
+    loop {
+        let c = *data.get(pos)?;
+        size |= usize::from(c & 0x7f) << shift;
+        shift += 7;
+        pos += 1;
+        if c & 0x80 == 0 || shift > 56 {
+            return Some((size, pos));
+        }
+    }
+}
+
+/// Rebuild an object from its base and a delta: instructions copying parts of the base, or inserting new data.
+fn apply_delta(base: &[u8], delta: &[u8]) -> Option<Vec<u8>> {
+    let (source, len) = delta_size(delta)?;
+    let mut pos = len;
+    let (target, len) = delta_size(&delta[pos..])?;
+    pos += len;
+    if source != base.len() {
+        return None;
+    }
+    let mut out = Vec::with_capacity(target);
+    while pos < delta.len() {
+        let op = delta[pos];
+        pos += 1;
+        if op & 0x80 != 0 {
+            let (mut start, mut size) = (0, 0);
+            for i in 0..4 {
+                if op & (1 << i) != 0 {
+                    start |= usize::from(*delta.get(pos)?) << (8 * i);
+                    pos += 1;
+                }
+            }
+            for i in 0..3 {
+                if op & (0x10 << i) != 0 {
+                    size |= usize::from(*delta.get(pos)?) << (8 * i);
+                    pos += 1;
+                }
+            }
+            if size == 0 {
+                size = 0x10000;
+            }
+            out.extend_from_slice(base.get(start..start + size)?);
+        } else if op != 0 {
+            let size = usize::from(op);
+            out.extend_from_slice(delta.get(pos..pos + size)?);
+            pos += size;
+        } else {
+            return None;
+        }
+    }
+    if out.len() == target {
+        Some(out)
+    } else {
+        None
+    }
+}
+
+/// Hexadecimal object name.
+fn hex(sha: &Sha) -> String {
+    sha.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Object name from its hexadecimal form.
+fn unhex(text: &str) -> Option<Sha> {
+    let text = text.trim();
+    if text.len() != 40 {
+        return None;
+    }
+    let mut sha = [0; 20];
+    for (i, byte) in sha.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(text.get(2 * i..2 * i + 2)?, 16).ok()?;
+    }
+    Some(sha)
+}
+
+/// Deflate lengths base values and extra bits, for length symbols 257 to 285.
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+/// Deflate distances base values and extra bits.
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// Order code lengths of dynamic Huffman codes are given in.
+const CODE_LENGTHS_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Deflate data bits, least significant first.
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl<'a> Bits<'a> {
+    /// Next `n` bits, None at the end of data.
+    fn get(&mut self, n: u32) -> Option<u32> {
+        while self.count < n {
+            self.buffer |= u32::from(*self.data.get(self.pos)?) << self.count;
+            self.pos += 1;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1 << n) - 1);
+        self.buffer >>= n;
+        self.count -= n;
+        Some(value)
+    }
+}
+
+/// Canonical Huffman code: number of codes of each length, and symbols ordered by code.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    /// Code given the length of each symbol code, 0 for unused symbols.
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0; 16];
+        for len in lengths {
+            counts[usize::from(*len)] += 1;
+        }
+        counts[0] = 0;
+        let mut symbols: Vec<u16> = (0..lengths.len() as u16)
+            .filter(|s| lengths[usize::from(*s)] != 0)
+            .collect();
+        symbols.sort_by_key(|s| lengths[usize::from(*s)]);
+        Huffman { counts, symbols }
+    }
+
+    /// Decode next symbol.
+    fn decode(&self, bits: &mut Bits) -> Option<u16> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..16 {
+            code |= bits.get(1)? as i32;
+            let count = i32::from(self.counts[len]);
+            if code - count < first {
+                return self.symbols.get((index + code - first) as usize).copied();
+            }
+            index += count;
+            first += count;
+            first <<= 1;
+            code <<= 1;
+        }
+        None
+    }
+}
+
+/// Inflate zlib compressed data. None if it is invalid or truncated.
+fn inflate(data: &[u8]) -> Option<Vec<u8>> {
+    let (cmf, flg) = (*data.first()?, *data.get(1)?);
+    if cmf & 0x0f != 8 || (u16::from(cmf) << 8 | u16::from(flg)) % 31 != 0 || flg & 0x20 != 0 {
+        return None;
+    }
+    let mut bits = Bits {
+        data: &data[2..],
+        pos: 0,
+        buffer: 0,
+        count: 0,
+    };
+    let mut out = Vec::new();
+    loop {
+        let last = bits.get(1)?;
+        match bits.get(2)? {
+            0 => {
+                // stored block, starting at next byte.
+                bits.buffer = 0;
+                bits.count = 0;
+                let header = bits.data.get(bits.pos..bits.pos + 4)?;
+                let len = usize::from(u16::from_le_bytes([header[0], header[1]]));
+                bits.pos += 4;
+                out.extend_from_slice(bits.data.get(bits.pos..bits.pos + len)?);
+                bits.pos += len;
+            }
+            1 => {
+                let mut lengths = [8; 288];
+                lengths[144..256].iter_mut().for_each(|l| *l = 9);
+                lengths[256..280].iter_mut().for_each(|l| *l = 7);
+                let literals = Huffman::new(&lengths);
+                let distances = Huffman::new(&[5; 30]);
+                codes(&mut bits, &mut out, &literals, &distances)?;
+            }
+            2 => {
+                let literals_count = bits.get(5)? as usize + 257;
+                let distances_count = bits.get(5)? as usize + 1;
+                let code_lengths_count = bits.get(4)? as usize + 4;
+                let mut code_lengths = [0; 19];
+                for i in CODE_LENGTHS_ORDER.iter().take(code_lengths_count) {
+                    code_lengths[*i] = bits.get(3)? as u8;
+                }
+                let code_lengths = Huffman::new(&code_lengths);
+                let mut lengths = Vec::with_capacity(literals_count + distances_count);
+                while lengths.len() < literals_count + distances_count {
+                    let (len, repeat) = match code_lengths.decode(&mut bits)? {
+                        16 => (*lengths.last()?, bits.get(2)? + 3),
+                        17 => (0, bits.get(3)? + 3),
+                        18 => (0, bits.get(7)? + 11),
+                        len => (len as u8, 1),
+                    };
+                    lengths.extend(std::iter::repeat_n(len, repeat as usize));
+                }
+                if lengths.len() > literals_count + distances_count {
+                    return None;
+                }
+                let literals = Huffman::new(&lengths[..literals_count]);
+                let distances = Huffman::new(&lengths[literals_count..]);
+                codes(&mut bits, &mut out, &literals, &distances)?;
+            }
+            _ => return None,
+        }
+        if last == 1 {
+            return Some(out);
+        }
+    }
+}
+
+/// Decode a compressed block: literals, and lengths with distances copying previous output.
+fn codes(
+    bits: &mut Bits,
+    out: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Option<()> {
+    loop {
+        let symbol = usize::from(literals.decode(bits)?);
+        if symbol < 256 {
+            out.push(symbol as u8);
+        } else if symbol == 256 {
+            return Some(());
+        } else {
+            let symbol = symbol - 257;
+            let len = usize::from(*LENGTH_BASE.get(symbol)?)
+                + bits.get(u32::from(LENGTH_EXTRA[symbol]))? as usize;
+            let symbol = usize::from(distances.decode(bits)?);
+            let distance = usize::from(*DISTANCE_BASE.get(symbol)?)
+                + bits.get(u32::from(DISTANCE_EXTRA[symbol]))? as usize;
+            if distance > out.len() {
+                return None;
+            }
+            for _ in 0..len {
+                out.push(out[out.len() - distance]);
+            }
+        }
+    }
+}
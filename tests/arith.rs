@@ -0,0 +1,96 @@
+extern crate rush;
+
+use rush::arith::eval;
+use rush::parse::parse;
+use rush::rush::RuSh;
+
+#[test]
+fn test_operators() {
+    let mut rush = RuSh::default();
+    assert_eq!(eval(&mut rush, ""), Ok(0));
+    assert_eq!(eval(&mut rush, "2 + 3 * 4 - 6 / 4 % 5"), Ok(13));
+    assert_eq!(eval(&mut rush, "(2 + 3) * 4"), Ok(20));
+    assert_eq!(eval(&mut rush, "2 ** 3 ** 2"), Ok(512));
+    assert_eq!(eval(&mut rush, "-2 ** 2"), Ok(4));
+    assert_eq!(eval(&mut rush, "-7 / 2"), Ok(-3));
+    assert_eq!(eval(&mut rush, "1 << 4 | 1 ^ 3 & 2"), Ok(19));
+    assert_eq!(eval(&mut rush, "!0 + !5 + ~0"), Ok(0));
+    assert_eq!(
+        eval(&mut rush, "1 < 2 && 2 <= 2 && 3 > 2 && 2 >= 3 || 4 == 4"),
+        Ok(1)
+    );
+    assert_eq!(eval(&mut rush, "1 != 1 ? 2 : 0 ? 3 : 4"), Ok(4));
+    assert_eq!(eval(&mut rush, "1, 2, 3"), Ok(3));
+}
+
+#[test]
+fn test_numbers() {
+    let mut rush = RuSh::default();
+    assert_eq!(eval(&mut rush, "010 + 0x1f + 0XA"), Ok(8 + 31 + 10));
+    assert_eq!(
+        eval(&mut rush, "2#101 + 16#ff + 36#Z + 64#_ + 64#A"),
+        Ok(5 + 255 + 35 + 63 + 36)
+    );
+    assert!(eval(&mut rush, "08").is_err());
+    assert!(eval(&mut rush, "2#102").is_err());
+    assert!(eval(&mut rush, "65#1").is_err());
+}
+
+#[test]
+fn test_variables() {
+    let mut rush = RuSh::default();
+    parse(&mut rush, "i=3 s=i*2 e=");
+    assert_eq!(eval(&mut rush, "i + unset + e"), Ok(3));
+    assert_eq!(eval(&mut rush, "s + 1"), Ok(7));
+    assert_eq!(eval(&mut rush, "i++ + i"), Ok(7));
+    assert_eq!(eval(&mut rush, "--i"), Ok(3));
+    assert_eq!(eval(&mut rush, "i *= 2, i <<= 1, i -= 2"), Ok(10));
+    assert_eq!(eval(&mut rush, "j = k = 5"), Ok(5));
+    assert_eq!(rush.shell_vars.get("k").unwrap().value.to_string(), "5");
+    // side effects only happen in evaluated operands.
+    assert_eq!(
+        eval(&mut rush, "0 && (i = 1), 1 || i++, i ? j : (j = 0), i"),
+        Ok(10)
+    );
+    assert_eq!(rush.shell_vars.get("j").unwrap().value.to_string(), "5");
+    // increments wrap around.
+    parse(&mut rush, "x=9223372036854775807 y=-9223372036854775808");
+    assert_eq!(eval(&mut rush, "x++"), Ok(i64::MAX));
+    assert_eq!(eval(&mut rush, "x"), Ok(i64::MIN));
+    assert_eq!(eval(&mut rush, "--x"), Ok(i64::MAX));
+    assert_eq!(eval(&mut rush, "++x"), Ok(i64::MIN));
+    assert_eq!(eval(&mut rush, "y--"), Ok(i64::MIN));
+    assert_eq!(eval(&mut rush, "y"), Ok(i64::MAX));
+    parse(&mut rush, "r=r");
+    assert!(eval(&mut rush, "r").is_err());
+}
+
+#[test]
+fn test_errors() {
+    let mut rush = RuSh::default();
+    assert_eq!(
+        eval(&mut rush, "1 / 0"),
+        Err(String::from("division by 0 (error token is \"0\")"))
+    );
+    assert_eq!(eval(&mut rush, "0 && 1 / 0"), Ok(0));
+    assert_eq!(
+        eval(&mut rush, "1 +"),
+        Err(String::from(
+            "syntax error: operand expected (error token is \"\")"
+        ))
+    );
+    assert_eq!(
+        eval(&mut rush, "1 2"),
+        Err(String::from(
+            "syntax error in expression (error token is \"2\")"
+        ))
+    );
+    assert!(eval(&mut rush, "(1").is_err());
+    assert!(eval(&mut rush, "1 ? 2").is_err());
+    assert!(eval(&mut rush, "2 ** -1").is_err());
+    assert!(eval(&mut rush, "1 @ 2").is_err());
+    // an arithmetic expansion error makes a non interactive shell exit with status 1.
+    parse(&mut rush, "A=$((1 / 0)); B=1");
+    assert_eq!(rush.exit, Some(1));
+    assert!(rush.shell_vars.get("B").is_none());
+}
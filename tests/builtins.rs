@@ -0,0 +1,586 @@
+extern crate libc;
+extern crate rush;
+
+use rush::arrays::Index;
+use rush::history;
+use rush::parse::parse;
+use rush::rush::RuSh;
+use rush::variables::{Access, Value};
+use std::fs::File;
+use std::io::Write;
+use std::os::unix::io::AsRawFd;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// stdout is shared by all tests: only one of them redirects it at a time.
+static STDOUT: Mutex<()> = Mutex::new(());
+/// number of the next output file, so that each redirection has its own.
+static OUTPUTS: AtomicUsize = AtomicUsize::new(0);
+
+/// Create a temporary file with given contents, opened for reading.
+fn input(name: &str, contents: &str) -> File {
+    let path = std::env::temp_dir().join(format!("rush_{}_{}", name, std::process::id()));
+    File::create(&path)
+        .unwrap()
+        .write_all(contents.as_bytes())
+        .unwrap();
+    let file = File::open(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    file
+}
+
+#[test]
+fn test_set_options() {
+    let mut rush = RuSh::default();
+    parse(&mut rush, "set -eu -o xtrace");
+    for name in &["errexit", "nounset", "xtrace"] {
+        match rush.set_options.get(name) {
+            Some(v) => assert!(v.set),
+            None => panic!("{} set option should be defined.", name),
+        }
+    }
+    match rush.shell_vars.get("SHELLOPTS") {
+        Some(v) => {
+            assert_eq!(
+                v.gets(),
+                "braceexpand:errexit:hashall:histexpand:monitor:nounset:xtrace"
+            );
+            assert_eq!(v.access, Access::ReadOnly);
+        }
+        None => panic!("SHELLOPTS should be defined."),
+    }
+    parse(&mut rush, "set +ue +o xtrace");
+    match rush.shell_vars.get("SHELLOPTS") {
+        Some(v) => assert_eq!(v.gets(), "braceexpand:hashall:histexpand:monitor"),
+        None => panic!("SHELLOPTS should be defined."),
+    }
+    parse(&mut rush, "set -o doesnotexist");
+    assert_eq!(rush.last_status, 1);
+    parse(&mut rush, "set -Z");
+    assert_eq!(rush.last_status, 2);
+    parse(&mut rush, "set -o vi");
+    parse(&mut rush, "set -o emacs");
+    match rush.set_options.get("vi") {
+        Some(v) => assert!(!v.set),
+        None => panic!("vi set option should be defined."),
+    }
+}
+
+#[test]
+fn test_set_positional() {
+    let mut rush = RuSh::default();
+    parse(&mut rush, "set a 'b c' d");
+    assert_eq!(rush.positional, vec!["rush", "a", "b c", "d"]);
+    parse(&mut rush, "set -f -- -e");
+    assert_eq!(rush.positional, vec!["rush", "-e"]);
+    match rush.set_options.get("errexit") {
+        Some(v) => assert!(!v.set),
+        None => panic!("errexit set option should be defined."),
+    }
+    parse(&mut rush, "FLAGS=$-");
+    match rush.shell_vars.get("FLAGS") {
+        Some(v) => assert_eq!(v.gets(), "fhmBH"),
+        None => panic!("FLAGS should be defined."),
+    }
+    parse(&mut rush, "set --");
+    assert_eq!(rush.positional, vec!["rush"]);
+}
+
+#[test]
+fn test_shopt() {
+    let mut rush = RuSh::default();
+    parse(&mut rush, "shopt -q autocd");
+    assert_eq!(rush.last_status, 1);
+    parse(&mut rush, "shopt -s autocd");
+    assert_eq!(rush.last_status, 0);
+    parse(&mut rush, "shopt -q autocd cmdhist");
+    assert_eq!(rush.last_status, 0);
+    match rush.shell_vars.get("RUSHOPTS") {
+        Some(v) => {
+            assert!(v.gets().starts_with("autocd:"));
+            assert_eq!(v.access, Access::ReadOnly);
+        }
+        None => panic!("RUSHOPTS should be defined."),
+    }
+    parse(&mut rush, "shopt -u autocd");
+    parse(&mut rush, "shopt -q autocd");
+    assert_eq!(rush.last_status, 1);
+    // login_shell may not be changed.
+    parse(&mut rush, "shopt -s login_shell");
+    assert_eq!(rush.last_status, 1);
+    parse(&mut rush, "shopt -q login_shell");
+    assert_eq!(rush.last_status, 1);
+    parse(&mut rush, "shopt -s doesnotexist");
+    assert_eq!(rush.last_status, 1);
+    parse(&mut rush, "shopt -s -o noglob");
+    match rush.set_options.get("noglob") {
+        Some(v) => assert!(v.set),
+        None => panic!("noglob set option should be defined."),
+    }
+    parse(&mut rush, "shopt -su autocd");
+    assert_eq!(rush.last_status, 1);
+}
+
+#[test]
+fn test_read() {
+    let mut rush = RuSh::default();
+    let file = input(
+        "read",
+        "one two  three four \n  lead\\ x\\\ncont  \nx\\y  z\nA:B:C.abcdef",
+    );
+    let fd = file.as_raw_fd();
+    parse(&mut rush, &format!("read -u {} a b", fd));
+    assert_eq!(rush.last_status, 0);
+    match rush.shell_vars.get("a") {
+        Some(v) => assert_eq!(v.gets(), "one"),
+        None => panic!("a should be defined."),
+    }
+    match rush.shell_vars.get("b") {
+        Some(v) => assert_eq!(v.gets(), "two  three four"),
+        None => panic!("b should be defined."),
+    }
+    // backslash escapes next character and continues lines, REPLY is not trimmed.
+    parse(&mut rush, &format!("read -u {}", fd));
+    match rush.shell_vars.get("REPLY") {
+        Some(v) => assert_eq!(v.gets(), "  lead xcont  "),
+        None => panic!("REPLY should be defined."),
+    }
+    parse(&mut rush, &format!("read -r -u {} -a arr", fd));
+    match rush.shell_array_vars.get("arr", &Index::I(0)) {
+        Some(v) => assert_eq!(v, Value::S(String::from("x\\y"))),
+        None => panic!("arr[0] should be defined."),
+    }
+    match rush.shell_array_vars.get("arr", &Index::I(1)) {
+        Some(v) => assert_eq!(v, Value::S(String::from("z"))),
+        None => panic!("arr[1] should be defined."),
+    }
+    parse(&mut rush, &format!("IFS=: read -d . -u {} p q", fd));
+    match rush.shell_vars.get("q") {
+        Some(v) => assert_eq!(v.gets(), "B:C"),
+        None => panic!("q should be defined."),
+    }
+    parse(&mut rush, &format!("read -n 3 -u {} c", fd));
+    match rush.shell_vars.get("c") {
+        Some(v) => assert_eq!(v.gets(), "abc"),
+        None => panic!("c should be defined."),
+    }
+    // end of file gives back 1, partial input is still assigned.
+    parse(&mut rush, &format!("read -u {} c", fd));
+    assert_eq!(rush.last_status, 1);
+    match rush.shell_vars.get("c") {
+        Some(v) => assert_eq!(v.gets(), "def"),
+        None => panic!("c should be defined."),
+    }
+    parse(&mut rush, "read -u 99");
+    assert_eq!(rush.last_status, 1);
+    parse(&mut rush, "read -z");
+    assert_eq!(rush.last_status, 2);
+}
+
+#[test]
+fn test_read_timeout() {
+    let mut fds = [0; 2];
+    assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+    let mut rush = RuSh::default();
+    parse(&mut rush, &format!("read -t 0 -u {}", fds[0]));
+    assert_eq!(rush.last_status, 1);
+    parse(&mut rush, &format!("read -t 0.1 -u {} t", fds[0]));
+    assert!(rush.last_status > 128);
+    unsafe {
+        libc::close(fds[0]);
+        libc::close(fds[1]);
+    }
+}
+
+#[test]
+fn test_printf() {
+    let mut rush = RuSh::default();
+    let cases = [
+        (
+            "'%5.2f|%-5d|%05d|%x|%o|%e|%g' 3.14159 42 -42 255 8 1234.5 0.0001",
+            " 3.14|42   |-0042|ff|10|1.234500e+03|0.0001",
+        ),
+        ("'%d %d,' \"'A\" 0x10 010", "65 16,8 0,"),
+        ("'%s-%s,' a b c", "a-b,c-,"),
+        ("'%.3s|%*d|%-*s|' abcdef 5 1 4 ab", "abc|    1|ab  |"),
+        ("'%i %u' -1 -1", "-1 18446744073709551615"),
+        ("'%+d % d %#x %#o' 5 5 255 8", "+5  5 0xff 010"),
+        ("'%q %q %q' 'a b' \"it's\" ''", "a\\ b it\\'s ''"),
+        ("'%b|' 'x\\101\\0101\\c y'", "xAA"),
+        ("'\\x41\\u00e9\\101\\c'", "AéA\\c"),
+        // mid-year, so that the month is the same in every timezone.
+        ("'%(%Y-%m)T' 15552000", "1970-06"),
+        ("'%5s|%-5s|%.1s|%%' ab ab xyz", "   ab|ab   |x|%"),
+    ];
+    for (args, expected) in cases.iter() {
+        parse(&mut rush, &format!("printf -v out {}", args));
+        match rush.shell_vars.get("out") {
+            Some(v) => assert_eq!(v.value.to_string(), *expected, "printf {}", args),
+            None => panic!("out should be defined."),
+        }
+    }
+    parse(&mut rush, "printf -v out '%d' 3.5");
+    assert_eq!(rush.last_status, 1);
+    match rush.shell_vars.get("out") {
+        Some(v) => assert_eq!(v.geti(), 3),
+        None => panic!("out should be defined."),
+    }
+    // out of range numbers saturate.
+    parse(
+        &mut rush,
+        "printf -v out '%d,%d' 9223372036854775808 -9223372036854775809",
+    );
+    assert_eq!(rush.last_status, 1);
+    match rush.shell_vars.get("out") {
+        Some(v) => assert_eq!(
+            v.value.to_string(),
+            "9223372036854775807,-9223372036854775808"
+        ),
+        None => panic!("out should be defined."),
+    }
+    // widths and precisions out of C int range are not used.
+    parse(
+        &mut rush,
+        "printf -v out '%*d|%*s|%99999999999d|%.99999999999s' -9223372036854775808 1 99999999999 x 2 y",
+    );
+    assert_eq!(rush.last_status, 1);
+    match rush.shell_vars.get("out") {
+        Some(v) => assert_eq!(v.value.to_string(), "1|x|2|y"),
+        None => panic!("out should be defined."),
+    }
+    parse(&mut rush, "printf -v out '%*d|' -3 1");
+    match rush.shell_vars.get("out") {
+        Some(v) => assert_eq!(v.value.to_string(), "1  |"),
+        None => panic!("out should be defined."),
+    }
+    // variables values are used with their type.
+    parse(&mut rush, "f=2.5; i=7");
+    parse(&mut rush, "printf -v out '%.2f %.1f %x' $f \"$i\" ${i}");
+    assert_eq!(rush.last_status, 0);
+    match rush.shell_vars.get("out") {
+        Some(v) => assert_eq!(v.value.to_string(), "2.50 7.0 7"),
+        None => panic!("out should be defined."),
+    }
+    parse(&mut rush, "printf -v out '%z'");
+    assert_eq!(rush.last_status, 1);
+    parse(&mut rush, "printf");
+    assert_eq!(rush.last_status, 2);
+}
+
+/// Run given input, returning what was written to stdout.
+fn output(rush: &mut RuSh, line: &str) -> String {
+    let path = std::env::temp_dir().join(format!(
+        "rush_output_{}_{}",
+        std::process::id(),
+        OUTPUTS.fetch_add(1, Ordering::SeqCst)
+    ));
+    let file = File::create(&path).unwrap();
+    let _lock = STDOUT.lock().unwrap_or_else(|e| e.into_inner());
+    let saved = unsafe { libc::dup(1) };
+    unsafe { libc::dup2(file.as_raw_fd(), 1) };
+    parse(rush, line);
+    unsafe {
+        libc::dup2(saved, 1);
+        libc::close(saved);
+    }
+    let out = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    out
+}
+
+#[test]
+fn test_echo() {
+    let mut rush = RuSh::default();
+    assert_eq!(output(&mut rush, "echo a  'b  c'"), "a b  c\n");
+    assert_eq!(output(&mut rush, "echo -n a b"), "a b");
+    assert_eq!(output(&mut rush, "echo 'a\\tb'"), "a\\tb\n");
+    assert_eq!(
+        output(&mut rush, "echo -e 'a\\tb\\0101\\101\\x41\\u00e9'"),
+        "a\tbA\\101Aé\n"
+    );
+    assert_eq!(output(&mut rush, "echo -e 'a\\cb' c"), "a");
+    assert_eq!(output(&mut rush, "echo -nE 'a\\n'"), "a\\n");
+    assert_eq!(output(&mut rush, "echo -x -- -n"), "-x -- -n\n");
+    assert_eq!(output(&mut rush, "echo"), "\n");
+    parse(&mut rush, "shopt -s xpg_echo");
+    assert_eq!(output(&mut rush, "echo 'a\\tb'"), "a\tb\n");
+    assert_eq!(output(&mut rush, "echo -E 'a\\tb'"), "a\\tb\n");
+}
+
+#[test]
+fn test_mapfile() {
+    let mut rush = RuSh::default();
+    let file = input("mapfile", "zero\none\ntwo\nthree\nfour\n");
+    parse(
+        &mut rush,
+        &format!("mapfile -t -s 1 -n 3 -u {} lines", file.as_raw_fd()),
+    );
+    assert_eq!(rush.last_status, 0);
+    for (i, expected) in ["one", "two", "three"].iter().enumerate() {
+        match rush.shell_array_vars.get("lines", &Index::I(i)) {
+            Some(v) => assert_eq!(v, Value::S(expected.to_string())),
+            None => panic!("lines[{}] should be defined.", i),
+        }
+    }
+    assert!(rush.shell_array_vars.get("lines", &Index::I(3)).is_none());
+    // what remains, appended from origin 5, with a callback every 1 line.
+    parse(
+        &mut rush,
+        &format!(
+            "readarray -O 5 -C 'set --' -c 1 -u {} lines",
+            file.as_raw_fd()
+        ),
+    );
+    match rush.shell_array_vars.get("lines", &Index::I(5)) {
+        Some(v) => assert_eq!(v, Value::S("four\n".to_string())),
+        None => panic!("lines[5] should be defined."),
+    }
+    assert_eq!(rush.positional, vec!["rush", "5", "four\n"]);
+    match rush.shell_array_vars.get("lines", &Index::I(0)) {
+        Some(v) => assert_eq!(v, Value::S("one".to_string())),
+        None => panic!("lines[0] should be kept with -O."),
+    }
+    let file = input("mapfile_delim", "a:b:c");
+    parse(
+        &mut rush,
+        &format!("mapfile -t -d : -u {}", file.as_raw_fd()),
+    );
+    for (i, expected) in ["a", "b", "c"].iter().enumerate() {
+        match rush.shell_array_vars.get("MAPFILE", &Index::I(i)) {
+            Some(v) => assert_eq!(v, Value::S(expected.to_string())),
+            None => panic!("MAPFILE[{}] should be defined.", i),
+        }
+    }
+    parse(&mut rush, "mapfile -u 99 lines");
+    assert_eq!(rush.last_status, 1);
+    parse(&mut rush, "mapfile -c 0 lines");
+    assert_eq!(rush.last_status, 1);
+}
+
+#[test]
+fn test_getopts() {
+    let mut rush = RuSh::default();
+    let cases = [
+        (
+            "set -- -ab -cval -c next -d; while getopts ab:cd: opt; do printf -v out '%s%s[%s]' \"$out\" \"$opt\" \"$OPTARG\"; done",
+            "a[]b[-cval]c[]",
+            4,
+        ),
+        (
+            "while getopts :xb: opt -q -b; do printf -v out '%s%s[%s]' \"$out\" \"$opt\" \"$OPTARG\"; done",
+            "?[q]:[b]",
+            3,
+        ),
+        (
+            "OPTERR=0; while getopts x opt -q -x -- -x; do printf -v out '%s%s[%s]' \"$out\" \"$opt\" \"$OPTARG\"; done",
+            "?[]x[]",
+            4,
+        ),
+    ];
+    for (line, expected, optind) in cases.iter() {
+        parse(&mut rush, "OPTIND=1; out=");
+        parse(&mut rush, line);
+        assert_eq!(rush.last_status, 0);
+        match rush.shell_vars.get("out") {
+            Some(v) => assert_eq!(v.value.to_string(), *expected, "{}", line),
+            None => panic!("out should be defined."),
+        }
+        match rush.shell_vars.get("OPTIND") {
+            Some(v) => assert_eq!(v.geti(), *optind, "{}", line),
+            None => panic!("OPTIND should be defined."),
+        }
+        match rush.shell_vars.get("opt") {
+            Some(v) => assert_eq!(v.gets(), "?"),
+            None => panic!("opt should be defined."),
+        }
+    }
+    // arguments changed while an argument was being scanned: next call starts from the new one.
+    parse(
+        &mut rush,
+        "OPTIND=1; OPTERR=0; getopts abc opt -abc; getopts abc opt -x",
+    );
+    assert_eq!(rush.last_status, 0);
+    match rush.shell_vars.get("opt") {
+        Some(v) => assert_eq!(v.gets(), "?"),
+        None => panic!("opt should be defined."),
+    }
+    parse(&mut rush, "getopts x");
+    assert_eq!(rush.last_status, 2);
+}
+
+#[test]
+fn test_alias() {
+    let mut rush = RuSh::default();
+    parse(&mut rush, "unalias -a; alias b=\"it's\" a='x y'");
+    assert_eq!(rush.last_status, 0);
+    assert_eq!(
+        output(&mut rush, "alias"),
+        "alias a='x y'\nalias b='it'\\''s'\n"
+    );
+    assert_eq!(output(&mut rush, "alias b"), "alias b='it'\\''s'\n");
+    match rush
+        .shell_array_vars
+        .get("RUSH_ALIASES", &Index::A("a".to_string()))
+    {
+        Some(v) => assert_eq!(v, Value::S("x y".to_string())),
+        None => panic!("RUSH_ALIASES[a] should be defined."),
+    }
+    parse(&mut rush, "alias nope");
+    assert_eq!(rush.last_status, 1);
+    parse(&mut rush, "alias 'a/b=c'");
+    assert_eq!(rush.last_status, 1);
+    parse(&mut rush, "unalias a nope");
+    assert_eq!(rush.last_status, 1);
+    assert_eq!(output(&mut rush, "alias -p"), "alias b='it'\\''s'\n");
+    parse(&mut rush, "unalias");
+    assert_eq!(rush.last_status, 2);
+}
+
+/// Run a command the way an interactive shell does, adding it to history first. Returns its output.
+fn interactive(rush: &mut RuSh, line: &str) -> String {
+    history::add(rush, line);
+    output(rush, line)
+}
+
+#[test]
+fn test_history() {
+    let mut rush = RuSh::default();
+    parse(&mut rush, "set -o history");
+    interactive(&mut rush, "echo a");
+    interactive(&mut rush, "echo b");
+    assert_eq!(
+        interactive(&mut rush, "history"),
+        "    1  echo a\n    2  echo b\n    3  history\n"
+    );
+    assert_eq!(interactive(&mut rush, "history 1"), "    4  history 1\n");
+    assert_eq!(
+        interactive(&mut rush, "history -p '!1' '!?b?:1'"),
+        "echo a\nb\n"
+    );
+    interactive(&mut rush, "history -d 1-2; history -d -1");
+    assert_eq!(rush.last_status, 0);
+    assert_eq!(rush.history.len(), 2);
+    interactive(&mut rush, "history -d 9");
+    assert_eq!(rush.last_status, 1);
+    interactive(&mut rush, "history -s x y");
+    assert_eq!(rush.history.entries.last().unwrap().line, "x y");
+    interactive(&mut rush, "history 1 2");
+    assert_eq!(rush.last_status, 2);
+    interactive(&mut rush, "history x");
+    assert_eq!(rush.last_status, 1);
+    interactive(&mut rush, "history -c");
+    assert!(rush.history.is_empty());
+    // history file.
+    let path = std::env::temp_dir().join(format!("rush_histfile_{}", std::process::id()));
+    let path = path.to_str().unwrap();
+    std::fs::write(path, "one\ntwo\n").unwrap();
+    interactive(&mut rush, &format!("history -r {}", path));
+    assert_eq!(rush.history.len(), 3);
+    std::fs::write(path, "one\ntwo\nthree\n").unwrap();
+    interactive(&mut rush, &format!("history -n {}", path));
+    assert_eq!(rush.history.entries.last().unwrap().line, "three");
+    interactive(&mut rush, &format!("history -w {}", path));
+    assert_eq!(std::fs::read_to_string(path).unwrap().lines().count(), 6);
+    interactive(&mut rush, "echo c");
+    interactive(&mut rush, &format!("history -a {}", path));
+    assert!(std::fs::read_to_string(path)
+        .unwrap()
+        .ends_with(&format!("echo c\nhistory -a {}\n", path)));
+    interactive(&mut rush, "history -an");
+    assert_eq!(rush.last_status, 1);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_fc() {
+    let mut rush = RuSh::default();
+    parse(&mut rush, "set -o history");
+    interactive(&mut rush, "echo one");
+    interactive(&mut rush, "echo two");
+    assert_eq!(
+        interactive(&mut rush, "fc -l"),
+        "1\t echo one\n2\t echo two\n"
+    );
+    assert_eq!(
+        interactive(&mut rush, "fc -lnr -3 ec"),
+        "\t echo two\n\t echo one\n"
+    );
+    assert_eq!(interactive(&mut rush, "fc -s one=1 1"), "1\n");
+    assert_eq!(rush.history.entries.last().unwrap().line, "echo 1");
+    assert_eq!(interactive(&mut rush, "fc -e - e"), "1\n");
+    interactive(&mut rush, "fc -s nope");
+    assert_eq!(rush.last_status, 1);
+    interactive(&mut rush, "FCEDIT='sed -i s/two/deux/'");
+    assert_eq!(interactive(&mut rush, "fc 2"), "deux\n");
+    assert_eq!(rush.history.entries.last().unwrap().line, "echo deux");
+    // the edited file is only readable by its owner.
+    assert_eq!(interactive(&mut rush, "fc -e 'stat -c %a'"), "600\ndeux\n");
+    interactive(&mut rush, "fc -e false");
+    assert_eq!(rush.last_status, 1);
+}
+
+#[test]
+fn test_bind() {
+    let mut rush = RuSh::default();
+    parse(
+        &mut rush,
+        "bind '\"\\C-t\": backward-word' 'Meta-l: \"ls -l\"'",
+    );
+    assert_eq!(rush.last_status, 0);
+    assert!(output(&mut rush, "bind -p").contains(
+        "\"\\C-t\": backward-word\n\"\\eb\": backward-word\n\"\\e<\": beginning-of-history\n"
+    ));
+    assert!(output(&mut rush, "bind -p").contains("# history-search-backward (not bound)\n"));
+    assert_eq!(output(&mut rush, "bind -s"), "\"\\el\": \"ls -l\"\n");
+    assert_eq!(output(&mut rush, "bind -S"), "\\el outputs ls -l\n");
+    assert_eq!(
+        output(&mut rush, "bind -q backward-word"),
+        "backward-word can be invoked via \"\\C-t\", \"\\eb\".\n"
+    );
+    // line editor bindings stay, unless the key is bound otherwise.
+    parse(&mut rush, "bind -u backward-word");
+    assert_eq!(
+        output(&mut rush, "bind -q backward-word"),
+        "backward-word can be invoked via \"\\eb\".\n"
+    );
+    parse(&mut rush, "bind '\"\\C-f\": backward-char'");
+    assert_eq!(
+        output(&mut rush, "bind -q forward-char"),
+        "forward-char can be invoked via \"\\e[C\".\n"
+    );
+    assert_eq!(rush.last_status, 0);
+    assert_eq!(
+        output(&mut rush, "bind -q history-search-backward"),
+        "history-search-backward is not bound to any keys.\n"
+    );
+    assert_eq!(rush.last_status, 1);
+    parse(&mut rush, "bind -r '\\el'");
+    assert_eq!(output(&mut rush, "bind -s"), "");
+    // keymaps.
+    parse(&mut rush, "bind -m vi-insert '\"\\C-e\": end-of-line'");
+    assert_eq!(
+        output(&mut rush, "bind -m vi-insert -P").lines().nth(16),
+        Some("end-of-line can be found on \"\\C-e\".")
+    );
+    parse(&mut rush, "bind -m emacs-meta '\"\\C-e\": end-of-line'");
+    assert_eq!(rush.last_status, 1);
+    // variables.
+    parse(
+        &mut rush,
+        "bind 'set bell-style none' 'set editing-mode vi'",
+    );
+    assert!(output(&mut rush, "bind -v").starts_with("set bell-style none\nset completion-query-items 100\nset editing-mode vi\nset keymap vi-insert\n"));
+    assert!(rush.set_options.get("vi").unwrap().get());
+    assert!(!rush.set_options.get("emacs").unwrap().get());
+    parse(&mut rush, "set -o emacs");
+    assert!(output(&mut rush, "bind -V").contains("editing-mode is set to `emacs'\n"));
+    // errors.
+    parse(&mut rush, "bind '\"\\C-t\": nope'");
+    assert_eq!(rush.last_status, 1);
+    parse(&mut rush, "bind '\"\\C-x\\C-r\": undo'");
+    assert_eq!(rush.last_status, 1);
+    parse(&mut rush, "bind -x '\"\\C-t\": ls'");
+    assert_eq!(rush.last_status, 1);
+    parse(&mut rush, "bind -k");
+    assert_eq!(rush.last_status, 2);
+}
@@ -0,0 +1,306 @@
+extern crate rush;
+
+use rush::arrays::Index;
+use rush::completion::{self, split};
+use rush::parse::parse;
+use rush::rush::RuSh;
+use std::fs;
+
+/// Values of an indexed array, in index order.
+fn array(rush: &RuSh, name: &str) -> Vec<String> {
+    (0..)
+        .map_while(|i| rush.shell_array_vars.get(name, &Index::I(i)))
+        .map(|v| v.to_string())
+        .collect()
+}
+
+/// Replacements completion gives for a line, the cursor being at its end.
+fn complete(rush: &mut RuSh, line: &str) -> (usize, Vec<String>) {
+    let (start, candidates) = completion::complete(rush, line, line.len());
+    (
+        start,
+        candidates.into_iter().map(|c| c.replacement).collect(),
+    )
+}
+
+/// A directory with a few files.
+fn directory(name: &str) -> String {
+    let dir = std::env::temp_dir().join(format!("rush_completion_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("subdir")).unwrap();
+    for file in &["alpha.txt", "alpha.rs", "beta file", ".hidden"] {
+        fs::write(dir.join(file), "").unwrap();
+    }
+    dir.to_str().unwrap().to_string()
+}
+
+#[test]
+fn test_split() {
+    let breaks = " \t\n\"'><=;|&(:";
+    assert_eq!(split("echo a", 6, breaks), (vec![(0, 4), (5, 6)], 1));
+    assert_eq!(
+        split("echo a ", 7, breaks),
+        (vec![(0, 4), (5, 6), (7, 7)], 2)
+    );
+    assert_eq!(split("echo \"a b", 9, breaks), (vec![(0, 4), (5, 9)], 1));
+    assert_eq!(split("echo a\\ b", 9, breaks), (vec![(0, 4), (5, 9)], 1));
+    assert_eq!(
+        split("cmd host:", 9, breaks),
+        (vec![(0, 3), (4, 8), (8, 9), (9, 9)], 3)
+    );
+    assert_eq!(
+        split("cmd >>out", 9, breaks),
+        (vec![(0, 3), (4, 6), (6, 9)], 2)
+    );
+    assert_eq!(split("", 0, breaks), (vec![(0, 0)], 0));
+}
+
+#[test]
+fn test_compgen() {
+    let mut rush = RuSh::default();
+    parse(&mut rush, "compgen -V m -W 'start stop restart' st");
+    assert_eq!(array(&rush, "m"), vec!["start", "stop"]);
+    assert_eq!(rush.last_status, 0);
+    parse(&mut rush, "compgen -V m -W 'a\\ b \"c d\" e' ''");
+    assert_eq!(array(&rush, "m"), vec!["a b", "c d", "e"]);
+    parse(&mut rush, "compgen -V m -W 'start stop' x");
+    assert!(array(&rush, "m").is_empty());
+    assert_eq!(rush.last_status, 1);
+    parse(&mut rush, "compgen -V m -A shopt -X '!*glob*' ''");
+    assert_eq!(
+        array(&rush, "m"),
+        vec![
+            "dotglob",
+            "extglob",
+            "failglob",
+            "globasciiranges",
+            "globstar",
+            "nocaseglob",
+            "nullglob"
+        ]
+    );
+    parse(&mut rush, "compgen -V m -A setopt -X '*o*' e");
+    assert_eq!(array(&rush, "m"), vec!["emacs", "errexit", "errtrace"]);
+    parse(&mut rush, "compgen -V m -b -P '<' -S '>' compg");
+    assert_eq!(array(&rush, "m"), vec!["<compgen>"]);
+    parse(&mut rush, "compgen -V m -k th");
+    assert_eq!(array(&rush, "m"), vec!["then"]);
+    parse(&mut rush, "compvar1=1; compvar2=2; compgen -V m -v compvar");
+    assert_eq!(array(&rush, "m"), vec!["compvar1", "compvar2"]);
+    parse(&mut rush, "alias compll='ls -l'; compgen -V m -a compl");
+    assert_eq!(array(&rush, "m"), vec!["compll"]);
+    parse(&mut rush, "compgen -V m -A signal SIGT");
+    assert_eq!(
+        array(&rush, "m"),
+        vec!["SIGTERM", "SIGTRAP", "SIGTSTP", "SIGTTIN", "SIGTTOU"]
+    );
+    parse(&mut rush, "_words() { COMPREPLY=(\"$2-a\" \"$2-b\"); }");
+    parse(&mut rush, "compgen -V m -F _words x");
+    assert_eq!(array(&rush, "m"), vec!["x-a", "x-b"]);
+    parse(
+        &mut rush,
+        "_list() { w='start stop'; COMPREPLY=( $(compgen -W \"$w\" -- \"$2\") ); }",
+    );
+    parse(&mut rush, "compgen -V m -F _list sta");
+    assert_eq!(array(&rush, "m"), vec!["start"]);
+    parse(
+        &mut rush,
+        "_lines() { echo one; echo \"$2\"; }; compgen -V m -C _lines x",
+    );
+    assert_eq!(array(&rush, "m"), vec!["one", "x"]);
+    parse(&mut rush, "compgen -o nosuch");
+    assert_eq!(rush.last_status, 2);
+    parse(&mut rush, "compgen -A nosuch");
+    assert_eq!(rush.last_status, 2);
+}
+
+#[test]
+fn test_compgen_files() {
+    let mut rush = RuSh::default();
+    let dir = directory("compgen");
+    parse(&mut rush, &format!("compgen -V m -f {}/a", dir));
+    assert_eq!(
+        array(&rush, "m"),
+        vec![format!("{}/alpha.rs", dir), format!("{}/alpha.txt", dir)]
+    );
+    parse(&mut rush, &format!("compgen -V m -d {}/", dir));
+    assert_eq!(array(&rush, "m"), vec![format!("{}/subdir", dir)]);
+    parse(&mut rush, &format!("compgen -V m -G '{}/*.txt'", dir));
+    assert_eq!(array(&rush, "m"), vec![format!("{}/alpha.txt", dir)]);
+    parse(&mut rush, &format!("compgen -V m -G '{}/*'", dir));
+    assert_eq!(array(&rush, "m").len(), 4);
+    parse(
+        &mut rush,
+        &format!("compgen -V m -W none -o dirnames {}/s", dir),
+    );
+    assert_eq!(array(&rush, "m"), vec![format!("{}/subdir", dir)]);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_complete() {
+    let mut rush = RuSh::default();
+    parse(&mut rush, "complete -W 'start stop' -o nospace svc");
+    parse(&mut rush, "complete -F _f -A signal kill2");
+    parse(&mut rush, "complete -d -E");
+    assert_eq!(
+        rush.completions["svc"].print("svc"),
+        "complete -o nospace -W 'start stop' svc"
+    );
+    assert_eq!(
+        rush.completions["kill2"].print("kill2"),
+        "complete -A signal -F _f kill2"
+    );
+    assert!(rush.completions.contains_key(completion::EMPTY));
+    parse(&mut rush, "complete -r svc nosuch");
+    assert_eq!(rush.last_status, 1);
+    assert!(!rush.completions.contains_key("svc"));
+    parse(&mut rush, "complete -p nosuch");
+    assert_eq!(rush.last_status, 1);
+    parse(&mut rush, "complete -F _f");
+    assert_eq!(rush.last_status, 2);
+    parse(&mut rush, "complete -r");
+    assert!(rush.completions.is_empty());
+}
+
+#[test]
+fn test_complete_line() {
+    let mut rush = RuSh::default();
+    parse(&mut rush, "complete -W 'start stop status' svc");
+    assert_eq!(
+        complete(&mut rush, "svc st"),
+        (
+            4,
+            vec![
+                "start".to_string(),
+                "status".to_string(),
+                "stop".to_string()
+            ]
+        )
+    );
+    assert_eq!(
+        complete(&mut rush, "svc sta"),
+        (4, vec!["start".to_string(), "status".to_string()])
+    );
+    assert_eq!(
+        complete(&mut rush, "echo a && /usr/bin/svc sto"),
+        (23, vec!["stop ".to_string()])
+    );
+    parse(&mut rush, "compopt -o nospace svc");
+    assert_eq!(
+        complete(&mut rush, "svc sto"),
+        (4, vec!["stop".to_string()])
+    );
+    // completion function sees COMP_* variables, which are unset afterwards.
+    parse(
+        &mut rush,
+        "_f() { COMPREPLY=(\"${COMP_WORDS[0]}/$COMP_CWORD/$COMP_LINE/$COMP_POINT/$COMP_TYPE/$3\"); }; complete -F _f cmd",
+    );
+    assert_eq!(
+        complete(&mut rush, "true; cmd a=b"),
+        (12, vec!["cmd/3/cmd\\ a=b/7/9/= ".to_string()])
+    );
+    assert!(rush.shell_vars.get("COMP_LINE").is_none());
+    // compopt changes options of the completion being generated.
+    parse(
+        &mut rush,
+        "_g() { compopt -o nospace; COMPREPLY=(x); }; complete -F _g cmd2",
+    );
+    assert_eq!(complete(&mut rush, "cmd2 "), (5, vec!["x".to_string()]));
+    assert!(!rush.completions["cmd2"].option("nospace"));
+    parse(&mut rush, "compopt -o nospace");
+    assert_eq!(rush.last_status, 1);
+    // a function exiting with 124 after defining a specification has completion tried again.
+    parse(
+        &mut rush,
+        "_load() { complete -W loaded \"$1\"; return 124; }; complete -D -F _load",
+    );
+    assert_eq!(
+        complete(&mut rush, "lazy l"),
+        (5, vec!["loaded ".to_string()])
+    );
+    // empty line specification.
+    parse(&mut rush, "complete -W 'empty' -E");
+    assert_eq!(complete(&mut rush, ""), (0, vec!["empty ".to_string()]));
+    // command name completion.
+    let (_, commands) = complete(&mut rush, "compg");
+    assert_eq!(commands, vec!["compgen ".to_string()]);
+}
+
+#[test]
+fn test_complete_files() {
+    let mut rush = RuSh::default();
+    let dir = directory("complete");
+    let (start, candidates) =
+        completion::complete(&mut rush, &format!("cat {}/b", dir), dir.len() + 6);
+    assert_eq!(start, 4);
+    assert_eq!(candidates[0].replacement, format!("{}/beta\\ file ", dir));
+    assert_eq!(candidates[0].display, "beta file");
+    let line = format!("cat {}/su", dir);
+    let (_, candidates) = completion::complete(&mut rush, &line, line.len());
+    assert_eq!(candidates[0].replacement, format!("{}/subdir/", dir));
+    let line = format!("cat {}/", dir);
+    let (_, candidates) = completion::complete(&mut rush, &line, line.len());
+    assert_eq!(candidates.len(), 5);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_default_completion() {
+    let mut rush = RuSh::default();
+    parse(&mut rush, "rush_completion_var=1");
+    assert_eq!(
+        complete(&mut rush, "echo $rush_completion_v"),
+        (5, vec!["$rush_completion_var ".to_string()])
+    );
+    assert_eq!(
+        complete(&mut rush, "echo ${rush_completion_v"),
+        (5, vec!["${rush_completion_var} ".to_string()])
+    );
+    assert_eq!(
+        complete(&mut rush, "ls ~roo"),
+        (3, vec!["~root/".to_string()])
+    );
+    let dir = directory("default");
+    let hosts = format!("{}/hosts", dir);
+    fs::write(
+        &hosts,
+        "127.0.0.1 localhost rushhost\n# 10.0.0.1 commented\n",
+    )
+    .unwrap();
+    parse(&mut rush, &format!("HOSTFILE={}", hosts));
+    assert_eq!(
+        complete(&mut rush, "ssh user@rush"),
+        (9, vec!["rushhost ".to_string()])
+    );
+    parse(&mut rush, "COMP_WORDBREAKS=' '");
+    assert_eq!(
+        complete(&mut rush, "ssh @rush"),
+        (4, vec!["@rushhost ".to_string()])
+    );
+    parse(&mut rush, "shopt -u hostcomplete");
+    assert!(complete(&mut rush, "ssh @rush").1.is_empty());
+    // empty command name.
+    assert!(complete(&mut rush, "").1.len() > 1);
+    parse(&mut rush, "shopt -s no_empty_cmd_completion");
+    assert!(complete(&mut rush, "").1.is_empty());
+    // FIGNORE suffixes, ignored when they are the only matches unless force_fignore is set.
+    parse(&mut rush, "FIGNORE=.rs:.o");
+    assert_eq!(
+        complete(&mut rush, &format!("cat {}/alp", dir)).1,
+        vec![format!("{}/alpha.txt ", dir)]
+    );
+    parse(&mut rush, "FIGNORE=.txt:.rs");
+    assert!(complete(&mut rush, &format!("cat {}/alp", dir))
+        .1
+        .is_empty());
+    parse(&mut rush, "shopt -u force_fignore");
+    assert_eq!(complete(&mut rush, &format!("cat {}/alp", dir)).1.len(), 2);
+    // bashdefault option falls back to default completion.
+    parse(&mut rush, "complete -W none -o bashdefault cmd");
+    assert_eq!(
+        complete(&mut rush, "cmd $rush_completion_v"),
+        (4, vec!["$rush_completion_var ".to_string()])
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,300 @@
+extern crate rush;
+
+use rush::arrays::Index;
+use rush::opt::OptionRW;
+use rush::parse::parse;
+use rush::rush::RuSh;
+use rush::variables::{Access, Value};
+use std::os::unix::io::AsRawFd;
+
+#[test]
+fn test_assignment() {
+    let mut rush = RuSh::default();
+    parse(&mut rush, "TESTI=42 TESTF=-4.5 TESTS=abc");
+    match rush.shell_vars.get("TESTI") {
+        Some(v) => assert_eq!(v.geti(), 42),
+        None => panic!("TESTI should be defined."),
+    }
+    match rush.shell_vars.get("TESTF") {
+        Some(v) => assert_eq!(v.getf(), -4.5),
+        None => panic!("TESTF should be defined."),
+    }
+    match rush.shell_vars.get("TESTS") {
+        Some(v) => {
+            assert_eq!(v.gets(), "abc");
+            assert!(!v.export);
+        }
+        None => panic!("TESTS should be defined."),
+    }
+}
+
+#[test]
+fn test_array_assignment() {
+    let mut rush = RuSh::default();
+    parse(&mut rush, "w='c d'; TESTA=(a \"b b\" $w\n 4 # comment\n)");
+    assert_eq!(rush.last_status, 0);
+    assert_eq!(
+        rush.shell_array_vars.get("TESTA", &Index::I(1)),
+        Some(Value::S(String::from("b b")))
+    );
+    assert_eq!(
+        rush.shell_array_vars.get("TESTA", &Index::I(4)),
+        Some(Value::I(4))
+    );
+    // elements not given anymore are removed.
+    parse(&mut rush, "TESTA=()");
+    assert!(rush.shell_array_vars.get("TESTA", &Index::I(0)).is_none());
+    parse(&mut rush, "TESTA[1]=(x)");
+    assert_eq!(rush.last_status, 1);
+}
+
+#[test]
+fn test_prefix_assignment() {
+    let mut rush = RuSh::default();
+    parse(&mut rush, "TESTPREFIX=1 true");
+    assert_eq!(rush.last_status, 0);
+    if rush.shell_vars.get("TESTPREFIX").is_some() {
+        panic!("TESTPREFIX should only be set for the command.");
+    }
+    parse(&mut rush, "TESTPREFIX=1 sh -c 'test \"$TESTPREFIX\" = 1'");
+    assert_eq!(rush.last_status, 0);
+    // a readonly variable leaves other prefix assignments undone.
+    parse(&mut rush, "f() { :; }; TESTPREFIX=1 UID=0 f");
+    assert_eq!(rush.last_status, 1);
+    assert!(rush.shell_vars.get("TESTPREFIX").is_none());
+}
+
+#[test]
+fn test_export() {
+    let mut rush = RuSh::default();
+    parse(&mut rush, "export TESTEXPORT=abc");
+    assert_eq!(rush.shell_vars.get_export("TESTEXPORT"), Some(true));
+    parse(&mut rush, "sh -c 'test \"$TESTEXPORT\" = abc'");
+    assert_eq!(rush.last_status, 0);
+    parse(&mut rush, "export -n TESTEXPORT");
+    assert_eq!(rush.shell_vars.get_export("TESTEXPORT"), Some(false));
+    parse(&mut rush, "sh -c 'test -z \"$TESTEXPORT\"'");
+    assert_eq!(rush.last_status, 0);
+}
+
+#[test]
+fn test_allexport() {
+    let mut rush = RuSh::default();
+    rush.set_options.set(
+        String::from("allexport"),
+        OptionRW {
+            set: true,
+            access: Access::ReadWrite,
+        },
+    );
+    parse(&mut rush, "TESTALLEXPORT=1");
+    assert_eq!(rush.shell_vars.get_export("TESTALLEXPORT"), Some(true));
+}
+
+#[test]
+fn test_xtrace() {
+    let path = std::env::temp_dir().join(format!("rush_xtrace_{}", std::process::id()));
+    let file = std::fs::File::create(&path).unwrap();
+    let mut rush = RuSh::default();
+    parse(
+        &mut rush,
+        &format!("RUSH_XTRACEFD={}; PS4='+ '", file.as_raw_fd()),
+    );
+    parse(&mut rush, "set -x");
+    parse(&mut rush, "x='a b' true $x c");
+    rush.nesting = 2;
+    parse(&mut rush, "true");
+    rush.nesting = 0;
+    parse(&mut rush, "set +x");
+    parse(&mut rush, "true");
+    let trace = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(trace, "+ x='a b' true c\n+++ true\n+ set +x\n");
+}
+
+#[test]
+fn test_compound_commands() {
+    let mut rush = RuSh::default();
+    parse(
+        &mut rush,
+        "if false; then X=if; elif true; then X=elif; else X=else; fi",
+    );
+    match rush.shell_vars.get("X") {
+        Some(v) => assert_eq!(v.gets(), "elif"),
+        None => panic!("X should be defined."),
+    }
+    parse(&mut rush, "I=a\nwhile test $I != aaa\ndo\n  I=${I}a\ndone");
+    match rush.shell_vars.get("I") {
+        Some(v) => assert_eq!(v.gets(), "aaa"),
+        None => panic!("I should be defined."),
+    }
+    parse(&mut rush, "until test $I = a; do I=${I%a}; done");
+    match rush.shell_vars.get("I") {
+        Some(v) => assert_eq!(v.gets(), "a"),
+        None => panic!("I should be defined."),
+    }
+    parse(&mut rush, "{ Y=1; false; }");
+    assert_eq!(rush.last_status, 1);
+    match rush.shell_vars.get("Y") {
+        Some(v) => assert_eq!(v.geti(), 1),
+        None => panic!("Y should be defined."),
+    }
+}
+
+#[test]
+fn test_and_or_pipelines() {
+    let mut rush = RuSh::default();
+    parse(&mut rush, "false && A=1 || B=2");
+    assert!(rush.shell_vars.get("A").is_none());
+    assert!(rush.shell_vars.get("B").is_some());
+    parse(&mut rush, "true || C=3");
+    assert!(rush.shell_vars.get("C").is_none());
+    parse(&mut rush, "! true");
+    assert_eq!(rush.last_status, 1);
+    parse(&mut rush, "! false");
+    assert_eq!(rush.last_status, 0);
+    parse(&mut rush, "echo abc | grep -q b");
+    assert_eq!(rush.last_status, 0);
+    parse(&mut rush, "echo abc |\n grep -q d");
+    assert_eq!(rush.last_status, 1);
+    parse(&mut rush, "false | true");
+    assert_eq!(rush.last_status, 0);
+    parse(&mut rush, "set -o pipefail; false | true");
+    assert_eq!(rush.last_status, 1);
+}
+
+#[test]
+fn test_nounset() {
+    let mut rush = RuSh::default();
+    parse(&mut rush, "set -u; A=${UNSET:-default} B=$@");
+    assert!(rush.exit.is_none());
+    parse(&mut rush, "C=$UNSET; D=1");
+    assert_eq!(rush.exit, Some(127));
+    assert!(rush.shell_vars.get("D").is_none());
+    // an interactive shell only aborts current input.
+    let mut rush = RuSh {
+        interactive: true,
+        ..Default::default()
+    };
+    parse(&mut rush, "set -u; C=$UNSET; D=1");
+    assert!(rush.exit.is_none());
+    assert_eq!(rush.last_status, 1);
+    assert!(rush.shell_vars.get("D").is_none());
+    parse(&mut rush, "D=1");
+    assert!(rush.shell_vars.get("D").is_some());
+}
+
+#[test]
+fn test_errexit() {
+    let mut rush = RuSh::default();
+    parse(
+        &mut rush,
+        "set -e; false && true; true && false || true; ! true; if false; then :; fi",
+    );
+    assert!(rush.exit.is_none());
+    parse(
+        &mut rush,
+        "while false; do :; done; { false && true; }; false | true",
+    );
+    assert!(rush.exit.is_none());
+    parse(&mut rush, "A=1; true | false; B=2");
+    assert_eq!(rush.exit, Some(1));
+    assert!(rush.shell_vars.get("A").is_some());
+    assert!(rush.shell_vars.get("B").is_none());
+}
+
+#[test]
+fn test_alias() {
+    let mut rush = RuSh::default();
+    let get = |rush: &RuSh, name: &str| match rush.shell_vars.get(name) {
+        Some(v) => v.value.to_string(),
+        None => panic!("{} should be defined.", name),
+    };
+    parse(
+        &mut rush,
+        "alias pv='printf -v v' p='printf -v v ' fmt=\"'%s-%s'\"",
+    );
+    parse(&mut rush, "pv '%s' abc");
+    assert_eq!(get(&rush, "v"), "abc");
+    // an alias value ending with a blank makes next word checked too.
+    parse(&mut rush, "p fmt a b");
+    assert_eq!(get(&rush, "v"), "a-b");
+    // an alias is not expanded again within its own expansion.
+    parse(&mut rush, "alias printf='printf -v w'");
+    parse(&mut rush, "printf '%s' z");
+    assert_eq!(get(&rush, "w"), "z");
+    // aliases are expanded as input is read: one defined on a line applies from next line.
+    parse(&mut rush, "alias two='v=1; w=2'; two");
+    assert_eq!(rush.last_status, 127);
+    parse(&mut rush, "two");
+    assert_eq!(get(&rush, "v"), "1");
+    assert_eq!(get(&rush, "w"), "2");
+    // an alias may open a compound command.
+    parse(&mut rush, "alias cond='if true; then'");
+    parse(&mut rush, "cond pv '%s' yes; fi");
+    assert_eq!(get(&rush, "v"), "yes");
+    // quoted words are not aliases.
+    parse(&mut rush, "\\pv '%s' quoted");
+    assert_eq!(rush.last_status, 127);
+    parse(&mut rush, "unalias pv; pv '%s' 'same line'");
+    assert_eq!(get(&rush, "v"), "same line");
+    parse(&mut rush, "pv '%s' removed");
+    assert_eq!(rush.last_status, 127);
+    parse(&mut rush, "shopt -u expand_aliases");
+    parse(&mut rush, "two");
+    assert_eq!(rush.last_status, 127);
+}
+
+#[test]
+fn test_functions() {
+    let mut rush = RuSh::default();
+    let get = |rush: &RuSh, name: &str| match rush.shell_vars.get(name) {
+        Some(v) => v.value.to_string(),
+        None => panic!("{} should be defined.", name),
+    };
+    parse(&mut rush, "f() { n=$#; first=$1; name=$FUNCNAME; }; f a b");
+    assert_eq!(get(&rush, "n"), "2");
+    assert_eq!(get(&rush, "first"), "a");
+    assert_eq!(get(&rush, "name"), "f");
+    // positional parameters are restored once the function is left.
+    assert_eq!(rush.positional, vec!["rush"]);
+    parse(
+        &mut rush,
+        "function g { while true; do return 3; done; v=no; }; v=yes; g",
+    );
+    assert_eq!(rush.last_status, 3);
+    assert_eq!(get(&rush, "v"), "yes");
+    parse(&mut rush, "function h() { g; r=$?; }; h");
+    assert_eq!(get(&rush, "r"), "3");
+    assert_eq!(rush.last_status, 0);
+    parse(&mut rush, "return");
+    assert_eq!(rush.last_status, 1);
+    parse(&mut rush, "k() { return x; }; k");
+    assert_eq!(rush.last_status, 2);
+}
+
+#[test]
+fn test_substitution() {
+    let mut rush = RuSh::default();
+    let get = |rush: &RuSh, name: &str| match rush.shell_vars.get(name) {
+        Some(v) => v.value.to_string(),
+        None => panic!("{} should be defined.", name),
+    };
+    parse(&mut rush, "a=$(echo one; echo two) b=`echo '\\`' \\$HOME`");
+    assert_eq!(get(&rush, "a"), "one\ntwo");
+    assert_eq!(get(&rush, "b"), format!("` {}", get(&rush, "HOME")));
+    parse(&mut rush, "c=\"$(echo \"(nested $(echo deep))\")\"");
+    assert_eq!(get(&rush, "c"), "(nested deep)");
+    // word splitting applies to unquoted substitutions only.
+    parse(&mut rush, "f() { n=$#; }; f $(echo 1 2 3) \"$(echo 1 2)\"");
+    assert_eq!(get(&rush, "n"), "4");
+    // assignments only exit with the status of the last substitution.
+    parse(&mut rush, "d=$(sh -c 'exit 3')");
+    assert_eq!(rush.last_status, 3);
+    parse(&mut rush, "d=$(sh -c 'exit 3') true");
+    assert_eq!(rush.last_status, 0);
+    parse(&mut rush, "e=$((1 + 2 * 3))");
+    parse(&mut rush, "f=$(( (e - 1) / 2 ))$((e % 4))");
+    assert_eq!(get(&rush, "e"), "7");
+    assert_eq!(get(&rush, "f"), "33");
+}
@@ -0,0 +1,108 @@
+extern crate rush;
+
+use rush::highlight::{spans, Kind};
+use rush::parse::parse;
+use rush::rush::RuSh;
+
+/// Highlighted parts of a line, with their kind.
+fn parts<'a>(rush: &RuSh, line: &'a str) -> Vec<(&'a str, Kind)> {
+    spans(rush, line)
+        .into_iter()
+        .map(|(start, end, kind)| (&line[start..end], kind))
+        .collect()
+}
+
+#[test]
+fn test_commands() {
+    let mut rush = RuSh::default();
+    assert_eq!(parts(&rush, "cat -l"), vec![]);
+    assert_eq!(
+        parts(&rush, "nosuchcommand a"),
+        vec![("nosuchcommand", Kind::Error)]
+    );
+    assert_eq!(
+        parts(&rush, "/bin/ls; ./nosuch"),
+        vec![("./nosuch", Kind::Error)]
+    );
+    assert_eq!(
+        parts(&rush, "a=1 printf 2"),
+        vec![
+            ("1", Kind::Number),
+            ("printf", Kind::Builtin),
+            ("2", Kind::Number)
+        ]
+    );
+    parse(&mut rush, "f() { :; }; alias ll='ls -l'");
+    assert_eq!(
+        parts(&rush, "f | ll"),
+        vec![("f", Kind::Builtin), ("ll", Kind::Builtin)]
+    );
+    // names given by expansions are not looked for.
+    assert_eq!(parts(&rush, "$cmd"), vec![("$cmd", Kind::Variable)]);
+    assert_eq!(parts(&rush, "\"ls\""), vec![("\"ls\"", Kind::String)]);
+}
+
+#[test]
+fn test_words() {
+    let rush = RuSh::default();
+    assert_eq!(
+        parts(&rush, "echo \"a $b ${c:-d}\" 'e $f' `g` 3.5 x2"),
+        vec![
+            ("echo", Kind::Builtin),
+            ("\"a ", Kind::String),
+            ("$b", Kind::Variable),
+            (" ", Kind::String),
+            ("${c:-d}", Kind::Variable),
+            ("\"", Kind::String),
+            ("'e $f'", Kind::String),
+            ("`g`", Kind::String),
+            ("3.5", Kind::Number),
+        ]
+    );
+    assert_eq!(
+        parts(&rush, "echo a # b c"),
+        vec![("echo", Kind::Builtin), ("# b c", Kind::Comment)]
+    );
+    assert_eq!(parts(&rush, "echo a#b"), vec![("echo", Kind::Builtin)]);
+}
+
+#[test]
+fn test_compound_commands() {
+    let rush = RuSh::default();
+    assert_eq!(
+        parts(&rush, "while ! cat; do echo; done"),
+        vec![
+            ("while", Kind::Keyword),
+            ("!", Kind::Keyword),
+            ("do", Kind::Keyword),
+            ("echo", Kind::Builtin),
+            ("done", Kind::Keyword),
+        ]
+    );
+    assert_eq!(
+        parts(&rush, "function g { echo; }"),
+        vec![
+            ("function", Kind::Keyword),
+            ("{", Kind::Keyword),
+            ("echo", Kind::Builtin),
+            ("}", Kind::Keyword)
+        ]
+    );
+    // incomplete commands are highlighted as they are typed.
+    assert_eq!(
+        parts(&rush, "if cat; then echo"),
+        vec![
+            ("if", Kind::Keyword),
+            ("then", Kind::Keyword),
+            ("echo", Kind::Builtin)
+        ]
+    );
+    assert_eq!(
+        parts(&rush, "echo \"a $b"),
+        vec![("echo", Kind::Builtin), ("\"a $b", Kind::Error)]
+    );
+    assert_eq!(
+        parts(&rush, "echo `a"),
+        vec![("echo", Kind::Builtin), ("`a", Kind::Error)]
+    );
+}
@@ -0,0 +1,196 @@
+extern crate rush;
+
+use rush::history::{self, expand, words};
+use rush::parse::parse;
+use rush::rush::RuSh;
+use std::fs;
+
+/// A shell with a few history entries.
+fn rush() -> RuSh {
+    let mut rush = RuSh::default();
+    for line in [
+        "tar xzf /tmp/archive.tar.gz -C /usr/local/src",
+        "grep -n 'a b' file.txt | sort > out.txt",
+        "echo one two three",
+    ] {
+        rush.history.add(line);
+    }
+    rush
+}
+
+#[test]
+fn test_events() {
+    let rush = rush();
+    for (line, expected) in [
+        ("!!", "echo one two three"),
+        ("!! four", "echo one two three four"),
+        ("!1", "tar xzf /tmp/archive.tar.gz -C /usr/local/src"),
+        ("!-2", "grep -n 'a b' file.txt | sort > out.txt"),
+        ("!gr", "grep -n 'a b' file.txt | sort > out.txt"),
+        ("!?local?", "tar xzf /tmp/archive.tar.gz -C /usr/local/src"),
+        ("x !#", "x x "),
+        ("^two^2", "echo one 2 three"),
+        ("^o^0^ x", "ech0 one two three x"),
+        ("a!=b", "a!=b"),
+        ("echo ! a", "echo ! a"),
+        ("echo '!!'", "echo '!!'"),
+        ("echo \\!!", "echo \\!!"),
+        ("echo \"!!\"", "echo \"echo one two three\""),
+        ("echo \"a!\"", "echo \"a!\""),
+    ] {
+        assert_eq!(expand(&rush, line).unwrap().line, expected, "{}", line);
+    }
+    assert!(!expand(&rush, "echo '!!'").unwrap().changed);
+    assert!(expand(&rush, "!!").unwrap().changed);
+    for (line, error) in [
+        ("!foo", "!foo: event not found"),
+        ("!4", "!4: event not found"),
+        ("!-4", "!-4: event not found"),
+        ("!?zzz?", "!?zzz?: event not found"),
+        ("!!:5", "!!:5: bad word specifier"),
+        ("^zz^y", "zz: substitution failed"),
+        ("!!:z", ":z: unrecognized history modifier"),
+    ] {
+        assert_eq!(expand(&rush, line).unwrap_err(), error, "{}", line);
+    }
+}
+
+#[test]
+fn test_words() {
+    let rush = rush();
+    for (line, expected) in [
+        ("!$", "three"),
+        ("!^", "one"),
+        ("!*", "one two three"),
+        ("!:0", "echo"),
+        ("!!:2", "two"),
+        ("!!:1-2", "one two"),
+        ("!!:2*", "two three"),
+        ("!!:1-", "one two"),
+        ("!!:-2", "echo one two"),
+        ("!gr:2", "'a b'"),
+        ("!gr:$", "out.txt"),
+        ("!gr:4", "|"),
+        ("!?arch?:%", "/tmp/archive.tar.gz"),
+    ] {
+        assert_eq!(expand(&rush, line).unwrap().line, expected, "{}", line);
+    }
+    assert_eq!(words("a  b;c"), vec!["a", "b", ";", "c"]);
+    assert_eq!(words("x \"y z\"||w"), vec!["x", "\"y z\"", "||", "w"]);
+}
+
+#[test]
+fn test_modifiers() {
+    let rush = rush();
+    for (line, expected) in [
+        ("!tar:2:h", "/tmp"),
+        ("!tar:2:t", "archive.tar.gz"),
+        ("!tar:2:r", "/tmp/archive.tar"),
+        ("!tar:2:r:r", "/tmp/archive"),
+        ("!tar:2:e", ".gz"),
+        ("!tar:2:t:r:e", ".tar"),
+        ("!$:q", "'three'"),
+        ("!*:x", "'one' 'two' 'three'"),
+        ("!!:s/o/0/", "ech0 one two three"),
+        ("!!:gs/o/0/", "ech0 0ne tw0 three"),
+        ("!!:Gs/o/0/", "ech0 0ne tw0 three"),
+        ("!!:s/one/&&/", "echo oneone two three"),
+        ("!!:s#two#2", "echo one 2 three"),
+        ("!!:s/o/0/:&", "ech0 0ne two three"),
+        ("!!:s/e/E/:s//_/", "Echo on_ two three"),
+    ] {
+        assert_eq!(expand(&rush, line).unwrap().line, expected, "{}", line);
+    }
+    let exp = expand(&rush, "!!:p").unwrap();
+    assert!(exp.print);
+    assert_eq!(exp.line, "echo one two three");
+}
+
+/// A shell keeping history in a temporary file.
+fn interactive(name: &str) -> (RuSh, String) {
+    let dir = std::env::temp_dir().join(format!("rush_history_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name).to_str().unwrap().to_string();
+    let _ = fs::remove_file(&path);
+    let mut rush = RuSh::default();
+    parse(&mut rush, &format!("set -o history; HISTFILE={}", path));
+    (rush, path)
+}
+
+#[test]
+fn test_add() {
+    let (mut rush, _) = interactive("add");
+    parse(
+        &mut rush,
+        "HISTCONTROL=erasedups:ignorespace; HISTIGNORE='&:exit:\\:*'",
+    );
+    for line in ["ls", "pwd", "ls", "ls", " secret", "exit", ":x", "", "cd /"] {
+        history::add(&mut rush, line);
+    }
+    let lines: Vec<&str> = rush.history.iter().map(|e| e.line.as_str()).collect();
+    assert_eq!(lines, vec!["pwd", "ls", "cd /"]);
+    assert_eq!(rush.shell_vars.get("HISTCMD").unwrap().geti(), 3);
+    parse(&mut rush, "HISTSIZE=2");
+    history::add(&mut rush, "true");
+    assert_eq!(rush.history.base, 3);
+    assert_eq!(rush.history.number(), 5);
+    assert_eq!(rush.shell_vars.get("HISTCMD").unwrap().geti(), 4);
+    assert_eq!(expand(&rush, "!3").unwrap().line, "cd /");
+    assert!(expand(&rush, "!1").is_err());
+    // blank lines of a quoted string are kept.
+    history::add(&mut rush, "echo 'a\n\nb'\n");
+    assert_eq!(rush.history.entries.last().unwrap().line, "echo 'a\n\nb'");
+    parse(&mut rush, "set +o history");
+    assert!(!history::add(&mut rush, "false"));
+}
+
+#[test]
+fn test_sizes() {
+    let (mut rush, path) = interactive("sizes");
+    history::defaults(&mut rush);
+    history::load(&mut rush);
+    assert_eq!(rush.shell_vars.get("HISTSIZE").unwrap().geti(), 500);
+    assert_eq!(rush.shell_vars.get("HISTFILESIZE").unwrap().geti(), 500);
+    let lines: Vec<String> = (0..600).map(|n| n.to_string()).collect();
+    fs::write(&path, lines.join("\n") + "\n").unwrap();
+    history::load(&mut rush);
+    assert_eq!(rush.history.len(), 500);
+    assert_eq!(rush.history.get(1).unwrap().line, "100");
+    assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 500);
+}
+
+#[test]
+fn test_file() {
+    let (mut rush, path) = interactive("file");
+    fs::write(&path, "one\n#1000\ntwo\nthree\n").unwrap();
+    parse(&mut rush, "HISTFILESIZE=2");
+    history::load(&mut rush);
+    let entries: Vec<(&str, Option<i64>)> = rush
+        .history
+        .iter()
+        .map(|e| (e.line.as_str(), e.time))
+        .collect();
+    assert_eq!(entries, vec![("two", Some(1000)), ("three", None)]);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "#1000\ntwo\nthree\n");
+    history::add(&mut rush, "four");
+    parse(&mut rush, "HISTFILESIZE=10; shopt -s histappend");
+    fs::write(&path, "other\n").unwrap();
+    history::save(&mut rush);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "other\nfour\n");
+    history::add(&mut rush, "five");
+    parse(&mut rush, "shopt -u histappend; HISTTIMEFORMAT=");
+    history::save(&mut rush);
+    let contents = fs::read_to_string(&path).unwrap();
+    assert!(contents.starts_with("#1000\ntwo\nthree\n#"), "{}", contents);
+    assert!(contents.ends_with("\nfive\n"), "{}", contents);
+    // multi-line entries are kept with lithist.
+    parse(&mut rush, "shopt -s lithist");
+    history::add(&mut rush, "for i in a\ndo echo $i\ndone");
+    history::save(&mut rush);
+    let entries = history::read(&path, true).unwrap();
+    assert_eq!(entries.last().unwrap().line, "for i in a\ndo echo $i\ndone");
+    assert_eq!(
+        history::read(&path, false).unwrap().last().unwrap().line,
+        "done"
+    );
+}
@@ -0,0 +1,108 @@
+extern crate rush;
+extern crate rustyline;
+
+use rush::inputrc::{self, Binding};
+use rush::parse::parse;
+use rush::rush::RuSh;
+use rustyline::KeyPress;
+
+#[test]
+fn test_keys() {
+    let keys = [
+        ("\\C-a", KeyPress::Ctrl('A')),
+        ("\\M-f", KeyPress::Meta('f')),
+        ("\\ef", KeyPress::Meta('f')),
+        ("\\t", KeyPress::Tab),
+        ("\\C-?", KeyPress::Backspace),
+        ("\\e[3~", KeyPress::Delete),
+        ("\\eOA", KeyPress::Up),
+        ("x", KeyPress::Char('x')),
+    ];
+    for (seq, key) in keys.iter() {
+        assert_eq!(inputrc::key(&inputrc::unescape(seq)), Ok(*key));
+    }
+    assert_eq!(inputrc::keyseq(KeyPress::Delete), "\\e[3~");
+    assert!(inputrc::key(&inputrc::unescape("\\C-x\\C-e")).is_err());
+}
+
+#[test]
+fn test_lines() {
+    let mut rush = RuSh::default();
+    for line in &[
+        "Control-u: unix-line-discard",
+        "M-DEL: backward-kill-word",
+        "TAB: complete",
+        "\"\\e[A\": history-search-backward",
+        "\"\\C-o\": 'cd ..\\n'",
+    ] {
+        inputrc::line(&mut rush, line, None).unwrap();
+    }
+    assert_eq!(
+        rush.inputrc.bindings("emacs"),
+        &[
+            (
+                KeyPress::Ctrl('U'),
+                Binding::Function(String::from("unix-line-discard"))
+            ),
+            (
+                KeyPress::Meta('\x7f'),
+                Binding::Function(String::from("backward-kill-word"))
+            ),
+            (KeyPress::Tab, Binding::Function(String::from("complete"))),
+            (
+                KeyPress::Up,
+                Binding::Function(String::from("history-search-backward"))
+            ),
+            (KeyPress::Ctrl('O'), Binding::Macro(String::from("cd ..\n"))),
+        ]
+    );
+    assert!(inputrc::line(&mut rush, "set editing-mode ed", None).is_err());
+    assert!(inputrc::line(&mut rush, "set keymap nope", None).is_err());
+    assert!(inputrc::line(&mut rush, "\"\\C-t: undo", None).is_err());
+    assert!(inputrc::line(&mut rush, "Hyper-x: undo", None).is_err());
+}
+
+#[test]
+fn test_read() {
+    let path = std::env::temp_dir().join(format!("rush_inputrc_{}", std::process::id()));
+    let path = path.to_str().unwrap();
+    std::fs::write(
+        path,
+        "# comment\n\
+         set editing-mode vi\n\
+         $if mode=vi\n\
+         set keymap vi-command\n\
+         \"\\C-l\": clear-screen\n\
+         $else\n\
+         \"\\C-l\": undo\n\
+         $endif\n\
+         $if Bash\n\
+         set bell-style visible\n\
+         $endif\n\
+         $if RuSh\n\
+         set bell-style none\n\
+         set unknown-variable on\n\
+         $endif\n\
+         \"\\C-x\\C-r\": re-read-init-file\n",
+    )
+    .unwrap();
+    let mut rush = RuSh::default();
+    assert!(inputrc::read(&mut rush, path, None));
+    assert_eq!(inputrc::variable(&rush, "editing-mode"), "vi");
+    assert_eq!(inputrc::variable(&rush, "bell-style"), "none");
+    assert_eq!(inputrc::variable(&rush, "unknown-variable"), "on");
+    assert_eq!(
+        rush.inputrc.bindings("vi-command"),
+        &[(
+            KeyPress::Ctrl('L'),
+            Binding::Function(String::from("clear-screen"))
+        )]
+    );
+    // INPUTRC is the file read at startup.
+    let mut rush = RuSh::default();
+    parse(&mut rush, &format!("INPUTRC={}", path));
+    inputrc::startup(&mut rush);
+    assert_eq!(inputrc::variable(&rush, "keymap"), "vi-command");
+    std::fs::remove_file(path).unwrap();
+    assert!(!inputrc::read(&mut rush, path, None));
+}
@@ -1,3 +1,5 @@
+#![allow(clippy::bool_assert_comparison)]
+
 extern crate rush;
 
 use rush::opt::Opt;
@@ -58,3 +60,38 @@ fn test_opt_set() {
         None => panic!("opttest shopt option should be defined."),
     }
 }
+
+#[test]
+fn test_opt_update() {
+    let mut o = Opt::init_shopt_options();
+    assert!(o.update("autocd", true));
+    match o.get("autocd") {
+        Some(v) => assert!(v.set),
+        None => panic!("autocd shopt option should be defined."),
+    }
+    // login_shell is locked.
+    assert!(!o.update("login_shell", true));
+    match o.get("login_shell") {
+        Some(v) => {
+            assert!(!v.set);
+            assert_eq!(v.access, Access::ReadOnly);
+        }
+        None => panic!("login_shell shopt option should be defined."),
+    }
+    assert!(!o.update("doesnotexist", true));
+    assert!(o.get("doesnotexist").is_none());
+}
+
+#[test]
+fn test_opt_enabled_and_flags() {
+    let mut o = Opt::init_set_options();
+    assert_eq!(o.enabled(), "braceexpand:hashall:histexpand:monitor");
+    assert_eq!(o.flags(), "hmBH");
+    o.update("errexit", true);
+    o.update("xtrace", true);
+    assert_eq!(
+        o.enabled(),
+        "braceexpand:errexit:hashall:histexpand:monitor:xtrace"
+    );
+    assert_eq!(o.flags(), "ehmxBH");
+}
@@ -1,15 +1,115 @@
+extern crate chrono;
 extern crate rush;
 
-use rush::prompt::Prompt;
+use rush::arrays::Index;
+use rush::parse::parse;
+use rush::prompt::{self, Prompt};
 use rush::rush::RuSh;
+use rush::variables::Value;
 
 #[test]
 fn test_get() {
     let mut rush = RuSh::default();
     let mut p = Prompt::get(&mut rush, "PS2");
-    assert_eq!(p.prompt, ">");
+    assert_eq!(p.prompt, "> ");
     p = Prompt::get(&mut rush, "PS3");
     assert_eq!(p.prompt, ">");
     p = Prompt::get(&mut rush, "PS4");
     assert_eq!(p.prompt, ">");
+    p = Prompt::get(&mut rush, "RPS1");
+    assert_eq!(p.prompt, "");
+    assert!(rush.shell_vars.get("RPS1").is_none());
+}
+
+/// Expand a prompt string, given as PS1.
+fn expand(rush: &mut RuSh, ps1: &str) -> Prompt {
+    parse(rush, &format!("PS1={}", rush::builtins::quote(ps1)));
+    Prompt::get(rush, "PS1")
+}
+
+#[test]
+fn test_escapes() {
+    let mut rush = RuSh::default();
+    assert_eq!(expand(&mut rush, "\\s-\\v").prompt, "rush-0.0");
+    assert_eq!(expand(&mut rush, "\\V").prompt, "0.0.0");
+    assert_eq!(expand(&mut rush, "\\j \\# \\!").prompt, "0 1 1");
+    assert_eq!(
+        expand(&mut rush, "\\a\\e\\101\\\\\\z\\").prompt,
+        "\x07\x1bA\\\\z\\"
+    );
+    assert_eq!(
+        expand(&mut rush, "\\D{%Y}").prompt,
+        chrono::Local::now().format("%Y").to_string()
+    );
+    assert_eq!(expand(&mut rush, "\\D{}").prompt.len(), 8);
+    assert_eq!(expand(&mut rush, "[\\D{%Q}] $ ").prompt, "[] $ ");
+    assert_eq!(expand(&mut rush, "\\A").prompt.len(), 5);
+    assert_eq!(expand(&mut rush, "\\@").prompt.len(), 8);
+    let euid = rush.shell_vars.get("EUID").unwrap().geti();
+    assert_eq!(
+        expand(&mut rush, "\\$").prompt,
+        if euid == 0 { "#" } else { "$" }
+    );
+    // non-printing characters.
+    let p = expand(&mut rush, "\\[\\e[1m\\]bold\\[\\e[0m\\]> ");
+    assert_eq!(p.prompt, "\x1b[1mbold\x1b[0m> ");
+    assert_eq!(p.visible, "bold> ");
+}
+
+#[test]
+fn test_pwd() {
+    let mut rush = RuSh::default();
+    parse(&mut rush, "HOME=/home/u PWD=/home/u/a/b/c/d");
+    assert_eq!(expand(&mut rush, "\\w \\W").prompt, "~/a/b/c/d d");
+    parse(&mut rush, "PROMPT_DIRTRIM=1");
+    assert_eq!(expand(&mut rush, "\\w").prompt, "~/.../d");
+    // trimming has to make it shorter.
+    parse(&mut rush, "PROMPT_DIRTRIM=2");
+    assert_eq!(expand(&mut rush, "\\w").prompt, "~/a/b/c/d");
+    parse(&mut rush, "PWD=/usr/share/doc");
+    assert_eq!(expand(&mut rush, "\\w").prompt, ".../share/doc");
+    parse(&mut rush, "PWD=/home/u");
+    assert_eq!(expand(&mut rush, "\\w \\W").prompt, "~ ~");
+    parse(&mut rush, "PWD=/home/user");
+    assert_eq!(expand(&mut rush, "\\w").prompt, "/home/user");
+    parse(&mut rush, "PWD=/");
+    assert_eq!(expand(&mut rush, "\\w \\W").prompt, "/ /");
+}
+
+#[test]
+fn test_promptvars() {
+    let mut rush = RuSh::default();
+    parse(&mut rush, "HOME=/home/u PWD='/home/u/$x' x=1");
+    let p = expand(&mut rush, "$x $((x + 1)) $(echo sub) \\w \\[$x\\]> ");
+    assert_eq!(p.prompt, "1 2 sub ~/$x 1> ");
+    assert_eq!(p.visible, "1 2 sub ~/$x > ");
+    // substitutions do not change $?.
+    parse(&mut rush, "PS1='$(true)$?'");
+    parse(&mut rush, "false");
+    assert_eq!(Prompt::get(&mut rush, "PS1").prompt, "0");
+    assert_eq!(rush.last_status, 1);
+    parse(&mut rush, "shopt -u promptvars");
+    assert_eq!(expand(&mut rush, "$x \\w").prompt, "$x ~/$x");
+}
+
+#[test]
+fn test_prompt_command() {
+    let mut rush = RuSh::default();
+    prompt::command(&mut rush);
+    parse(&mut rush, "PROMPT_COMMAND='n=$((n + 1)); false'");
+    prompt::command(&mut rush);
+    prompt::command(&mut rush);
+    assert_eq!(rush.shell_vars.get("n").unwrap().value.to_string(), "2");
+    assert_eq!(rush.last_status, 0);
+    // an array runs each of its elements.
+    parse(&mut rush, "unset PROMPT_COMMAND");
+    rush.shell_array_vars
+        .set("PROMPT_COMMAND", Index::I(0), Value::S(String::from("a=1")));
+    rush.shell_array_vars.set(
+        "PROMPT_COMMAND",
+        Index::I(1),
+        Value::S(String::from("b=$a")),
+    );
+    prompt::command(&mut rush);
+    assert_eq!(rush.shell_vars.get("b").unwrap().value.to_string(), "1");
 }
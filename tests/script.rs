@@ -0,0 +1,294 @@
+extern crate rush;
+
+use rush::arrays::Index;
+use rush::opt::OptionRW;
+use rush::parse::parse;
+use rush::rush::RuSh;
+use rush::script;
+use rush::variables::Access;
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Write a script in a temporary directory, returning its path.
+fn script(name: &str, contents: &str) -> String {
+    let dir = std::env::temp_dir().join(format!("rush_script_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    fs::write(&path, contents).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+#[test]
+fn test_source() {
+    let mut rush = RuSh::default();
+    let inner = script(
+        "inner.sh",
+        "src=${RUSH_SOURCE[0]}; caller=${RUSH_LINENO[0]}; args=\"$0 $*\"\nline=$LINENO\nfalse\n",
+    );
+    parse(&mut rush, "set -- x y");
+    parse(
+        &mut rush,
+        &format!("true\n. {} a b; status=$?\nsourced=$LINENO", inner),
+    );
+    for (name, expected) in [
+        ("src", inner.as_str()),
+        ("caller", "2"),
+        ("args", "rush a b"),
+        ("line", "2"),
+        ("status", "1"),
+        ("sourced", "3"),
+    ]
+    .iter()
+    {
+        match rush.shell_vars.get(name) {
+            Some(v) => assert_eq!(v.value.to_string(), *expected, "{}", name),
+            None => panic!("{} should be defined.", name),
+        }
+    }
+    assert_eq!(rush.positional, vec!["rush", "x", "y"]);
+    assert!(rush
+        .shell_array_vars
+        .get("RUSH_SOURCE", &Index::I(0))
+        .is_none());
+    assert_eq!(rush.nesting, 0);
+    // files without a slash are looked for in PATH with sourcepath.
+    let dir = &inner[..inner.rfind('/').unwrap()];
+    parse(&mut rush, &format!("PATH={}; source inner.sh", dir));
+    match rush.shell_vars.get("src") {
+        Some(v) => assert_eq!(v.gets(), inner),
+        None => panic!("src should be defined."),
+    }
+    parse(&mut rush, "shopt -u sourcepath; source inner.sh");
+    assert_eq!(rush.last_status, 1);
+    parse(&mut rush, ".");
+    assert_eq!(rush.last_status, 2);
+    let empty = script("empty.sh", "# nothing\n\n");
+    parse(&mut rush, &format!("false; . {}", empty));
+    assert_eq!(rush.last_status, 0);
+    let bad = script("bad.sh", "ok=1\nif then\nok=2\n");
+    parse(&mut rush, &format!(". {}; status=$?", bad));
+    match (rush.shell_vars.get("ok"), rush.shell_vars.get("status")) {
+        (Some(ok), Some(status)) => {
+            assert_eq!(ok.geti(), 1);
+            assert_eq!(status.geti(), 2);
+        }
+        _ => panic!("ok and status should be defined."),
+    }
+}
+
+#[test]
+fn test_string() {
+    let mut rush = RuSh::default();
+    assert_eq!(
+        script::string(&mut rush, "a=1\nif true\nthen b=$LINENO\nfi"),
+        0
+    );
+    match rush.shell_vars.get("b") {
+        Some(v) => assert_eq!(v.geti(), 3),
+        None => panic!("b should be defined."),
+    }
+    match rush.shell_vars.get("RUSH_EXECUTION_STRING") {
+        Some(v) => assert_eq!(v.gets(), "a=1\nif true\nthen b=$LINENO\nfi"),
+        None => panic!("RUSH_EXECUTION_STRING should be defined."),
+    }
+    // commands preceding a syntax error are executed.
+    assert_eq!(script::string(&mut rush, "c=1\nfi\nc=2"), 2);
+    match rush.shell_vars.get("c") {
+        Some(v) => assert_eq!(v.geti(), 1),
+        None => panic!("c should be defined."),
+    }
+}
+
+#[test]
+fn test_invocation() {
+    let rush = env!("CARGO_BIN_EXE_rush");
+    let path = script("args.sh", "printf '%s|' \"$0\" \"$@\"\nfalse\n");
+    let out = Command::new(rush)
+        .args([&path, "a", "b c"])
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&out.stdout),
+        format!("{}|a|b c|", path)
+    );
+    assert_eq!(out.status.code(), Some(1));
+    let out = Command::new(rush)
+        .args(["-c", "printf '%s|' \"$0\" \"$1\"", "name", "arg"])
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "name|arg|");
+    let out = Command::new(rush).arg("/doesnotexist").output().unwrap();
+    assert_eq!(out.status.code(), Some(127));
+    // standard input is read a line at a time: read gets the line following it.
+    let mut child = Command::new(rush)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"read -r line\nhello world\nprintf '%s' \"$line\"\nfi\nprintf no\n")
+        .unwrap();
+    let out = child.wait_with_output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "hello world");
+    assert_eq!(out.status.code(), Some(2));
+}
+
+#[test]
+fn test_startup() {
+    let other = script("otherrc", "from=otherrc\n");
+    let home = format!("{}/home", &other[..other.rfind('/').unwrap()]);
+    fs::create_dir_all(&home).unwrap();
+    fs::write(format!("{}/.rushrc", home), "from=rushrc\n").unwrap();
+    fs::write(format!("{}/.rush_profile", home), "from=rush_profile\n").unwrap();
+    fs::write(format!("{}/.profile", home), "from=profile\n").unwrap();
+    fs::write(format!("{}/.rush_logout", home), "from=logout\n").unwrap();
+    fs::write(
+        format!("{}/system_profile", home),
+        "from=system; system=1\n",
+    )
+    .unwrap();
+    let from = |rush: &RuSh| match rush.shell_vars.get("from") {
+        Some(v) => v.gets(),
+        None => String::new(),
+    };
+    let mut rush = RuSh::default();
+    parse(&mut rush, &format!("HOME={}", home));
+    script::rc(&mut rush, None);
+    assert_eq!(from(&rush), "rushrc");
+    script::rc(&mut rush, Some(&other));
+    assert_eq!(from(&rush), "otherrc");
+    // not a login shell, ~/.rush_logout is ignored.
+    script::logout(&mut rush);
+    assert_eq!(from(&rush), "otherrc");
+    rush.shopt_options.set(
+        "login_shell".to_string(),
+        OptionRW {
+            set: true,
+            access: Access::ReadOnly,
+        },
+    );
+    script::logout(&mut rush);
+    assert_eq!(from(&rush), "logout");
+    // posix mode reads ENV instead of ~/.rushrc.
+    parse(&mut rush, "set -o posix; ENV=");
+    script::rc(&mut rush, None);
+    assert_eq!(from(&rush), "logout");
+    parse(&mut rush, "ENV='$HOME/../otherrc'");
+    script::rc(&mut rush, None);
+    assert_eq!(from(&rush), "otherrc");
+    // login shells read the system profile, then the first of ~/.rush_profile and ~/.profile.
+    let system = format!("{}/system_profile", home);
+    let mut login = RuSh::default();
+    parse(&mut login, &format!("HOME={}", home));
+    script::profile(&mut login, &system);
+    assert_eq!(login.shell_vars.get("system").unwrap().geti(), 1);
+    assert_eq!(from(&login), "rush_profile");
+    let rush = env!("CARGO_BIN_EXE_rush");
+    let run = |args: &[&str]| {
+        let out = Command::new(rush)
+            .env("HOME", &home)
+            .args(args)
+            .output()
+            .unwrap();
+        String::from_utf8_lossy(&out.stdout).into_owned()
+    };
+    assert_eq!(
+        run(&["--login", "--noprofile", "-c", "printf '[%s]' \"$from\""]),
+        "[]"
+    );
+    assert!(run(&[
+        "--login",
+        "--noprofile",
+        "-c",
+        "printf '[%s]' \"$RUSHOPTS\""
+    ])
+    .contains("login_shell"));
+    // rc files are for interactive shells only.
+    assert_eq!(run(&["-c", "printf '[%s]' \"$from\""]), "[]");
+    fs::remove_file(format!("{}/.rush_profile", home)).unwrap();
+    let mut login = RuSh::default();
+    parse(&mut login, &format!("HOME={}", home));
+    script::profile(&mut login, &system);
+    assert_eq!(from(&login), "profile");
+    // restrictions apply once startup files are read. /etc/profile is read too, its output is ignored.
+    fs::write(format!("{}/.profile", home), "/bin/echo profile\n").unwrap();
+    let out = run(&["--login", "-r", "-c", "/bin/echo command"]);
+    assert!(out.ends_with("profile\n"), "{}", out);
+    assert!(!out.contains("command"), "{}", out);
+}
+
+#[test]
+fn test_options() {
+    let rush = env!("CARGO_BIN_EXE_rush");
+    let run = |args: &[&str], input: &str| {
+        let mut child = Command::new(rush)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(input.as_bytes())
+            .unwrap();
+        let out = child.wait_with_output().unwrap();
+        (
+            String::from_utf8_lossy(&out.stdout).into_owned(),
+            out.status.code(),
+        )
+    };
+    let (out, status) = run(
+        &[
+            "-eu",
+            "+B",
+            "-o",
+            "pipefail",
+            "-O",
+            "extglob",
+            "-c",
+            "printf '%s|%s|%s|%s' \"$0\" \"$*\" \"$SHELLOPTS\" \"$RUSHOPTS\"",
+            "name",
+            "a",
+            "b",
+        ],
+        "",
+    );
+    assert_eq!(status, Some(0));
+    let fields: Vec<&str> = out.split('|').collect();
+    assert_eq!(fields[0], "name");
+    assert_eq!(fields[1], "a b");
+    assert_eq!(
+        fields[2],
+        "errexit:hashall:histexpand:monitor:nounset:pipefail"
+    );
+    assert!(fields[3].contains("extglob"));
+    // -s reads commands from standard input, operands being positional parameters.
+    let (out, _) = run(&["-s", "a", "b"], "printf '%s' \"$*\"\n");
+    assert_eq!(out, "a b");
+    // -n only checks syntax.
+    assert_eq!(
+        run(&["-n", "-c", "printf run"], ""),
+        (String::new(), Some(0))
+    );
+    assert_eq!(run(&["-n", "-c", "if"], "").1, Some(2));
+    assert!(run(&["--posix", "-c", "printf '%s' \"$SHELLOPTS\""], "")
+        .0
+        .contains("posix"));
+    assert_eq!(
+        run(&["-r", "-c", "/bin/echo x"], ""),
+        (String::new(), Some(1))
+    );
+    assert_eq!(run(&["-z"], "").1, Some(2));
+    assert_eq!(run(&["-o", "doesnotexist"], "").1, Some(2));
+    assert_eq!(run(&["--doesnotexist"], "").1, Some(2));
+    let (out, status) = run(&["--version"], "");
+    assert!(out.starts_with("RuSh, version "));
+    assert_eq!(status, Some(0));
+}
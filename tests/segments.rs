@@ -0,0 +1,76 @@
+extern crate rush;
+
+use rush::parse::parse;
+use rush::prompt::Prompt;
+use rush::rush::RuSh;
+use rush::segments::{Segments, MAX_VALUES};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_get() {
+    let mut segments = Segments::default();
+    assert_eq!(segments.get("none", Duration::from_millis(10)), "");
+    segments.submit("fast", Box::new(|| String::from("1")));
+    assert_eq!(segments.get("fast", Duration::from_secs(5)), "1");
+    // a job which is not done in time leaves previous value.
+    segments.submit(
+        "fast",
+        Box::new(|| {
+            thread::sleep(Duration::from_millis(300));
+            String::from("2")
+        }),
+    );
+    assert_eq!(segments.get("fast", Duration::from_millis(10)), "1");
+    assert!(segments.pending("fast"));
+    assert_eq!(segments.get("fast", Duration::from_secs(5)), "2");
+    assert!(!segments.pending("fast"));
+    // a slow segment does not hold others back.
+    segments.submit(
+        "slow",
+        Box::new(|| {
+            thread::sleep(Duration::from_millis(500));
+            String::from("slow")
+        }),
+    );
+    segments.submit("fast", Box::new(|| String::from("3")));
+    let start = Instant::now();
+    assert_eq!(segments.get("fast", Duration::from_secs(5)), "3");
+    assert!(start.elapsed() < Duration::from_millis(500));
+    assert!(segments.pending("slow"));
+    assert_eq!(segments.get("slow", Duration::from_secs(5)), "slow");
+}
+
+#[test]
+fn test_evict() {
+    let mut segments = Segments::default();
+    for n in 0..=MAX_VALUES {
+        let key = n.to_string();
+        segments.submit(&key, Box::new(move || n.to_string()));
+        assert_eq!(segments.get(&key, Duration::from_secs(5)), key);
+        // first value keeps being displayed.
+        assert_eq!(segments.get("0", Duration::from_secs(5)), "0");
+    }
+    assert_eq!(segments.get("1", Duration::from_millis(10)), "");
+    assert_eq!(segments.get("2", Duration::from_millis(10)), "2");
+}
+
+#[test]
+fn test_prompt() {
+    let mut rush = RuSh::default();
+    parse(
+        &mut rush,
+        "RUSH_PROMPT_TIMEOUT=20 RPS1='[$(sleep 0.3; echo slow)]'",
+    );
+    // non interactive shells wait for substitutions.
+    assert_eq!(Prompt::get(&mut rush, "RPS1").prompt, "[slow]");
+    rush.interactive = true;
+    parse(&mut rush, "RPS1='<$(sleep 0.3; echo slow)>'");
+    let start = Instant::now();
+    assert_eq!(Prompt::get(&mut rush, "RPS1").prompt, "<>");
+    assert_eq!(Prompt::get(&mut rush, "RPS1").prompt, "<>");
+    assert!(start.elapsed() < Duration::from_millis(300));
+    thread::sleep(Duration::from_millis(500));
+    assert_eq!(Prompt::get(&mut rush, "RPS1").prompt, "<slow>");
+    assert_eq!(Prompt::get(&mut rush, "PS2").prompt, "> ");
+}
@@ -1,3 +1,5 @@
+#![allow(clippy::assertions_on_constants)]
+
 extern crate rush;
 
 //use crate::variables::Variables;
@@ -11,7 +13,7 @@ fn test_init_shell_vars() {
         None => panic!("RUSH_COMMAND should be defined."),
     }
     match vars.get("HISTSIZE") {
-        Some(v) => assert_eq!(v.geti(), 1000),
+        Some(v) => assert_eq!(v.geti(), 500),
         None => panic!("HISTSIZE should be defined."),
     }
 }
@@ -38,7 +40,7 @@ fn test_get_and_getifs() {
         None => panic!("RUSH_COMMAND should be defined."),
     }
     match vars.get("HISTSIZE") {
-        Some(v) => assert_eq!(v.geti(), 1000),
+        Some(v) => assert_eq!(v.geti(), 500),
         None => panic!("HISTSIZE should be defined."),
     }
     vars.set(
@@ -46,6 +48,7 @@ fn test_get_and_getifs() {
         Variable {
             value: Value::F(-49.3),
             access: Access::ReadWrite,
+            export: false,
         },
     );
     match vars.get("TEST") {
@@ -62,6 +65,7 @@ fn test_set() {
         Variable {
             value: Value::F(-49.3),
             access: Access::ReadWrite,
+            export: false,
         },
     );
     match vars.get("TESTF") {
@@ -73,6 +77,7 @@ fn test_set() {
         Variable {
             value: Value::I(-42),
             access: Access::ReadWrite,
+            export: false,
         },
     );
     match vars.get("TESTI") {
@@ -84,6 +89,7 @@ fn test_set() {
         Variable {
             value: Value::S(String::from("RuSh will rock (one day)")),
             access: Access::ReadWrite,
+            export: false,
         },
     );
     match vars.get("TESTS") {
@@ -133,3 +139,70 @@ fn test_set_access() {
         None => panic!("doesnotexist variable should be defined and Access::ReadOnly"),
     }
 }
+
+#[test]
+fn test_export() {
+    let mut vars = Variables::init_shell_vars();
+    vars.set(
+        String::from("TESTEXPORT"),
+        Variable {
+            value: Value::I(42),
+            access: Access::ReadWrite,
+            export: false,
+        },
+    );
+    assert_eq!(vars.get_export("TESTEXPORT"), Some(false));
+    assert!(!vars.exported().contains(&"TESTEXPORT".to_string()));
+    vars.set_export(String::from("TESTEXPORT"), true);
+    assert_eq!(vars.get_export("TESTEXPORT"), Some(true));
+    assert!(vars.exported().contains(&"TESTEXPORT".to_string()));
+    // an exported variable stays exported when updated.
+    vars.set(
+        String::from("TESTEXPORT"),
+        Variable {
+            value: Value::I(43),
+            access: Access::ReadWrite,
+            export: false,
+        },
+    );
+    match vars.get("TESTEXPORT") {
+        Some(v) => {
+            assert_eq!(v.geti(), 43);
+            assert!(v.export);
+        }
+        None => panic!("TESTEXPORT should be defined."),
+    }
+    vars.set_export(String::from("TESTEXPORT"), false);
+    assert_eq!(vars.get_export("TESTEXPORT"), Some(false));
+}
+
+#[test]
+fn test_envp() {
+    let mut vars = Variables::init_shell_vars();
+    vars.set(
+        String::from("TESTENVP"),
+        Variable {
+            value: Value::S(String::from("value")),
+            access: Access::ReadWrite,
+            export: true,
+        },
+    );
+    let envp = vars.envp(&[]);
+    assert!(envp.iter().any(|e| e.to_str() == Ok("TESTENVP=value")));
+    let envp = vars.envp(&[(String::from("TESTENVP"), String::from("other"))]);
+    assert!(envp.iter().any(|e| e.to_str() == Ok("TESTENVP=other")));
+    assert!(!envp.iter().any(|e| e.to_str() == Ok("TESTENVP=value")));
+}
+
+#[test]
+fn test_environment_import() {
+    let vars = Variables::init_shell_vars();
+    match vars.get("PATH") {
+        Some(v) => assert!(v.export),
+        None => panic!("PATH should be imported from environment."),
+    }
+    match vars.get("SHLVL") {
+        Some(v) => assert!(v.export),
+        None => panic!("SHLVL should be defined."),
+    }
+}
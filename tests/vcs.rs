@@ -0,0 +1,182 @@
+extern crate rush;
+
+use rush::parse::parse;
+use rush::prompt::Prompt;
+use rush::rush::RuSh;
+use rush::segments;
+use rush::vcs::{self, Status};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// An empty repository, whose branch is main.
+fn repository(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rush_vcs_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    git(&dir, &["init", "-q", "-b", "main"]);
+    dir
+}
+
+/// Run git within `dir`, with a fixed identity. Returns its output.
+fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .env("GIT_AUTHOR_NAME", "RuSh")
+        .env("GIT_AUTHOR_EMAIL", "rush@example.org")
+        .env("GIT_COMMITTER_NAME", "RuSh")
+        .env("GIT_COMMITTER_EMAIL", "rush@example.org")
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .env("HOME", dir)
+        .output()
+        .unwrap();
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+/// Write `file` and commit it, at time `time`.
+fn commit(dir: &Path, file: &str, text: &str, time: u32) {
+    fs::write(dir.join(file), text).unwrap();
+    git(dir, &["add", file]);
+    let date = format!("{} +0000", 1_500_000_000 + time);
+    let output = Command::new("git")
+        .args(["commit", "-q", "-m", text])
+        .current_dir(dir)
+        .env("GIT_AUTHOR_NAME", "RuSh")
+        .env("GIT_AUTHOR_EMAIL", "rush@example.org")
+        .env("GIT_COMMITTER_NAME", "RuSh")
+        .env("GIT_COMMITTER_EMAIL", "rush@example.org")
+        .env("GIT_AUTHOR_DATE", &date)
+        .env("GIT_COMMITTER_DATE", &date)
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .env("HOME", dir)
+        .status()
+        .unwrap();
+    assert!(output.success());
+}
+
+#[test]
+fn test_branch() {
+    let dir = repository("branch");
+    assert_eq!(vcs::prompt(dir.to_str().unwrap()), "main");
+    commit(&dir, "a", "one", 1);
+    fs::create_dir(dir.join("sub")).unwrap();
+    assert_eq!(
+        vcs::status(&dir.join("sub")),
+        Some(Status {
+            branch: String::from("main"),
+            ..Status::default()
+        })
+    );
+    fs::write(dir.join("a"), "changed").unwrap();
+    assert_eq!(vcs::prompt(dir.to_str().unwrap()), "main *");
+    fs::remove_file(dir.join("a")).unwrap();
+    assert_eq!(vcs::prompt(dir.to_str().unwrap()), "main *");
+    git(&dir, &["checkout", "-q", "a"]);
+    // staged changes, compared with HEAD tree.
+    fs::write(dir.join("a"), "one\nb\n").unwrap();
+    git(&dir, &["add", "a"]);
+    assert_eq!(vcs::prompt(dir.to_str().unwrap()), "main +");
+    fs::write(dir.join("a"), "one\nb\nc\n").unwrap();
+    assert_eq!(vcs::prompt(dir.to_str().unwrap()), "main *+");
+    commit(&dir, "a", "two", 2);
+    assert_eq!(vcs::prompt(dir.to_str().unwrap()), "main");
+    fs::write(dir.join("sub/b"), "b").unwrap();
+    git(&dir, &["add", "sub/b"]);
+    assert_eq!(vcs::prompt(dir.to_str().unwrap()), "main +");
+    git(&dir, &["rm", "-q", "--cached", "sub/b"]);
+    git(&dir, &["rm", "-q", "--cached", "a"]);
+    assert_eq!(vcs::prompt(dir.to_str().unwrap()), "main +");
+    git(&dir, &["reset", "-q"]);
+    assert_eq!(vcs::prompt(dir.to_str().unwrap()), "main");
+    let head = git(&dir, &["rev-parse", "--short=7", "HEAD"]);
+    git(&dir, &["checkout", "-q", "--detach"]);
+    assert_eq!(vcs::prompt(dir.to_str().unwrap()), format!("({}...)", head));
+    assert_eq!(vcs::prompt("/"), "");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_upstream() {
+    let dir = repository("upstream");
+    commit(&dir, "a", "one", 1);
+    git(&dir, &["branch", "topic"]);
+    git(&dir, &["branch", "--set-upstream-to", "main", "topic"]);
+    git(&dir, &["checkout", "-q", "topic"]);
+    assert_eq!(vcs::prompt(dir.to_str().unwrap()), "topic u=");
+    commit(&dir, "b", "two", 2);
+    commit(&dir, "b", "three", 3);
+    assert_eq!(vcs::prompt(dir.to_str().unwrap()), "topic u+2");
+    git(&dir, &["checkout", "-q", "main"]);
+    commit(&dir, "c", "four", 4);
+    git(&dir, &["checkout", "-q", "topic"]);
+    assert_eq!(vcs::prompt(dir.to_str().unwrap()), "topic u+2-1");
+    // merged upstream is not behind anymore.
+    git(&dir, &["merge", "-q", "main", "-m", "merge"]);
+    assert_eq!(vcs::prompt(dir.to_str().unwrap()), "topic u+3");
+    // objects and refs once packed.
+    git(&dir, &["gc", "-q", "--aggressive"]);
+    assert!(!dir.join(".git/refs/heads/main").exists());
+    assert_eq!(vcs::prompt(dir.to_str().unwrap()), "topic u+3");
+    git(&dir, &["reset", "-q", "--hard", "main"]);
+    assert_eq!(vcs::prompt(dir.to_str().unwrap()), "topic u=");
+    git(&dir, &["reset", "-q", "--hard", "HEAD~"]);
+    assert_eq!(vcs::prompt(dir.to_str().unwrap()), "topic u-1");
+    git(&dir, &["checkout", "-q", "main"]);
+    assert_eq!(vcs::prompt(dir.to_str().unwrap()), "main");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_operation() {
+    let dir = repository("operation");
+    commit(&dir, "a", "one", 1);
+    git(&dir, &["checkout", "-q", "-b", "topic"]);
+    commit(&dir, "a", "two", 2);
+    git(&dir, &["checkout", "-q", "main"]);
+    commit(&dir, "a", "three", 3);
+    git(&dir, &["merge", "-q", "topic"]);
+    assert_eq!(vcs::prompt(dir.to_str().unwrap()), "main *+|MERGING");
+    git(&dir, &["merge", "--abort"]);
+    assert_eq!(vcs::prompt(dir.to_str().unwrap()), "main");
+    git(&dir, &["checkout", "-q", "topic"]);
+    git(&dir, &["rebase", "-q", "main"]);
+    assert_eq!(vcs::prompt(dir.to_str().unwrap()), "topic *+|REBASE 1/1");
+    git(&dir, &["rebase", "--abort"]);
+    assert_eq!(vcs::prompt(dir.to_str().unwrap()), "topic");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_prompt() {
+    let dir = repository("prompt");
+    commit(&dir, "a", "one", 1);
+    let mut rush = RuSh::default();
+    parse(
+        &mut rush,
+        &format!("PWD={} PS1='[\\g]'", dir.to_str().unwrap()),
+    );
+    assert_eq!(Prompt::get(&mut rush, "PS1").prompt, "[main]");
+    rush.interactive = true;
+    parse(&mut rush, "RUSH_PROMPT_TIMEOUT=5000");
+    assert_eq!(Prompt::get(&mut rush, "PS1").prompt, "[main]");
+    // worktree files are looked at each time, objects walked again once index or refs changed.
+    let pwd = dir.to_str().unwrap().to_string();
+    fs::write(dir.join("a"), "two").unwrap();
+    assert_eq!(segments::vcs(&mut rush, pwd.clone()), "main *");
+    git(&dir, &["add", "a"]);
+    assert_eq!(segments::vcs(&mut rush, pwd.clone()), "main +");
+    fs::write(dir.join("a"), "three").unwrap();
+    assert_eq!(segments::vcs(&mut rush, pwd.clone()), "main *+");
+    commit(&dir, "a", "three", 2);
+    assert_eq!(segments::vcs(&mut rush, pwd.clone()), "main");
+    git(&dir, &["branch", "topic"]);
+    git(&dir, &["branch", "--set-upstream-to", "main", "topic"]);
+    git(&dir, &["checkout", "-q", "topic"]);
+    assert_eq!(segments::vcs(&mut rush, pwd.clone()), "topic u=");
+    git(&dir, &["update-ref", "refs/heads/main", "HEAD~"]);
+    assert_eq!(segments::vcs(&mut rush, pwd), "topic u+1");
+    parse(&mut rush, "PWD=/");
+    assert_eq!(Prompt::get(&mut rush, "PS1").prompt, "[]");
+    fs::remove_dir_all(&dir).unwrap();
+}